@@ -298,6 +298,7 @@ impl Reconciler {
                 shard_number: shard.number.0,
                 shard_count: shard.count.literal(),
                 shard_stripe_size: shard.stripe_size.0,
+                read_only: false,
             }
         }
 
@@ -493,6 +494,7 @@ impl Reconciler {
                     shard_number: self.shard.number.0,
                     shard_count: self.shard.count.literal(),
                     shard_stripe_size: self.shard.stripe_size.0,
+                    read_only: false,
                     tenant_conf: self.config.clone(),
                 },
             ));
@@ -544,6 +546,7 @@ pub(crate) fn attached_location_conf(
         shard_number: shard.number.0,
         shard_count: shard.count.literal(),
         shard_stripe_size: shard.stripe_size.0,
+        read_only: false,
         tenant_conf: config.clone(),
     }
 }
@@ -559,6 +562,7 @@ pub(crate) fn secondary_location_conf(
         shard_number: shard.number.0,
         shard_count: shard.count.literal(),
         shard_stripe_size: shard.stripe_size.0,
+        read_only: false,
         tenant_conf: config.clone(),
     }
 }
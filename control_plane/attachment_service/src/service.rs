@@ -440,6 +440,7 @@ impl Service {
                         shard_number: tenant_shard_id.shard_number.0,
                         shard_count: tenant_shard_id.shard_count.literal(),
                         shard_stripe_size: 0,
+                        read_only: false,
                         tenant_conf: models::TenantConfig::default(),
                     },
                     None,
@@ -1633,9 +1634,11 @@ impl Service {
         )
         .await?;
 
-        // Propagate the LSN that shard zero picked, if caller didn't provide one
+        // Propagate the LSN that shard zero picked, if caller didn't provide one.  This also
+        // covers a start timestamp, which only shard zero can resolve to an LSN.
         if create_req.ancestor_timeline_id.is_some() && create_req.ancestor_start_lsn.is_none() {
             create_req.ancestor_start_lsn = timeline_info.ancestor_lsn;
+            create_req.ancestor_start_timestamp = None;
         }
 
         // Create timeline on remaining shards with number >0
@@ -480,6 +480,8 @@ async fn handle_tenant(
                         ancestor_start_lsn: None,
                         existing_initdb_timeline_id: None,
                         pg_version: Some(pg_version),
+                        ancestor_start_timestamp: None,
+                        force_below_gc_cutoff: false,
                     },
                 )
                 .await?;
@@ -633,6 +635,8 @@ async fn handle_timeline(timeline_match: &ArgMatches, env: &mut local_env::Local
                 existing_initdb_timeline_id: None,
                 ancestor_start_lsn: None,
                 pg_version: Some(pg_version),
+                ancestor_start_timestamp: None,
+                force_below_gc_cutoff: false,
             };
             let timeline_info = attachment_service
                 .tenant_timeline_create(tenant_id, create_req)
@@ -730,6 +734,8 @@ async fn handle_timeline(timeline_match: &ArgMatches, env: &mut local_env::Local
                 existing_initdb_timeline_id: None,
                 ancestor_start_lsn: start_lsn,
                 pg_version: None,
+                ancestor_start_timestamp: None,
+                force_below_gc_cutoff: false,
             };
             let timeline_info = attachment_service
                 .tenant_timeline_create(tenant_id, create_req)
@@ -9,7 +9,7 @@ use std::collections::HashMap;
 
 use std::io;
 use std::io::Write;
-use std::num::NonZeroU64;
+use std::num::{NonZeroU64, NonZeroUsize};
 use std::path::PathBuf;
 use std::process::Command;
 use std::time::Duration;
@@ -343,6 +343,11 @@ impl PageServerNode {
                 .map(|x| x.parse::<u64>())
                 .transpose()?,
             checkpoint_timeout: settings.remove("checkpoint_timeout").map(|x| x.to_string()),
+            max_inmem_layer_bytes: settings
+                .remove("max_inmem_layer_bytes")
+                .map(|x| x.parse::<u64>())
+                .transpose()
+                .context("Failed to parse 'max_inmem_layer_bytes' as integer")?,
             compaction_target_size: settings
                 .remove("compaction_target_size")
                 .map(|x| x.parse::<u64>())
@@ -388,6 +393,11 @@ impl PageServerNode {
                 .map(|x| x.parse::<u64>())
                 .transpose()
                 .context("Failed to parse 'min_resident_size_override' as integer")?,
+            local_disk_high_watermark_bytes: settings
+                .remove("local_disk_high_watermark_bytes")
+                .map(|x| x.parse::<u64>())
+                .transpose()
+                .context("Failed to parse 'local_disk_high_watermark_bytes' as integer")?,
             evictions_low_residence_duration_metric_threshold: settings
                 .remove("evictions_low_residence_duration_metric_threshold")
                 .map(|x| x.to_string()),
@@ -396,6 +406,16 @@ impl PageServerNode {
                 .map(|x| x.parse::<bool>())
                 .transpose()
                 .context("Failed to parse 'gc_feedback' as bool")?,
+            gc_parallelism: settings
+                .remove("gc_parallelism")
+                .map(|x| x.parse::<NonZeroUsize>())
+                .transpose()
+                .context("Failed to parse 'gc_parallelism' as non zero integer")?,
+            skip_orphan_timelines: settings
+                .remove("skip_orphan_timelines")
+                .map(|x| x.parse::<bool>())
+                .transpose()
+                .context("Failed to parse 'skip_orphan_timelines' as bool")?,
             heatmap_period: settings.remove("heatmap_period").map(|x| x.to_string()),
             lazy_slru_download: settings
                 .remove("lazy_slru_download")
@@ -407,6 +427,16 @@ impl PageServerNode {
                 .map(serde_json::from_str)
                 .transpose()
                 .context("parse `timeline_get_throttle` from json")?,
+            synthetic_size_calculation_enabled: settings
+                .remove("synthetic_size_calculation_enabled")
+                .map(|x| x.parse::<bool>())
+                .transpose()
+                .context("Failed to parse 'synthetic_size_calculation_enabled' as bool")?,
+            timeline_gc_horizon_overrides: settings
+                .remove("timeline_gc_horizon_overrides")
+                .map(serde_json::from_str)
+                .transpose()
+                .context("parse `timeline_gc_horizon_overrides` from json")?,
         };
         if !settings.is_empty() {
             bail!("Unrecognized tenant settings: {settings:?}")
@@ -449,6 +479,11 @@ impl PageServerNode {
                     .transpose()
                     .context("Failed to parse 'checkpoint_distance' as an integer")?,
                 checkpoint_timeout: settings.remove("checkpoint_timeout").map(|x| x.to_string()),
+                max_inmem_layer_bytes: settings
+                    .remove("max_inmem_layer_bytes")
+                    .map(|x| x.parse::<u64>())
+                    .transpose()
+                    .context("Failed to parse 'max_inmem_layer_bytes' as an integer")?,
                 compaction_target_size: settings
                     .remove("compaction_target_size")
                     .map(|x| x.parse::<u64>())
@@ -498,6 +533,11 @@ impl PageServerNode {
                     .map(|x| x.parse::<u64>())
                     .transpose()
                     .context("Failed to parse 'min_resident_size_override' as an integer")?,
+                local_disk_high_watermark_bytes: settings
+                    .remove("local_disk_high_watermark_bytes")
+                    .map(|x| x.parse::<u64>())
+                    .transpose()
+                    .context("Failed to parse 'local_disk_high_watermark_bytes' as an integer")?,
                 evictions_low_residence_duration_metric_threshold: settings
                     .remove("evictions_low_residence_duration_metric_threshold")
                     .map(|x| x.to_string()),
@@ -506,6 +546,16 @@ impl PageServerNode {
                     .map(|x| x.parse::<bool>())
                     .transpose()
                     .context("Failed to parse 'gc_feedback' as bool")?,
+                gc_parallelism: settings
+                    .remove("gc_parallelism")
+                    .map(|x| x.parse::<NonZeroUsize>())
+                    .transpose()
+                    .context("Failed to parse 'gc_parallelism' as non zero integer")?,
+                skip_orphan_timelines: settings
+                    .remove("skip_orphan_timelines")
+                    .map(|x| x.parse::<bool>())
+                    .transpose()
+                    .context("Failed to parse 'skip_orphan_timelines' as bool")?,
                 heatmap_period: settings.remove("heatmap_period").map(|x| x.to_string()),
                 lazy_slru_download: settings
                     .remove("lazy_slru_download")
@@ -517,6 +567,16 @@ impl PageServerNode {
                     .map(serde_json::from_str)
                     .transpose()
                     .context("parse `timeline_get_throttle` from json")?,
+                synthetic_size_calculation_enabled: settings
+                    .remove("synthetic_size_calculation_enabled")
+                    .map(|x| x.parse::<bool>())
+                    .transpose()
+                    .context("Failed to parse 'synthetic_size_calculation_enabled' as bool")?,
+                timeline_gc_horizon_overrides: settings
+                    .remove("timeline_gc_horizon_overrides")
+                    .map(serde_json::from_str)
+                    .transpose()
+                    .context("parse `timeline_gc_horizon_overrides` from json")?,
             }
         };
 
@@ -572,6 +632,8 @@ impl PageServerNode {
             ancestor_timeline_id,
             pg_version,
             existing_initdb_timeline_id,
+            ancestor_start_timestamp: None,
+            force_below_gc_cutoff: false,
         };
         Ok(self
             .http_client
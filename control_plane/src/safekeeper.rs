@@ -159,6 +159,9 @@ impl SafekeeperNode {
 
         if let Some(ref remote_storage) = self.conf.remote_storage {
             args.extend(["--remote-storage".to_owned(), remote_storage.clone()]);
+        } else {
+            // The safekeeper refuses to start with WAL backup enabled but nowhere to offload to.
+            args.push("--disable-wal-backup".to_owned());
         }
 
         let key_path = self.env.base_data_dir.join("auth_public_key.pem");
@@ -250,6 +250,12 @@ impl<P: Atomic> GenericCounterPair<P> {
     pub fn dec_by(&self, v: P::T) {
         self.dec.inc_by(v);
     }
+
+    /// The total amount the gauge has been increased by, ignoring decreases.
+    #[inline]
+    pub fn get_inc(&self) -> P::T {
+        self.inc.get()
+    }
 }
 
 impl<P: Atomic> Clone for GenericCounterPair<P> {
@@ -17,6 +17,7 @@ use serde_with::serde_as;
 use strum_macros;
 use utils::{
     completion,
+    generation::Generation,
     history_buffer::HistoryBufferWithDropCounter,
     id::{NodeId, TenantId, TimelineId},
     lsn::Lsn,
@@ -73,7 +74,8 @@ pub enum TenantState {
     ///
     /// `set_stopping()` and `set_broken()` do not work in this state and wait for it to pass.
     Attaching,
-    /// The tenant is transitioning from Loading/Attaching to Active.
+    /// The tenant is transitioning from Loading/Attaching to Active (or, in testing builds only,
+    /// from Broken back to Active).
     ///
     /// While in this state, the individual timelines are being activated.
     ///
@@ -122,7 +124,8 @@ impl TenantState {
             Self::Loading | Self::Activating(ActivatingFrom::Loading) => Attached,
             // We only reach Active after successful load / attach.
             // So, call atttachment status Attached.
-            Self::Active => Attached,
+            // Reactivating a Broken tenant only happens in tests, on a tenant that had been Active.
+            Self::Active | Self::Activating(ActivatingFrom::Broken) => Attached,
             // If the (initial or resumed) attach procedure fails, the tenant becomes Broken.
             // However, it also becomes Broken if the regular load fails.
             // From Console's perspective there's no practical difference
@@ -164,10 +167,15 @@ pub enum ActivatingFrom {
     Loading,
     /// Arrived to [`TenantState::Activating`] from [`TenantState::Attaching`]
     Attaching,
+    /// Arrived to [`TenantState::Activating`] from [`TenantState::Broken`], which is only
+    /// possible in testing builds
+    Broken,
 }
 
 /// A state of a timeline in pageserver's memory.
-#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, strum_macros::IntoStaticStr,
+)]
 pub enum TimelineState {
     /// The timeline is recognized by the pageserver but is not yet operational.
     /// In particular, the walreceiver connection loop is not running for this timeline.
@@ -192,7 +200,15 @@ pub struct TimelineCreateRequest {
     pub existing_initdb_timeline_id: Option<TimelineId>,
     #[serde(default)]
     pub ancestor_start_lsn: Option<Lsn>,
+    /// Branch at the last commit on the ancestor at or before this time, instead of at
+    /// `ancestor_start_lsn`.
+    #[serde(default, with = "humantime_serde")]
+    pub ancestor_start_timestamp: Option<SystemTime>,
     pub pg_version: Option<u32>,
+    /// Skip the checks that `ancestor_start_lsn` is above the ancestor's GC cutoffs.  Only for
+    /// disaster recovery, when we know that the data at the branch point still exists.
+    #[serde(default)]
+    pub force_below_gc_cutoff: bool,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -269,6 +285,7 @@ impl std::ops::Deref for TenantCreateRequest {
 pub struct TenantConfig {
     pub checkpoint_distance: Option<u64>,
     pub checkpoint_timeout: Option<String>,
+    pub max_inmem_layer_bytes: Option<u64>,
     pub compaction_target_size: Option<u64>,
     pub compaction_period: Option<String>,
     pub compaction_threshold: Option<usize>,
@@ -282,11 +299,16 @@ pub struct TenantConfig {
     pub trace_read_requests: Option<bool>,
     pub eviction_policy: Option<EvictionPolicy>,
     pub min_resident_size_override: Option<u64>,
+    pub local_disk_high_watermark_bytes: Option<u64>,
     pub evictions_low_residence_duration_metric_threshold: Option<String>,
     pub gc_feedback: Option<bool>,
+    pub gc_parallelism: Option<NonZeroUsize>,
+    pub skip_orphan_timelines: Option<bool>,
     pub heatmap_period: Option<String>,
     pub lazy_slru_download: Option<bool>,
     pub timeline_get_throttle: Option<ThrottleConfig>,
+    pub synthetic_size_calculation_enabled: Option<bool>,
+    pub timeline_gc_horizon_overrides: Option<HashMap<TimelineId, u64>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -383,6 +405,11 @@ pub struct LocationConfig {
     #[serde(default)]
     pub shard_stripe_size: u32,
 
+    /// If attaching, serve reads only: don't ingest WAL, run GC or compaction, or upload
+    /// anything to remote storage.
+    #[serde(default)]
+    pub read_only: bool,
+
     // This configuration only affects attached mode, but should be provided irrespective
     // of the mode, as a secondary location might transition on startup if the response
     // to the `/re-attach` control plane API requests it.
@@ -500,6 +527,32 @@ pub struct TenantInfo {
     pub generation: Option<u32>,
 }
 
+/// Number of tenant shard slots on a pageserver, by the kind of slot.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TenantSlotCounts {
+    pub attached: usize,
+    pub secondary: usize,
+    /// Slots that are currently being transitioned between states, e.g. during attach or detach.
+    pub in_progress: usize,
+}
+
+/// An attached tenant shard that has not reached [`TenantState::Active`] yet, e.g. because it
+/// is still waiting for its turn in the startup warmup.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TenantNotYetActive {
+    pub id: TenantShardId,
+    pub state: TenantState,
+}
+
+/// A tenant shard whose slot is in the middle of a transition, e.g. an attach or detach, and
+/// how long it has been in that state.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TenantInProgress {
+    pub id: TenantShardId,
+    #[serde(with = "humantime_serde")]
+    pub age: Duration,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct TenantDetails {
     #[serde(flatten)]
@@ -508,6 +561,14 @@ pub struct TenantDetails {
     pub walredo: Option<WalRedoManagerStatus>,
 
     pub timelines: Vec<TimelineId>,
+
+    /// Sum of the sizes of the layer files present on the pageserver's local disk.
+    #[serde(default)]
+    pub local_physical_size: u64,
+    /// Sum of the sizes of the layer files in remote storage, as also returned per timeline
+    /// by the `remote_size` endpoint.
+    #[serde(default)]
+    pub remote_physical_size: u64,
 }
 
 /// This represents the output of the "timeline_detail" and "timeline_list" API calls.
@@ -670,6 +731,47 @@ pub enum HistoricLayerInfo {
     },
 }
 
+/// A disagreement between a timeline's layer map and its remote index, as reported by the
+/// layer audit debug endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum LayerDiscrepancy {
+    /// The layer is in the layer map, but not in the index.
+    NotInIndex {
+        layer_file_name: String,
+        generation: Generation,
+    },
+    /// The layer is in the index, but not in the layer map.
+    NotInLayerMap {
+        layer_file_name: String,
+        generation: Generation,
+    },
+    /// The layer is in both, but the size or generation differ.
+    MetadataMismatch {
+        layer_file_name: String,
+        local_size: u64,
+        remote_size: u64,
+        local_generation: Generation,
+        remote_generation: Generation,
+    },
+}
+
+impl LayerDiscrepancy {
+    pub fn layer_file_name(&self) -> &str {
+        match self {
+            LayerDiscrepancy::NotInIndex {
+                layer_file_name, ..
+            }
+            | LayerDiscrepancy::NotInLayerMap {
+                layer_file_name, ..
+            }
+            | LayerDiscrepancy::MetadataMismatch {
+                layer_file_name, ..
+            } => layer_file_name,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DownloadRemoteLayersTaskSpawnRequest {
     pub max_concurrent_downloads: NonZeroUsize,
@@ -1181,6 +1283,28 @@ mod tests {
         assert!(format!("{:?}", &original_broken.state).contains("backtrace info"));
     }
 
+    #[test]
+    fn test_tenantdetails_without_physical_sizes() {
+        // Older pageservers do not report the physical sizes
+        let id = TenantShardId::unsharded(TenantId::generate());
+        let details = json!({
+            "id": id.to_string(),
+            "state": {
+                "slug": "Active",
+            },
+            "current_physical_size": 42,
+            "attachment_status": {
+                "slug": "attached",
+            },
+            "walredo": null,
+            "timelines": [],
+        });
+        let details = serde_json::from_value::<TenantDetails>(details).unwrap();
+        assert_eq!(details.tenant_info.id, id);
+        assert_eq!(details.local_physical_size, 0);
+        assert_eq!(details.remote_physical_size, 0);
+    }
+
     #[test]
     fn test_reject_unknown_field() {
         let id = TenantId::generate();
@@ -1,5 +1,6 @@
 use anyhow::*;
-use clap::{value_parser, Arg, ArgMatches, Command};
+use clap::builder::PossibleValuesParser;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
 use std::{path::PathBuf, str::FromStr};
 use wal_craft::*;
 
@@ -9,22 +10,18 @@ fn main() -> Result<()> {
     let arg_matches = cli().get_matches();
 
     let wal_craft = |arg_matches: &ArgMatches, client| {
-        let (intermediate_lsns, end_of_wal_lsn) = match arg_matches
+        let crafter_name = arg_matches
             .get_one::<String>("type")
             .map(|s| s.as_str())
-            .context("'type' is required")?
+            .context("'type' is required")?;
+        let craft = match all_crafters()
+            .into_iter()
+            .find(|(name, _)| *name == crafter_name)
         {
-            Simple::NAME => Simple::craft(client)?,
-            LastWalRecordXlogSwitch::NAME => LastWalRecordXlogSwitch::craft(client)?,
-            LastWalRecordXlogSwitchEndsOnPageBoundary::NAME => {
-                LastWalRecordXlogSwitchEndsOnPageBoundary::craft(client)?
-            }
-            WalRecordCrossingSegmentFollowedBySmallOne::NAME => {
-                WalRecordCrossingSegmentFollowedBySmallOne::craft(client)?
-            }
-            LastWalRecordCrossingSegment::NAME => LastWalRecordCrossingSegment::craft(client)?,
-            a => panic!("Unknown --type argument: {a}"),
+            Some((_, craft)) => craft,
+            None => panic!("Unknown --type argument: {crafter_name}"),
         };
+        let (intermediate_lsns, end_of_wal_lsn) = craft(client)?;
         for lsn in intermediate_lsns {
             println!("intermediate_lsn = {lsn}");
         }
@@ -54,6 +51,13 @@ fn main() -> Result<()> {
                     .get_one::<PathBuf>("datadir")
                     .context("'datadir' is required")?
                     .to_owned(),
+                extra_config: arg_matches
+                    .get_many::<String>("extra-config")
+                    .map(|values| values.cloned().collect())
+                    .unwrap_or_default(),
+                wal_segment_size: arg_matches.get_one::<usize>("wal-segment-size").copied(),
+                connect_timeout: connect_timeout_from_env()?,
+                connect_retry_interval: DEFAULT_CONNECT_RETRY_INTERVAL,
             };
             cfg.initdb()?;
             let srv = cfg.start_server()?;
@@ -80,13 +84,9 @@ fn main() -> Result<()> {
 fn cli() -> Command {
     let type_arg = &Arg::new("type")
         .help("Type of WAL to craft")
-        .value_parser([
-            Simple::NAME,
-            LastWalRecordXlogSwitch::NAME,
-            LastWalRecordXlogSwitchEndsOnPageBoundary::NAME,
-            WalRecordCrossingSegmentFollowedBySmallOne::NAME,
-            LastWalRecordCrossingSegment::NAME,
-        ])
+        .value_parser(PossibleValuesParser::new(
+            all_crafters().into_iter().map(|(name, _)| name),
+        ))
         .required(true);
 
     Command::new("Postgres WAL crafter")
@@ -120,6 +120,18 @@ fn cli() -> Command {
                     .required(true)
 
                 )
+                .arg(
+                    Arg::new("extra-config")
+                        .long("extra-config")
+                        .help("Additional name=value setting for the Postgres server, overrides the required configuration. May be repeated")
+                        .action(ArgAction::Append)
+                )
+                .arg(
+                    Arg::new("wal-segment-size")
+                        .long("wal-segment-size")
+                        .help("WAL segment size in bytes to pass to initdb, must be a whole number of megabytes")
+                        .value_parser(value_parser!(usize))
+                )
         )
         .subcommand(
             Command::new("in-existing")
@@ -1,4 +1,4 @@
-use anyhow::{bail, ensure};
+use anyhow::{bail, ensure, Context};
 use camino_tempfile::{tempdir, Utf8TempDir};
 use log::*;
 use postgres::types::PgLsn;
@@ -30,12 +30,53 @@ pub struct Conf {
     pub pg_version: u32,
     pub pg_distrib_dir: PathBuf,
     pub datadir: PathBuf,
+    /// Additional `name=value` settings passed to `postgres` after [`REQUIRED_POSTGRES_CONFIG`],
+    /// so they take precedence over it. [`ensure_server_config`] does not check settings
+    /// overridden here. Overriding `shared_preload_libraries` is rejected: the neon extension
+    /// must be loaded at startup.
+    pub extra_config: Vec<String>,
+    /// WAL segment size in bytes, passed to `initdb` as `--wal-segsize`. Must be a whole
+    /// number of megabytes. Defaults to [`WAL_SEGMENT_SIZE`].
+    pub wal_segment_size: Option<usize>,
+    /// How long [`PostgresServer::connect_with_timeout`] keeps trying to connect to a server
+    /// started with [`Conf::start_server`]. See [`connect_timeout_from_env`].
+    pub connect_timeout: Duration,
+    /// How long to wait between connection attempts in [`PostgresServer::connect_with_timeout`].
+    pub connect_retry_interval: Duration,
 }
 
 pub struct PostgresServer {
     process: std::process::Child,
-    _unix_socket_dir: Utf8TempDir,
+    unix_socket_dir: Utf8TempDir,
+    /// File the server's stderr is redirected to, lives in `unix_socket_dir`.
+    log_path: PathBuf,
     client_config: postgres::Config,
+    connect_retry_interval: Duration,
+}
+
+/// How many trailing lines of the server log to include in connection errors.
+const SERVER_LOG_TAIL_LINES: usize = 20;
+
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_CONNECT_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Environment variable overriding [`DEFAULT_CONNECT_TIMEOUT`], in seconds. Slow machines may
+/// need longer for a freshly initialized server to start accepting connections.
+pub const CONNECT_TIMEOUT_ENV: &str = "WAL_CRAFT_CONNECT_TIMEOUT_SECS";
+
+/// Returns the connection timeout set by [`CONNECT_TIMEOUT_ENV`], or [`DEFAULT_CONNECT_TIMEOUT`]
+/// if it is not set.
+pub fn connect_timeout_from_env() -> anyhow::Result<Duration> {
+    match std::env::var(CONNECT_TIMEOUT_ENV) {
+        Ok(secs) => {
+            let secs: u64 = secs
+                .parse()
+                .with_context(|| format!("{CONNECT_TIMEOUT_ENV} is not a number of seconds"))?;
+            Ok(Duration::from_secs(secs))
+        }
+        Err(std::env::VarError::NotPresent) => Ok(DEFAULT_CONNECT_TIMEOUT),
+        Err(e) => Err(e).context(CONNECT_TIMEOUT_ENV),
+    }
 }
 
 pub static REQUIRED_POSTGRES_CONFIG: [&str; 4] = [
@@ -46,7 +87,19 @@ pub static REQUIRED_POSTGRES_CONFIG: [&str; 4] = [
     "autovacuum=off",
 ];
 
+/// Custom GUC carrying a comma-separated list of settings overridden via [`Conf::extra_config`],
+/// so that [`ensure_server_config`] can skip them.
+const OVERRIDDEN_SETTINGS_GUC: &str = "wal_craft.overridden_settings";
+
+/// Custom GUC carrying [`Conf::wal_segment_size`], so that [`ensure_server_config`] knows
+/// which segment size to expect.
+const WAL_SEGMENT_SIZE_GUC: &str = "wal_craft.wal_segment_size";
+
 impl Conf {
+    /// Directory of the Postgres installation for [`Conf::pg_version`].
+    ///
+    /// Only the versions `postgres_ffi` has bindings for are accepted, see
+    /// [`postgres_ffi::for_all_postgres_versions`].
     pub fn pg_distrib_dir(&self) -> anyhow::Result<PathBuf> {
         let path = self.pg_distrib_dir.clone();
 
@@ -90,12 +143,19 @@ impl Conf {
             "Running initdb in {:?} with user \"postgres\"",
             self.datadir
         );
-        let output = self
-            .new_pg_command("initdb")?
-            .arg("-D")
+        let mut cmd = self.new_pg_command("initdb")?;
+        cmd.arg("-D")
             .arg(&self.datadir)
-            .args(["-U", "postgres", "--no-instructions", "--no-sync"])
-            .output()?;
+            .args(["-U", "postgres", "--no-instructions", "--no-sync"]);
+        if let Some(wal_segment_size) = self.wal_segment_size {
+            const MB: usize = 1024 * 1024;
+            ensure!(
+                wal_segment_size > 0 && wal_segment_size % MB == 0,
+                "WAL segment size {wal_segment_size} is not a whole number of megabytes"
+            );
+            cmd.arg(format!("--wal-segsize={}", wal_segment_size / MB));
+        }
+        let output = cmd.output()?;
         debug!("initdb output: {:?}", output);
         ensure!(
             output.status.success(),
@@ -106,10 +166,39 @@ impl Conf {
         Ok(())
     }
 
+    /// Validates [`Conf::extra_config`] and returns the `-c` arguments it translates to.
+    fn extra_config_args(&self) -> anyhow::Result<Vec<String>> {
+        if self.extra_config.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut overridden = Vec::with_capacity(self.extra_config.len());
+        for cfg in &self.extra_config {
+            let Some((name, _)) = cfg.split_once('=') else {
+                bail!("Extra config {cfg:?} is not in the name=value format");
+            };
+            let name = name.trim();
+            ensure!(
+                name != "shared_preload_libraries",
+                "Overriding shared_preload_libraries is not supported, the neon extension must be loaded at startup"
+            );
+            overridden.push(name);
+        }
+        let mut args = Vec::with_capacity(self.extra_config.len() + 1);
+        args.extend(self.extra_config.iter().cloned());
+        args.push(format!(
+            "{OVERRIDDEN_SETTINGS_GUC}={}",
+            overridden.join(",")
+        ));
+        Ok(args)
+    }
+
     pub fn start_server(&self) -> anyhow::Result<PostgresServer> {
         info!("Starting Postgres server in {:?}", self.datadir);
+        let extra_config_args = self.extra_config_args()?;
         let unix_socket_dir = tempdir()?; // We need a directory with a short name for Unix socket (up to 108 symbols)
         let unix_socket_dir_path = unix_socket_dir.path().to_owned();
+        let log_path: PathBuf = unix_socket_dir_path.join("postgres.log").into();
+        let log_file = std::fs::File::create(&log_path)?;
         let server_process = self
             .new_pg_command("postgres")?
             .args(["-c", "listen_addresses="])
@@ -118,17 +207,27 @@ impl Conf {
             .arg("-D")
             .arg(&self.datadir)
             .args(REQUIRED_POSTGRES_CONFIG.iter().flat_map(|cfg| ["-c", cfg]))
+            .args(extra_config_args.iter().flat_map(|cfg| ["-c", cfg]))
+            .args(
+                self.wal_segment_size
+                    .map(|size| format!("{WAL_SEGMENT_SIZE_GUC}={size}"))
+                    .iter()
+                    .flat_map(|cfg| ["-c", cfg]),
+            )
+            .stderr(log_file)
             .spawn()?;
         let server = PostgresServer {
             process: server_process,
-            _unix_socket_dir: unix_socket_dir,
+            unix_socket_dir,
+            log_path,
             client_config: {
                 let mut c = postgres::Config::new();
                 c.host_path(&unix_socket_dir_path);
                 c.user("postgres");
-                c.connect_timeout(Duration::from_millis(10000));
+                c.connect_timeout(self.connect_timeout);
                 c
             },
+            connect_retry_interval: self.connect_retry_interval,
         };
         Ok(server)
     }
@@ -152,18 +251,130 @@ impl Conf {
         debug!("waldump output: {:?}", output);
         Ok(output)
     }
+
+    /// Like [`Conf::pg_waldump`], but parses the output into records.
+    pub fn pg_waldump_records(
+        &self,
+        first_segment_name: &str,
+        last_segment_name: &str,
+    ) -> anyhow::Result<WalDump> {
+        let output = self.pg_waldump(first_segment_name, last_segment_name)?;
+        let mut dump = WalDump::default();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        for line in stdout.lines().chain(stderr.lines()) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match WalDumpRecord::parse(line) {
+                Some(record) => dump.records.push(record),
+                None => dump.warnings.push(line.to_string()),
+            }
+        }
+        Ok(dump)
+    }
+}
+
+/// Parsed output of `pg_waldump`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WalDump {
+    pub records: Vec<WalDumpRecord>,
+    /// Output lines which could not be parsed as records, e.g. the final
+    /// "invalid record length" error at the end of WAL.
+    pub warnings: Vec<String>,
+}
+
+/// A single record line of `pg_waldump` output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalDumpRecord {
+    pub lsn: PgLsn,
+    pub prev: PgLsn,
+    pub rmgr: String,
+    /// Total length of the record, including the backup blocks.
+    pub len: u32,
+    pub description: String,
+}
+
+impl WalDumpRecord {
+    /// Parses a line like
+    /// `rmgr: Heap len (rec/tot): 54/ 54, tx: 735, lsn: 0/0169A6C8, prev 0/0169A690, desc: INSERT off 2`
+    fn parse(line: &str) -> Option<Self> {
+        let rest = line.strip_prefix("rmgr:")?;
+        let (rmgr, rest) = rest.split_once("len (rec/tot):")?;
+        let (lens, rest) = rest.split_once(',')?;
+        let (_, total_len) = lens.split_once('/')?;
+        let (_, rest) = rest.split_once("lsn:")?;
+        let (lsn, rest) = rest.split_once(", prev")?;
+        let (prev, description) = rest.split_once(", desc:")?;
+        Some(WalDumpRecord {
+            lsn: lsn.trim().parse().ok()?,
+            prev: prev.trim().parse().ok()?,
+            rmgr: rmgr.trim().to_string(),
+            len: total_len.trim().parse().ok()?,
+            description: description.trim().to_string(),
+        })
+    }
 }
 
 impl PostgresServer {
+    /// Sets up a streaming standby of `primary` in the [`Conf::datadir`] of `conf` and starts it.
+    ///
+    /// The data directory is created with `pg_basebackup --write-recovery-conf`, which creates
+    /// `standby.signal` and sets `primary_conninfo` to the primary's Unix socket. Use
+    /// [`craft_with_standby`] to craft WAL on the primary and wait for the standby to replay it.
+    pub fn start_standby(conf: &Conf, primary: &PostgresServer) -> anyhow::Result<PostgresServer> {
+        if let Some(parent) = conf.datadir.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let primary_socket_dir = primary.unix_socket_dir.path();
+        info!(
+            "Running pg_basebackup into {:?} from the primary at {:?}",
+            conf.datadir, primary_socket_dir
+        );
+        let output = conf
+            .new_pg_command("pg_basebackup")?
+            .arg("-D")
+            .arg(&conf.datadir)
+            .arg("-h")
+            .arg(primary_socket_dir)
+            .args([
+                "-U",
+                "postgres",
+                "--write-recovery-conf",
+                "--checkpoint=fast",
+                "--no-sync",
+            ])
+            .output()?;
+        debug!("pg_basebackup output: {:?}", output);
+        ensure!(
+            output.status.success(),
+            "pg_basebackup failed, stdout and stderr follow:\n{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr),
+        );
+        conf.start_server()
+    }
+
     pub fn connect_with_timeout(&self) -> anyhow::Result<Client> {
         let retry_until = Instant::now() + *self.client_config.get_connect_timeout().unwrap();
         while Instant::now() < retry_until {
             if let Ok(client) = self.client_config.connect(postgres::NoTls) {
                 return Ok(client);
             }
-            std::thread::sleep(Duration::from_millis(100));
+            std::thread::sleep(self.connect_retry_interval);
         }
-        bail!("Connection timed out");
+        let log = self.last_server_log();
+        let log_lines: Vec<&str> = log.lines().collect();
+        bail!(
+            "Connection timed out, last server log lines:\n{}",
+            log_lines[log_lines.len().saturating_sub(SERVER_LOG_TAIL_LINES)..].join("\n")
+        );
+    }
+
+    /// Returns everything the server has written to its log (stderr) so far.
+    pub fn last_server_log(&self) -> String {
+        std::fs::read_to_string(&self.log_path)
+            .unwrap_or_else(|e| format!("<unable to read server log {:?}: {e}>", self.log_path))
     }
 
     pub fn kill(mut self) {
@@ -198,6 +409,63 @@ pub trait PostgresClientExt: postgres::GenericClient {
             .query_one("SELECT pg_current_wal_flush_lsn()", &[])?
             .get(0))
     }
+    /// Returns the server's `wal_segment_size` in bytes.
+    fn wal_segment_size(&mut self) -> anyhow::Result<u64> {
+        let wal_segment_size = self.query_one(
+            "select cast(setting as bigint) as setting, unit \
+             from pg_settings where name = 'wal_segment_size'",
+            &[],
+        )?;
+        ensure!(
+            wal_segment_size.get::<_, String>("unit") == "B",
+            "Unexpected wal_segment_size unit"
+        );
+        Ok(wal_segment_size.get::<_, i64>("setting") as u64)
+    }
+    /// Returns the end of the last record replayed by a standby.
+    fn pg_last_wal_replay_lsn(&mut self) -> anyhow::Result<PgLsn> {
+        self.query_one("SELECT pg_last_wal_replay_lsn()", &[])?
+            .get::<_, Option<PgLsn>>(0)
+            .context("pg_last_wal_replay_lsn() is null, the server is not a standby")
+    }
+    /// Polls `pg_last_wal_replay_lsn` until it reaches `target` or `timeout` elapses.
+    fn wait_for_replay_lsn(&mut self, target: PgLsn, timeout: Duration) -> anyhow::Result<()> {
+        let started_at = Instant::now();
+        loop {
+            let replay_lsn = self.pg_last_wal_replay_lsn()?;
+            if replay_lsn >= target {
+                return Ok(());
+            }
+            if started_at.elapsed() >= timeout {
+                bail!(
+                    "Timed out after {:?} waiting for replay_lsn to reach {}, current replay_lsn = {}",
+                    timeout,
+                    target,
+                    replay_lsn
+                );
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+    /// Polls `pg_current_wal_flush_lsn` until it reaches `target` or `timeout` elapses.
+    fn wait_for_flush_lsn(&mut self, target: PgLsn, timeout: Duration) -> anyhow::Result<()> {
+        let started_at = Instant::now();
+        loop {
+            let flush_lsn = self.pg_current_wal_flush_lsn()?;
+            if flush_lsn >= target {
+                return Ok(());
+            }
+            if started_at.elapsed() >= timeout {
+                bail!(
+                    "Timed out after {:?} waiting for flush_lsn to reach {}, current flush_lsn = {}",
+                    timeout,
+                    target,
+                    flush_lsn
+                );
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
 }
 
 impl<C: postgres::GenericClient> PostgresClientExt for C {}
@@ -205,25 +473,46 @@ impl<C: postgres::GenericClient> PostgresClientExt for C {}
 pub fn ensure_server_config(client: &mut impl postgres::GenericClient) -> anyhow::Result<()> {
     client.execute("create extension if not exists neon_test_utils", &[])?;
 
-    let wal_keep_size: String = client.query_one("SHOW wal_keep_size", &[])?.get(0);
-    ensure!(wal_keep_size == "50MB");
-    let wal_writer_delay: String = client.query_one("SHOW wal_writer_delay", &[])?.get(0);
-    ensure!(wal_writer_delay == "10s");
-    let autovacuum: String = client.query_one("SHOW autovacuum", &[])?.get(0);
-    ensure!(autovacuum == "off");
-
-    let wal_segment_size = client.query_one(
-        "select cast(setting as bigint) as setting, unit \
-         from pg_settings where name = 'wal_segment_size'",
-        &[],
-    )?;
-    ensure!(
-        wal_segment_size.get::<_, String>("unit") == "B",
-        "Unexpected wal_segment_size unit"
-    );
+    let overridden: Option<String> = client
+        .query_one(
+            "SELECT current_setting($1, true)",
+            &[&OVERRIDDEN_SETTINGS_GUC],
+        )?
+        .get(0);
+    let overridden: Vec<&str> = overridden
+        .as_deref()
+        .map(|s| s.split(',').collect())
+        .unwrap_or_default();
+    let mut ensure_setting = |name: &str, expected: &str| -> anyhow::Result<()> {
+        if overridden.contains(&name) {
+            return Ok(());
+        }
+        let value: String = client
+            .query_one(format!("SHOW {name}").as_str(), &[])?
+            .get(0);
+        ensure!(
+            value == expected,
+            "Unexpected {name}: {value}, expected {expected}"
+        );
+        Ok(())
+    };
+    ensure_setting("wal_keep_size", "50MB")?;
+    ensure_setting("wal_writer_delay", "10s")?;
+    ensure_setting("autovacuum", "off")?;
+
+    let expected_wal_segment_size: Option<String> = client
+        .query_one("SELECT current_setting($1, true)", &[&WAL_SEGMENT_SIZE_GUC])?
+        .get(0);
+    let expected_wal_segment_size = match expected_wal_segment_size.as_deref() {
+        None | Some("") => WAL_SEGMENT_SIZE as u64,
+        Some(size) => size
+            .parse()
+            .with_context(|| format!("Invalid {WAL_SEGMENT_SIZE_GUC}: {size}"))?,
+    };
+    let wal_segment_size = client.wal_segment_size()?;
     ensure!(
-        wal_segment_size.get::<_, i64>("setting") == WAL_SEGMENT_SIZE as i64,
-        "Unexpected wal_segment_size in bytes"
+        wal_segment_size == expected_wal_segment_size,
+        "Unexpected wal_segment_size in bytes: {wal_segment_size}, expected {expected_wal_segment_size}"
     );
 
     Ok(())
@@ -239,6 +528,59 @@ pub trait Crafter {
     fn craft(client: &mut impl postgres::GenericClient) -> anyhow::Result<(Vec<PgLsn>, PgLsn)>;
 }
 
+/// Runs the crafter `C` against `primary`, then waits until `standby`, e.g. one started with
+/// [`PostgresServer::start_standby`], has replayed all of the crafted WAL. Returns the same LSNs
+/// as [`Crafter::craft`].
+pub fn craft_with_standby<C: Crafter>(
+    primary: &mut impl postgres::GenericClient,
+    standby: &mut impl postgres::GenericClient,
+) -> anyhow::Result<(Vec<PgLsn>, PgLsn)> {
+    let (intermediate_lsns, end_of_wal) = C::craft(primary)?;
+    info!("Waiting for the standby to replay WAL up to {}", end_of_wal);
+    standby.wait_for_replay_lsn(end_of_wal, Duration::from_secs(30))?;
+    Ok((intermediate_lsns, end_of_wal))
+}
+
+/// Signature of [`Crafter::craft`] instantiated for a blocking [`Client`].
+pub type CraftFn = fn(&mut Client) -> anyhow::Result<(Vec<PgLsn>, PgLsn)>;
+
+/// Returns every known [`Crafter`] as a pair of its `NAME` and its `craft` function,
+/// so callers can iterate over all of them without hardcoding the list.
+pub fn all_crafters() -> Vec<(&'static str, CraftFn)> {
+    vec![
+        (Simple::NAME, Simple::craft as CraftFn),
+        (
+            AbortedTransaction::NAME,
+            AbortedTransaction::craft as CraftFn,
+        ),
+        (ToastedRowInsert::NAME, ToastedRowInsert::craft as CraftFn),
+        (
+            FullPageImageInsert::NAME,
+            FullPageImageInsert::craft as CraftFn,
+        ),
+        (
+            LastWalRecordXlogSwitch::NAME,
+            LastWalRecordXlogSwitch::craft as CraftFn,
+        ),
+        (
+            LastWalRecordXlogSwitchEndsOnPageBoundary::NAME,
+            LastWalRecordXlogSwitchEndsOnPageBoundary::craft as CraftFn,
+        ),
+        (
+            WalRecordCrossingSegmentFollowedBySmallOne::NAME,
+            WalRecordCrossingSegmentFollowedBySmallOne::craft as CraftFn,
+        ),
+        (
+            LastWalRecordCrossingSegment::NAME,
+            LastWalRecordCrossingSegment::craft as CraftFn,
+        ),
+        (
+            WalRecordSpanningThreeSegments::NAME,
+            WalRecordSpanningThreeSegments::craft as CraftFn,
+        ),
+    ]
+}
+
 fn craft_internal<C: postgres::GenericClient>(
     client: &mut C,
     f: impl Fn(&mut C, PgLsn) -> anyhow::Result<(Vec<PgLsn>, Option<PgLsn>)>,
@@ -270,6 +612,7 @@ fn craft_internal<C: postgres::GenericClient>(
 
     // Some records may be not flushed, e.g. non-transactional logical messages.
     client.execute("select neon_xlogflush(pg_current_wal_insert_lsn())", &[])?;
+    client.wait_for_flush_lsn(last_lsn, Duration::from_secs(10))?;
     match last_lsn.cmp(&client.pg_current_wal_flush_lsn()?) {
         Ordering::Less => bail!("Some records were flushed after the crafted WAL"),
         Ordering::Equal => {}
@@ -289,6 +632,160 @@ impl Crafter for Simple {
     }
 }
 
+pub struct AbortedTransaction;
+impl Crafter for AbortedTransaction {
+    const NAME: &'static str = "aborted_transaction";
+    fn craft(client: &mut impl postgres::GenericClient) -> anyhow::Result<(Vec<PgLsn>, PgLsn)> {
+        craft_internal(client, |client, _| {
+            client.batch_execute("BEGIN; CREATE table t(x int)")?;
+            let before_abort = client.pg_current_wal_insert_lsn()?;
+            client.batch_execute("ROLLBACK")?;
+            // The DDL forces an xid to be assigned, so the rollback must write an abort record.
+            let after_abort = client.pg_current_wal_insert_lsn()?;
+            ensure!(
+                before_abort < after_abort,
+                "No abort record was written: {} vs {}",
+                before_abort,
+                after_abort
+            );
+            Ok((vec![before_abort], Some(after_abort)))
+        })
+    }
+}
+
+pub struct ToastedRowInsert;
+impl Crafter for ToastedRowInsert {
+    const NAME: &'static str = "toasted_row_insert";
+    fn craft(client: &mut impl postgres::GenericClient) -> anyhow::Result<(Vec<PgLsn>, PgLsn)> {
+        craft_internal(client, |client, _| {
+            client.execute("create extension if not exists pageinspect", &[])?;
+            client.execute("CREATE table t(x text)", &[])?;
+            // Disable compression so that the value really ends up as megabytes of toast chunks.
+            client.execute("ALTER table t ALTER COLUMN x SET STORAGE EXTERNAL", &[])?;
+
+            let before_insert = client.pg_current_wal_insert_lsn()?;
+            client.execute("INSERT INTO t SELECT repeat('abcd', 1024 * 1024)", &[])?;
+            let after_insert = client.pg_current_wal_insert_lsn()?;
+            ensure!(
+                u64::from(after_insert) / XLOG_BLCKSZ as u64
+                    > u64::from(before_insert) / XLOG_BLCKSZ as u64,
+                "Toasted insert did not cross a page boundary: {} .. {}",
+                before_insert,
+                after_insert
+            );
+
+            // A page's LSN points right past the last record which touched it, so page LSNs
+            // give us record boundaries in the middle of the insert: after the first toast
+            // chunks, after the last toast index insert (i.e. right before the heap insert),
+            // and after the heap insert itself (right before the commit record).
+            let (toast_rel, toast_index_rel): (String, String) = {
+                let row = client.query_one(
+                    "select c.reltoastrelid::regclass::text, i.indexrelid::regclass::text \
+                     from pg_class c join pg_index i on i.indrelid = c.reltoastrelid \
+                     where c.oid = 't'::regclass",
+                    &[],
+                )?;
+                (row.get(0), row.get(1))
+            };
+            let first_toast_lsn = *page_lsns(client, &toast_rel)?
+                .first()
+                .context("Toast relation is empty")?;
+            let last_toast_index_lsn = *page_lsns(client, &toast_index_rel)?
+                .last()
+                .context("Toast index is empty")?;
+            let heap_insert_lsn = *page_lsns(client, "t")?
+                .last()
+                .context("Heap relation is empty")?;
+
+            let intermediate_lsns = vec![
+                before_insert,
+                first_toast_lsn,
+                last_toast_index_lsn,
+                heap_insert_lsn,
+            ];
+            ensure!(
+                intermediate_lsns.windows(2).all(|w| w[0] < w[1]) && heap_insert_lsn < after_insert,
+                "Unexpected order of records in the toasted insert: {:?}, insert ended at {}",
+                intermediate_lsns,
+                after_insert
+            );
+            Ok((intermediate_lsns, None))
+        })
+    }
+}
+
+/// Modifies a heap page for the first time after a checkpoint, so that the WAL record carries a
+/// full page image of it. Requires `full_page_writes=on`, which may need to be set via
+/// [`Conf::extra_config`].
+pub struct FullPageImageInsert;
+impl Crafter for FullPageImageInsert {
+    const NAME: &'static str = "full_page_image_insert";
+    fn craft(client: &mut impl postgres::GenericClient) -> anyhow::Result<(Vec<PgLsn>, PgLsn)> {
+        craft_internal(client, |client, _| {
+            let full_page_writes: String = client.query_one("SHOW full_page_writes", &[])?.get(0);
+            ensure!(
+                full_page_writes == "on",
+                "full_page_writes is {full_page_writes}, full page images would not be written"
+            );
+
+            client.execute("create extension if not exists pageinspect", &[])?;
+            // Leave room on the page so that the update below stays on it as a HOT update.
+            client.execute("CREATE table t(x int) WITH (fillfactor = 50)", &[])?;
+            client.execute("INSERT INTO t SELECT generate_series(1, 100)", &[])?;
+            ensure!(
+                page_lsns(client, "t")?.len() == 1,
+                "Rows did not fit on a single heap page"
+            );
+
+            // The first modification of the page after the checkpoint's redo pointer has to
+            // include an image of the whole page.
+            client.execute("CHECKPOINT", &[])?;
+            let fpi_update_start = client.pg_current_wal_insert_lsn()?;
+            client.execute("UPDATE t SET x = -x WHERE x = 1", &[])?;
+            let after_commit = client.pg_current_wal_insert_lsn()?;
+
+            // The page LSN points right past the update record, i.e. before the commit record.
+            let fpi_update_end = *page_lsns(client, "t")?
+                .first()
+                .context("Heap relation is empty")?;
+            ensure!(
+                fpi_update_start < fpi_update_end && fpi_update_end < after_commit,
+                "Unexpected order of records in the FPI update: {} .. {} .. {}",
+                fpi_update_start,
+                fpi_update_end,
+                after_commit
+            );
+            // Without the page image, the update record would only take a few dozen bytes.
+            ensure!(
+                u64::from(fpi_update_end) - u64::from(fpi_update_start) > XLOG_BLCKSZ as u64 / 4,
+                "FPI update record is too small to carry a full page image: {} .. {}",
+                fpi_update_start,
+                fpi_update_end
+            );
+            Ok((vec![fpi_update_start, fpi_update_end], None))
+        })
+    }
+}
+
+/// Returns the LSNs of all pages of the relation `rel`, in ascending order.
+fn page_lsns(client: &mut impl postgres::GenericClient, rel: &str) -> anyhow::Result<Vec<PgLsn>> {
+    Ok(client
+        .query(
+            "select (page_header(get_raw_page($1, 'main', blkno))).lsn \
+             from generate_series(0, pg_relation_size($1::text::regclass) / $2 - 1) blkno \
+             order by 1",
+            &[&rel, &(XLOG_BLCKSZ as i64)],
+        )?
+        .iter()
+        .map(|row| row.get(0))
+        .collect())
+}
+
+/// Returns the first WAL segment boundary strictly after `lsn`.
+fn next_segment_boundary(lsn: PgLsn, segment_size: u64) -> PgLsn {
+    PgLsn::from((u64::from(lsn) / segment_size + 1) * segment_size)
+}
+
 pub struct LastWalRecordXlogSwitch;
 impl Crafter for LastWalRecordXlogSwitch {
     const NAME: &'static str = "last_wal_record_xlog_switch";
@@ -299,8 +796,8 @@ impl Crafter for LastWalRecordXlogSwitch {
 
         client.execute("CREATE table t(x int)", &[])?;
         let before_xlog_switch = client.pg_current_wal_insert_lsn()?;
+        let next_segment = next_segment_boundary(before_xlog_switch, client.wal_segment_size()?);
         let after_xlog_switch: PgLsn = client.query_one("SELECT pg_switch_wal()", &[])?.get(0);
-        let next_segment = PgLsn::from(0x0200_0000);
         ensure!(
             after_xlog_switch <= next_segment,
             "XLOG_SWITCH message ended after the expected segment boundary: {} > {}",
@@ -361,8 +858,8 @@ impl Crafter for LastWalRecordXlogSwitchEndsOnPageBoundary {
 
         // Emit the XLOG_SWITCH
         let before_xlog_switch = client.pg_current_wal_insert_lsn()?;
+        let next_segment = next_segment_boundary(before_xlog_switch, client.wal_segment_size()?);
         let after_xlog_switch: PgLsn = client.query_one("SELECT pg_switch_wal()", &[])?.get(0);
-        let next_segment = PgLsn::from(0x0200_0000);
         ensure!(
             after_xlog_switch < next_segment,
             "XLOG_SWITCH message ended on or after the expected segment boundary: {} > {}",
@@ -384,24 +881,28 @@ fn craft_single_logical_message(
     transactional: bool,
 ) -> anyhow::Result<(Vec<PgLsn>, PgLsn)> {
     craft_internal(client, |client, initial_lsn| {
+        let segment_size = client.wal_segment_size()?;
+        let next_segment = u64::from(next_segment_boundary(initial_lsn, segment_size));
         ensure!(
-            initial_lsn < PgLsn::from(0x0200_0000 - 1024 * 1024),
-            "Initial LSN is too far in the future"
+            u64::from(initial_lsn) < next_segment - segment_size / 16,
+            "Initial LSN is too close to the segment boundary"
         );
 
+        // A message as big as a whole segment crosses the next segment boundary, but not the
+        // one after it.
         let message_lsn: PgLsn = client
             .query_one(
-                "select pg_logical_emit_message($1, 'big-16mb-msg', \
-                 concat(repeat('abcd', 16 * 256 * 1024), 'end')) as message_lsn",
-                &[&transactional],
+                "select pg_logical_emit_message($1, 'big-segment-msg', \
+                 concat(repeat('abcd', $2 / 4), 'end')) as message_lsn",
+                &[&transactional, &(segment_size as i32)],
             )?
             .get("message_lsn");
         ensure!(
-            message_lsn > PgLsn::from(0x0200_0000 + 4 * 8192),
+            message_lsn > PgLsn::from(next_segment + 4 * XLOG_BLCKSZ as u64),
             "Logical message did not cross the segment boundary"
         );
         ensure!(
-            message_lsn < PgLsn::from(0x0400_0000),
+            message_lsn < PgLsn::from(next_segment + segment_size),
             "Logical message crossed two segments"
         );
 
@@ -436,3 +937,175 @@ impl Crafter for LastWalRecordCrossingSegment {
         craft_single_logical_message(client, false)
     }
 }
+
+pub struct WalRecordSpanningThreeSegments;
+impl Crafter for WalRecordSpanningThreeSegments {
+    const NAME: &'static str = "wal_record_spanning_three_segments";
+    fn craft(client: &mut impl postgres::GenericClient) -> anyhow::Result<(Vec<PgLsn>, PgLsn)> {
+        craft_internal(client, |client, initial_lsn| {
+            let segment_size = client.wal_segment_size()?;
+            let next_segment = next_segment_boundary(initial_lsn, segment_size);
+            ensure!(
+                u64::from(initial_lsn) < u64::from(next_segment) - segment_size / 16,
+                "Initial LSN is too close to the segment boundary"
+            );
+
+            let message_size = i32::try_from(segment_size * 5 / 2).with_context(|| {
+                format!("WAL segment size {segment_size} is too large for a three segment record")
+            })?;
+            let message_lsn: PgLsn = client
+                .query_one(
+                    "select pg_logical_emit_message(false, 'big-multi-segment-msg', \
+                     concat(repeat('abcd', $1 / 4), 'end')) as message_lsn",
+                    &[&message_size],
+                )?
+                .get("message_lsn");
+
+            // Every segment boundary crossed lands in the middle of the record, so readers
+            // started there have to skip the continuation data first.
+            let segment_boundaries: Vec<PgLsn> =
+                std::iter::successors(Some(next_segment), |&lsn| {
+                    Some(next_segment_boundary(lsn, segment_size))
+                })
+                .take_while(|&lsn| lsn < message_lsn)
+                .collect();
+            ensure!(
+                segment_boundaries.len() >= 2,
+                "Logical message did not span three segments: {} .. {}",
+                initial_lsn,
+                message_lsn
+            );
+            Ok((segment_boundaries, Some(message_lsn)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn all_crafters_have_unique_names() {
+        let crafters = all_crafters();
+        let mut names = HashSet::new();
+        for (name, _) in &crafters {
+            assert!(!name.is_empty(), "crafter with an empty name");
+            assert!(names.insert(*name), "duplicate crafter name {name}");
+        }
+        assert_eq!(names.len(), crafters.len());
+    }
+
+    #[test]
+    fn pg_distrib_dir_rejects_versions_without_bindings() {
+        let conf = Conf {
+            pg_version: 16,
+            pg_distrib_dir: PathBuf::from("/pg_install"),
+            datadir: PathBuf::from("/pg_install/data"),
+            extra_config: Vec::new(),
+            wal_segment_size: None,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            connect_retry_interval: DEFAULT_CONNECT_RETRY_INTERVAL,
+        };
+        assert!(conf.pg_distrib_dir().unwrap().ends_with("v16"));
+        assert!(conf.pg_bin_dir().unwrap().ends_with("v16/bin"));
+        assert!(conf.pg_lib_dir().unwrap().ends_with("v16/lib"));
+
+        // postgres_ffi can't decode v17 WAL, so we must not craft it either
+        let conf = Conf {
+            pg_version: 17,
+            ..conf
+        };
+        assert!(conf.pg_distrib_dir().is_err());
+        assert!(conf.pg_bin_dir().is_err());
+        assert!(conf.pg_lib_dir().is_err());
+    }
+
+    #[test]
+    fn next_segment_boundary_math() {
+        let mb = 1024 * 1024;
+        let lsn = PgLsn::from(0x0169_A6C8);
+        for (segment_size, expected) in [
+            (mb, 0x0170_0000),
+            (16 * mb, 0x0200_0000),
+            (64 * mb, 0x0400_0000),
+            (1024 * mb, 0x4000_0000),
+        ] {
+            assert_eq!(
+                next_segment_boundary(lsn, segment_size),
+                PgLsn::from(expected),
+                "segment size {segment_size}"
+            );
+        }
+
+        // A boundary itself is not "after" the LSN.
+        for segment_size in [mb, 16 * mb, 64 * mb, 1024 * mb] {
+            let boundary = PgLsn::from(3 * segment_size);
+            assert_eq!(
+                next_segment_boundary(boundary, segment_size),
+                PgLsn::from(4 * segment_size)
+            );
+            assert_eq!(
+                next_segment_boundary(PgLsn::from(3 * segment_size - 1), segment_size),
+                boundary
+            );
+        }
+    }
+
+    #[test]
+    fn extra_config_args() {
+        let mut conf = Conf {
+            pg_version: 16,
+            pg_distrib_dir: PathBuf::from("/pg_install"),
+            datadir: PathBuf::from("/pg_install/data"),
+            extra_config: Vec::new(),
+            wal_segment_size: None,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            connect_retry_interval: DEFAULT_CONNECT_RETRY_INTERVAL,
+        };
+        assert!(conf.extra_config_args().unwrap().is_empty());
+
+        conf.extra_config = vec!["full_page_writes=off".to_string()];
+        assert_eq!(
+            conf.extra_config_args().unwrap(),
+            vec![
+                "full_page_writes=off".to_string(),
+                format!("{OVERRIDDEN_SETTINGS_GUC}=full_page_writes"),
+            ]
+        );
+
+        conf.extra_config = vec!["shared_preload_libraries=foo".to_string()];
+        assert!(conf.extra_config_args().is_err());
+
+        conf.extra_config = vec!["full_page_writes".to_string()];
+        assert!(conf.extra_config_args().is_err());
+    }
+
+    #[test]
+    fn parse_waldump_record() {
+        let record = WalDumpRecord::parse(
+            "rmgr: Heap        len (rec/tot):     54/   174, tx:        735, \
+             lsn: 0/0169A6C8, prev 0/0169A690, desc: INSERT off 2 flags 0x00, \
+             blkref #0: rel 1663/5/16384 blk 0",
+        )
+        .unwrap();
+        assert_eq!(
+            record,
+            WalDumpRecord {
+                lsn: PgLsn::from(0x0169_A6C8),
+                prev: PgLsn::from(0x0169_A690),
+                rmgr: "Heap".to_string(),
+                len: 174,
+                description: "INSERT off 2 flags 0x00, blkref #0: rel 1663/5/16384 blk 0"
+                    .to_string(),
+            }
+        );
+
+        assert_eq!(
+            WalDumpRecord::parse(
+                "pg_waldump: error: error in WAL record at 0/1699D30: invalid record length"
+            ),
+            None
+        );
+    }
+}
@@ -6,6 +6,7 @@ use regex::Regex;
 use std::cmp::min;
 use std::fs::{self, File};
 use std::io::Write;
+use std::time::{Duration, Instant};
 use std::{env, str::FromStr};
 use utils::const_assert;
 use utils::lsn::Lsn;
@@ -18,24 +19,45 @@ fn init_logging() {
     .try_init();
 }
 
-fn test_end_of_wal<C: crate::Crafter>(test_name: &str) {
-    use crate::*;
-
+/// Configuration for a server with a fresh data directory under `test_output/`.
+fn test_conf(datadir_name: &str) -> crate::Conf {
     let pg_version = PG_MAJORVERSION[1..3].parse::<u32>().unwrap();
-
-    // Craft some WAL
     let top_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
         .join("..")
         .join("..")
         .join("..");
-    let cfg = Conf {
+    let cfg = crate::Conf {
         pg_version,
         pg_distrib_dir: top_path.join("pg_install"),
-        datadir: top_path.join(format!("test_output/{}-{PG_MAJORVERSION}", test_name)),
+        datadir: top_path.join(format!("test_output/{datadir_name}-{PG_MAJORVERSION}")),
+        extra_config: Vec::new(),
+        wal_segment_size: None,
+        connect_timeout: crate::connect_timeout_from_env().unwrap(),
+        connect_retry_interval: crate::DEFAULT_CONNECT_RETRY_INTERVAL,
     };
     if cfg.datadir.exists() {
         fs::remove_dir_all(&cfg.datadir).unwrap();
     }
+    cfg
+}
+
+/// The WAL segment size `cfg` initializes its server with.
+fn wal_segment_size(cfg: &crate::Conf) -> usize {
+    cfg.wal_segment_size.unwrap_or(WAL_SEGMENT_SIZE)
+}
+
+fn test_end_of_wal<C: crate::Crafter>(test_name: &str) {
+    test_end_of_wal_with_config::<C>(test_conf(test_name), |_, _, _| {});
+}
+
+/// Like [`test_end_of_wal`], but starts the server with `cfg` and calls `check_crafted_wal`
+/// with the last WAL segment name and the intermediate LSNs before any WAL is erased.
+fn test_end_of_wal_with_config<C: crate::Crafter>(
+    cfg: crate::Conf,
+    check_crafted_wal: impl FnOnce(&crate::Conf, &str, &[Lsn]),
+) {
+    // Craft some WAL
+    let wal_seg_size = wal_segment_size(&cfg);
     cfg.initdb().unwrap();
     let srv = cfg.start_server().unwrap();
     let (intermediate_lsns, expected_end_of_wal_partial) =
@@ -57,6 +79,7 @@ fn test_end_of_wal<C: crate::Crafter>(test_name: &str) {
         .max()
         .unwrap();
     check_pg_waldump_end_of_wal(&cfg, &last_segment, expected_end_of_wal);
+    check_crafted_wal(&cfg, &last_segment, &intermediate_lsns);
     for start_lsn in intermediate_lsns
         .iter()
         .chain(std::iter::once(&expected_end_of_wal))
@@ -72,20 +95,20 @@ fn test_end_of_wal<C: crate::Crafter>(test_name: &str) {
             if !IsXLogFileName(&fname) {
                 continue;
             }
-            let (segno, _) = XLogFromFileName(&fname, WAL_SEGMENT_SIZE);
-            let seg_start_lsn = XLogSegNoOffsetToRecPtr(segno, 0, WAL_SEGMENT_SIZE);
+            let (segno, _) = XLogFromFileName(&fname, wal_seg_size);
+            let seg_start_lsn = XLogSegNoOffsetToRecPtr(segno, 0, wal_seg_size);
             if seg_start_lsn > u64::from(*start_lsn) {
                 continue;
             }
             let mut f = File::options().write(true).open(file.path()).unwrap();
-            const ZEROS: [u8; WAL_SEGMENT_SIZE] = [0u8; WAL_SEGMENT_SIZE];
-            f.write_all(
-                &ZEROS[0..min(
-                    WAL_SEGMENT_SIZE,
+            let zeros = vec![
+                0u8;
+                min(
+                    wal_seg_size,
                     (u64::from(*start_lsn) - seg_start_lsn) as usize,
-                )],
-            )
-            .unwrap();
+                )
+            ];
+            f.write_all(&zeros).unwrap();
         }
         check_end_of_wal(&cfg, &last_segment, *start_lsn, expected_end_of_wal);
     }
@@ -140,7 +163,7 @@ fn check_end_of_wal(
         cfg.wal_dir().join(format!("{}.partial", last_segment)),
     )
     .unwrap();
-    let wal_end = find_end_of_wal(&cfg.wal_dir(), WAL_SEGMENT_SIZE, start_lsn).unwrap();
+    let wal_end = find_end_of_wal(&cfg.wal_dir(), wal_segment_size(cfg), start_lsn).unwrap();
     info!(
         "find_end_of_wal returned wal_end={} with partial WAL segment",
         wal_end
@@ -161,6 +184,50 @@ pub fn test_find_end_of_wal_simple() {
     test_end_of_wal::<crate::Simple>("test_find_end_of_wal_simple");
 }
 
+#[test]
+pub fn test_find_end_of_wal_aborted_transaction() {
+    init_logging();
+    test_end_of_wal::<crate::AbortedTransaction>("test_find_end_of_wal_aborted_transaction");
+}
+
+#[test]
+pub fn test_find_end_of_wal_toasted_row_insert() {
+    init_logging();
+    test_end_of_wal::<crate::ToastedRowInsert>("test_find_end_of_wal_toasted_row_insert");
+}
+
+#[test]
+pub fn test_find_end_of_wal_full_page_image_insert() {
+    init_logging();
+    test_end_of_wal_with_config::<crate::FullPageImageInsert>(
+        crate::Conf {
+            extra_config: vec!["full_page_writes=on".to_string()],
+            ..test_conf("test_find_end_of_wal_full_page_image_insert")
+        },
+        |cfg, last_segment, intermediate_lsns| {
+            // The crafter returns [initial, start of the FPI update, end of the FPI update].
+            let [_, fpi_update_start, fpi_update_end] = intermediate_lsns else {
+                panic!("Unexpected full page image insert LSNs: {intermediate_lsns:?}");
+            };
+            let dump = cfg
+                .pg_waldump_records("000000010000000000000001", last_segment)
+                .unwrap();
+            let fpi_update = dump
+                .records
+                .iter()
+                .find(|r| {
+                    let lsn = Lsn(u64::from(r.lsn));
+                    *fpi_update_start <= lsn && lsn < *fpi_update_end && r.rmgr == "Heap"
+                })
+                .unwrap_or_else(|| panic!("No heap record found for the FPI update: {dump:?}"));
+            assert!(
+                fpi_update.description.contains("FPW"),
+                "FPI update record carries no full page image: {fpi_update:?}"
+            );
+        },
+    );
+}
+
 #[test]
 pub fn test_find_end_of_wal_crossing_segment_followed_by_small_one() {
     init_logging();
@@ -177,6 +244,112 @@ pub fn test_find_end_of_wal_last_crossing_segment() {
     );
 }
 
+#[test]
+pub fn test_find_end_of_wal_spanning_three_segments() {
+    init_logging();
+    test_end_of_wal::<crate::WalRecordSpanningThreeSegments>(
+        "test_find_end_of_wal_spanning_three_segments",
+    );
+}
+
+/// The crafters find segment boundaries from the server's segment size, not the default one.
+#[test]
+pub fn test_find_end_of_wal_crossing_segment_with_32mb_segments() {
+    init_logging();
+    test_end_of_wal_with_config::<crate::WalRecordCrossingSegmentFollowedBySmallOne>(
+        crate::Conf {
+            wal_segment_size: Some(32 * 1024 * 1024),
+            ..test_conf("test_find_end_of_wal_crossing_segment_with_32mb_segments")
+        },
+        |cfg, last_segment, _| {
+            let last_segment_size = fs::metadata(cfg.wal_dir().join(last_segment))
+                .unwrap()
+                .len();
+            assert_eq!(last_segment_size, 32 * 1024 * 1024);
+            let (segno, _) = XLogFromFileName(last_segment, wal_segment_size(cfg));
+            assert!(segno > 1, "Crafted WAL never crossed a segment boundary");
+        },
+    );
+}
+
+/// WAL crafted on a primary and streamed to a standby must end at the same LSN in the
+/// standby's `pg_wal`.
+#[test]
+pub fn test_find_end_of_wal_on_standby() {
+    init_logging();
+    let primary_cfg = test_conf("test_find_end_of_wal_on_standby_primary");
+    let standby_cfg = test_conf("test_find_end_of_wal_on_standby_standby");
+
+    primary_cfg.initdb().unwrap();
+    let primary = primary_cfg.start_server().unwrap();
+    let mut primary_client = primary.connect_with_timeout().unwrap();
+    let standby = crate::PostgresServer::start_standby(&standby_cfg, &primary).unwrap();
+    let mut standby_client = standby.connect_with_timeout().unwrap();
+
+    let (intermediate_lsns, end_of_wal) =
+        crate::craft_with_standby::<crate::Simple>(&mut primary_client, &mut standby_client)
+            .unwrap();
+    let start_lsn: Lsn = u64::from(intermediate_lsns[0]).into();
+    let expected_end_of_wal: Lsn = u64::from(end_of_wal).into();
+
+    // The crafted table is visible on the standby once the WAL is replayed.
+    standby_client
+        .execute("SELECT 1 FROM t LIMIT 1", &[])
+        .unwrap();
+
+    drop(standby_client);
+    drop(primary_client);
+    standby.kill();
+    primary.kill();
+
+    let last_segment = standby_cfg
+        .wal_dir()
+        .read_dir()
+        .unwrap()
+        .map(|f| f.unwrap().file_name().into_string().unwrap())
+        .filter(|fname| IsXLogFileName(fname))
+        .max()
+        .unwrap();
+    check_end_of_wal(&standby_cfg, &last_segment, start_lsn, expected_end_of_wal);
+}
+
+/// A short connection timeout gives up quickly on a server which never comes up.
+#[test]
+pub fn test_connect_with_short_timeout_fails_fast() {
+    init_logging();
+    // Without initdb, the server exits right away.
+    let cfg = crate::Conf {
+        connect_timeout: Duration::from_millis(200),
+        connect_retry_interval: Duration::from_millis(10),
+        ..test_conf("test_connect_with_short_timeout_fails_fast")
+    };
+    let srv = cfg.start_server().unwrap();
+    let started_at = Instant::now();
+    let err = srv.connect_with_timeout().unwrap_err();
+    assert!(
+        started_at.elapsed() < crate::DEFAULT_CONNECT_TIMEOUT / 2,
+        "took {:?} to fail",
+        started_at.elapsed()
+    );
+    assert!(err.to_string().contains("Connection timed out"), "{err:#}");
+}
+
+/// A generous connection timeout waits for a freshly initialized server to come up.
+#[test]
+pub fn test_connect_with_generous_timeout_succeeds() {
+    init_logging();
+    let cfg = crate::Conf {
+        connect_timeout: Duration::from_secs(120),
+        ..test_conf("test_connect_with_generous_timeout_succeeds")
+    };
+    cfg.initdb().unwrap();
+    let srv = cfg.start_server().unwrap();
+    let mut client = srv.connect_with_timeout().unwrap();
+    client.execute("SELECT 1", &[]).unwrap();
+    drop(client);
+    srv.kill();
+}
+
 /// Check the math in update_next_xid
 ///
 /// NOTE: These checks are sensitive to the value of XID_CHECKPOINT_INTERVAL,
@@ -8,5 +8,9 @@ pub mod models;
 pub const DEFAULT_PG_LISTEN_PORT: u16 = 5454;
 pub const DEFAULT_PG_LISTEN_ADDR: &str = formatcp!("127.0.0.1:{DEFAULT_PG_LISTEN_PORT}");
 
+pub const DEFAULT_PG_LISTEN_PORT_TENANT_ONLY: u16 = 5455;
+pub const DEFAULT_PG_LISTEN_ADDR_TENANT_ONLY: &str =
+    formatcp!("127.0.0.1:{DEFAULT_PG_LISTEN_PORT_TENANT_ONLY}");
+
 pub const DEFAULT_HTTP_LISTEN_PORT: u16 = 7676;
 pub const DEFAULT_HTTP_LISTEN_ADDR: &str = formatcp!("127.0.0.1:{DEFAULT_HTTP_LISTEN_PORT}");
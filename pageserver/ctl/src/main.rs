@@ -16,11 +16,13 @@ use layers::LayerCmd;
 use pageserver::{
     context::{DownloadBehavior, RequestContext},
     page_cache,
+    repository::Key,
     task_mgr::TaskKind,
     tenant::{dump_layerfile_from_path, metadata::TimelineMetadata},
     virtual_file,
 };
 use postgres_ffi::ControlFileData;
+use std::ops::Range;
 use utils::{lsn::Lsn, project_git_version};
 
 project_git_version!(GIT_VERSION);
@@ -66,6 +68,12 @@ struct MetadataCmd {
 struct PrintLayerFileCmd {
     /// Pageserver data path
     path: Utf8PathBuf,
+    /// Only print the entries of keys at or after this one (in hex, as in layer file names)
+    #[arg(long)]
+    key_start: Option<Key>,
+    /// Only print the entries of keys before this one (in hex, as in layer file names)
+    #[arg(long)]
+    key_end: Option<Key>,
 }
 
 #[derive(Parser)]
@@ -102,7 +110,11 @@ async fn main() -> anyhow::Result<()> {
                     "Failed to read input file as a pg control one: {e:#}\n\
                     Attempting to read it as layer file"
                 );
-                print_layerfile(&cmd.path).await?;
+                let key_filter = match (cmd.key_start, cmd.key_end) {
+                    (None, None) => None,
+                    (start, end) => Some(start.unwrap_or(Key::MIN)..end.unwrap_or(Key::MAX)),
+                };
+                print_layerfile(&cmd.path, key_filter).await?;
             }
         }
     };
@@ -121,12 +133,12 @@ fn read_pg_control_file(control_file_path: &Utf8Path) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn print_layerfile(path: &Utf8Path) -> anyhow::Result<()> {
+async fn print_layerfile(path: &Utf8Path, key_filter: Option<Range<Key>>) -> anyhow::Result<()> {
     // Basic initialization of things that don't change after startup
     virtual_file::init(10, virtual_file::api::IoEngineKind::StdFs);
     page_cache::init(100);
     let ctx = RequestContext::new(TaskKind::DebugTool, DownloadBehavior::Error);
-    dump_layerfile_from_path(path, true, &ctx).await
+    dump_layerfile_from_path(path, true, key_filter, &ctx).await
 }
 
 fn handle_metadata(
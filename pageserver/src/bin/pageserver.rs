@@ -423,6 +423,7 @@ fn start_pageserver(
             broker_client: broker_client.clone(),
             remote_storage: remote_storage.clone(),
             deletion_queue_client,
+            broken_events: mgr::broken_events_channel(),
         },
         order,
         shutdown_pageserver.clone(),
@@ -43,6 +43,7 @@ use crate::{
     TENANT_LOCATION_CONFIG_NAME, TIMELINE_DELETE_MARK_SUFFIX, TIMELINE_UNINIT_MARK_SUFFIX,
 };
 
+use self::defaults::DEFAULT_CONCURRENT_INITDB_UPLOADS;
 use self::defaults::DEFAULT_CONCURRENT_TENANT_WARMUP;
 
 use self::defaults::DEFAULT_VIRTUAL_FILE_IO_ENGINE;
@@ -69,6 +70,10 @@ pub mod defaults {
 
     pub const DEFAULT_CONCURRENT_TENANT_WARMUP: usize = 8;
 
+    pub const DEFAULT_CONCURRENT_INITDB_UPLOADS: usize = 4;
+
+    pub const DEFAULT_INITDB_STDERR_MAX_BYTES: usize = 64 * 1024;
+
     pub const DEFAULT_CONCURRENT_TENANT_SIZE_LOGICAL_SIZE_QUERIES: usize =
         super::ConfigurableSemaphore::DEFAULT_INITIAL.get();
 
@@ -83,6 +88,10 @@ pub mod defaults {
 
     pub const DEFAULT_INGEST_BATCH_SIZE: u64 = 100;
 
+    pub const DEFAULT_INDEX_PART_DOWNLOAD_MAX_ATTEMPTS: u32 = 3;
+
+    pub const DEFAULT_TENANT_QUARANTINE_TTL: &str = "10m";
+
     pub const DEFAULT_VIRTUAL_FILE_IO_ENGINE: &str = "std-fs";
 
     pub const DEFAULT_GET_VECTORED_IMPL: &str = "sequential";
@@ -111,6 +120,10 @@ pub mod defaults {
 
 #concurrent_tenant_size_logical_size_queries = '{DEFAULT_CONCURRENT_TENANT_SIZE_LOGICAL_SIZE_QUERIES}'
 #concurrent_tenant_warmup = '{DEFAULT_CONCURRENT_TENANT_WARMUP}'
+#disable_on_demand_activation = false
+#write_legacy_tenant_config = true
+#concurrent_initdb_uploads = '{DEFAULT_CONCURRENT_INITDB_UPLOADS}'
+#initdb_stderr_max_bytes = {DEFAULT_INITDB_STDERR_MAX_BYTES}
 
 #metric_collection_interval = '{DEFAULT_METRIC_COLLECTION_INTERVAL}'
 #cached_metric_collection_interval = '{DEFAULT_CACHED_METRIC_COLLECTION_INTERVAL}'
@@ -122,6 +135,10 @@ pub mod defaults {
 
 #ingest_batch_size = {DEFAULT_INGEST_BATCH_SIZE}
 
+#index_part_download_max_attempts = {DEFAULT_INDEX_PART_DOWNLOAD_MAX_ATTEMPTS}
+
+#tenant_quarantine_ttl = '{DEFAULT_TENANT_QUARANTINE_TTL}'
+
 #virtual_file_io_engine = '{DEFAULT_VIRTUAL_FILE_IO_ENGINE}'
 
 #get_vectored_impl = '{DEFAULT_GET_VECTORED_IMPL}'
@@ -129,18 +146,23 @@ pub mod defaults {
 [tenant_config]
 #checkpoint_distance = {DEFAULT_CHECKPOINT_DISTANCE} # in bytes
 #checkpoint_timeout = {DEFAULT_CHECKPOINT_TIMEOUT}
+#max_inmem_layer_bytes = .. # in bytes
 #compaction_target_size = {DEFAULT_COMPACTION_TARGET_SIZE} # in bytes
 #compaction_period = '{DEFAULT_COMPACTION_PERIOD}'
 #compaction_threshold = {DEFAULT_COMPACTION_THRESHOLD}
 
 #gc_period = '{DEFAULT_GC_PERIOD}'
 #gc_horizon = {DEFAULT_GC_HORIZON}
+#gc_parallelism = {DEFAULT_GC_PARALLELISM}
 #image_creation_threshold = {DEFAULT_IMAGE_CREATION_THRESHOLD}
 #pitr_interval = '{DEFAULT_PITR_INTERVAL}'
 
 #min_resident_size_override = .. # in bytes
+#local_disk_high_watermark_bytes = .. # in bytes
 #evictions_low_residence_duration_metric_threshold = '{DEFAULT_EVICTIONS_LOW_RESIDENCE_DURATION_METRIC_THRESHOLD}'
 #gc_feedback = false
+#skip_orphan_timelines = false
+#synthetic_size_calculation_enabled = true
 
 #heatmap_upload_concurrency = {DEFAULT_HEATMAP_UPLOAD_CONCURRENCY}
 #secondary_download_concurrency = {DEFAULT_SECONDARY_DOWNLOAD_CONCURRENCY}
@@ -209,6 +231,26 @@ pub struct PageServerConf {
     /// loading such tenants, vs. other work in the system.
     pub concurrent_tenant_warmup: ConfigurableSemaphore,
 
+    /// If true, tenants attaching during startup never jump the warmup queue in response to
+    /// client I/O: they only activate once they get a `concurrent_tenant_warmup` permit (or are
+    /// shut down).  Client requests to such a tenant wait for its warmup rather than triggering
+    /// an immediate attach, which gives a predictable, ordered warmup at the cost of latency.
+    pub disable_on_demand_activation: bool,
+
+    /// If true, every tenant config write also writes the old-style `config` file next to the
+    /// new-style `config-v1`, so that an older pageserver version can read it after a rollback.
+    /// Once no rollback to such a version is possible, this can be disabled to save the fsyncs.
+    pub write_legacy_tenant_config: bool,
+
+    /// Number of concurrent initdb archive uploads done while bootstrapping timelines. Taring up
+    /// and uploading the archive is CPU and network heavy, so this is limited separately from
+    /// running initdb itself.
+    pub concurrent_initdb_uploads: ConfigurableSemaphore,
+
+    /// How many bytes of a failed initdb's stderr output are kept for error reporting. Only the
+    /// tail of the output is kept, so that a very chatty initdb doesn't bloat logs and API errors.
+    pub initdb_stderr_max_bytes: usize,
+
     /// Number of concurrent [`Tenant::gather_size_inputs`](crate::tenant::Tenant::gather_size_inputs) allowed.
     pub concurrent_tenant_size_logical_size_queries: ConfigurableSemaphore,
     /// Limit of concurrent [`Tenant::gather_size_inputs`] issued by module `eviction_task`.
@@ -260,6 +302,14 @@ pub struct PageServerConf {
     /// Maximum number of WAL records to be ingested and committed at the same time
     pub ingest_batch_size: u64,
 
+    /// How many attempts are made to download a timeline's index part before giving up,
+    /// e.g. on that timeline when attaching a tenant.
+    pub index_part_download_max_attempts: u32,
+
+    /// How long the local directory of a tenant detached with `preserve_local` is kept, so that
+    /// re-attaching it to this pageserver can reuse its layers.
+    pub tenant_quarantine_ttl: Duration,
+
     pub virtual_file_io_engine: virtual_file::IoEngineKind,
 
     pub get_vectored_impl: GetVectoredImpl,
@@ -324,6 +374,10 @@ struct PageServerConfigBuilder {
     log_format: BuilderValue<LogFormat>,
 
     concurrent_tenant_warmup: BuilderValue<NonZeroUsize>,
+    disable_on_demand_activation: BuilderValue<bool>,
+    write_legacy_tenant_config: BuilderValue<bool>,
+    concurrent_initdb_uploads: BuilderValue<NonZeroUsize>,
+    initdb_stderr_max_bytes: BuilderValue<usize>,
     concurrent_tenant_size_logical_size_queries: BuilderValue<NonZeroUsize>,
 
     metric_collection_interval: BuilderValue<Duration>,
@@ -348,6 +402,10 @@ struct PageServerConfigBuilder {
 
     ingest_batch_size: BuilderValue<u64>,
 
+    index_part_download_max_attempts: BuilderValue<u32>,
+
+    tenant_quarantine_ttl: BuilderValue<Duration>,
+
     virtual_file_io_engine: BuilderValue<virtual_file::IoEngineKind>,
 
     get_vectored_impl: BuilderValue<GetVectoredImpl>,
@@ -390,6 +448,11 @@ impl Default for PageServerConfigBuilder {
 
             concurrent_tenant_warmup: Set(NonZeroUsize::new(DEFAULT_CONCURRENT_TENANT_WARMUP)
                 .expect("Invalid default constant")),
+            disable_on_demand_activation: Set(false),
+            write_legacy_tenant_config: Set(true),
+            concurrent_initdb_uploads: Set(NonZeroUsize::new(DEFAULT_CONCURRENT_INITDB_UPLOADS)
+                .expect("Invalid default constant")),
+            initdb_stderr_max_bytes: Set(DEFAULT_INITDB_STDERR_MAX_BYTES),
             concurrent_tenant_size_logical_size_queries: Set(
                 ConfigurableSemaphore::DEFAULT_INITIAL,
             ),
@@ -427,6 +490,13 @@ impl Default for PageServerConfigBuilder {
 
             ingest_batch_size: Set(DEFAULT_INGEST_BATCH_SIZE),
 
+            index_part_download_max_attempts: Set(DEFAULT_INDEX_PART_DOWNLOAD_MAX_ATTEMPTS),
+
+            tenant_quarantine_ttl: Set(humantime::parse_duration(
+                DEFAULT_TENANT_QUARANTINE_TTL,
+            )
+            .expect("cannot parse default tenant quarantine ttl")),
+
             virtual_file_io_engine: Set(DEFAULT_VIRTUAL_FILE_IO_ENGINE.parse().unwrap()),
 
             get_vectored_impl: Set(DEFAULT_GET_VECTORED_IMPL.parse().unwrap()),
@@ -514,6 +584,22 @@ impl PageServerConfigBuilder {
         self.concurrent_tenant_warmup = BuilderValue::Set(u);
     }
 
+    pub fn disable_on_demand_activation(&mut self, value: bool) {
+        self.disable_on_demand_activation = BuilderValue::Set(value);
+    }
+
+    pub fn write_legacy_tenant_config(&mut self, value: bool) {
+        self.write_legacy_tenant_config = BuilderValue::Set(value);
+    }
+
+    pub fn concurrent_initdb_uploads(&mut self, u: NonZeroUsize) {
+        self.concurrent_initdb_uploads = BuilderValue::Set(u);
+    }
+
+    pub fn initdb_stderr_max_bytes(&mut self, value: usize) {
+        self.initdb_stderr_max_bytes = BuilderValue::Set(value);
+    }
+
     pub fn concurrent_tenant_size_logical_size_queries(&mut self, u: NonZeroUsize) {
         self.concurrent_tenant_size_logical_size_queries = BuilderValue::Set(u);
     }
@@ -586,6 +672,14 @@ impl PageServerConfigBuilder {
         self.ingest_batch_size = BuilderValue::Set(ingest_batch_size)
     }
 
+    pub fn index_part_download_max_attempts(&mut self, value: u32) {
+        self.index_part_download_max_attempts = BuilderValue::Set(value)
+    }
+
+    pub fn tenant_quarantine_ttl(&mut self, value: Duration) {
+        self.tenant_quarantine_ttl = BuilderValue::Set(value)
+    }
+
     pub fn virtual_file_io_engine(&mut self, value: virtual_file::IoEngineKind) {
         self.virtual_file_io_engine = BuilderValue::Set(value);
     }
@@ -598,6 +692,9 @@ impl PageServerConfigBuilder {
         let concurrent_tenant_warmup = self
             .concurrent_tenant_warmup
             .ok_or(anyhow!("missing concurrent_tenant_warmup"))?;
+        let concurrent_initdb_uploads = self
+            .concurrent_initdb_uploads
+            .ok_or(anyhow!("missing concurrent_initdb_uploads"))?;
         let concurrent_tenant_size_logical_size_queries = self
             .concurrent_tenant_size_logical_size_queries
             .ok_or(anyhow!(
@@ -651,6 +748,16 @@ impl PageServerConfigBuilder {
                 .ok_or(anyhow!("No broker keepalive interval provided"))?,
             log_format: self.log_format.ok_or(anyhow!("missing log_format"))?,
             concurrent_tenant_warmup: ConfigurableSemaphore::new(concurrent_tenant_warmup),
+            disable_on_demand_activation: self
+                .disable_on_demand_activation
+                .ok_or(anyhow!("missing disable_on_demand_activation"))?,
+            write_legacy_tenant_config: self
+                .write_legacy_tenant_config
+                .ok_or(anyhow!("missing write_legacy_tenant_config"))?,
+            concurrent_initdb_uploads: ConfigurableSemaphore::new(concurrent_initdb_uploads),
+            initdb_stderr_max_bytes: self
+                .initdb_stderr_max_bytes
+                .ok_or(anyhow!("missing initdb_stderr_max_bytes"))?,
             concurrent_tenant_size_logical_size_queries: ConfigurableSemaphore::new(
                 concurrent_tenant_size_logical_size_queries,
             ),
@@ -701,6 +808,12 @@ impl PageServerConfigBuilder {
             ingest_batch_size: self
                 .ingest_batch_size
                 .ok_or(anyhow!("missing ingest_batch_size"))?,
+            index_part_download_max_attempts: self
+                .index_part_download_max_attempts
+                .ok_or(anyhow!("missing index_part_download_max_attempts"))?,
+            tenant_quarantine_ttl: self
+                .tenant_quarantine_ttl
+                .ok_or(anyhow!("missing tenant_quarantine_ttl"))?,
             virtual_file_io_engine: self
                 .virtual_file_io_engine
                 .ok_or(anyhow!("missing virtual_file_io_engine"))?,
@@ -898,6 +1011,14 @@ impl PageServerConf {
                     let permits = input.parse::<usize>().context("expected a number of initial permits, not {s:?}")?;
                     NonZeroUsize::new(permits).context("initial semaphore permits out of range: 0, use other configuration to disable a feature")?
                 }),
+                "disable_on_demand_activation" => builder.disable_on_demand_activation(parse_toml_bool(key, item)?),
+                "write_legacy_tenant_config" => builder.write_legacy_tenant_config(parse_toml_bool(key, item)?),
+                "concurrent_initdb_uploads" => builder.concurrent_initdb_uploads({
+                    let input = parse_toml_string(key, item)?;
+                    let permits = input.parse::<usize>().context("expected a number of initial permits, not {s:?}")?;
+                    NonZeroUsize::new(permits).context("initial semaphore permits out of range: 0, use other configuration to disable a feature")?
+                }),
+                "initdb_stderr_max_bytes" => builder.initdb_stderr_max_bytes(parse_toml_u64(key, item)? as usize),
                 "concurrent_tenant_size_logical_size_queries" => builder.concurrent_tenant_size_logical_size_queries({
                     let input = parse_toml_string(key, item)?;
                     let permits = input.parse::<usize>().context("expected a number of initial permits, not {s:?}")?;
@@ -947,6 +1068,12 @@ impl PageServerConf {
                     builder.secondary_download_concurrency(parse_toml_u64(key, item)? as usize)
                 },
                 "ingest_batch_size" => builder.ingest_batch_size(parse_toml_u64(key, item)?),
+                "index_part_download_max_attempts" => {
+                    let max_attempts = parse_toml_u64(key, item)?;
+                    ensure!(max_attempts > 0, "index_part_download_max_attempts must be at least 1");
+                    builder.index_part_download_max_attempts(u32::try_from(max_attempts)?)
+                }
+                "tenant_quarantine_ttl" => builder.tenant_quarantine_ttl(parse_toml_duration(key, item)?),
                 "virtual_file_io_engine" => {
                     builder.virtual_file_io_engine(parse_toml_from_str("virtual_file_io_engine", item)?)
                 }
@@ -1009,6 +1136,13 @@ impl PageServerConf {
                 NonZeroUsize::new(DEFAULT_CONCURRENT_TENANT_WARMUP)
                     .expect("Invalid default constant"),
             ),
+            disable_on_demand_activation: false,
+            write_legacy_tenant_config: true,
+            concurrent_initdb_uploads: ConfigurableSemaphore::new(
+                NonZeroUsize::new(DEFAULT_CONCURRENT_INITDB_UPLOADS)
+                    .expect("Invalid default constant"),
+            ),
+            initdb_stderr_max_bytes: DEFAULT_INITDB_STDERR_MAX_BYTES,
             concurrent_tenant_size_logical_size_queries: ConfigurableSemaphore::default(),
             eviction_task_immitated_concurrent_logical_size_queries: ConfigurableSemaphore::default(
             ),
@@ -1026,6 +1160,11 @@ impl PageServerConf {
             heatmap_upload_concurrency: defaults::DEFAULT_HEATMAP_UPLOAD_CONCURRENCY,
             secondary_download_concurrency: defaults::DEFAULT_SECONDARY_DOWNLOAD_CONCURRENCY,
             ingest_batch_size: defaults::DEFAULT_INGEST_BATCH_SIZE,
+            index_part_download_max_attempts: defaults::DEFAULT_INDEX_PART_DOWNLOAD_MAX_ATTEMPTS,
+            tenant_quarantine_ttl: humantime::parse_duration(
+                defaults::DEFAULT_TENANT_QUARANTINE_TTL,
+            )
+            .unwrap(),
             virtual_file_io_engine: DEFAULT_VIRTUAL_FILE_IO_ENGINE.parse().unwrap(),
             get_vectored_impl: defaults::DEFAULT_GET_VECTORED_IMPL.parse().unwrap(),
         }
@@ -1102,6 +1241,8 @@ where
 #[derive(Debug, Clone)]
 pub struct ConfigurableSemaphore {
     initial_permits: NonZeroUsize,
+    /// The total amount of permits, including changes made with [`Self::set_permits`].
+    permits: std::sync::Arc<std::sync::Mutex<NonZeroUsize>>,
     inner: std::sync::Arc<tokio::sync::Semaphore>,
 }
 
@@ -1121,6 +1262,7 @@ impl ConfigurableSemaphore {
     pub fn new(initial_permits: NonZeroUsize) -> Self {
         ConfigurableSemaphore {
             initial_permits,
+            permits: std::sync::Arc::new(std::sync::Mutex::new(initial_permits)),
             inner: std::sync::Arc::new(tokio::sync::Semaphore::new(initial_permits.get())),
         }
     }
@@ -1129,6 +1271,39 @@ impl ConfigurableSemaphore {
     pub fn initial_permits(&self) -> NonZeroUsize {
         self.initial_permits
     }
+
+    /// Returns the current total amount of permits, which differs from
+    /// [`Self::initial_permits`] after a [`Self::set_permits`].
+    pub fn permits(&self) -> NonZeroUsize {
+        *self.permits.lock().unwrap()
+    }
+
+    /// Change the total amount of permits at runtime.
+    ///
+    /// Raising it takes effect immediately.  Lowering it below the amount of permits currently
+    /// held doesn't affect the holders: the excess permits are forgotten as they are returned,
+    /// which simply delays new acquirers until then.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn set_permits(&self, permits: NonZeroUsize) {
+        let mut current = self.permits.lock().unwrap();
+        if permits > *current {
+            self.inner.add_permits(permits.get() - current.get());
+        } else if permits < *current {
+            let excess = current.get() - permits.get();
+            let semaphore = std::sync::Arc::clone(&self.inner);
+            tokio::spawn(async move {
+                for _ in 0..excess {
+                    match semaphore.acquire().await {
+                        Ok(permit) => permit.forget(),
+                        // Nobody can acquire from a closed semaphore anyway
+                        Err(_) => break,
+                    }
+                }
+            });
+        }
+        *current = permits;
+    }
 }
 
 impl Default for ConfigurableSemaphore {
@@ -1235,6 +1410,12 @@ background_task_maximum_delay = '334 s'
                 concurrent_tenant_warmup: ConfigurableSemaphore::new(
                     NonZeroUsize::new(DEFAULT_CONCURRENT_TENANT_WARMUP).unwrap()
                 ),
+                disable_on_demand_activation: false,
+                write_legacy_tenant_config: true,
+                concurrent_initdb_uploads: ConfigurableSemaphore::new(
+                    NonZeroUsize::new(DEFAULT_CONCURRENT_INITDB_UPLOADS).unwrap()
+                ),
+                initdb_stderr_max_bytes: DEFAULT_INITDB_STDERR_MAX_BYTES,
                 concurrent_tenant_size_logical_size_queries: ConfigurableSemaphore::default(),
                 eviction_task_immitated_concurrent_logical_size_queries:
                     ConfigurableSemaphore::default(),
@@ -1260,6 +1441,11 @@ background_task_maximum_delay = '334 s'
                 heatmap_upload_concurrency: defaults::DEFAULT_HEATMAP_UPLOAD_CONCURRENCY,
                 secondary_download_concurrency: defaults::DEFAULT_SECONDARY_DOWNLOAD_CONCURRENCY,
                 ingest_batch_size: defaults::DEFAULT_INGEST_BATCH_SIZE,
+                index_part_download_max_attempts:
+                    defaults::DEFAULT_INDEX_PART_DOWNLOAD_MAX_ATTEMPTS,
+                tenant_quarantine_ttl: humantime::parse_duration(
+                    defaults::DEFAULT_TENANT_QUARANTINE_TTL
+                )?,
                 virtual_file_io_engine: DEFAULT_VIRTUAL_FILE_IO_ENGINE.parse().unwrap(),
                 get_vectored_impl: defaults::DEFAULT_GET_VECTORED_IMPL.parse().unwrap(),
             },
@@ -1308,6 +1494,12 @@ background_task_maximum_delay = '334 s'
                 concurrent_tenant_warmup: ConfigurableSemaphore::new(
                     NonZeroUsize::new(DEFAULT_CONCURRENT_TENANT_WARMUP).unwrap()
                 ),
+                disable_on_demand_activation: false,
+                write_legacy_tenant_config: true,
+                concurrent_initdb_uploads: ConfigurableSemaphore::new(
+                    NonZeroUsize::new(DEFAULT_CONCURRENT_INITDB_UPLOADS).unwrap()
+                ),
+                initdb_stderr_max_bytes: DEFAULT_INITDB_STDERR_MAX_BYTES,
                 concurrent_tenant_size_logical_size_queries: ConfigurableSemaphore::default(),
                 eviction_task_immitated_concurrent_logical_size_queries:
                     ConfigurableSemaphore::default(),
@@ -1325,6 +1517,11 @@ background_task_maximum_delay = '334 s'
                 heatmap_upload_concurrency: defaults::DEFAULT_HEATMAP_UPLOAD_CONCURRENCY,
                 secondary_download_concurrency: defaults::DEFAULT_SECONDARY_DOWNLOAD_CONCURRENCY,
                 ingest_batch_size: 100,
+                index_part_download_max_attempts:
+                    defaults::DEFAULT_INDEX_PART_DOWNLOAD_MAX_ATTEMPTS,
+                tenant_quarantine_ttl: humantime::parse_duration(
+                    defaults::DEFAULT_TENANT_QUARANTINE_TTL
+                )?,
                 virtual_file_io_engine: DEFAULT_VIRTUAL_FILE_IO_ENGINE.parse().unwrap(),
                 get_vectored_impl: defaults::DEFAULT_GET_VECTORED_IMPL.parse().unwrap(),
             },
@@ -1605,6 +1802,41 @@ threshold = "20m"
         }
     }
 
+    #[tokio::test]
+    async fn configurable_semaphore_set_permits() {
+        let semaphore = ConfigurableSemaphore::new(NonZeroUsize::new(1).unwrap());
+
+        // With a single permit, a second warmup has to wait for the first one...
+        let first = semaphore.inner().try_acquire().unwrap();
+        assert!(semaphore.inner().try_acquire().is_err());
+
+        // ...until the limit is raised, which admits more immediately
+        semaphore.set_permits(NonZeroUsize::new(3).unwrap());
+        assert_eq!(semaphore.permits().get(), 3);
+        assert_eq!(semaphore.initial_permits().get(), 1);
+        let second = semaphore.inner().try_acquire().unwrap();
+        let third = semaphore.inner().try_acquire().unwrap();
+        assert!(semaphore.inner().try_acquire().is_err());
+
+        // Lowering the limit below the amount in use doesn't affect the holders, but the
+        // returned permits are taken out of circulation until we are back at the limit.
+        semaphore.set_permits(NonZeroUsize::new(1).unwrap());
+        assert_eq!(semaphore.permits().get(), 1);
+        drop(first);
+        drop(second);
+        drop(third);
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while semaphore.inner().available_permits() != 1 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+        let only = semaphore.inner().try_acquire().unwrap();
+        assert!(semaphore.inner().try_acquire().is_err());
+        drop(only);
+    }
+
     fn prepare_fs(tempdir: &Utf8TempDir) -> anyhow::Result<(Utf8PathBuf, Utf8PathBuf)> {
         let tempdir_path = tempdir.path();
 
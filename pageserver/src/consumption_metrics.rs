@@ -56,8 +56,7 @@ pub async fn collect_metrics(
     }
 
     // spin up background worker that caclulates tenant sizes
-    let worker_ctx =
-        ctx.detached_child(TaskKind::CalculateSyntheticSize, DownloadBehavior::Download);
+    let worker_ctx = synthetic_size_worker_ctx(&ctx);
     task_mgr::spawn(
         BACKGROUND_RUNTIME.handle(),
         TaskKind::CalculateSyntheticSize,
@@ -245,6 +244,15 @@ async fn reschedule(
     }
 }
 
+/// The context the synthetic size worker runs in.
+///
+/// The size calculation is internal work and must not use up the tenants' timeline get
+/// throttle budget, or be slowed down by it.
+pub(crate) fn synthetic_size_worker_ctx(ctx: &RequestContext) -> RequestContext {
+    ctx.detached_child(TaskKind::CalculateSyntheticSize, DownloadBehavior::Download)
+        .with_throttle_exempt(true)
+}
+
 /// Caclculate synthetic size for each active tenant
 async fn calculate_synthetic_size_worker(
     synthetic_size_calculation_interval: Duration,
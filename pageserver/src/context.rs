@@ -95,6 +95,7 @@ pub struct RequestContext {
     download_behavior: DownloadBehavior,
     access_stats_behavior: AccessStatsBehavior,
     page_content_kind: PageContentKind,
+    throttle_exempt: bool,
 }
 
 /// The kind of access to the page cache.
@@ -150,6 +151,7 @@ impl RequestContextBuilder {
                 download_behavior: DownloadBehavior::Download,
                 access_stats_behavior: AccessStatsBehavior::Update,
                 page_content_kind: PageContentKind::Unknown,
+                throttle_exempt: false,
             },
         }
     }
@@ -163,6 +165,7 @@ impl RequestContextBuilder {
                 download_behavior: original.download_behavior,
                 access_stats_behavior: original.access_stats_behavior,
                 page_content_kind: original.page_content_kind,
+                throttle_exempt: original.throttle_exempt,
             },
         }
     }
@@ -186,6 +189,14 @@ impl RequestContextBuilder {
         self
     }
 
+    /// Configure whether the context is exempt from the tenant's timeline get throttle.
+    /// Set this for internal work like GC and size calculation, which should not compete
+    /// with client reads for the throttle's budget.
+    pub(crate) fn throttle_exempt(mut self, exempt: bool) -> Self {
+        self.inner.throttle_exempt = exempt;
+        self
+    }
+
     pub fn build(self) -> RequestContext {
         self.inner
     }
@@ -268,7 +279,28 @@ impl RequestContext {
     }
 
     fn child_impl(&self, task_kind: TaskKind, download_behavior: DownloadBehavior) -> Self {
-        Self::new(task_kind, download_behavior)
+        RequestContextBuilder::new(task_kind)
+            .download_behavior(download_behavior)
+            .throttle_exempt(self.throttle_exempt)
+            .build()
+    }
+
+    /// Create a copy of `self` that only differs in its [`DownloadBehavior`].
+    ///
+    /// Use this to locally opt into (or out of) on-demand downloads for a single
+    /// operation, without changing the behavior of the context it was derived from.
+    pub fn with_download_behavior(&self, b: DownloadBehavior) -> Self {
+        RequestContextBuilder::extend(self)
+            .download_behavior(b)
+            .build()
+    }
+
+    /// Create a copy of `self` that only differs in whether it is exempt from throttling,
+    /// see [`RequestContextBuilder::throttle_exempt`].
+    pub(crate) fn with_throttle_exempt(&self, exempt: bool) -> Self {
+        RequestContextBuilder::extend(self)
+            .throttle_exempt(exempt)
+            .build()
     }
 
     pub fn task_kind(&self) -> TaskKind {
@@ -286,4 +318,43 @@ impl RequestContext {
     pub(crate) fn page_content_kind(&self) -> PageContentKind {
         self.page_content_kind
     }
+
+    pub(crate) fn throttle_exempt(&self) -> bool {
+        self.throttle_exempt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn download_behavior_override_does_not_affect_parent() {
+        let parent = RequestContextBuilder::new(TaskKind::PageRequestHandler)
+            .download_behavior(DownloadBehavior::Error)
+            .access_stats_behavior(AccessStatsBehavior::Skip)
+            .build();
+
+        let child = parent.with_download_behavior(DownloadBehavior::Download);
+
+        assert_eq!(child.download_behavior(), DownloadBehavior::Download);
+        assert_eq!(child.task_kind(), TaskKind::PageRequestHandler);
+        assert_eq!(child.access_stats_behavior(), AccessStatsBehavior::Skip);
+
+        assert_eq!(parent.download_behavior(), DownloadBehavior::Error);
+    }
+
+    #[test]
+    fn children_inherit_throttle_exempt() {
+        let parent = RequestContext::new(TaskKind::UnitTest, DownloadBehavior::Download)
+            .with_throttle_exempt(true);
+
+        assert!(parent.attached_child().throttle_exempt());
+        assert!(parent
+            .detached_child(TaskKind::GarbageCollector, DownloadBehavior::Download)
+            .throttle_exempt());
+
+        let not_exempt = parent.with_throttle_exempt(false);
+        assert!(!not_exempt.attached_child().throttle_exempt());
+    }
 }
@@ -418,6 +418,10 @@ impl DeletionQueueClient {
         &self,
         attached_tenants: HashMap<TenantShardId, Generation>,
     ) -> Result<(), DeletionQueueError> {
+        let _timer = metrics::DELETION_QUEUE
+            .push_seconds
+            .with_label_values(&["recover"])
+            .start_timer();
         self.do_push(
             &self.tx,
             ListWriterQueueMessage::Recover(RecoverOp { attached_tenants }),
@@ -482,6 +486,10 @@ impl DeletionQueueClient {
         current_generation: Generation,
         layers: Vec<(LayerFileName, LayerFileMetadata)>,
     ) -> Result<(), DeletionQueueError> {
+        let _timer = metrics::DELETION_QUEUE
+            .push_seconds
+            .with_label_values(&["push_layers"])
+            .start_timer();
         if current_generation.is_none() {
             debug!("Enqueuing deletions in legacy mode, skipping queue");
 
@@ -571,6 +579,9 @@ impl DeletionQueueClient {
 
     // Wait until all previous deletions are executed
     pub(crate) async fn flush_execute(&self) -> Result<(), DeletionQueueError> {
+        // Observed on drop, so early returns on ShuttingDown are recorded too.
+        let _timer = metrics::DELETION_QUEUE.flush_execute_seconds.start_timer();
+
         debug!("flush_execute: flushing to deletion lists...");
         // Flush any buffered work to deletion lists
         self.flush().await?;
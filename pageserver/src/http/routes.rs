@@ -3,6 +3,7 @@
 //!
 use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -17,7 +18,9 @@ use metrics::launch_timestamp::LaunchTimestamp;
 use pageserver_api::models::LocationConfigListResponse;
 use pageserver_api::models::ShardParameters;
 use pageserver_api::models::TenantDetails;
+use pageserver_api::models::TenantInProgress;
 use pageserver_api::models::TenantLocationConfigResponse;
+use pageserver_api::models::TenantNotYetActive;
 use pageserver_api::models::TenantShardLocation;
 use pageserver_api::models::TenantShardSplitRequest;
 use pageserver_api::models::TenantShardSplitResponse;
@@ -54,11 +57,12 @@ use crate::tenant::mgr::{TenantSlot, UpsertLocationError};
 use crate::tenant::remote_timeline_client;
 use crate::tenant::secondary::SecondaryController;
 use crate::tenant::size::ModelInputs;
-use crate::tenant::storage_layer::LayerAccessStatsReset;
+use crate::tenant::storage_layer::{LayerAccessStatsReset, LayerFileName};
 use crate::tenant::timeline::CompactFlags;
 use crate::tenant::timeline::Timeline;
 use crate::tenant::SpawnMode;
-use crate::tenant::{LogicalSizeCalculationCause, PageReconstructError};
+use crate::tenant::TenantSharedResources;
+use crate::tenant::{GetTimelineError, LogicalSizeCalculationCause, PageReconstructError};
 use crate::{config::PageServerConf, tenant::mgr};
 use crate::{disk_usage_eviction_task, tenant};
 use pageserver_api::models::{
@@ -283,6 +287,7 @@ impl From<SetNewTenantConfigError> for ApiError {
             SetNewTenantConfigError::GetTenant(tid) => {
                 ApiError::NotFound(anyhow!("tenant {}", tid).into())
             }
+            e @ SetNewTenantConfigError::Invalid(_) => ApiError::BadRequest(anyhow::Error::new(e)),
             e @ (SetNewTenantConfigError::Persist(_) | SetNewTenantConfigError::Other(_)) => {
                 ApiError::InternalServerError(anyhow::Error::new(e))
             }
@@ -290,6 +295,28 @@ impl From<SetNewTenantConfigError> for ApiError {
     }
 }
 
+impl From<crate::tenant::timeline::RedownloadLayerError> for ApiError {
+    fn from(value: crate::tenant::timeline::RedownloadLayerError) -> Self {
+        use crate::tenant::timeline::RedownloadLayerError::*;
+        match value {
+            e @ NotInRemoteIndex(_) => ApiError::BadRequest(anyhow::Error::new(e)),
+            e @ NotInLayerMap(_) => ApiError::NotFound(anyhow::Error::new(e).into()),
+            Cancelled => ApiError::ShuttingDown,
+            Other(e) => ApiError::InternalServerError(e),
+        }
+    }
+}
+
+impl From<crate::tenant::ReloadConfigError> for ApiError {
+    fn from(value: crate::tenant::ReloadConfigError) -> Self {
+        use crate::tenant::ReloadConfigError::*;
+        match value {
+            e @ Invalid(_) => ApiError::BadRequest(anyhow::Error::new(e)),
+            Other(e) => ApiError::InternalServerError(e),
+        }
+    }
+}
+
 impl From<crate::tenant::DeleteTimelineError> for ApiError {
     fn from(value: crate::tenant::DeleteTimelineError) -> Self {
         use crate::tenant::DeleteTimelineError::*;
@@ -305,6 +332,16 @@ impl From<crate::tenant::DeleteTimelineError> for ApiError {
     }
 }
 
+impl From<crate::tenant::GcError> for ApiError {
+    fn from(value: crate::tenant::GcError) -> Self {
+        use crate::tenant::GcError::*;
+        match value {
+            e @ TargetTimelineNotFound(_) => ApiError::NotFound(anyhow::Error::new(e).into()),
+            Other(e) => ApiError::InternalServerError(e),
+        }
+    }
+}
+
 impl From<crate::tenant::mgr::DeleteTimelineError> for ApiError {
     fn from(value: crate::tenant::mgr::DeleteTimelineError) -> Self {
         use crate::tenant::mgr::DeleteTimelineError::*;
@@ -508,8 +545,10 @@ async fn timeline_create_handler(
                 new_timeline_id,
                 request_data.ancestor_timeline_id,
                 request_data.ancestor_start_lsn,
+                request_data.ancestor_start_timestamp,
                 request_data.pg_version.unwrap_or(crate::DEFAULT_PG_VERSION),
                 request_data.existing_initdb_timeline_id,
+                request_data.force_below_gc_cutoff,
                 state.broker_client.clone(),
                 &ctx,
             )
@@ -557,6 +596,7 @@ async fn timeline_create_handler(
         shard_id = %tenant_shard_id.shard_slug(),
         timeline_id = %new_timeline_id,
         lsn=?request_data.ancestor_start_lsn,
+        timestamp=?request_data.ancestor_start_timestamp.map(humantime::format_rfc3339),
         pg_version=?request_data.pg_version
     ))
     .await
@@ -690,6 +730,41 @@ async fn timeline_detail_handler(
     json_response(StatusCode::OK, timeline_info)
 }
 
+/// Like [`timeline_detail_handler`], but looks the timeline up by a prefix of its id.
+async fn timeline_detail_by_prefix_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    let prefix: String = parse_request_param(&request, "prefix")?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+
+    let ctx = RequestContext::new(TaskKind::MgmtRequest, DownloadBehavior::Download);
+
+    let timeline_info = async {
+        let tenant = mgr::get_tenant(tenant_shard_id, true)?;
+
+        let timeline = tenant
+            .get_timeline_by_prefix(&prefix, false)
+            .map_err(|e| match e {
+                GetTimelineError::Ambiguous { .. } => ApiError::BadRequest(e.into()),
+                e => ApiError::NotFound(e.into()),
+            })?;
+
+        build_timeline_info(&timeline, false, false, &ctx)
+            .await
+            .context("get local timeline info")
+            .map_err(ApiError::InternalServerError)
+    }
+    .instrument(info_span!("timeline_detail_by_prefix",
+                tenant_id = %tenant_shard_id.tenant_id,
+                shard_id = %tenant_shard_id.shard_slug(),
+                %prefix))
+    .await?;
+
+    json_response(StatusCode::OK, timeline_info)
+}
+
 async fn get_lsn_by_timestamp_handler(
     request: Request<Body>,
     cancel: CancellationToken,
@@ -858,9 +933,17 @@ async fn timeline_delete_handler(
                 e => e.into(),
             }
         })?;
+    let idempotent = parse_query_param(&request, "idempotent")?.unwrap_or(false);
     tenant.wait_to_become_active(ACTIVE_TENANT_TIMEOUT).await?;
-    tenant.delete_timeline(timeline_id).instrument(info_span!("timeline_delete", tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(), %timeline_id))
-        .await?;
+    let span = info_span!("timeline_delete", tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(), %timeline_id);
+    if idempotent {
+        tenant
+            .delete_timeline_idempotent(timeline_id)
+            .instrument(span)
+            .await?;
+    } else {
+        tenant.delete_timeline(timeline_id).instrument(span).await?;
+    }
 
     json_response(StatusCode::ACCEPTED, ())
 }
@@ -872,6 +955,7 @@ async fn tenant_detach_handler(
     let tenant_id: TenantId = parse_request_param(&request, "tenant_id")?;
     check_permission(&request, Some(tenant_id))?;
     let detach_ignored: Option<bool> = parse_query_param(&request, "detach_ignored")?;
+    let preserve_local: Option<bool> = parse_query_param(&request, "preserve_local")?;
 
     // This is a legacy API (`/location_conf` is the replacement).  It only supports unsharded tenants
     let tenant_shard_id = TenantShardId::unsharded(tenant_id);
@@ -882,6 +966,7 @@ async fn tenant_detach_handler(
         conf,
         tenant_shard_id,
         detach_ignored.unwrap_or(false),
+        preserve_local.unwrap_or(false),
         &state.deletion_queue_client,
     )
     .instrument(info_span!("tenant_detach", %tenant_id, shard_id=%tenant_shard_id.shard_slug()))
@@ -931,9 +1016,12 @@ async fn tenant_load_handler(
         state.conf,
         tenant_id,
         generation,
-        state.broker_client.clone(),
-        state.remote_storage.clone(),
-        state.deletion_queue_client.clone(),
+        TenantSharedResources {
+            broker_client: state.broker_client.clone(),
+            remote_storage: state.remote_storage.clone(),
+            deletion_queue_client: state.deletion_queue_client.clone(),
+            broken_events: state.tenant_manager.broken_events_sender(),
+        },
         &ctx,
     )
     .instrument(info_span!("load", %tenant_id))
@@ -983,6 +1071,50 @@ async fn tenant_list_handler(
     json_response(StatusCode::OK, response_data)
 }
 
+async fn tenant_slot_counts_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    check_permission(&request, None)?;
+    let state = get_state(&request);
+
+    json_response(StatusCode::OK, state.tenant_manager.get_slot_counts())
+}
+
+async fn tenant_readiness_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    check_permission(&request, None)?;
+    let state = get_state(&request);
+
+    let response_data = state
+        .tenant_manager
+        .list_not_yet_active()
+        .into_iter()
+        .map(|(id, state)| TenantNotYetActive { id, state })
+        .collect::<Vec<_>>();
+
+    json_response(StatusCode::OK, response_data)
+}
+
+async fn tenant_in_progress_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    check_permission(&request, None)?;
+    let state = get_state(&request);
+
+    let response_data = state
+        .tenant_manager
+        .list_in_progress()
+        .into_iter()
+        .map(|(id, age)| TenantInProgress { id, age })
+        .collect::<Vec<_>>();
+
+    json_response(StatusCode::OK, response_data)
+}
+
 async fn tenant_status(
     request: Request<Body>,
     _cancel: CancellationToken,
@@ -1010,6 +1142,8 @@ async fn tenant_status(
             },
             walredo: tenant.wal_redo_manager_status(),
             timelines: tenant.list_timeline_ids(),
+            local_physical_size: tenant.local_physical_size().await,
+            remote_physical_size: tenant.remote_size(),
         })
     }
     .instrument(info_span!("tenant_status_handler",
@@ -1074,16 +1208,24 @@ async fn tenant_size_handler(
         )));
     }
 
-    // this can be long operation
+    // this can be long operation, so report its progress through tenant_size_progress_handler,
+    // until it is over or this request is dropped
+    let progress = Arc::new(AtomicU64::new(0));
+    tenant.publish_size_calculation_progress(&progress);
+    let published = scopeguard::guard(&progress, |progress| {
+        tenant.retract_size_calculation_progress(progress)
+    });
     let inputs = tenant
         .gather_size_inputs(
             retention_period,
+            Some(Arc::clone(&progress)),
             LogicalSizeCalculationCause::TenantSizeHandler,
             &cancel,
             &ctx,
         )
-        .await
-        .map_err(ApiError::InternalServerError)?;
+        .await;
+    drop(published);
+    let inputs = inputs.map_err(ApiError::InternalServerError)?;
 
     let mut sizes = None;
     let accepts_html = headers
@@ -1119,8 +1261,15 @@ async fn tenant_size_handler(
         /// Will be null if `?inputs_only=true` was given.
         segment_sizes: Option<Vec<tenant_size_model::SegmentSizeResult>>,
         inputs: crate::tenant::size::ModelInputs,
+        /// Synthetic size last cached by the background calculation, and how long ago it was
+        /// calculated. Both are null if no background calculation has completed yet.
+        cached_size: Option<u64>,
+        #[serde(with = "humantime_serde")]
+        cached_size_age: Option<std::time::Duration>,
     }
 
+    let (cached_size, cached_size_age) = tenant.cached_synthetic_size_with_age().unzip();
+
     json_response(
         StatusCode::OK,
         TenantHistorySize {
@@ -1128,6 +1277,40 @@ async fn tenant_size_handler(
             size: sizes.as_ref().map(|x| x.total_size),
             segment_sizes: sizes.map(|x| x.segments),
             inputs,
+            cached_size,
+            cached_size_age,
+        },
+    )
+}
+
+/// Reports how far the calculation started by [`tenant_size_handler`] has come.
+async fn tenant_size_progress_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+
+    let tenant = mgr::get_tenant(tenant_shard_id, true)?;
+
+    #[derive(serde::Serialize)]
+    struct TenantSizeProgress {
+        /// Timelines whose logical sizes the running calculation has gathered so far, null if
+        /// no calculation is running.
+        timelines_processed: Option<u64>,
+        /// Active timelines, which a complete calculation processes.
+        timelines: usize,
+    }
+
+    json_response(
+        StatusCode::OK,
+        TenantSizeProgress {
+            timelines_processed: tenant.size_calculation_progress(),
+            timelines: tenant
+                .list_timelines()
+                .iter()
+                .filter(|t| t.is_active())
+                .count(),
         },
     )
 }
@@ -1168,6 +1351,20 @@ async fn layer_map_info_handler(
     json_response(StatusCode::OK, layer_map_info)
 }
 
+async fn layer_audit_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    let timeline_id: TimelineId = parse_request_param(&request, "timeline_id")?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+
+    let timeline = active_timeline_of_active_tenant(tenant_shard_id, timeline_id).await?;
+    let discrepancies = timeline.audit_layer_consistency().await;
+
+    json_response(StatusCode::OK, discrepancies)
+}
+
 async fn layer_download_handler(
     request: Request<Body>,
     _cancel: CancellationToken,
@@ -1218,6 +1415,24 @@ async fn evict_timeline_layer_handler(
     }
 }
 
+async fn redownload_timeline_layer_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+    let timeline_id: TimelineId = parse_request_param(&request, "timeline_id")?;
+    let layer_file_name = get_request_param(&request, "layer_file_name")?;
+    let layer_file_name = LayerFileName::from_str(layer_file_name).map_err(|e| {
+        ApiError::BadRequest(anyhow!("invalid layer file name {layer_file_name}: {e}"))
+    })?;
+
+    let timeline = active_timeline_of_active_tenant(tenant_shard_id, timeline_id).await?;
+    timeline.redownload_layer(&layer_file_name).await?;
+
+    json_response(StatusCode::OK, ())
+}
+
 /// Get tenant_size SVG graph along with the JSON data.
 fn synthetic_size_html_response(
     inputs: ModelInputs,
@@ -1370,6 +1585,82 @@ async fn get_tenant_config_handler(
     json_response(StatusCode::OK, response)
 }
 
+async fn get_tenant_effective_config_toml_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+
+    let tenant = mgr::get_tenant(tenant_shard_id, false)?;
+
+    let config_toml = tenant
+        .effective_config_toml()
+        .map_err(ApiError::InternalServerError)?;
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(Body::from(config_toml))
+        .map_err(|e| ApiError::InternalServerError(e.into()))
+}
+
+async fn tenant_remote_size_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+
+    let tenant = mgr::get_tenant(tenant_shard_id, false)?;
+
+    json_response(StatusCode::OK, tenant.remote_size_by_timeline())
+}
+
+async fn tenant_size_ratio_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+
+    let ctx = RequestContext::new(TaskKind::MgmtRequest, DownloadBehavior::Download);
+    let tenant = mgr::get_tenant(tenant_shard_id, true)?;
+
+    json_response(StatusCode::OK, tenant.size_ratios(&ctx))
+}
+
+async fn tenant_gc_reclaim_estimate_handler(
+    request: Request<Body>,
+    cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+
+    let ctx = RequestContext::new(TaskKind::MgmtRequest, DownloadBehavior::Download);
+    let tenant = mgr::get_tenant(tenant_shard_id, true)?;
+
+    let reclaim = tenant
+        .estimate_gc_reclaim(&cancel, &ctx)
+        .await
+        .map_err(ApiError::InternalServerError)?;
+
+    json_response(StatusCode::OK, reclaim)
+}
+
+async fn reload_tenant_config_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    check_permission(&request, None)?;
+
+    let tenant = mgr::get_tenant(tenant_shard_id, false)?;
+    tenant.reload_config_from_disk()?;
+
+    json_response(StatusCode::OK, ())
+}
+
 async fn update_tenant_config_handler(
     mut request: Request<Body>,
     _cancel: CancellationToken,
@@ -1406,13 +1697,18 @@ async fn put_tenant_location_config_handler(
     // The `Detached` state is special, it doesn't upsert a tenant, it removes
     // its local disk content and drops it from memory.
     if let LocationConfigMode::Detached = request_data.config.mode {
-        if let Err(e) =
-            mgr::detach_tenant(conf, tenant_shard_id, true, &state.deletion_queue_client)
-                .instrument(info_span!("tenant_detach",
-                    tenant_id = %tenant_shard_id.tenant_id,
-                    shard_id = %tenant_shard_id.shard_slug()
-                ))
-                .await
+        if let Err(e) = mgr::detach_tenant(
+            conf,
+            tenant_shard_id,
+            true,
+            false,
+            &state.deletion_queue_client,
+        )
+        .instrument(info_span!("tenant_detach",
+            tenant_id = %tenant_shard_id.tenant_id,
+            shard_id = %tenant_shard_id.shard_slug()
+        ))
+        .await
         {
             match e {
                 TenantStateError::SlotError(TenantSlotError::NotFound(_)) => {
@@ -1483,7 +1779,7 @@ async fn list_location_config_handler(
                 let v = match slot {
                     TenantSlot::Attached(t) => Some(t.get_location_conf()),
                     TenantSlot::Secondary(s) => Some(s.get_location_conf()),
-                    TenantSlot::InProgress(_) => None,
+                    TenantSlot::InProgress(..) => None,
                 };
                 (tenant_shard_id, v)
             })
@@ -1581,6 +1877,9 @@ async fn handle_tenant_break(
 }
 
 // Run GC immediately on given timeline.
+//
+// With `dry_run=true`, nothing is removed and the names of the layer files that
+// would have been removed are returned in `layers_to_remove`.
 async fn timeline_gc_handler(
     mut request: Request<Body>,
     cancel: CancellationToken,
@@ -1589,16 +1888,16 @@ async fn timeline_gc_handler(
     let timeline_id: TimelineId = parse_request_param(&request, "timeline_id")?;
     check_permission(&request, Some(tenant_shard_id.tenant_id))?;
 
+    let dry_run = parse_query_param(&request, "dry_run")?.unwrap_or(false);
     let gc_req: TimelineGcRequest = json_request(&mut request).await?;
 
     let ctx = RequestContext::new(TaskKind::MgmtRequest, DownloadBehavior::Download);
     let wait_task_done =
-        mgr::immediate_gc(tenant_shard_id, timeline_id, gc_req, cancel, &ctx).await?;
+        mgr::immediate_gc(tenant_shard_id, timeline_id, gc_req, dry_run, cancel, &ctx).await?;
     let gc_result = wait_task_done
         .await
         .context("wait for gc task")
-        .map_err(ApiError::InternalServerError)?
-        .map_err(ApiError::InternalServerError)?;
+        .map_err(ApiError::InternalServerError)??;
 
     json_response(StatusCode::OK, gc_result)
 }
@@ -1618,17 +1917,45 @@ async fn timeline_compact_handler(
     }
     async {
         let ctx = RequestContext::new(TaskKind::MgmtRequest, DownloadBehavior::Download);
-        let timeline = active_timeline_of_active_tenant(tenant_shard_id, timeline_id).await?;
-        timeline
-            .compact(&cancel, flags, &ctx)
+        let tenant = mgr::get_tenant(tenant_shard_id, true)?;
+        tenant
+            .compact_timeline(timeline_id, flags, &cancel, &ctx)
             .await
-            .map_err(|e| ApiError::InternalServerError(e.into()))?;
+            .map_err(|e| match e.downcast::<GetTimelineError>() {
+                Ok(e) => ApiError::NotFound(e.into()),
+                Err(e) => ApiError::InternalServerError(e),
+            })?;
         json_response(StatusCode::OK, ())
     }
     .instrument(info_span!("manual_compaction", tenant_id = %tenant_shard_id.tenant_id, shard_id = %tenant_shard_id.shard_slug(), %timeline_id))
     .await
 }
 
+// Flush the given timeline's in-memory data and wait for its remote uploads to complete.
+async fn timeline_flush_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    let timeline_id: TimelineId = parse_request_param(&request, "timeline_id")?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+
+    async {
+        let tenant = mgr::get_tenant(tenant_shard_id, true)?;
+        tenant
+            .flush_timeline_remote(timeline_id)
+            .await
+            .map_err(|e| match e.downcast::<GetTimelineError>() {
+                Ok(e) => ApiError::NotFound(e.into()),
+                Err(e) => ApiError::InternalServerError(e),
+            })?;
+
+        json_response(StatusCode::OK, ())
+    }
+    .instrument(info_span!("timeline_flush", tenant_id = %tenant_shard_id.tenant_id, shard_id = %tenant_shard_id.shard_slug(), %timeline_id))
+    .await
+}
+
 // Run checkpoint immediately on given timeline.
 async fn timeline_checkpoint_handler(
     request: Request<Body>,
@@ -1725,6 +2052,17 @@ async fn deletion_queue_flush(
     }
 }
 
+/// A [`crate::repository::Key`] in a query parameter, in hex.
+struct HexKey(crate::repository::Key);
+
+impl std::str::FromStr for HexKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        crate::repository::Key::from_hex(s).map(HexKey)
+    }
+}
+
 /// Try if `GetPage@Lsn` is successful, useful for manual debugging.
 async fn getpage_at_lsn_handler(
     request: Request<Body>,
@@ -1734,17 +2072,7 @@ async fn getpage_at_lsn_handler(
     let timeline_id: TimelineId = parse_request_param(&request, "timeline_id")?;
     check_permission(&request, Some(tenant_shard_id.tenant_id))?;
 
-    struct Key(crate::repository::Key);
-
-    impl std::str::FromStr for Key {
-        type Err = anyhow::Error;
-
-        fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-            crate::repository::Key::from_hex(s).map(Key)
-        }
-    }
-
-    let key: Key = parse_query_param(&request, "key")?
+    let key: HexKey = parse_query_param(&request, "key")?
         .ok_or_else(|| ApiError::BadRequest(anyhow!("missing 'key' query parameter")))?;
     let lsn: Lsn = parse_query_param(&request, "lsn")?
         .ok_or_else(|| ApiError::BadRequest(anyhow!("missing 'lsn' query parameter")))?;
@@ -1767,6 +2095,33 @@ async fn getpage_at_lsn_handler(
     .await
 }
 
+async fn reconstruct_chain_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    let timeline_id: TimelineId = parse_request_param(&request, "timeline_id")?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+
+    let key: HexKey = parse_query_param(&request, "key")?
+        .ok_or_else(|| ApiError::BadRequest(anyhow!("missing 'key' query parameter")))?;
+    let lsn: Lsn = parse_query_param(&request, "lsn")?
+        .ok_or_else(|| ApiError::BadRequest(anyhow!("missing 'lsn' query parameter")))?;
+
+    async {
+        let ctx = RequestContext::new(TaskKind::MgmtRequest, DownloadBehavior::Download);
+        let timeline = active_timeline_of_active_tenant(tenant_shard_id, timeline_id).await?;
+
+        let chain = timeline
+            .get_reconstruct_data_debug(key.0, lsn, &ctx)
+            .await?;
+
+        json_response(StatusCode::OK, chain)
+    }
+    .instrument(info_span!("reconstruct_chain", tenant_id = %tenant_shard_id.tenant_id, shard_id = %tenant_shard_id.shard_slug(), %timeline_id))
+    .await
+}
+
 async fn timeline_collect_keyspace(
     request: Request<Body>,
     _cancel: CancellationToken,
@@ -1965,6 +2320,61 @@ async fn put_io_engine_handler(
     json_response(StatusCode::OK, ())
 }
 
+async fn put_tenant_compaction_soft_stop_handler(
+    mut r: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&r, "tenant_shard_id")?;
+    check_permission(&r, Some(tenant_shard_id.tenant_id))?;
+    let stop: bool = json_request(&mut r).await?;
+    let state = get_state(&r);
+    state
+        .tenant_manager
+        .set_compaction_soft_stop(tenant_shard_id, stop)?;
+    json_response(StatusCode::OK, ())
+}
+
+async fn get_tenant_compaction_candidates_handler(
+    r: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&r, "tenant_shard_id")?;
+    check_permission(&r, Some(tenant_shard_id.tenant_id))?;
+
+    #[derive(serde::Serialize)]
+    struct CompactionCandidate {
+        timeline_id: TimelineId,
+        level0_deltas: usize,
+    }
+
+    let tenant = mgr::get_tenant(tenant_shard_id, true)?;
+    let candidates = tenant
+        .compaction_candidates()
+        .await
+        .map_err(ApiError::InternalServerError)?
+        .into_iter()
+        .map(|(timeline_id, level0_deltas)| CompactionCandidate {
+            timeline_id,
+            level0_deltas,
+        })
+        .collect::<Vec<_>>();
+    json_response(StatusCode::OK, candidates)
+}
+
+async fn put_tenant_warmup_concurrency_handler(
+    mut r: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    check_permission(&r, None)?;
+    let concurrency: usize = json_request(&mut r).await?;
+    let state = get_state(&r);
+    state
+        .tenant_manager
+        .set_warmup_concurrency(concurrency)
+        .map_err(ApiError::BadRequest)?;
+    json_response(StatusCode::OK, ())
+}
+
 /// Polled by control plane.
 ///
 /// See [`crate::utilization`].
@@ -2147,6 +2557,15 @@ pub fn make_router(
         })
         .get("/v1/tenant", |r| api_handler(r, tenant_list_handler))
         .post("/v1/tenant", |r| api_handler(r, tenant_create_handler))
+        .get("/v1/tenant_slot_counts", |r| {
+            api_handler(r, tenant_slot_counts_handler)
+        })
+        .get("/v1/tenant_readiness", |r| {
+            api_handler(r, tenant_readiness_handler)
+        })
+        .get("/v1/tenant_in_progress", |r| {
+            api_handler(r, tenant_in_progress_handler)
+        })
         .get("/v1/tenant/:tenant_shard_id", |r| {
             api_handler(r, tenant_status)
         })
@@ -2156,6 +2575,18 @@ pub fn make_router(
         .get("/v1/tenant/:tenant_shard_id/synthetic_size", |r| {
             api_handler(r, tenant_size_handler)
         })
+        .get("/v1/tenant/:tenant_shard_id/synthetic_size/progress", |r| {
+            api_handler(r, tenant_size_progress_handler)
+        })
+        .get("/v1/tenant/:tenant_shard_id/remote_size", |r| {
+            api_handler(r, tenant_remote_size_handler)
+        })
+        .get("/v1/tenant/:tenant_shard_id/size_ratio", |r| {
+            api_handler(r, tenant_size_ratio_handler)
+        })
+        .get("/v1/tenant/:tenant_shard_id/gc_reclaim_estimate", |r| {
+            api_handler(r, tenant_gc_reclaim_estimate_handler)
+        })
         .put("/v1/tenant/config", |r| {
             api_handler(r, update_tenant_config_handler)
         })
@@ -2165,6 +2596,12 @@ pub fn make_router(
         .get("/v1/tenant/:tenant_shard_id/config", |r| {
             api_handler(r, get_tenant_config_handler)
         })
+        .get("/v1/tenant/:tenant_shard_id/config/effective_toml", |r| {
+            api_handler(r, get_tenant_effective_config_toml_handler)
+        })
+        .post("/v1/tenant/:tenant_shard_id/config/reload", |r| {
+            api_handler(r, reload_tenant_config_handler)
+        })
         .put("/v1/tenant/:tenant_shard_id/location_config", |r| {
             api_handler(r, put_tenant_location_config_handler)
         })
@@ -2203,6 +2640,10 @@ pub fn make_router(
         .get("/v1/tenant/:tenant_shard_id/timeline/:timeline_id", |r| {
             api_handler(r, timeline_detail_handler)
         })
+        .get(
+            "/v1/tenant/:tenant_shard_id/timeline_by_prefix/:prefix",
+            |r| api_handler(r, timeline_detail_by_prefix_handler),
+        )
         .get(
             "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/get_lsn_by_timestamp",
             |r| api_handler(r, get_lsn_by_timestamp_handler),
@@ -2223,6 +2664,10 @@ pub fn make_router(
             "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/checkpoint",
             |r| testing_api_handler("run timeline checkpoint", r, timeline_checkpoint_handler),
         )
+        .post(
+            "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/flush",
+            |r| api_handler(r, timeline_flush_handler),
+        )
         .post(
             "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/download_remote_layers",
             |r| api_handler(r, timeline_download_remote_layers_handler_post),
@@ -2242,10 +2687,18 @@ pub fn make_router(
             "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/layer/:layer_file_name",
             |r| api_handler(r, layer_download_handler),
         )
+        .get(
+            "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/layer_audit",
+            |r| api_handler(r, layer_audit_handler),
+        )
         .delete(
             "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/layer/:layer_file_name",
             |r| api_handler(r, evict_timeline_layer_handler),
         )
+        .post(
+            "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/layer/:layer_file_name/redownload",
+            |r| api_handler(r, redownload_timeline_layer_handler),
+        )
         .post("/v1/tenant/:tenant_shard_id/heatmap_upload", |r| {
             api_handler(r, secondary_upload_handler)
         })
@@ -2269,11 +2722,24 @@ pub fn make_router(
             "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/getpage",
             |r| testing_api_handler("getpage@lsn", r, getpage_at_lsn_handler),
         )
+        .get(
+            "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/reconstruct_chain",
+            |r| testing_api_handler("reconstruct_chain", r, reconstruct_chain_handler),
+        )
         .get(
             "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/keyspace",
             |r| api_handler(r, timeline_collect_keyspace),
         )
         .put("/v1/io_engine", |r| api_handler(r, put_io_engine_handler))
+        .put("/v1/tenant/:tenant_shard_id/compaction_soft_stop", |r| {
+            api_handler(r, put_tenant_compaction_soft_stop_handler)
+        })
+        .get("/v1/tenant/:tenant_shard_id/compaction_candidates", |r| {
+            api_handler(r, get_tenant_compaction_candidates_handler)
+        })
+        .put("/v1/tenant_warmup_concurrency", |r| {
+            api_handler(r, put_tenant_warmup_concurrency_handler)
+        })
         .get("/v1/utilization", |r| api_handler(r, get_utilization))
         .any(handler_404))
 }
@@ -36,6 +36,8 @@ use postgres_ffi::DBState_DB_SHUTDOWNED;
 use postgres_ffi::Oid;
 use postgres_ffi::XLogFileName;
 use postgres_ffi::{BLCKSZ, WAL_SEGMENT_SIZE};
+use remote_storage::TimeoutOrCancel;
+use tokio_util::sync::CancellationToken;
 use utils::lsn::Lsn;
 
 // Returns checkpoint LSN from controlfile
@@ -56,10 +58,14 @@ pub fn get_lsn_from_controlfile(path: &Utf8Path) -> Result<Lsn> {
 /// This is currently only used to import a cluster freshly created by initdb.
 /// The code that deals with the checkpoint would not work right if the
 /// cluster was not shut down cleanly.
+///
+/// The import is checked for cancellation between files, and returns an error
+/// caused by [`TimeoutOrCancel::Cancel`] if `cancel` fires.
 pub async fn import_timeline_from_postgres_datadir(
     tline: &Timeline,
     pgdata_path: &Utf8Path,
     pgdata_lsn: Lsn,
+    cancel: &CancellationToken,
     ctx: &RequestContext,
 ) -> Result<()> {
     let mut pg_control: Option<ControlFileData> = None;
@@ -74,6 +80,9 @@ pub async fn import_timeline_from_postgres_datadir(
         .into_iter()
         .filter_entry(|entry| !entry.path().ends_with("pg_wal"));
     for entry in all_but_wal {
+        if cancel.is_cancelled() {
+            return Err(anyhow::Error::new(TimeoutOrCancel::Cancel));
+        }
         let entry = entry?;
         let metadata = entry.metadata().expect("error getting dir entry metadata");
         if metadata.is_file() {
@@ -689,9 +698,13 @@ pub async fn create_tar_zst(pgdata_path: &Utf8Path, tmp_path: &Utf8Path) -> Resu
 pub async fn extract_tar_zst(
     pgdata_path: &Utf8Path,
     tar_zst: impl AsyncBufRead + Unpin,
+    cancel: &CancellationToken,
 ) -> Result<()> {
     let tar = Box::pin(ZstdDecoder::new(tar_zst));
     let mut archive = Archive::new(tar);
-    archive.unpack(pgdata_path).await?;
+    tokio::select! {
+        res = archive.unpack(pgdata_path) => res?,
+        _ = cancel.cancelled() => return Err(anyhow::Error::new(TimeoutOrCancel::Cancel)),
+    }
     Ok(())
 }
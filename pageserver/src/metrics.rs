@@ -620,6 +620,15 @@ pub(crate) static TENANT_STATE_METRIC: Lazy<UIntGaugeVec> = Lazy::new(|| {
     .expect("Failed to register pageserver_tenant_states_count metric")
 });
 
+pub(crate) static TIMELINE_STATE_METRIC: Lazy<UIntGaugeVec> = Lazy::new(|| {
+    register_uint_gauge_vec!(
+        "pageserver_timeline_states_count",
+        "Count of timelines per state",
+        &["state"]
+    )
+    .expect("Failed to register pageserver_timeline_states_count metric")
+});
+
 /// A set of broken tenants.
 ///
 /// These are expected to be so rare that a set is fine. Set as in a new timeseries per each broken
@@ -633,6 +642,15 @@ pub(crate) static BROKEN_TENANTS_SET: Lazy<UIntGaugeVec> = Lazy::new(|| {
     .expect("Failed to register pageserver_tenant_states_count metric")
 });
 
+pub(crate) static TENANT_ORPHAN_TIMELINES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pageserver_tenant_orphan_timelines_total",
+        "Number of timelines found during tenant attach whose ancestor timeline is missing",
+        &["tenant_id", "shard_id"]
+    )
+    .expect("failed to define a metric")
+});
+
 pub(crate) static TENANT_SYNTHETIC_SIZE_METRIC: Lazy<UIntGaugeVec> = Lazy::new(|| {
     register_uint_gauge_vec!(
         "pageserver_tenant_synthetic_cached_size_bytes",
@@ -729,7 +747,8 @@ pub(crate) struct TenantMetrics {
     /// How long did tenants take to go from construction to active state?
     pub(crate) activation: Histogram,
     pub(crate) preload: Histogram,
-    pub(crate) attach: Histogram,
+    /// Labelled by `attach_mode`: one of `warmup`, `on_demand` or `normal`.
+    pub(crate) attach: HistogramVec,
 
     /// How many tenants are included in the initial startup of the pagesrever?
     pub(crate) startup_scheduled: IntCounter,
@@ -750,9 +769,10 @@ pub(crate) static TENANT: Lazy<TenantMetrics> = Lazy::new(|| {
         CRITICAL_OP_BUCKETS.into()
     )
     .expect("Failed to register metric"),
-    attach: register_histogram!(
+    attach: register_histogram_vec!(
         "pageserver_tenant_attach_seconds",
         "Time taken by tenants to intialize, after remote metadata is already loaded",
+        &["attach_mode"],
         CRITICAL_OP_BUCKETS.into()
     )
     .expect("Failed to register metric"),
@@ -1338,6 +1358,8 @@ pub(crate) struct DeletionQueueMetrics {
     pub(crate) dropped_lsn_updates: IntCounter,
     pub(crate) unexpected_errors: IntCounter,
     pub(crate) remote_errors: IntCounterVec,
+    pub(crate) flush_execute_seconds: Histogram,
+    pub(crate) push_seconds: HistogramVec,
 }
 pub(crate) static DELETION_QUEUE: Lazy<DeletionQueueMetrics> = Lazy::new(|| {
     DeletionQueueMetrics{
@@ -1381,7 +1403,20 @@ pub(crate) static DELETION_QUEUE: Lazy<DeletionQueueMetrics> = Lazy::new(|| {
         "Retryable remote I/O errors while executing deletions, for example 503 responses to DeleteObjects",
         &["op_kind"],
     )
-    .expect("failed to define a metric")
+    .expect("failed to define a metric"),
+    flush_execute_seconds: register_histogram!(
+        "pageserver_deletion_queue_flush_execute_seconds",
+        "Time spent in DeletionQueueClient::flush_execute, including calls failed due to shutdown",
+        CRITICAL_OP_BUCKETS.into(),
+    )
+    .expect("failed to define a metric"),
+    push_seconds: register_histogram_vec!(
+        "pageserver_deletion_queue_push_seconds",
+        "Time spent submitting work to the deletion queue, by operation",
+        &["op"],
+        CRITICAL_OP_BUCKETS.into(),
+    )
+    .expect("failed to define a metric"),
 }
 });
 
@@ -2009,6 +2044,10 @@ pub(crate) fn remove_tenant_metrics(tenant_shard_id: &TenantShardId) {
         let _ = TENANT_SYNTHETIC_SIZE_METRIC.remove_label_values(&[&tid]);
     }
 
+    let tid = tenant_shard_id.tenant_id.to_string();
+    let shard_id = tenant_shard_id.shard_slug().to_string();
+    let _ = TENANT_ORPHAN_TIMELINES.remove_label_values(&[&tid, &shard_id]);
+
     // we leave the BROKEN_TENANTS_SET entry if any
 }
 
@@ -2197,6 +2236,16 @@ impl RemoteTimelineClientMetrics {
         let key = (file_kind.as_str(), op_kind.as_str());
         guard.get(&key).map(|counter| counter.get())
     }
+
+    pub fn get_calls_started_value(
+        &self,
+        file_kind: &RemoteOpFileKind,
+        op_kind: &RemoteOpKind,
+    ) -> Option<u64> {
+        let guard = self.calls.lock().unwrap();
+        let key = (file_kind.as_str(), op_kind.as_str());
+        guard.get(&key).map(|pair| pair.get_inc())
+    }
 }
 
 /// See [`RemoteTimelineClientMetrics::call_begin`].
@@ -1211,6 +1211,8 @@ impl PageServerHandler {
         let timeline = self
             .get_active_tenant_timeline(tenant_id, timeline_id, ShardSelector::Zero)
             .await?;
+        // the basebackup reads most of the timeline's layers, don't evict them meanwhile
+        let _eviction_pin = timeline.pin_eviction();
         let latest_gc_cutoff_lsn = timeline.get_latest_gc_cutoff_lsn();
         if let Some(lsn) = lsn {
             // Backup was requested at a particular LSN. Wait for it to arrive.
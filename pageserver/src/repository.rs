@@ -112,6 +112,20 @@ pub struct GcResult {
     #[serde(serialize_with = "serialize_duration_as_millis")]
     pub elapsed: Duration,
 
+    /// The names of the layer files that a dry run found to be removable.
+    ///
+    /// Only set by dry runs, which leave the layer files in place.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layers_to_remove: Option<Vec<String>>,
+
+    /// The total size in bytes of [`Self::layers_to_remove`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes_to_remove: Option<u64>,
+
+    /// True if the GC iteration was cancelled before it got to every timeline that needed GC,
+    /// in which case the other fields only cover the timelines that were processed.
+    pub cancelled: bool,
+
     /// The layers which were garbage collected.
     ///
     /// Used in `/v1/tenant/:tenant_id/timeline/:timeline_id/do_gc` to wait for the layers to be
@@ -130,7 +144,7 @@ where
 }
 
 impl AddAssign for GcResult {
-    fn add_assign(&mut self, other: Self) {
+    fn add_assign(&mut self, mut other: Self) {
         self.layers_total += other.layers_total;
         self.layers_needed_by_pitr += other.layers_needed_by_pitr;
         self.layers_needed_by_cutoff += other.layers_needed_by_cutoff;
@@ -139,11 +153,18 @@ impl AddAssign for GcResult {
         self.layers_removed += other.layers_removed;
 
         self.elapsed += other.elapsed;
+        self.cancelled |= other.cancelled;
 
-        #[cfg(feature = "testing")]
-        {
-            let mut other = other;
-            self.doomed_layers.append(&mut other.doomed_layers);
+        if let Some(mut layers_to_remove) = other.layers_to_remove.take() {
+            self.layers_to_remove
+                .get_or_insert_with(Vec::new)
+                .append(&mut layers_to_remove);
+        }
+        if let Some(bytes_to_remove) = other.bytes_to_remove {
+            *self.bytes_to_remove.get_or_insert(0) += bytes_to_remove;
         }
+
+        #[cfg(feature = "testing")]
+        self.doomed_layers.append(&mut other.doomed_layers);
     }
 }
@@ -28,6 +28,7 @@ use remote_storage::GenericRemoteStorage;
 use remote_storage::TimeoutOrCancel;
 use std::fmt;
 use storage_broker::BrokerClientChannel;
+use tokio::io::AsyncReadExt;
 use tokio::io::BufReader;
 use tokio::sync::watch;
 use tokio::task::JoinSet;
@@ -45,19 +46,25 @@ use utils::timeout::TimeoutCancellableError;
 
 use self::config::AttachedLocationConfig;
 use self::config::AttachmentMode;
+use self::config::LenientLocationConf;
 use self::config::LocationConf;
 use self::config::TenantConf;
 use self::delete::DeleteTenantFlow;
+use self::lock_order::{OrderedGuard, TenantLock};
 use self::metadata::TimelineMetadata;
 use self::mgr::GetActiveTenantError;
 use self::mgr::GetTenantError;
+use self::mgr::TenantBrokenEvent;
 use self::mgr::TenantsMap;
 use self::remote_timeline_client::upload::upload_index_part;
 use self::remote_timeline_client::RemoteTimelineClient;
 use self::timeline::uninit::TimelineExclusionError;
 use self::timeline::uninit::TimelineUninitMark;
 use self::timeline::uninit::UninitializedTimeline;
+use self::timeline::CompactFlags;
 use self::timeline::EvictionTaskTenantState;
+use self::timeline::GcInfo;
+use self::timeline::GetLogicalSizePriority;
 use self::timeline::TimelineResources;
 use self::timeline::WaitLsnError;
 use crate::config::PageServerConf;
@@ -68,13 +75,16 @@ use crate::import_datadir;
 use crate::is_uninit_mark;
 use crate::metrics::TENANT;
 use crate::metrics::{
-    remove_tenant_metrics, BROKEN_TENANTS_SET, TENANT_STATE_METRIC, TENANT_SYNTHETIC_SIZE_METRIC,
+    remove_tenant_metrics, BROKEN_TENANTS_SET, TENANT_ORPHAN_TIMELINES, TENANT_STATE_METRIC,
+    TENANT_SYNTHETIC_SIZE_METRIC,
 };
+use crate::pgdatadir_mapping::LsnForTimestamp;
 use crate::repository::GcResult;
 use crate::task_mgr;
 use crate::task_mgr::TaskKind;
 use crate::tenant::config::LocationMode;
 use crate::tenant::config::TenantConfOpt;
+use crate::tenant::remote_timeline_client::index::GcCutoffs;
 pub use crate::tenant::remote_timeline_client::index::IndexPart;
 use crate::tenant::remote_timeline_client::remote_initdb_archive_path;
 use crate::tenant::remote_timeline_client::MaybeDeletedIndexPart;
@@ -87,16 +97,17 @@ use std::collections::hash_map::Entry;
 use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fs;
 use std::fs::File;
+use std::num::NonZeroUsize;
 use std::ops::Bound::Included;
-use std::sync::atomic::AtomicU64;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::sync::{Mutex, RwLock};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
 use crate::span;
 use crate::tenant::timeline::delete::DeleteTimelineFlow;
@@ -108,7 +119,13 @@ use once_cell::sync::Lazy;
 pub use pageserver_api::models::TenantState;
 use tokio::sync::Semaphore;
 
-static INIT_DB_SEMAPHORE: Lazy<Semaphore> = Lazy::new(|| Semaphore::new(8));
+/// How many timeline bootstraps may have an initdb datadir on local disk at the same time.
+const INIT_DB_CONCURRENCY: usize = 8;
+
+static INIT_DB_SEMAPHORE: Lazy<Semaphore> = Lazy::new(|| Semaphore::new(INIT_DB_CONCURRENCY));
+
+/// How many child shard indices [`Tenant::split_prepare`] uploads concurrently for each timeline.
+const SPLIT_PREPARE_UPLOAD_CONCURRENCY: usize = 8;
 use toml_edit;
 use utils::{
     crashsafe,
@@ -168,6 +185,8 @@ pub mod size;
 
 pub(crate) mod throttle;
 
+mod lock_order;
+
 pub(crate) use crate::span::debug_assert_current_span_has_tenant_and_timeline_id;
 pub(crate) use timeline::{LogicalSizeCalculationCause, PageReconstructError, Timeline};
 
@@ -189,6 +208,7 @@ pub struct TenantSharedResources {
     pub broker_client: storage_broker::BrokerClientChannel,
     pub remote_storage: Option<GenericRemoteStorage>,
     pub deletion_queue_client: DeletionQueueClient,
+    pub broken_events: tokio::sync::broadcast::Sender<TenantBrokenEvent>,
 }
 
 /// A [`Tenant`] is really an _attached_ tenant.  The configuration
@@ -201,6 +221,11 @@ pub(super) struct AttachedTenantConf {
 
 impl AttachedTenantConf {
     fn try_from(location_conf: LocationConf) -> anyhow::Result<Self> {
+        location_conf
+            .tenant_conf
+            .validate()
+            .map_err(|e| anyhow::anyhow!("invalid tenant config: {e}"))?;
+
         match &location_conf.mode {
             LocationMode::Attached(attach_conf) => Ok(Self {
                 tenant_conf: location_conf.tenant_conf,
@@ -228,6 +253,9 @@ pub(crate) struct TenantPreload {
 pub(crate) enum SpawnMode {
     Normal,
     Create,
+    /// Attach like [`SpawnMode::Normal`], but never ingest WAL, run GC or compaction, or
+    /// upload anything.  See [`AttachedLocationConfig::read_only`].
+    ReadOnly,
 }
 
 ///
@@ -261,12 +289,17 @@ pub struct Tenant {
     /// this copy enforces the invariant that generatio doesn't change during a Tenant's lifetime.
     generation: Generation,
 
+    /// **Lock order**: `gc_cs`, `timelines`, `timelines_creating`, `tenant_conf`. Acquire these
+    /// through [`Self::lock_timelines`] and friends, which check the order in debug builds.
     timelines: Mutex<HashMap<TimelineId, Arc<Timeline>>>,
 
     /// During timeline creation, we first insert the TimelineId to the
     /// creating map, then `timelines`, then remove it from the creating map.
     /// **Lock order**: if acquring both, acquire`timelines` before `timelines_creating`
     timelines_creating: std::sync::Mutex<HashSet<TimelineId>>,
+    /// Notified whenever an entry is removed from [`Self::timelines_creating`], see
+    /// [`Self::wait_no_timelines_creating`].
+    timelines_creating_removed: tokio::sync::Notify,
 
     // This mutex prevents creation of new timelines during GC.
     // Adding yet another mutex (in addition to `timelines`) is needed because holding
@@ -284,8 +317,12 @@ pub struct Tenant {
     deletion_queue_client: DeletionQueueClient,
 
     /// Cached logical sizes updated updated on each [`Tenant::gather_size_inputs`].
-    cached_logical_sizes: tokio::sync::Mutex<HashMap<(TimelineId, Lsn), u64>>,
-    cached_synthetic_tenant_size: Arc<AtomicU64>,
+    cached_logical_sizes: Mutex<size::LogicalSizeCache>,
+    /// Last synthetic size set by [`Tenant::set_cached_synthetic_size`], and when it was set.
+    cached_synthetic_tenant_size: Mutex<Option<(u64, Instant)>>,
+    /// Progress of the size calculation requested through the HTTP API, while it runs, see
+    /// [`Tenant::publish_size_calculation_progress`].
+    size_calculation_progress: Mutex<Option<Arc<AtomicU64>>>,
 
     eviction_task_tenant_state: tokio::sync::Mutex<EvictionTaskTenantState>,
 
@@ -294,6 +331,14 @@ pub struct Tenant {
     /// background warmup.
     pub(crate) activate_now_sem: tokio::sync::Semaphore,
 
+    /// Set when a testing build moved this tenant from Active to Broken in
+    /// [`Self::set_broken_no_wait`]: only such tenants may be brought back with
+    /// [`Self::clear_broken_and_reactivate`].
+    broken_by_test: AtomicBool,
+
+    /// See [`Self::set_compaction_soft_stop`].
+    compaction_soft_stop: AtomicBool,
+
     pub(crate) delete_progress: Arc<tokio::sync::Mutex<DeleteTenantFlow>>,
 
     // Cancellation token fires when we have entered shutdown().  This is a parent of
@@ -316,6 +361,15 @@ impl std::fmt::Debug for Tenant {
     }
 }
 
+/// What [`Tenant::clean_up_timelines`] did with the entries of a tenant's timelines directory.
+#[derive(Debug, Default)]
+struct CleanupReport {
+    /// Entries which were removed because they were temporary or their timeline no longer exists.
+    purged: Vec<Utf8PathBuf>,
+    /// Entries which were left alone because their name is not a timeline ID.
+    kept_unparseable: Vec<Utf8PathBuf>,
+}
+
 pub(crate) enum WalRedoManager {
     Prod(PostgresRedoManager),
     #[cfg(test)]
@@ -374,7 +428,7 @@ impl WalRedoManager {
         match self {
             WalRedoManager::Prod(m) => m.status(),
             #[cfg(test)]
-            WalRedoManager::Test(_) => None,
+            WalRedoManager::Test(m) => m.status(),
         }
     }
 }
@@ -392,6 +446,13 @@ pub enum GetTimelineError {
         tenant_id: TenantShardId,
         timeline_id: TimelineId,
     },
+    #[error("No timeline of tenant {tenant_id} has an id starting with {prefix:?}")]
+    PrefixNotFound {
+        tenant_id: TenantShardId,
+        prefix: String,
+    },
+    #[error("Timeline id prefix is ambiguous, matching timelines: {matches:?}")]
+    Ambiguous { matches: Vec<TimelineId> },
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -458,6 +519,74 @@ pub enum CreateTimelineError {
     Other(#[from] anyhow::Error),
 }
 
+/// The initial uploads of a timeline created by [`Tenant::create_timeline_deferred_upload`].
+///
+/// Until [`Self::wait`] has returned `Ok`, the timeline is not durable: if the pageserver
+/// restarts or the uploads fail, the timeline may be lost, even though it was already usable.
+#[must_use = "the timeline is not durable until its initial uploads have been waited for"]
+pub struct PendingTimelineUpload {
+    timeline: Arc<Timeline>,
+    kind: &'static str,
+}
+
+impl PendingTimelineUpload {
+    /// Wait for the initial uploads, after which the timeline is durable on remote storage.
+    pub async fn wait(self) -> anyhow::Result<()> {
+        if let Some(remote_client) = self.timeline.remote_client.as_ref() {
+            remote_client
+                .wait_completion()
+                .await
+                .with_context(|| format!("wait for {} timeline uploads to complete", self.kind))?;
+        }
+        Ok(())
+    }
+}
+
+fn timeline_creation_kind(ancestor_timeline_id: Option<TimelineId>) -> &'static str {
+    if ancestor_timeline_id.is_some() {
+        "branched"
+    } else {
+        "bootstrapped"
+    }
+}
+
+/// What [`Tenant::start_timeline_creation`] did.
+enum TimelineCreation {
+    /// A new timeline was created. It is not activated and not durable yet.
+    Created(Arc<Timeline>),
+    /// An identical timeline already existed. Its uploads may still be in progress.
+    Existing(Arc<Timeline>),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GcError {
+    #[error("gc target timeline {0} does not exist")]
+    TargetTimelineNotFound(TimelineId),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl GcError {
+    /// Unwrap [`GcError::Other`] so that callers can still downcast the underlying error,
+    /// e.g. to tell a [`PageReconstructError::Cancelled`] apart from a real failure.
+    fn into_anyhow(self) -> anyhow::Error {
+        match self {
+            GcError::Other(e) => e,
+            e => anyhow::Error::new(e),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum ReloadConfigError {
+    /// The config on disk cannot be applied to the running tenant.
+    #[error("invalid tenant config on disk: {0:#}")]
+    Invalid(anyhow::Error),
+    /// Reading the config failed, e.g. on an I/O error.
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
 #[derive(thiserror::Error, Debug)]
 enum InitdbError {
     Other(anyhow::Error),
@@ -491,6 +620,9 @@ impl From<std::io::Error> for InitdbError {
 enum CreateTimelineCause {
     Load,
     Delete,
+    /// Loading a timeline that cannot be loaded normally (e.g. its ancestor is missing
+    /// or its metadata is inconsistent), to be marked Broken right away.
+    Broken,
 }
 
 impl Tenant {
@@ -539,6 +671,14 @@ impl Tenant {
                 .as_ref()
                 .unwrap()
                 .init_upload_queue(index_part)?;
+
+            // Restore the GC cutoffs from before the restart, so that branch creation
+            // sees them before the first GC iteration has run.
+            if let Some(gc_cutoffs) = index_part.gc_cutoffs {
+                let mut gc_info = timeline.gc_info.write().unwrap();
+                gc_info.horizon_cutoff = gc_cutoffs.horizon;
+                gc_info.pitr_cutoff = gc_cutoffs.pitr;
+            }
         } else if self.remote_storage.is_some() {
             // No data on the remote storage, but we have local metadata file. We can end up
             // here with timeline_create being interrupted before finishing index part upload.
@@ -547,7 +687,9 @@ impl Tenant {
             // for timeline creation will coalesce on the upload we queue here.
             let rtc = timeline.remote_client.as_ref().unwrap();
             rtc.init_upload_queue_for_empty_remote(&metadata)?;
-            rtc.schedule_index_upload_for_metadata_update(&metadata)?;
+            if !self.is_read_only() {
+                rtc.schedule_index_upload_for_metadata_update(&metadata)?;
+            }
         }
 
         timeline
@@ -559,7 +701,7 @@ impl Tenant {
 
         {
             // avoiding holding it across awaits
-            let mut timelines_accessor = self.timelines.lock().unwrap();
+            let mut timelines_accessor = self.lock_timelines();
             match timelines_accessor.entry(timeline_id) {
                 Entry::Occupied(_) => {
                     // The uninit mark file acts as a lock that prevents another task from
@@ -613,6 +755,11 @@ impl Tenant {
         mode: SpawnMode,
         ctx: &RequestContext,
     ) -> anyhow::Result<Arc<Tenant>> {
+        let mut attached_conf = attached_conf;
+        if let SpawnMode::ReadOnly = mode {
+            attached_conf.location.read_only = true;
+        }
+
         let wal_redo_manager = Arc::new(WalRedoManager::from(PostgresRedoManager::new(
             conf,
             tenant_shard_id,
@@ -622,6 +769,7 @@ impl Tenant {
             broker_client,
             remote_storage,
             deletion_queue_client,
+            broken_events,
         } = resources;
 
         let attach_mode = attached_conf.location.attach_mode;
@@ -636,6 +784,7 @@ impl Tenant {
             tenant_shard_id,
             remote_storage.clone(),
             deletion_queue_client,
+            broken_events,
         ));
 
         // The attach task will carry a GateGuard, so that shutdown() reliably waits for it to drop out if
@@ -708,15 +857,26 @@ impl Tenant {
                     Normal,
                 }
 
+                impl AttachType<'_> {
+                    fn metric_label(&self) -> &'static str {
+                        match self {
+                            AttachType::Warmup(_) => "warmup",
+                            AttachType::OnDemand => "on_demand",
+                            AttachType::Normal => "normal",
+                        }
+                    }
+                }
+
                 // Before doing any I/O, wait for either or:
-                // - A client to attempt to access to this tenant (on-demand loading)
+                // - A client to attempt to access to this tenant (on-demand loading), unless
+                //   `disable_on_demand_activation` is set, in which case clients wait for warmup
                 // - A permit to become available in the warmup semaphore (background warmup)
                 //
                 // Some-ness of init_order is how we know if we're attaching during startup or later
                 // in process lifetime.
                 let attach_type = if init_order.is_some() {
                     tokio::select!(
-                        _ = tenant_clone.activate_now_sem.acquire() => {
+                        _ = tenant_clone.activate_now_sem.acquire(), if !conf.disable_on_demand_activation => {
                             tracing::info!("Activating tenant (on-demand)");
                             AttachType::OnDemand
                         },
@@ -755,7 +915,7 @@ impl Tenant {
                     (SpawnMode::Create, _) => {
                         None
                     },
-                    (SpawnMode::Normal, Some(remote_storage)) => {
+                    (SpawnMode::Normal | SpawnMode::ReadOnly, Some(remote_storage)) => {
                         let _preload_timer = TENANT.preload.start_timer();
                         let res = tenant_clone
                             .preload(remote_storage, task_mgr::shutdown_token())
@@ -768,7 +928,7 @@ impl Tenant {
                             }
                         }
                     }
-                    (SpawnMode::Normal, None) => {
+                    (SpawnMode::Normal | SpawnMode::ReadOnly, None) => {
                         let _preload_timer = TENANT.preload.start_timer();
                         None
                     }
@@ -827,7 +987,7 @@ impl Tenant {
                 let attached = {
                     let _attach_timer = match mode {
                         SpawnMode::Create => None,
-                        SpawnMode::Normal => {Some(TENANT.attach.start_timer())}
+                        SpawnMode::Normal | SpawnMode::ReadOnly => {Some(TENANT.attach.with_label_values(&[attach_type.metric_label()]).start_timer())}
                     };
                     tenant_clone.attach(preload, mode, &ctx).await
                 };
@@ -850,7 +1010,7 @@ impl Tenant {
                 // logical size calculations: if logical size calculation semaphore is saturated,
                 // then warmup will wait for that before proceeding to the next tenant.
                 if let AttachType::Warmup(_permit) = attach_type {
-                    let mut futs: FuturesUnordered<_> = tenant_clone.timelines.lock().unwrap().values().cloned().map(|t| t.await_initial_logical_size()).collect();
+                    let mut futs: FuturesUnordered<_> = tenant_clone.lock_timelines().values().cloned().map(|t| t.await_initial_logical_size()).collect();
                     tracing::info!("Waiting for initial logical sizes while warming up...");
                     while futs.next().await.is_some() {}
                     tracing::info!("Warm-up complete");
@@ -922,7 +1082,7 @@ impl Tenant {
                 deleting: false,
                 timelines: HashMap::new(),
             },
-            (None, SpawnMode::Normal) => {
+            (None, SpawnMode::Normal | SpawnMode::ReadOnly) => {
                 anyhow::bail!("local-only deployment is no longer supported, https://github.com/neondatabase/neon/issues/5624");
             }
         };
@@ -976,15 +1136,63 @@ impl Tenant {
             }
         }
 
+        let (sorted_timelines, orphan_timelines) =
+            tree_sort_timelines_with_orphans(timeline_ancestors, |m| m.ancestor_timeline());
+        if !orphan_timelines.is_empty() {
+            let tid = self.tenant_shard_id.tenant_id.to_string();
+            let shard_id = self.tenant_shard_id.shard_slug().to_string();
+            for (orphan_id, missing_id, _) in &orphan_timelines {
+                error!("could not load timeline {orphan_id} because its ancestor timeline {missing_id} could not be loaded");
+                TENANT_ORPHAN_TIMELINES
+                    .with_label_values(&[&tid, &shard_id])
+                    .inc();
+            }
+            if !self.get_skip_orphan_timelines() {
+                bail!("could not load tenant because some timelines are missing ancestors");
+            }
+        }
+
         // For every timeline, download the metadata file, scan the local directory,
         // and build a layer map that contains an entry for each remote and local
         // layer file.
-        let sorted_timelines = tree_sort_timelines(timeline_ancestors, |m| m.ancestor_timeline())?;
+        let mut broken_timelines = HashSet::new();
         for (timeline_id, remote_metadata) in sorted_timelines {
             let (index_part, remote_client) = remote_index_and_client
                 .remove(&timeline_id)
                 .expect("just put it in above");
 
+            // A corrupt index only takes down its own timeline (and anything branched off
+            // it, since that can't be loaded without its ancestor), not the whole tenant.
+            let broken_reason = match remote_metadata.check_consistency() {
+                Err(e) => Some(format!("inconsistent metadata in index_part: {e:#}")),
+                Ok(()) => remote_metadata
+                    .ancestor_timeline()
+                    .filter(|ancestor_id| broken_timelines.contains(ancestor_id))
+                    .map(|ancestor_id| format!("ancestor timeline {ancestor_id} is broken")),
+            };
+            if let Some(reason) = broken_reason {
+                error!(%timeline_id, "could not load timeline: {reason}");
+                broken_timelines.insert(timeline_id);
+                self.load_broken_timeline(
+                    timeline_id,
+                    reason,
+                    index_part,
+                    remote_metadata,
+                    TimelineResources {
+                        remote_client: Some(remote_client),
+                        deletion_queue_client: self.deletion_queue_client.clone(),
+                        timeline_get_throttle: self.timeline_get_throttle.clone(),
+                    },
+                )
+                .with_context(|| {
+                    format!(
+                        "failed to load broken timeline {} for tenant {}",
+                        timeline_id, self.tenant_shard_id
+                    )
+                })?;
+                continue;
+            }
+
             // TODO again handle early failure
             self.load_remote_timeline(
                 timeline_id,
@@ -1006,6 +1214,32 @@ impl Tenant {
             })?;
         }
 
+        // Orphans are only left over if `skip_orphan_timelines` is set: keep them around as
+        // Broken timelines rather than failing the whole tenant.
+        for (timeline_id, missing_id, remote_metadata) in orphan_timelines {
+            let (index_part, remote_client) = remote_index_and_client
+                .remove(&timeline_id)
+                .expect("just put it in above");
+
+            self.load_broken_timeline(
+                timeline_id,
+                format!("ancestor timeline {missing_id} could not be loaded"),
+                index_part,
+                remote_metadata,
+                TimelineResources {
+                    remote_client: Some(remote_client),
+                    deletion_queue_client: self.deletion_queue_client.clone(),
+                    timeline_get_throttle: self.timeline_get_throttle.clone(),
+                },
+            )
+            .with_context(|| {
+                format!(
+                    "failed to load orphan timeline {} for tenant {}",
+                    timeline_id, self.tenant_shard_id
+                )
+            })?;
+        }
+
         // Walk through deleted timelines, resume deletion
         for (timeline_id, index_part, remote_timeline_client) in timelines_to_resume_deletions {
             remote_timeline_client
@@ -1028,7 +1262,12 @@ impl Tenant {
 
         // The local filesystem contents are a cache of what's in the remote IndexPart;
         // IndexPart is the source of truth.
-        self.clean_up_timelines(&existent_timelines)?;
+        let cleanup_report = self.clean_up_timelines(&existent_timelines)?;
+        debug!(
+            purged = cleanup_report.purged.len(),
+            kept_unparseable = cleanup_report.kept_unparseable.len(),
+            "cleaned up timelines directory"
+        );
 
         fail::fail_point!("attach-before-activate", |_| {
             anyhow::bail!("attach-before-activate");
@@ -1043,14 +1282,18 @@ impl Tenant {
     /// Check for any local timeline directories that are temporary, or do not correspond to a
     /// timeline that still exists: this can happen if we crashed during a deletion/creation, or
     /// if a timeline was deleted while the tenant was attached to a different pageserver.
-    fn clean_up_timelines(&self, existent_timelines: &HashSet<TimelineId>) -> anyhow::Result<()> {
+    fn clean_up_timelines(
+        &self,
+        existent_timelines: &HashSet<TimelineId>,
+    ) -> anyhow::Result<CleanupReport> {
         let timelines_dir = self.conf.timelines_path(&self.tenant_shard_id);
+        let mut report = CleanupReport::default();
 
         let entries = match timelines_dir.read_dir_utf8() {
             Ok(d) => d,
             Err(e) => {
                 if e.kind() == std::io::ErrorKind::NotFound {
-                    return Ok(());
+                    return Ok(report);
                 } else {
                     return Err(e).context("list timelines directory for tenant");
                 }
@@ -1079,6 +1322,7 @@ impl Tenant {
                             "Unparseable directory in timelines directory: {entry_path}, ignoring ({e})"
                         );
                         // Do not purge junk: if we don't recognize it, be cautious and leave it for a human.
+                        report.kept_unparseable.push(entry_path.to_owned());
                         false
                     }
                 }
@@ -1096,28 +1340,76 @@ impl Tenant {
                     Err(e) => Err(e),
                 } {
                     tracing::warn!("Failed to purge stale timeline dentry {entry_path}: {e}");
+                } else {
+                    report.purged.push(entry_path.to_owned());
                 }
             }
         }
 
-        Ok(())
+        Ok(report)
     }
 
     /// Get sum of all remote timelines sizes
     ///
     /// This function relies on the index_part instead of listing the remote storage
     pub fn remote_size(&self) -> u64 {
-        let mut size = 0;
+        self.remote_size_by_timeline().values().sum()
+    }
 
+    /// Get the sum of the sizes of the layer files on local disk, across all timelines.
+    ///
+    /// Unlike [`Self::remote_size`], which counts what is in remote storage, this leaves out
+    /// evicted layers, and is what eviction needs to bring down.
+    pub async fn local_physical_size(&self) -> u64 {
+        let mut size = 0;
         for timeline in self.list_timelines() {
-            if let Some(remote_client) = &timeline.remote_client {
-                size += remote_client.get_remote_physical_size();
-            }
+            size += timeline.resident_layer_size_sum().await;
         }
-
         size
     }
 
+    /// Get the remote size of each timeline of this shard, see [`Self::remote_size`].
+    ///
+    /// Timelines without remote storage are left out.
+    pub fn remote_size_by_timeline(&self) -> HashMap<TimelineId, u64> {
+        self.list_timelines()
+            .into_iter()
+            .filter_map(|timeline| {
+                let remote_client = timeline.remote_client.as_ref()?;
+                Some((
+                    timeline.timeline_id,
+                    remote_client.get_remote_physical_size(),
+                ))
+            })
+            .collect()
+    }
+
+    /// Get the ratio of remote physical size to current logical size of each timeline of this
+    /// shard, a rough measure of write amplification: timelines with a high ratio are likely
+    /// to benefit from image layer creation.
+    ///
+    /// Timelines without remote storage, or with a zero logical size, are left out. The logical
+    /// size may still be approximate if its initial calculation has not completed yet.
+    pub fn size_ratios(&self, ctx: &RequestContext) -> HashMap<TimelineId, f64> {
+        self.list_timelines()
+            .into_iter()
+            .filter_map(|timeline| {
+                let remote_client = timeline.remote_client.as_ref()?;
+                let logical_size = timeline
+                    .get_current_logical_size(GetLogicalSizePriority::Background, ctx)
+                    .size_dont_care_about_accuracy();
+                if logical_size == 0 {
+                    return None;
+                }
+                let physical_size = remote_client.get_remote_physical_size();
+                Some((
+                    timeline.timeline_id,
+                    physical_size as f64 / logical_size as f64,
+                ))
+            })
+            .collect()
+    }
+
     #[instrument(skip_all, fields(timeline_id=%timeline_id))]
     async fn load_remote_timeline(
         &self,
@@ -1135,7 +1427,7 @@ impl Tenant {
             .context("Failed to create new timeline directory")?;
 
         let ancestor = if let Some(ancestor_id) = remote_metadata.ancestor_timeline() {
-            let timelines = self.timelines.lock().unwrap();
+            let timelines = self.lock_timelines();
             Some(Arc::clone(timelines.get(&ancestor_id).ok_or_else(
                 || {
                     anyhow::anyhow!(
@@ -1158,11 +1450,50 @@ impl Tenant {
         .await
     }
 
+    /// Insert a Broken placeholder for a timeline that can't be loaded, e.g. because its
+    /// ancestor timeline is missing, so that it stays visible to operators without holding
+    /// up the rest of the tenant.
+    fn load_broken_timeline(
+        &self,
+        timeline_id: TimelineId,
+        reason: String,
+        index_part: IndexPart,
+        remote_metadata: TimelineMetadata,
+        resources: TimelineResources,
+    ) -> anyhow::Result<()> {
+        let timeline = self.create_timeline_struct(
+            timeline_id,
+            &remote_metadata,
+            None,
+            resources,
+            CreateTimelineCause::Broken,
+        )?;
+        timeline
+            .remote_client
+            .as_ref()
+            .unwrap()
+            .init_upload_queue(&index_part)?;
+        timeline.set_broken(reason);
+
+        let mut timelines_accessor = self.lock_timelines();
+        match timelines_accessor.entry(timeline_id) {
+            Entry::Occupied(_) => unreachable!(
+                "Timeline {}/{timeline_id} already exists in the tenant map",
+                self.tenant_shard_id
+            ),
+            Entry::Vacant(v) => {
+                v.insert(timeline);
+            }
+        }
+        Ok(())
+    }
+
     /// Create a placeholder Tenant object for a broken tenant
     pub fn create_broken_tenant(
         conf: &'static PageServerConf,
         tenant_shard_id: TenantShardId,
         reason: String,
+        broken_events: tokio::sync::broadcast::Sender<TenantBrokenEvent>,
     ) -> Arc<Tenant> {
         Arc::new(Tenant::new(
             TenantState::Broken {
@@ -1178,6 +1509,7 @@ impl Tenant {
             tenant_shard_id,
             None,
             DeletionQueueClient::broken(),
+            broken_events,
         ))
     }
 
@@ -1255,7 +1587,7 @@ impl Tenant {
         timeline_id: TimelineId,
         active_only: bool,
     ) -> Result<Arc<Timeline>, GetTimelineError> {
-        let timelines_accessor = self.timelines.lock().unwrap();
+        let timelines_accessor = self.lock_timelines();
         let timeline = timelines_accessor
             .get(&timeline_id)
             .ok_or(GetTimelineError::NotFound {
@@ -1274,19 +1606,134 @@ impl Tenant {
         }
     }
 
+    /// Get the timeline whose id, as a hex string, starts with `prefix`.
+    ///
+    /// Meant for operators who only remember the first few characters of a timeline id.
+    /// Fails with [`GetTimelineError::Ambiguous`] if more than one timeline matches.
+    pub fn get_timeline_by_prefix(
+        &self,
+        prefix: &str,
+        active_only: bool,
+    ) -> Result<Arc<Timeline>, GetTimelineError> {
+        let prefix = prefix.to_ascii_lowercase();
+        let mut matches: Vec<TimelineId> = self
+            .lock_timelines()
+            .keys()
+            .filter(|timeline_id| timeline_id.to_string().starts_with(&prefix))
+            .copied()
+            .collect();
+
+        match matches.len() {
+            0 => Err(GetTimelineError::PrefixNotFound {
+                tenant_id: self.tenant_shard_id,
+                prefix,
+            }),
+            1 => self.get_timeline(matches[0], active_only),
+            _ => {
+                matches.sort();
+                Err(GetTimelineError::Ambiguous { matches })
+            }
+        }
+    }
+
+    fn lock_timelines(
+        &self,
+    ) -> OrderedGuard<std::sync::MutexGuard<'_, HashMap<TimelineId, Arc<Timeline>>>> {
+        lock_order::ordered(TenantLock::Timelines, || self.timelines.lock().unwrap())
+    }
+
+    fn lock_timelines_creating(
+        &self,
+    ) -> OrderedGuard<std::sync::MutexGuard<'_, HashSet<TimelineId>>> {
+        lock_order::ordered(TenantLock::TimelinesCreating, || {
+            self.timelines_creating.lock().unwrap()
+        })
+    }
+
+    fn read_tenant_conf(&self) -> OrderedGuard<std::sync::RwLockReadGuard<'_, AttachedTenantConf>> {
+        lock_order::ordered(TenantLock::TenantConf, || self.tenant_conf.read().unwrap())
+    }
+
+    fn write_tenant_conf(
+        &self,
+    ) -> OrderedGuard<std::sync::RwLockWriteGuard<'_, AttachedTenantConf>> {
+        lock_order::ordered(TenantLock::TenantConf, || self.tenant_conf.write().unwrap())
+    }
+
+    async fn lock_gc_cs(&self) -> tokio::sync::MutexGuard<'_, ()> {
+        lock_order::check(TenantLock::GcCs);
+        self.gc_cs.lock().await
+    }
+
     /// Lists timelines the tenant contains.
     /// Up to tenant's implementation to omit certain timelines that ar not considered ready for use.
     pub fn list_timelines(&self) -> Vec<Arc<Timeline>> {
-        self.timelines
-            .lock()
-            .unwrap()
+        self.lock_timelines().values().map(Arc::clone).collect()
+    }
+
+    /// Number of timelines the tenant contains, without cloning their handles like
+    /// [`Self::list_timelines`] does.
+    pub fn timeline_count(&self) -> usize {
+        self.lock_timelines().len()
+    }
+
+    /// Whether the tenant contains the given timeline, in any state.  Cheaper than
+    /// [`Self::get_timeline`] when the handle itself is not needed.
+    pub fn has_timeline(&self, timeline_id: TimelineId) -> bool {
+        self.lock_timelines().contains_key(&timeline_id)
+    }
+
+    /// The active timelines with their number of level-0 delta layers, the ones with the most
+    /// pending compaction work first.  Unlike [`Self::compaction_iteration`], this doesn't
+    /// compact anything.
+    pub async fn compaction_candidates(&self) -> anyhow::Result<Vec<(TimelineId, usize)>> {
+        let timelines = self
+            .lock_timelines()
             .values()
-            .map(Arc::clone)
+            .filter(|timeline| timeline.is_active())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut candidates = Vec::with_capacity(timelines.len());
+        for timeline in timelines {
+            candidates.push((timeline.timeline_id, timeline.level0_delta_count().await?));
+        }
+        candidates
+            .sort_by(|(a_id, a_count), (b_id, b_count)| b_count.cmp(a_count).then(a_id.cmp(b_id)));
+        Ok(candidates)
+    }
+
+    /// Like [`Self::list_timelines`], but every timeline comes after its ancestor.
+    ///
+    /// Timelines whose ancestor is not loaded (see [`TenantConf::skip_orphan_timelines`])
+    /// are listed last, also after their ancestors.
+    pub fn list_timelines_sorted(&self) -> Vec<Arc<Timeline>> {
+        let timelines = self.lock_timelines().clone();
+        let (sorted, orphans) =
+            tree_sort_timelines_with_orphans(timelines, |t| t.get_ancestor_timeline_id());
+
+        // Sort the orphans among themselves, as if their missing ancestors were roots
+        let orphan_ids: HashSet<TimelineId> = orphans.iter().map(|(id, _, _)| *id).collect();
+        let (orphans, _) = tree_sort_timelines_with_orphans(
+            orphans
+                .into_iter()
+                .map(|(id, _, timeline)| (id, timeline))
+                .collect(),
+            |t| {
+                t.get_ancestor_timeline_id()
+                    .filter(|ancestor_id| orphan_ids.contains(ancestor_id))
+            },
+        );
+
+        sorted
+            .into_iter()
+            .chain(orphans)
+            .map(|(_, timeline)| timeline)
             .collect()
     }
 
     pub fn list_timeline_ids(&self) -> Vec<TimelineId> {
-        self.timelines.lock().unwrap().keys().cloned().collect()
+        self.lock_timelines().keys().cloned().collect()
     }
 
     /// This is used to create the initial 'main' timeline during bootstrapping,
@@ -1392,17 +1839,143 @@ impl Tenant {
     ///
     /// If the caller specified the timeline ID to use (`new_timeline_id`), and timeline with
     /// the same timeline ID already exists, returns CreateTimelineError::AlreadyExists.
+    ///
+    /// `pg_version` is ignored when branching from `ancestor_timeline_id`: branches always
+    /// use their ancestor's version, also when checking a repeated creation for idempotency.
+    ///
+    /// Instead of `ancestor_start_lsn`, the branch point can be given as a wall-clock
+    /// `ancestor_start_timestamp`, which is resolved to the LSN of the last commit on the
+    /// ancestor at or before that time.
+    ///
+    /// The timeline is durable on remote storage when this returns `Ok`.
     #[allow(clippy::too_many_arguments)]
     pub(crate) async fn create_timeline(
         &self,
         new_timeline_id: TimelineId,
         ancestor_timeline_id: Option<TimelineId>,
-        mut ancestor_start_lsn: Option<Lsn>,
+        ancestor_start_lsn: Option<Lsn>,
+        ancestor_start_timestamp: Option<SystemTime>,
         pg_version: u32,
         load_existing_initdb: Option<TimelineId>,
+        force_below_gc_cutoff: bool,
         broker_client: storage_broker::BrokerClientChannel,
         ctx: &RequestContext,
     ) -> Result<Arc<Timeline>, CreateTimelineError> {
+        let creation = self
+            .start_timeline_creation(
+                new_timeline_id,
+                ancestor_timeline_id,
+                ancestor_start_lsn,
+                ancestor_start_timestamp,
+                pg_version,
+                load_existing_initdb,
+                force_below_gc_cutoff,
+                ctx,
+            )
+            .await?;
+
+        match creation {
+            TimelineCreation::Existing(existing) => {
+                // Wait for uploads to complete, so that when we return Ok, the timeline
+                // is known to be durable on remote storage. Just like we do below,
+                // after we have created the timeline ourselves.
+                //
+                // We only really care that the initial version of `index_part.json` has
+                // been uploaded. That's enough to remember that the timeline
+                // exists. However, there is no function to wait specifically for that so
+                // we just wait for all in-progress uploads to finish.
+                PendingTimelineUpload {
+                    timeline: existing.clone(),
+                    kind: "existing",
+                }
+                .wait()
+                .await?;
+                Ok(existing)
+            }
+            TimelineCreation::Created(loaded_timeline) => {
+                // The timeline is visible in [`Self::timelines`], but it is _not_ durable
+                // yet.  We must not send a success to the caller until it is.  The same
+                // applies to handling retries, see the handling of existing timelines above.
+                PendingTimelineUpload {
+                    timeline: loaded_timeline.clone(),
+                    kind: timeline_creation_kind(ancestor_timeline_id),
+                }
+                .wait()
+                .await?;
+
+                loaded_timeline.activate(broker_client, None, ctx);
+
+                Ok(loaded_timeline)
+            }
+        }
+    }
+
+    /// Like [`Self::create_timeline`], but doesn't wait for the initial uploads of the new
+    /// timeline. Meant for bulk creation, where callers create many timelines and then wait
+    /// for all the returned [`PendingTimelineUpload`]s at once.
+    ///
+    /// **The timeline is not durable until the [`PendingTimelineUpload`] has been waited
+    /// for**, even though it is activated right away. Until then, it may be lost on restart
+    /// or if the uploads fail, so callers must not report the creation as done before that.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_timeline_deferred_upload(
+        &self,
+        new_timeline_id: TimelineId,
+        ancestor_timeline_id: Option<TimelineId>,
+        ancestor_start_lsn: Option<Lsn>,
+        ancestor_start_timestamp: Option<SystemTime>,
+        pg_version: u32,
+        load_existing_initdb: Option<TimelineId>,
+        force_below_gc_cutoff: bool,
+        broker_client: storage_broker::BrokerClientChannel,
+        ctx: &RequestContext,
+    ) -> Result<(Arc<Timeline>, PendingTimelineUpload), CreateTimelineError> {
+        let creation = self
+            .start_timeline_creation(
+                new_timeline_id,
+                ancestor_timeline_id,
+                ancestor_start_lsn,
+                ancestor_start_timestamp,
+                pg_version,
+                load_existing_initdb,
+                force_below_gc_cutoff,
+                ctx,
+            )
+            .await?;
+
+        let (timeline, kind) = match creation {
+            TimelineCreation::Existing(existing) => (existing, "existing"),
+            TimelineCreation::Created(loaded_timeline) => {
+                loaded_timeline.activate(broker_client, None, ctx);
+                (
+                    loaded_timeline,
+                    timeline_creation_kind(ancestor_timeline_id),
+                )
+            }
+        };
+
+        let pending_upload = PendingTimelineUpload {
+            timeline: timeline.clone(),
+            kind,
+        };
+        Ok((timeline, pending_upload))
+    }
+
+    /// The part of timeline creation shared by [`Self::create_timeline`] and
+    /// [`Self::create_timeline_deferred_upload`]: everything up to, but not including,
+    /// waiting for the initial uploads and activating the timeline.
+    #[allow(clippy::too_many_arguments)]
+    async fn start_timeline_creation(
+        &self,
+        new_timeline_id: TimelineId,
+        ancestor_timeline_id: Option<TimelineId>,
+        mut ancestor_start_lsn: Option<Lsn>,
+        ancestor_start_timestamp: Option<SystemTime>,
+        pg_version: u32,
+        load_existing_initdb: Option<TimelineId>,
+        force_below_gc_cutoff: bool,
+        ctx: &RequestContext,
+    ) -> Result<TimelineCreation, CreateTimelineError> {
         if !self.is_active() {
             if matches!(self.current_state(), TenantState::Stopping { .. }) {
                 return Err(CreateTimelineError::ShuttingDown);
@@ -1413,6 +1986,18 @@ impl Tenant {
             }
         }
 
+        if self.is_read_only() {
+            return Err(CreateTimelineError::Other(anyhow::anyhow!(
+                "Cannot create timelines on a read-only tenant"
+            )));
+        }
+
+        if ancestor_start_lsn.is_some() && ancestor_start_timestamp.is_some() {
+            return Err(CreateTimelineError::AncestorLsn(anyhow::anyhow!(
+                "only one of ancestor_start_lsn and ancestor_start_timestamp may be specified"
+            )));
+        }
+
         let _gate = self
             .gate
             .enter()
@@ -1436,31 +2021,20 @@ impl Tenant {
                 debug!("timeline {new_timeline_id} already exists");
 
                 // Idempotency: creating the same timeline twice is not an error, unless
-                // the second creation has different parameters.
+                // the second creation has different parameters. Branches inherit their
+                // pg_version from the ancestor rather than choosing their own, so the
+                // caller's pg_version is only compared for root timelines. A start
+                // timestamp is not resolved again, like a missing start LSN it matches
+                // any branch point.
                 if existing.get_ancestor_timeline_id() != ancestor_timeline_id
-                    || existing.pg_version != pg_version
+                    || (ancestor_timeline_id.is_none() && existing.pg_version != pg_version)
                     || (ancestor_start_lsn.is_some()
                         && ancestor_start_lsn != Some(existing.get_ancestor_lsn()))
                 {
                     return Err(CreateTimelineError::Conflict);
                 }
 
-                if let Some(remote_client) = existing.remote_client.as_ref() {
-                    // Wait for uploads to complete, so that when we return Ok, the timeline
-                    // is known to be durable on remote storage. Just like we do at the end of
-                    // this function, after we have created the timeline ourselves.
-                    //
-                    // We only really care that the initial version of `index_part.json` has
-                    // been uploaded. That's enough to remember that the timeline
-                    // exists. However, there is no function to wait specifically for that so
-                    // we just wait for all in-progress uploads to finish.
-                    remote_client
-                        .wait_completion()
-                        .await
-                        .context("wait for timeline uploads to complete")?;
-                }
-
-                return Ok(existing);
+                return Ok(TimelineCreation::Existing(existing));
             }
         };
 
@@ -1476,6 +2050,17 @@ impl Tenant {
                     return Err(CreateTimelineError::AncestorNotActive);
                 }
 
+                if let Some(timestamp) = ancestor_start_timestamp {
+                    let lsn = self
+                        .find_branch_lsn_for_timestamp(&ancestor_timeline, timestamp, ctx)
+                        .await?;
+                    info!(
+                        "resolved start timestamp {} to lsn {lsn}",
+                        humantime::format_rfc3339(timestamp)
+                    );
+                    ancestor_start_lsn = Some(lsn);
+                }
+
                 if let Some(lsn) = ancestor_start_lsn.as_mut() {
                     *lsn = lsn.align();
 
@@ -1511,39 +2096,33 @@ impl Tenant {
                     &ancestor_timeline,
                     new_timeline_id,
                     ancestor_start_lsn,
+                    force_below_gc_cutoff,
                     uninit_mark,
                     ctx,
                 )
                 .await?
             }
-            None => {
-                self.bootstrap_timeline(
+            None => self
+                .bootstrap_timeline(
                     new_timeline_id,
                     pg_version,
                     load_existing_initdb,
                     uninit_mark,
                     ctx,
                 )
-                .await?
-            }
+                .await
+                .map_err(|e| {
+                    if TimeoutOrCancel::caused_by_cancel(&e) || self.cancel.is_cancelled() {
+                        CreateTimelineError::ShuttingDown
+                    } else {
+                        CreateTimelineError::Other(e)
+                    }
+                })?,
         };
 
         // At this point we have dropped our guard on [`Self::timelines_creating`], and
-        // the timeline is visible in [`Self::timelines`], but it is _not_ durable yet.  We must
-        // not send a success to the caller until it is.  The same applies to handling retries,
-        // see the handling of [`TimelineExclusionError::AlreadyExists`] above.
-        if let Some(remote_client) = loaded_timeline.remote_client.as_ref() {
-            let kind = ancestor_timeline_id
-                .map(|_| "branched")
-                .unwrap_or("bootstrapped");
-            remote_client.wait_completion().await.with_context(|| {
-                format!("wait for {} timeline initial uploads to complete", kind)
-            })?;
-        }
-
-        loaded_timeline.activate(broker_client, None, ctx);
-
-        Ok(loaded_timeline)
+        // the timeline is visible in [`Self::timelines`], but it is _not_ durable yet.
+        Ok(TimelineCreation::Created(loaded_timeline))
     }
 
     pub(crate) async fn delete_timeline(
@@ -1555,6 +2134,22 @@ impl Tenant {
         Ok(())
     }
 
+    /// Like [`Self::delete_timeline`], but a timeline which doesn't exist (e.g. because an
+    /// earlier attempt already deleted it) counts as successfully deleted, so that callers can
+    /// safely retry.
+    pub(crate) async fn delete_timeline_idempotent(
+        self: Arc<Self>,
+        timeline_id: TimelineId,
+    ) -> Result<(), DeleteTimelineError> {
+        match self.delete_timeline(timeline_id).await {
+            Err(DeleteTimelineError::NotFound) => {
+                info!("timeline {timeline_id} is already deleted");
+                Ok(())
+            }
+            res => res,
+        }
+    }
+
     /// perform one garbage collection iteration, removing old data files from disk.
     /// this function is periodically called by gc task.
     /// also it can be explicitly requested through page server api 'do_gc' command.
@@ -1575,20 +2170,19 @@ impl Tenant {
         pitr: Duration,
         cancel: &CancellationToken,
         ctx: &RequestContext,
-    ) -> anyhow::Result<GcResult> {
+    ) -> Result<GcResult, GcError> {
         // Don't start doing work during shutdown
         if let TenantState::Stopping { .. } = self.current_state() {
             return Ok(GcResult::default());
         }
 
         // there is a global allowed_error for this
-        anyhow::ensure!(
-            self.is_active(),
-            "Cannot run GC iteration on inactive tenant"
-        );
+        if !self.is_active() {
+            return Err(anyhow::anyhow!("Cannot run GC iteration on inactive tenant").into());
+        }
 
         {
-            let conf = self.tenant_conf.read().unwrap();
+            let conf = self.read_tenant_conf();
 
             if !conf.location.may_delete_layers_hint() {
                 info!("Skipping GC in location state {:?}", conf.location);
@@ -1596,7 +2190,27 @@ impl Tenant {
             }
         }
 
-        self.gc_iteration_internal(target_timeline_id, horizon, pitr, cancel, ctx)
+        self.gc_iteration_internal(target_timeline_id, horizon, pitr, false, cancel, ctx)
+            .await
+    }
+
+    /// Like [`Tenant::gc_iteration`], but only reports which layer files would be removed,
+    /// without removing them or advancing any GC cutoffs.
+    ///
+    /// The names of the layer files are returned in [`GcResult::layers_to_remove`].
+    pub async fn gc_iteration_dry_run(
+        &self,
+        target_timeline_id: Option<TimelineId>,
+        horizon: u64,
+        pitr: Duration,
+        cancel: &CancellationToken,
+        ctx: &RequestContext,
+    ) -> Result<GcResult, GcError> {
+        if !self.is_active() {
+            return Err(anyhow::anyhow!("Cannot run GC dry run on inactive tenant").into());
+        }
+
+        self.gc_iteration_internal(target_timeline_id, horizon, pitr, true, cancel, ctx)
             .await
     }
 
@@ -1614,8 +2228,13 @@ impl Tenant {
             return Ok(());
         }
 
+        if self.compaction_soft_stop.load(Ordering::Relaxed) {
+            debug!("Skipping compaction, it is soft-stopped");
+            return Ok(());
+        }
+
         {
-            let conf = self.tenant_conf.read().unwrap();
+            let conf = self.read_tenant_conf();
             if !conf.location.may_delete_layers_hint() || !conf.location.may_upload_layers_hint() {
                 info!("Skipping compaction in location state {:?}", conf.location);
                 return Ok(());
@@ -1627,7 +2246,7 @@ impl Tenant {
         // compactions.  We don't want to block everything else while the
         // compaction runs.
         let timelines_to_compact = {
-            let timelines = self.timelines.lock().unwrap();
+            let timelines = self.lock_timelines();
             let timelines_to_compact = timelines
                 .iter()
                 .filter_map(|(timeline_id, timeline)| {
@@ -1643,6 +2262,12 @@ impl Tenant {
         };
 
         for (timeline_id, timeline) in &timelines_to_compact {
+            // Checked between timelines only: unlike `cancel`, a soft stop lets the timeline
+            // being compacted finish.
+            if self.compaction_soft_stop.load(Ordering::Relaxed) {
+                info!("Compaction was soft-stopped, not compacting the remaining timelines");
+                break;
+            }
             timeline
                 .compact(cancel, EnumSet::empty(), ctx)
                 .instrument(info_span!("compact_timeline", %timeline_id))
@@ -1652,6 +2277,43 @@ impl Tenant {
         Ok(())
     }
 
+    /// Ask background compaction to stop at the next timeline boundary, or let it run again.
+    ///
+    /// An iteration that is in progress finishes compacting its current timeline, but doesn't
+    /// start on the next one, and later iterations do nothing until the soft stop is lifted.
+    /// This allows draining compaction without interrupting it in the middle of writing layers.
+    pub(crate) fn set_compaction_soft_stop(&self, stop: bool) {
+        self.compaction_soft_stop.store(stop, Ordering::Relaxed);
+    }
+
+    /// Compact a single active timeline, e.g. on request from the management API.
+    ///
+    /// Like [`Self::compaction_iteration`], this does nothing in location states that may
+    /// not upload or delete layers.  Returns an error wrapping [`GetTimelineError`] if the
+    /// timeline does not exist or is not active.
+    pub(crate) async fn compact_timeline(
+        &self,
+        timeline_id: TimelineId,
+        flags: EnumSet<CompactFlags>,
+        cancel: &CancellationToken,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
+        let timeline = self.get_timeline(timeline_id, true)?;
+
+        {
+            let conf = self.read_tenant_conf();
+            if !conf.location.may_delete_layers_hint() || !conf.location.may_upload_layers_hint() {
+                info!("Skipping compaction in location state {:?}", conf.location);
+                return Ok(());
+            }
+        }
+
+        timeline
+            .compact(cancel, flags, ctx)
+            .await
+            .map_err(anyhow::Error::from)
+    }
+
     pub fn current_state(&self) -> TenantState {
         self.state.borrow().clone()
     }
@@ -1701,7 +2363,7 @@ impl Tenant {
         });
 
         if activating {
-            let timelines_accessor = self.timelines.lock().unwrap();
+            let timelines_accessor = self.lock_timelines();
             let timelines_to_activate = timelines_accessor
                 .values()
                 .filter(|timeline| !(timeline.is_broken() || timeline.is_stopping()));
@@ -1802,7 +2464,7 @@ impl Tenant {
 
         let mut js = tokio::task::JoinSet::new();
         {
-            let timelines = self.timelines.lock().unwrap();
+            let timelines = self.lock_timelines();
             timelines.values().for_each(|timeline| {
                 let timeline = Arc::clone(timeline);
                 let timeline_id = timeline.timeline_id;
@@ -1932,7 +2594,7 @@ impl Tenant {
             ),
         }
 
-        let timelines_accessor = self.timelines.lock().unwrap();
+        let timelines_accessor = self.lock_timelines();
         let not_broken_timelines = timelines_accessor
             .values()
             .filter(|timeline| !timeline.is_broken());
@@ -1981,6 +2643,8 @@ impl Tenant {
                     if cfg!(feature = "testing") {
                         warn!("Changing Active tenant to Broken state, reason: {}", reason);
                         *current_state = TenantState::broken_from_reason(reason);
+                        self.broken_by_test
+                            .store(true, Ordering::Relaxed);
                     } else {
                         unreachable!("not allowed to call set_broken on Active tenants in non-testing builds")
                     }
@@ -2000,6 +2664,63 @@ impl Tenant {
         });
     }
 
+    /// Bring a tenant which a testing build set Broken while it was Active back to Active,
+    /// activating any of its timelines which are not active.
+    ///
+    /// Tenants that are Broken for any other reason are refused: their in-memory state may not
+    /// be usable.  Background loops are still running, waiting for the tenant to be Active.
+    pub fn clear_broken_and_reactivate(
+        &self,
+        broker_client: BrokerClientChannel,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
+        if !cfg!(feature = "testing") {
+            unreachable!("not allowed to reactivate Broken tenants in non-testing builds")
+        }
+
+        let mut err = None;
+        self.state
+            .send_if_modified(|current_state| match current_state {
+                TenantState::Broken { reason, .. }
+                    if self.broken_by_test.load(Ordering::Relaxed) =>
+                {
+                    info!("Reactivating tenant that was Broken due to: {reason}");
+                    *current_state =
+                        TenantState::Activating(pageserver_api::models::ActivatingFrom::Broken);
+                    true
+                }
+                other => {
+                    err = Some(anyhow::anyhow!("not Broken by a test, state: {other}"));
+                    false
+                }
+            });
+        if let Some(err) = err {
+            return Err(err);
+        }
+
+        let timelines_accessor = self.lock_timelines();
+        let timelines_to_activate = timelines_accessor.values().filter(|timeline| {
+            !(timeline.is_active() || timeline.is_broken() || timeline.is_stopping())
+        });
+        let mut activated_timelines = 0;
+        for timeline in timelines_to_activate {
+            timeline.activate(broker_client.clone(), None, ctx);
+            activated_timelines += 1;
+        }
+
+        self.broken_by_test.store(false, Ordering::Relaxed);
+        self.state.send_modify(|current_state| {
+            assert!(
+                matches!(current_state, TenantState::Activating(_)),
+                "set_stopping and set_broken wait for us to leave Activating state",
+            );
+            *current_state = TenantState::Active;
+        });
+        info!(activated_timelines, "reactivated tenant");
+
+        Ok(())
+    }
+
     pub fn subscribe_for_state_updates(&self) -> watch::Receiver<TenantState> {
         self.state.subscribe()
     }
@@ -2010,10 +2731,13 @@ impl Tenant {
         self.activate_now_sem.add_permits(1);
     }
 
+    /// Wait for the tenant to become Active, for at most `timeout` in total: the deadline is
+    /// fixed on entry, so a tenant that keeps changing state doesn't extend the wait.
     pub(crate) async fn wait_to_become_active(
         &self,
         timeout: Duration,
     ) -> Result<(), GetActiveTenantError> {
+        let deadline = Instant::now() + timeout;
         let mut receiver = self.state.subscribe();
         loop {
             let current_state = receiver.borrow_and_update().clone();
@@ -2021,7 +2745,8 @@ impl Tenant {
                 TenantState::Loading | TenantState::Attaching | TenantState::Activating(_) => {
                     // in these states, there's a chance that we can reach ::Active
                     self.activate_now();
-                    match timeout_cancellable(timeout, &self.cancel, receiver.changed()).await {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    match timeout_cancellable(remaining, &self.cancel, receiver.changed()).await {
                         Ok(r) => {
                             r.map_err(
                             |_e: tokio::sync::watch::error::RecvError|
@@ -2051,15 +2776,49 @@ impl Tenant {
         }
     }
 
+    /// Wait until no timeline creations are in progress, for at most `timeout` in total.
+    ///
+    /// This does not prevent new creations from starting: callers which need creation to stay
+    /// quiesced (e.g. before a split) must exclude new creations themselves.
+    pub async fn wait_no_timelines_creating(&self, timeout: Duration) -> anyhow::Result<()> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            // Register for the notification before checking the set, so that a removal
+            // between the check and the wait is not missed.
+            let removed = self.timelines_creating_removed.notified();
+            tokio::pin!(removed);
+            removed.as_mut().enable();
+
+            let creating = self.lock_timelines_creating().clone();
+            if creating.is_empty() {
+                return Ok(());
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if tokio::time::timeout(remaining, removed).await.is_err() {
+                anyhow::bail!(
+                    "Timed out after {timeout:?} waiting for {} timeline creation(s) to finish: {:?}",
+                    creating.len(),
+                    creating
+                );
+            }
+        }
+    }
+
     pub(crate) fn get_attach_mode(&self) -> AttachmentMode {
-        self.tenant_conf.read().unwrap().location.attach_mode
+        self.read_tenant_conf().location.attach_mode
+    }
+
+    /// Whether the tenant is attached read-only, see [`AttachedLocationConfig::read_only`].
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.read_tenant_conf().location.read_only
     }
 
     /// For API access: generate a LocationConfig equivalent to the one that would be used to
     /// create a Tenant in the same state.  Do not use this in hot paths: it's for relatively
     /// rare external API calls, like a reconciliation at startup.
     pub(crate) fn get_location_conf(&self) -> models::LocationConfig {
-        let conf = self.tenant_conf.read().unwrap();
+        let conf = self.read_tenant_conf();
 
         let location_config_mode = match conf.location.attach_mode {
             AttachmentMode::Single => models::LocationConfigMode::AttachedSingle,
@@ -2077,6 +2836,7 @@ impl Tenant {
             shard_number: self.shard_identity.number.0,
             shard_count: self.shard_identity.count.literal(),
             shard_stripe_size: self.shard_identity.stripe_size.0,
+            read_only: conf.location.read_only,
             tenant_conf: tenant_config,
         }
     }
@@ -2096,7 +2856,7 @@ impl Tenant {
         &self,
         child_shards: &Vec<TenantShardId>,
     ) -> anyhow::Result<()> {
-        let timelines = self.timelines.lock().unwrap().clone();
+        let timelines = self.lock_timelines().clone();
         for timeline in timelines.values() {
             let Some(tl_client) = &timeline.remote_client else {
                 anyhow::bail!("Remote storage is mandatory");
@@ -2134,16 +2894,40 @@ impl Tenant {
                 MaybeDeletedIndexPart::IndexPart(p) => p,
             };
 
+            // Child indices are independent of each other, so upload them concurrently. Dropping
+            // the JoinSet on the first error aborts the uploads which are still in flight.
+            let index_part = Arc::new(index_part);
+            let mut uploads = tokio::task::JoinSet::new();
             for child_shard in child_shards {
-                upload_index_part(
-                    remote_storage,
-                    child_shard,
-                    &timeline.timeline_id,
-                    self.generation,
-                    &index_part,
-                    &self.cancel,
-                )
-                .await?;
+                if uploads.len() >= SPLIT_PREPARE_UPLOAD_CONCURRENCY {
+                    if let Some(res) = uploads.join_next().await {
+                        res.context("join child shard index upload")??;
+                    }
+                }
+
+                let remote_storage = remote_storage.clone();
+                let child_shard = *child_shard;
+                let timeline_id = timeline.timeline_id;
+                let generation = self.generation;
+                let index_part = Arc::clone(&index_part);
+                let cancel = self.cancel.clone();
+                uploads.spawn(
+                    async move {
+                        upload_index_part(
+                            &remote_storage,
+                            &child_shard,
+                            &timeline_id,
+                            generation,
+                            &index_part,
+                            &cancel,
+                        )
+                        .await
+                    }
+                    .instrument(info_span!("upload_index_part", tenant_id=%child_shard.tenant_id, shard_id=%child_shard.shard_slug(), %timeline_id)),
+                );
+            }
+            while let Some(res) = uploads.join_next().await {
+                res.context("join child shard index upload")??;
             }
         }
 
@@ -2156,10 +2940,14 @@ impl Tenant {
 /// before the children.
 /// E extracts the ancestor from T
 /// This allows for T to be different. It can be TimelineMetadata, can be Timeline itself, etc.
-fn tree_sort_timelines<T, E>(
+///
+/// The timelines that could not be reached from a root timeline are returned separately, each
+/// with the id of its missing ancestor.
+#[allow(clippy::type_complexity)]
+fn tree_sort_timelines_with_orphans<T, E>(
     timelines: HashMap<TimelineId, T>,
     extractor: E,
-) -> anyhow::Result<Vec<(TimelineId, T)>>
+) -> (Vec<(TimelineId, T)>, Vec<(TimelineId, TimelineId, T)>)
 where
     E: Fn(&T) -> Option<TimelineId>,
 {
@@ -2187,22 +2975,22 @@ where
         }
     }
 
-    // All timelines should be visited now. Unless there were timelines with missing ancestors.
-    if !later.is_empty() {
-        for (missing_id, orphan_ids) in later {
-            for (orphan_id, _) in orphan_ids {
-                error!("could not load timeline {orphan_id} because its ancestor timeline {missing_id} could not be loaded");
-            }
-        }
-        bail!("could not load tenant because some timelines are missing ancestors");
-    }
+    // Whatever is left could not be reached from a root timeline.
+    let orphans = later
+        .into_iter()
+        .flat_map(|(missing_id, orphans)| {
+            orphans
+                .into_iter()
+                .map(move |(orphan_id, value)| (orphan_id, missing_id, value))
+        })
+        .collect();
 
-    Ok(result)
+    (result, orphans)
 }
 
 impl Tenant {
     pub fn tenant_specific_overrides(&self) -> TenantConfOpt {
-        self.tenant_conf.read().unwrap().tenant_conf.clone()
+        self.read_tenant_conf().tenant_conf.clone()
     }
 
     pub fn effective_config(&self) -> TenantConf {
@@ -2210,85 +2998,137 @@ impl Tenant {
             .merge(self.conf.default_tenant_conf.clone())
     }
 
+    /// [`Self::effective_config`] serialized to TOML the same way [`Self::persist_tenant_config_at`]
+    /// does, so that the live config can be diffed against the persisted one.
+    pub fn effective_config_toml(&self) -> anyhow::Result<String> {
+        toml_edit::ser::to_string_pretty(&self.effective_config())
+            .context("serialize effective tenant config")
+    }
+
     pub fn get_checkpoint_distance(&self) -> u64 {
-        let tenant_conf = self.tenant_conf.read().unwrap().tenant_conf.clone();
+        let tenant_conf = self.read_tenant_conf().tenant_conf.clone();
         tenant_conf
             .checkpoint_distance
             .unwrap_or(self.conf.default_tenant_conf.checkpoint_distance)
     }
 
     pub fn get_checkpoint_timeout(&self) -> Duration {
-        let tenant_conf = self.tenant_conf.read().unwrap().tenant_conf.clone();
+        let tenant_conf = self.read_tenant_conf().tenant_conf.clone();
         tenant_conf
             .checkpoint_timeout
             .unwrap_or(self.conf.default_tenant_conf.checkpoint_timeout)
     }
 
     pub fn get_compaction_target_size(&self) -> u64 {
-        let tenant_conf = self.tenant_conf.read().unwrap().tenant_conf.clone();
+        let tenant_conf = self.read_tenant_conf().tenant_conf.clone();
         tenant_conf
             .compaction_target_size
             .unwrap_or(self.conf.default_tenant_conf.compaction_target_size)
     }
 
     pub fn get_compaction_period(&self) -> Duration {
-        let tenant_conf = self.tenant_conf.read().unwrap().tenant_conf.clone();
+        let tenant_conf = self.read_tenant_conf().tenant_conf.clone();
         tenant_conf
             .compaction_period
             .unwrap_or(self.conf.default_tenant_conf.compaction_period)
     }
 
     pub fn get_compaction_threshold(&self) -> usize {
-        let tenant_conf = self.tenant_conf.read().unwrap().tenant_conf.clone();
+        let tenant_conf = self.read_tenant_conf().tenant_conf.clone();
         tenant_conf
             .compaction_threshold
             .unwrap_or(self.conf.default_tenant_conf.compaction_threshold)
     }
 
     pub fn get_gc_horizon(&self) -> u64 {
-        let tenant_conf = self.tenant_conf.read().unwrap().tenant_conf.clone();
+        let tenant_conf = self.read_tenant_conf().tenant_conf.clone();
         tenant_conf
             .gc_horizon
             .unwrap_or(self.conf.default_tenant_conf.gc_horizon)
     }
 
+    /// The `gc_horizon` to use for the given timeline, if it or one of its ancestors has a
+    /// per-timeline override. The closest one wins, so a branch inherits its ancestor's override
+    /// unless it has one of its own.
+    pub fn get_timeline_gc_horizon_override(&self, timeline_id: TimelineId) -> Option<u64> {
+        let overrides = self
+            .read_tenant_conf()
+            .tenant_conf
+            .timeline_gc_horizon_overrides
+            .clone()?;
+        let timelines = self.lock_timelines();
+        let mut timeline_id = Some(timeline_id);
+        while let Some(id) = timeline_id {
+            if let Some(&horizon) = overrides.get(&id) {
+                return Some(horizon);
+            }
+            timeline_id = timelines
+                .get(&id)
+                .and_then(|timeline| timeline.get_ancestor_timeline_id());
+        }
+        None
+    }
+
     pub fn get_gc_period(&self) -> Duration {
-        let tenant_conf = self.tenant_conf.read().unwrap().tenant_conf.clone();
+        let tenant_conf = self.read_tenant_conf().tenant_conf.clone();
         tenant_conf
             .gc_period
             .unwrap_or(self.conf.default_tenant_conf.gc_period)
     }
 
+    pub fn get_gc_parallelism(&self) -> NonZeroUsize {
+        let tenant_conf = self.read_tenant_conf().tenant_conf.clone();
+        tenant_conf
+            .gc_parallelism
+            .unwrap_or(self.conf.default_tenant_conf.gc_parallelism)
+    }
+
+    pub fn get_skip_orphan_timelines(&self) -> bool {
+        let tenant_conf = self.read_tenant_conf().tenant_conf.clone();
+        tenant_conf
+            .skip_orphan_timelines
+            .unwrap_or(self.conf.default_tenant_conf.skip_orphan_timelines)
+    }
+
     pub fn get_image_creation_threshold(&self) -> usize {
-        let tenant_conf = self.tenant_conf.read().unwrap().tenant_conf.clone();
+        let tenant_conf = self.read_tenant_conf().tenant_conf.clone();
         tenant_conf
             .image_creation_threshold
             .unwrap_or(self.conf.default_tenant_conf.image_creation_threshold)
     }
 
     pub fn get_pitr_interval(&self) -> Duration {
-        let tenant_conf = self.tenant_conf.read().unwrap().tenant_conf.clone();
+        let tenant_conf = self.read_tenant_conf().tenant_conf.clone();
         tenant_conf
             .pitr_interval
             .unwrap_or(self.conf.default_tenant_conf.pitr_interval)
     }
 
+    pub fn get_synthetic_size_calculation_enabled(&self) -> bool {
+        let tenant_conf = self.read_tenant_conf().tenant_conf.clone();
+        tenant_conf.synthetic_size_calculation_enabled.unwrap_or(
+            self.conf
+                .default_tenant_conf
+                .synthetic_size_calculation_enabled,
+        )
+    }
+
     pub fn get_trace_read_requests(&self) -> bool {
-        let tenant_conf = self.tenant_conf.read().unwrap().tenant_conf.clone();
+        let tenant_conf = self.read_tenant_conf().tenant_conf.clone();
         tenant_conf
             .trace_read_requests
             .unwrap_or(self.conf.default_tenant_conf.trace_read_requests)
     }
 
     pub fn get_min_resident_size_override(&self) -> Option<u64> {
-        let tenant_conf = self.tenant_conf.read().unwrap().tenant_conf.clone();
+        let tenant_conf = self.read_tenant_conf().tenant_conf.clone();
         tenant_conf
             .min_resident_size_override
             .or(self.conf.default_tenant_conf.min_resident_size_override)
     }
 
     pub fn get_heatmap_period(&self) -> Option<Duration> {
-        let tenant_conf = self.tenant_conf.read().unwrap().tenant_conf.clone();
+        let tenant_conf = self.read_tenant_conf().tenant_conf.clone();
         let heatmap_period = tenant_conf
             .heatmap_period
             .unwrap_or(self.conf.default_tenant_conf.heatmap_period);
@@ -2299,8 +3139,12 @@ impl Tenant {
         }
     }
 
-    pub fn set_new_tenant_config(&self, new_tenant_conf: TenantConfOpt) {
-        self.tenant_conf.write().unwrap().tenant_conf = new_tenant_conf;
+    pub fn set_new_tenant_config(&self, new_tenant_conf: TenantConfOpt) -> anyhow::Result<()> {
+        new_tenant_conf
+            .validate()
+            .map_err(|e| anyhow::anyhow!("invalid tenant config: {e}"))?;
+
+        self.write_tenant_conf().tenant_conf = new_tenant_conf;
         self.tenant_conf_updated();
         // Don't hold self.timelines.lock() during the notifies.
         // There's no risk of deadlock right now, but there could be if we consolidate
@@ -2309,10 +3153,20 @@ impl Tenant {
         for timeline in timelines {
             timeline.tenant_conf_updated();
         }
+
+        Ok(())
     }
 
-    pub(crate) fn set_new_location_config(&self, new_conf: AttachedTenantConf) {
-        *self.tenant_conf.write().unwrap() = new_conf;
+    pub(crate) fn set_new_location_config(
+        &self,
+        new_conf: AttachedTenantConf,
+    ) -> anyhow::Result<()> {
+        new_conf
+            .tenant_conf
+            .validate()
+            .map_err(|e| anyhow::anyhow!("invalid tenant config: {e}"))?;
+
+        *self.write_tenant_conf() = new_conf;
         self.tenant_conf_updated();
         // Don't hold self.timelines.lock() during the notifies.
         // There's no risk of deadlock right now, but there could be if we consolidate
@@ -2321,6 +3175,52 @@ impl Tenant {
         for timeline in timelines {
             timeline.tenant_conf_updated();
         }
+
+        Ok(())
+    }
+
+    /// Re-read this tenant's config from disk and apply it, for when an operator edited the
+    /// config file of a running tenant.
+    ///
+    /// The location part of the config has to match what we are running with: we cannot
+    /// change our generation or attachment mode without going through the location_config API.
+    pub(crate) fn reload_config_from_disk(&self) -> Result<(), ReloadConfigError> {
+        let location_conf =
+            Self::load_tenant_config(self.conf, &self.tenant_shard_id).map_err(|e| {
+                if e.chain().any(|c| c.is::<std::io::Error>()) {
+                    ReloadConfigError::Other(e)
+                } else {
+                    ReloadConfigError::Invalid(e)
+                }
+            })?;
+        let new_conf =
+            AttachedTenantConf::try_from(location_conf).map_err(ReloadConfigError::Invalid)?;
+
+        let current = self.read_tenant_conf().location;
+        let on_disk = new_conf.location;
+        if on_disk.generation < current.generation {
+            return Err(ReloadConfigError::Invalid(anyhow::anyhow!(
+                "refusing to downgrade generation from {:?} to {:?}",
+                current.generation,
+                on_disk.generation
+            )));
+        }
+        if on_disk.generation != current.generation {
+            return Err(ReloadConfigError::Invalid(anyhow::anyhow!(
+                "generation on disk {:?} does not match running generation {:?}, re-attach to change it",
+                on_disk.generation,
+                current.generation
+            )));
+        }
+        if on_disk != current {
+            return Err(ReloadConfigError::Invalid(anyhow::anyhow!(
+                "location mode on disk {on_disk:?} does not match running mode {current:?}"
+            )));
+        }
+
+        info!("reloading tenant config from disk");
+        self.set_new_location_config(new_conf)
+            .map_err(ReloadConfigError::Invalid)
     }
 
     fn get_timeline_get_throttle_config(
@@ -2335,7 +3235,7 @@ impl Tenant {
 
     pub(crate) fn tenant_conf_updated(&self) {
         let conf = {
-            let guard = self.tenant_conf.read().unwrap();
+            let guard = self.read_tenant_conf();
             Self::get_timeline_get_throttle_config(self.conf, &guard.tenant_conf)
         };
         self.timeline_get_throttle.reconfigure(conf)
@@ -2368,6 +3268,7 @@ impl Tenant {
                 TimelineState::Loading
             }
             CreateTimelineCause::Delete => TimelineState::Stopping,
+            CreateTimelineCause::Broken => TimelineState::Loading,
         };
 
         let pg_version = new_metadata.pg_version();
@@ -2403,6 +3304,7 @@ impl Tenant {
         tenant_shard_id: TenantShardId,
         remote_storage: Option<GenericRemoteStorage>,
         deletion_queue_client: DeletionQueueClient,
+        broken_events: tokio::sync::broadcast::Sender<TenantBrokenEvent>,
     ) -> Tenant {
         let (state, mut rx) = watch::channel(state);
 
@@ -2413,18 +3315,28 @@ impl Tenant {
             //
             // set of broken tenants should not have zero counts so that it remains accessible for
             // alerting.
+            //
+            // Every transition into Broken is also published as a mgr::TenantBrokenEvent: doing it
+            // here rather than at the call sites covers all paths, including late failures.
 
             let tid = tenant_shard_id.to_string();
             let shard_id = tenant_shard_id.shard_slug().to_string();
             let set_key = &[tid.as_str(), shard_id.as_str()][..];
 
-            fn inspect_state(state: &TenantState) -> ([&'static str; 1], bool) {
-                ([state.into()], matches!(state, TenantState::Broken { .. }))
+            fn inspect_state(state: &TenantState) -> ([&'static str; 1], Option<String>) {
+                let broken_reason = match state {
+                    TenantState::Broken { reason, .. } => Some(reason.clone()),
+                    _ => None,
+                };
+                ([state.into()], broken_reason)
             }
 
             let mut tuple = inspect_state(&rx.borrow_and_update());
 
-            let is_broken = tuple.1;
+            let mut is_broken = tuple.1.is_some();
+            if let Some(reason) = tuple.1.take() {
+                mgr::notify_tenant_broken(&broken_events, tenant_shard_id, reason);
+            }
             let mut counted_broken = if is_broken {
                 // add the id to the set right away, there should not be any updates on the channel
                 // after before tenant is removed, if ever
@@ -2449,7 +3361,13 @@ impl Tenant {
                 current.dec();
                 tuple = inspect_state(&rx.borrow_and_update());
 
-                let is_broken = tuple.1;
+                let was_broken = is_broken;
+                is_broken = tuple.1.is_some();
+                if let Some(reason) = tuple.1.take() {
+                    if !was_broken {
+                        mgr::notify_tenant_broken(&broken_events, tenant_shard_id, reason);
+                    }
+                }
                 if is_broken && !counted_broken {
                     counted_broken = true;
                     // insert the tenant_id (back) into the set while avoiding needless counter
@@ -2469,15 +3387,19 @@ impl Tenant {
             constructed_at: Instant::now(),
             timelines: Mutex::new(HashMap::new()),
             timelines_creating: Mutex::new(HashSet::new()),
+            timelines_creating_removed: tokio::sync::Notify::new(),
             gc_cs: tokio::sync::Mutex::new(()),
             walredo_mgr,
             remote_storage,
             deletion_queue_client,
             state,
-            cached_logical_sizes: tokio::sync::Mutex::new(HashMap::new()),
-            cached_synthetic_tenant_size: Arc::new(AtomicU64::new(0)),
+            cached_logical_sizes: Mutex::new(HashMap::new()),
+            cached_synthetic_tenant_size: Mutex::new(None),
+            size_calculation_progress: Mutex::new(None),
             eviction_task_tenant_state: tokio::sync::Mutex::new(EvictionTaskTenantState::default()),
             activate_now_sem: tokio::sync::Semaphore::new(0),
+            broken_by_test: AtomicBool::new(false),
+            compaction_soft_stop: AtomicBool::new(false),
             delete_progress: Arc::new(tokio::sync::Mutex::new(DeleteTenantFlow::default())),
             cancel: CancellationToken::default(),
             gate: Gate::default(),
@@ -2499,10 +3421,10 @@ impl Tenant {
 
         if config_path.exists() {
             // New-style config takes precedence
-            let deserialized = Self::read_config(&config_path)?;
-            Ok(toml_edit::de::from_document::<LocationConf>(deserialized)?)
+            Ok(Self::read_location_config(&config_path)?.conf)
         } else if legacy_config_path.exists() {
-            // Upgrade path: found an old-style configuration only
+            // Upgrade path: found an old-style configuration only. Also the case for tenants
+            // without generations, which get it written regardless of `write_legacy_tenant_config`.
             let deserialized = Self::read_config(&legacy_config_path)?;
 
             let mut tenant_conf = TenantConfOpt::default();
@@ -2548,6 +3470,20 @@ impl Tenant {
             .with_context(|| format!("Failed to parse config from file '{path}' as toml file"))
     }
 
+    /// Read a new-style config, tolerating keys that this version doesn't know about.
+    fn read_location_config(path: &Utf8Path) -> anyhow::Result<LenientLocationConf> {
+        let deserialized = Self::read_config(path)?;
+        let lenient = LenientLocationConf::from_document(deserialized)
+            .with_context(|| format!("Failed to parse location config from file '{path}'"))?;
+        if !lenient.unknown.is_empty() {
+            warn!(
+                "config file {path} has unrecognized keys {:?}, they will be preserved",
+                lenient.unknown.keys().collect::<Vec<_>>()
+            );
+        }
+        Ok(lenient)
+    }
+
     #[tracing::instrument(skip_all, fields(tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug()))]
     pub(super) async fn persist_tenant_config(
         conf: &'static PageServerConf,
@@ -2562,6 +3498,7 @@ impl Tenant {
             &config_path,
             &legacy_config_path,
             location_conf,
+            conf.write_legacy_tenant_config,
         )
         .await
     }
@@ -2572,22 +3509,28 @@ impl Tenant {
         config_path: &Utf8Path,
         legacy_config_path: &Utf8Path,
         location_conf: &LocationConf,
+        write_legacy: bool,
     ) -> anyhow::Result<()> {
+        // Once we use LocationMode, generations are mandatory.  If we aren't using generations,
+        // then the legacy-style config is the only one we write.
+        let legacy_only = matches!(
+            &location_conf.mode,
+            LocationMode::Attached(attach_conf) if attach_conf.generation.is_none()
+        );
+
         // Forward compat: write out an old-style configuration that old versions can read, in case we roll back
-        Self::persist_tenant_config_legacy(
-            tenant_shard_id,
-            legacy_config_path,
-            &location_conf.tenant_conf,
-        )
-        .await?;
+        if write_legacy || legacy_only {
+            Self::persist_tenant_config_legacy(
+                tenant_shard_id,
+                legacy_config_path,
+                &location_conf.tenant_conf,
+            )
+            .await?;
+        }
 
-        if let LocationMode::Attached(attach_conf) = &location_conf.mode {
-            // Once we use LocationMode, generations are mandatory.  If we aren't using generations,
-            // then drop out after writing legacy-style config.
-            if attach_conf.generation.is_none() {
-                tracing::debug!("Running without generations, not writing new-style LocationConf");
-                return Ok(());
-            }
+        if legacy_only {
+            tracing::debug!("Running without generations, not writing new-style LocationConf");
+            return Ok(());
         }
 
         debug!("persisting tenantconf to {config_path}");
@@ -2601,8 +3544,25 @@ impl Tenant {
             anyhow::bail!("tenant-config-before-write");
         });
 
+        // Carry over keys from the previous config that we don't recognize.
+        let previous = if config_path.exists() {
+            Self::read_location_config(config_path)
+                .map_err(|e| {
+                    warn!("not preserving unrecognized keys of {config_path}: {e:#}");
+                })
+                .ok()
+        } else {
+            None
+        };
+
         // Convert the config to a toml file.
-        conf_content += &toml_edit::ser::to_string_pretty(&location_conf)?;
+        let mut serialized = toml_edit::ser::to_string_pretty(&location_conf)?;
+        if let Some(previous) = previous.filter(|p| !p.unknown.is_empty()) {
+            let mut document = serialized.parse::<toml_edit::Document>()?;
+            previous.preserve_unknown(&mut document);
+            serialized = document.to_string();
+        }
+        conf_content += &serialized;
 
         let temp_path = path_with_suffix_extension(config_path, TEMP_FILE_SUFFIX);
 
@@ -2613,6 +3573,22 @@ impl Tenant {
             .await
             .with_context(|| format!("write tenant {tenant_shard_id} config to {config_path}"))?;
 
+        // A legacy config that we no longer keep up to date would hand stale settings to an
+        // older version after a rollback: remove it, now that the new-style config is durable.
+        if !write_legacy {
+            match tokio::fs::remove_file(legacy_config_path).await {
+                Ok(()) => {
+                    info!("removed legacy tenant config {legacy_config_path}");
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(anyhow::Error::new(e).context(format!(
+                        "remove legacy tenant {tenant_shard_id} config {legacy_config_path}"
+                    )))
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -2672,38 +3648,61 @@ impl Tenant {
     // - if a relation has a non-incremental persistent layer on a child branch, then we
     //   don't need to keep that in the parent anymore. But currently
     //   we do.
+    //
+    // With `dry_run`, steps 1 and 2 are performed, but the refreshed GC information is not
+    // stored on the timelines, and step 3 only collects the names of the files it would remove.
     async fn gc_iteration_internal(
         &self,
         target_timeline_id: Option<TimelineId>,
         horizon: u64,
         pitr: Duration,
+        dry_run: bool,
         cancel: &CancellationToken,
         ctx: &RequestContext,
-    ) -> anyhow::Result<GcResult> {
+    ) -> Result<GcResult, GcError> {
         let mut totals: GcResult = Default::default();
+        if dry_run {
+            totals.layers_to_remove = Some(Vec::new());
+        }
         let now = Instant::now();
 
         let gc_timelines = match self
-            .refresh_gc_info_internal(target_timeline_id, horizon, pitr, cancel, ctx)
+            .refresh_gc_info_internal(target_timeline_id, horizon, pitr, dry_run, cancel, ctx)
             .await
         {
             Ok(result) => result,
+            Err(GcError::Other(e))
+                if matches!(
+                    e.downcast_ref::<PageReconstructError>(),
+                    Some(PageReconstructError::Cancelled)
+                ) =>
+            {
+                // Handle cancellation
+                totals.elapsed = now.elapsed();
+                totals.cancelled = true;
+                return Ok(totals);
+            }
             Err(e) => {
-                if let Some(PageReconstructError::Cancelled) =
-                    e.downcast_ref::<PageReconstructError>()
-                {
-                    // Handle cancellation
-                    totals.elapsed = now.elapsed();
-                    return Ok(totals);
-                } else {
-                    // Propagate other errors
-                    return Err(e);
-                }
+                // Propagate other errors
+                return Err(e);
             }
         };
 
         failpoint_support::sleep_millis_async!("gc_iteration_internal_after_getting_gc_timelines");
 
+        // Persist the refreshed cutoffs, so that they can be restored after a restart, before
+        // the first GC iteration runs.
+        if !dry_run && self.read_tenant_conf().location.may_upload_layers_hint() {
+            for (timeline, gc_info) in &gc_timelines {
+                if let Some(remote_client) = &timeline.remote_client {
+                    remote_client.schedule_index_upload_for_gc_cutoffs(GcCutoffs {
+                        horizon: gc_info.horizon_cutoff,
+                        pitr: gc_info.pitr_cutoff,
+                    })?;
+                }
+            }
+        }
+
         // If there is nothing to GC, we don't want any messages in the INFO log.
         if !gc_timelines.is_empty() {
             info!("{} timelines need GC", gc_timelines.len());
@@ -2711,7 +3710,7 @@ impl Tenant {
             debug!("{} timelines need GC", gc_timelines.len());
         }
 
-        // Perform GC for each timeline.
+        // Perform GC for each timeline, running up to `gc_parallelism` of them at a time.
         //
         // Note that we don't hold the `Tenant::gc_cs` lock here because we don't want to delay the
         // branch creation task, which requires the GC lock. A GC iteration can run concurrently
@@ -2719,14 +3718,47 @@ impl Tenant {
         //
         // See comments in [`Tenant::branch_timeline`] for more information about why branch
         // creation task can run concurrently with timeline's GC iteration.
-        for timeline in gc_timelines {
-            if task_mgr::is_shutdown_requested() || cancel.is_cancelled() {
-                // We were requested to shut down. Stop and return with the progress we
-                // made.
-                break;
+        let gc_parallelism = self.get_gc_parallelism().get();
+        let mut gc_timelines = gc_timelines.into_iter();
+        let mut in_progress = FuturesUnordered::new();
+        let mut first_error = None;
+        loop {
+            // Don't start any more timelines once one of them has failed, but let the ones
+            // already running finish rather than dropping them halfway through.
+            while in_progress.len() < gc_parallelism && first_error.is_none() {
+                if task_mgr::is_shutdown_requested() || cancel.is_cancelled() {
+                    // We were requested to shut down. Stop and return with the progress we
+                    // made, flagging the result as partial if any timelines were left out.
+                    if !gc_timelines.as_slice().is_empty() {
+                        totals.cancelled = true;
+                    }
+                    break;
+                }
+                let Some((timeline, gc_info)) = gc_timelines.next() else {
+                    break;
+                };
+                in_progress.push(async move {
+                    if dry_run {
+                        timeline.gc_dry_run(&gc_info).await
+                    } else {
+                        timeline.gc().await
+                    }
+                });
+            }
+
+            match in_progress.next().await {
+                Some(Ok(result)) => totals += result,
+                Some(Err(e)) => {
+                    if first_error.is_none() {
+                        first_error = Some(e);
+                    }
+                }
+                None => break,
             }
-            let result = timeline.gc().await?;
-            totals += result;
+        }
+
+        if let Some(e) = first_error {
+            return Err(GcError::Other(e));
         }
 
         totals.elapsed = now.elapsed();
@@ -2752,30 +3784,74 @@ impl Tenant {
         // refresh all timelines
         let target_timeline_id = None;
 
-        self.refresh_gc_info_internal(target_timeline_id, horizon, pitr, cancel, ctx)
+        let gc_timelines = self
+            .refresh_gc_info_internal(target_timeline_id, horizon, pitr, false, cancel, ctx)
             .await
+            .map_err(GcError::into_anyhow)?;
+
+        Ok(gc_timelines
+            .into_iter()
+            .map(|(timeline, _)| timeline)
+            .collect())
+    }
+
+    /// Estimate how many bytes of layer files a GC iteration with the current configuration
+    /// would reclaim from each timeline, without removing anything.
+    ///
+    /// This runs the same layer selection as GC, so layers are only counted if they are below
+    /// the timeline's cutoffs and not needed by a child branch.  Timelines that are too young to
+    /// be garbage collected at all are not included.
+    pub async fn estimate_gc_reclaim(
+        &self,
+        cancel: &CancellationToken,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<HashMap<TimelineId, u64>> {
+        let gc_timelines = self
+            .refresh_gc_info_internal(
+                None,
+                self.get_gc_horizon(),
+                self.get_pitr_interval(),
+                true,
+                cancel,
+                ctx,
+            )
+            .await
+            .map_err(GcError::into_anyhow)?;
+
+        let mut reclaim = HashMap::with_capacity(gc_timelines.len());
+        for (timeline, gc_info) in gc_timelines {
+            if cancel.is_cancelled() {
+                anyhow::bail!("cancelled");
+            }
+            let result = timeline.gc_dry_run(&gc_info).await?;
+            reclaim.insert(timeline.timeline_id, result.bytes_to_remove.unwrap_or(0));
+        }
+        Ok(reclaim)
     }
 
+    /// Returns the timelines needing GC together with their refreshed GC information.
+    /// With `dry_run`, the information is calculated but not stored on the timelines.
     async fn refresh_gc_info_internal(
         &self,
         target_timeline_id: Option<TimelineId>,
         horizon: u64,
         pitr: Duration,
+        dry_run: bool,
         cancel: &CancellationToken,
         ctx: &RequestContext,
-    ) -> anyhow::Result<Vec<Arc<Timeline>>> {
+    ) -> Result<Vec<(Arc<Timeline>, GcInfo)>, GcError> {
         // grab mutex to prevent new timelines from being created here.
-        let gc_cs = self.gc_cs.lock().await;
+        let gc_cs = self.lock_gc_cs().await;
 
         // Scan all timelines. For each timeline, remember the timeline ID and
         // the branch point where it was created.
         let (all_branchpoints, timeline_ids): (BTreeSet<(TimelineId, Lsn)>, _) = {
-            let timelines = self.timelines.lock().unwrap();
+            let timelines = self.lock_timelines();
             let mut all_branchpoints = BTreeSet::new();
             let timeline_ids = {
                 if let Some(target_timeline_id) = target_timeline_id.as_ref() {
                     if timelines.get(target_timeline_id).is_none() {
-                        bail!("gc target timeline does not exist")
+                        return Err(GcError::TargetTimelineNotFound(*target_timeline_id));
                     }
                 };
 
@@ -2826,6 +3902,11 @@ impl Tenant {
                 }
             }
 
+            // A per-timeline override takes precedence over the tenant-wide horizon
+            let horizon = self
+                .get_timeline_gc_horizon_override(timeline_id)
+                .unwrap_or(horizon);
+
             if let Some(cutoff) = timeline.get_last_record_lsn().checked_sub(horizon) {
                 let branchpoints: Vec<Lsn> = all_branchpoints
                     .range((
@@ -2834,17 +3915,55 @@ impl Tenant {
                     ))
                     .map(|&x| x.1)
                     .collect();
-                timeline
-                    .update_gc_info(branchpoints, cutoff, pitr, cancel, ctx)
+                let gc_info = timeline
+                    .update_gc_info(branchpoints, cutoff, pitr, dry_run, cancel, ctx)
                     .await?;
 
-                gc_timelines.push(timeline);
+                gc_timelines.push((timeline, gc_info));
             }
         }
         drop(gc_cs);
         Ok(gc_timelines)
     }
 
+    /// Resolves the start timestamp of a new branch to an LSN on its ancestor, using the
+    /// commit timestamps in the ancestor's CLOG.  The resulting LSN is still subject to the
+    /// usual checks against the ancestor's GC cutoffs when branching.
+    async fn find_branch_lsn_for_timestamp(
+        &self,
+        ancestor_timeline: &Timeline,
+        timestamp: SystemTime,
+        ctx: &RequestContext,
+    ) -> Result<Lsn, CreateTimelineError> {
+        if !self.tenant_shard_id.is_zero() {
+            // Requires SLRU contents, which are only stored on shard zero
+            return Err(CreateTimelineError::AncestorLsn(anyhow::anyhow!(
+                "branching at a timestamp is only possible on shard zero"
+            )));
+        }
+
+        let found = ancestor_timeline
+            .find_lsn_for_timestamp(postgres_ffi::to_pg_timestamp(timestamp), &self.cancel, ctx)
+            .await
+            .map_err(|e| match e {
+                PageReconstructError::Cancelled => CreateTimelineError::ShuttingDown,
+                e => CreateTimelineError::Other(anyhow::Error::new(e)),
+            })?;
+
+        let timestamp = humantime::format_rfc3339(timestamp);
+        match found {
+            LsnForTimestamp::Present(lsn) | LsnForTimestamp::Future(lsn) => Ok(lsn),
+            LsnForTimestamp::Past(lsn) => Err(CreateTimelineError::AncestorLsn(anyhow::anyhow!(
+                "start timestamp {timestamp} predates the GC cutoff of ancestor timeline {}, the earliest available lsn is {lsn}",
+                ancestor_timeline.timeline_id
+            ))),
+            LsnForTimestamp::NoData(_) => Err(CreateTimelineError::AncestorLsn(anyhow::anyhow!(
+                "ancestor timeline {} has no commit records to resolve start timestamp {timestamp}",
+                ancestor_timeline.timeline_id
+            ))),
+        }
+    }
+
     /// A substitute for `branch_timeline` for use in unit tests.
     /// The returned timeline will have state value `Active` to make various `anyhow::ensure!()`
     /// calls pass, but, we do not actually call `.activate()` under the hood. So, none of the
@@ -2859,7 +3978,7 @@ impl Tenant {
     ) -> Result<Arc<Timeline>, CreateTimelineError> {
         let uninit_mark = self.create_timeline_uninit_mark(dst_id).unwrap();
         let tl = self
-            .branch_timeline_impl(src_timeline, dst_id, start_lsn, uninit_mark, ctx)
+            .branch_timeline_impl(src_timeline, dst_id, start_lsn, false, uninit_mark, ctx)
             .await?;
         tl.set_state(TimelineState::Active);
         Ok(tl)
@@ -2867,17 +3986,29 @@ impl Tenant {
 
     /// Branch an existing timeline.
     ///
+    /// With `force_below_gc_cutoff`, the branch point is allowed to be below the source
+    /// timeline's GC cutoffs. This is for disaster recovery only: the caller must know that
+    /// the data at the branch point has not actually been garbage collected.
+    ///
     /// The caller is responsible for activating the returned timeline.
     async fn branch_timeline(
         &self,
         src_timeline: &Arc<Timeline>,
         dst_id: TimelineId,
         start_lsn: Option<Lsn>,
+        force_below_gc_cutoff: bool,
         timeline_uninit_mark: TimelineUninitMark<'_>,
         ctx: &RequestContext,
     ) -> Result<Arc<Timeline>, CreateTimelineError> {
-        self.branch_timeline_impl(src_timeline, dst_id, start_lsn, timeline_uninit_mark, ctx)
-            .await
+        self.branch_timeline_impl(
+            src_timeline,
+            dst_id,
+            start_lsn,
+            force_below_gc_cutoff,
+            timeline_uninit_mark,
+            ctx,
+        )
+        .await
     }
 
     async fn branch_timeline_impl(
@@ -2885,6 +4016,7 @@ impl Tenant {
         src_timeline: &Arc<Timeline>,
         dst_id: TimelineId,
         start_lsn: Option<Lsn>,
+        force_below_gc_cutoff: bool,
         timeline_uninit_mark: TimelineUninitMark<'_>,
         _ctx: &RequestContext,
     ) -> Result<Arc<Timeline>, CreateTimelineError> {
@@ -2893,7 +4025,7 @@ impl Tenant {
         // We will validate our ancestor LSN in this function.  Acquire the GC lock so that
         // this check cannot race with GC, and the ancestor LSN is guaranteed to remain
         // valid while we are creating the branch.
-        let _gc_cs = self.gc_cs.lock().await;
+        let _gc_cs = self.lock_gc_cs().await;
 
         // If no start LSN is specified, we branch the new timeline from the source timeline's last record LSN
         let start_lsn = start_lsn.unwrap_or_else(|| {
@@ -2913,25 +4045,40 @@ impl Tenant {
         // larger, but some of the data was already removed by an earlier GC
         // iteration.
 
-        // check against last actual 'latest_gc_cutoff' first
-        let latest_gc_cutoff_lsn = src_timeline.get_latest_gc_cutoff_lsn();
-        src_timeline
-            .check_lsn_is_in_scope(start_lsn, &latest_gc_cutoff_lsn)
-            .context(format!(
-                "invalid branch start lsn: less than latest GC cutoff {}",
-                *latest_gc_cutoff_lsn,
-            ))
-            .map_err(CreateTimelineError::AncestorLsn)?;
-
-        // and then the planned GC cutoff
-        {
-            let gc_info = src_timeline.gc_info.read().unwrap();
-            let cutoff = min(gc_info.pitr_cutoff, gc_info.horizon_cutoff);
-            if start_lsn < cutoff {
+        if force_below_gc_cutoff {
+            // The data below the source's own ancestor LSN lives in its ancestor, so we
+            // cannot branch there no matter what.
+            let src_ancestor_lsn = src_timeline.get_ancestor_lsn();
+            if start_lsn < src_ancestor_lsn {
                 return Err(CreateTimelineError::AncestorLsn(anyhow::anyhow!(
-                    "invalid branch start lsn: less than planned GC cutoff {cutoff}"
+                    "invalid branch start lsn: less than timeline ancestor lsn {src_ancestor_lsn}"
                 )));
             }
+            warn!(
+                "branching timeline {dst_id} from timeline {src_id} at {start_lsn} without checking GC cutoffs (latest: {}), as requested",
+                *src_timeline.get_latest_gc_cutoff_lsn()
+            );
+        } else {
+            // check against last actual 'latest_gc_cutoff' first
+            let latest_gc_cutoff_lsn = src_timeline.get_latest_gc_cutoff_lsn();
+            src_timeline
+                .check_lsn_is_in_scope(start_lsn, &latest_gc_cutoff_lsn)
+                .context(format!(
+                    "invalid branch start lsn: less than latest GC cutoff {}",
+                    *latest_gc_cutoff_lsn,
+                ))
+                .map_err(CreateTimelineError::AncestorLsn)?;
+
+            // and then the planned GC cutoff
+            {
+                let gc_info = src_timeline.gc_info.read().unwrap();
+                let cutoff = min(gc_info.pitr_cutoff, gc_info.horizon_cutoff);
+                if start_lsn < cutoff {
+                    return Err(CreateTimelineError::AncestorLsn(anyhow::anyhow!(
+                        "invalid branch start lsn: less than planned GC cutoff {cutoff}"
+                    )));
+                }
+            }
         }
 
         //
@@ -2960,7 +4107,8 @@ impl Tenant {
             dst_prev,
             Some(src_id),
             start_lsn,
-            *src_timeline.latest_gc_cutoff_lsn.read(), // FIXME: should we hold onto this guard longer?
+            // When forced below the cutoff, the branch's own cutoff must not exceed its start.
+            min(*src_timeline.latest_gc_cutoff_lsn.read(), start_lsn), // FIXME: should we hold onto this guard longer?
             src_timeline.initdb_lsn,
             src_timeline.pg_version,
         );
@@ -3033,6 +4181,15 @@ impl Tenant {
             }
         }
 
+        // Held until the upload below completes, and released on any early return.
+        let _permit = self
+            .conf
+            .concurrent_initdb_uploads
+            .inner()
+            .acquire()
+            .await
+            .expect("this semaphore is never closed");
+
         let (pgdata_zstd, tar_zst_size) =
             import_datadir::create_tar_zst(pgdata_path, &temp_path).await?;
 
@@ -3082,6 +4239,12 @@ impl Tenant {
             TEMP_FILE_SUFFIX,
         );
 
+        // The permit covers the whole lifetime of the temporary directory, not just the initdb
+        // run: the directory stays around while it is uploaded and imported, and a storm of
+        // bootstraps could otherwise fill up the local disk with datadirs.  It is declared
+        // before the cleanup guard below, so that it is released after the directory is gone.
+        let _permit = INIT_DB_SEMAPHORE.acquire().await;
+
         // an uninit mark was placed before, nothing else can access this timeline files
         // current initdb was not run yet, so remove whatever was left from the previous runs
         if pgdata_path.exists() {
@@ -3109,10 +4272,14 @@ impl Tenant {
                     &remote_initdb_archive_path(&self.tenant_shard_id.tenant_id, &timeline_id);
 
                 // if this fails, it will get retried by retried control plane requests
-                storage
-                    .copy_object(source_path, dest_path, &self.cancel)
-                    .await
-                    .context("copy initdb tar")?;
+                self::remote_timeline_client::copy_initdb_archive(
+                    storage,
+                    source_path,
+                    dest_path,
+                    &self.cancel,
+                )
+                .await
+                .context("copy initdb tar")?;
             }
             let (initdb_tar_zst_path, initdb_tar_zst) =
                 self::remote_timeline_client::download_initdb_tar_zst(
@@ -3133,12 +4300,17 @@ impl Tenant {
 
             let buf_read =
                 BufReader::with_capacity(remote_timeline_client::BUFFER_SIZE, initdb_tar_zst);
-            import_datadir::extract_tar_zst(&pgdata_path, buf_read)
+            import_datadir::extract_tar_zst(&pgdata_path, buf_read, &self.cancel)
                 .await
                 .context("extract initdb tar")?;
         } else {
             // Init temporarily repo to get bootstrap data, this creates a directory in the `pgdata_path` path
-            run_initdb(self.conf, &pgdata_path, pg_version, &self.cancel).await?;
+            run_initdb(self.conf, &pgdata_path, pg_version, &self.cancel)
+                .await
+                .map_err(|e| match e {
+                    InitdbError::Cancelled => anyhow::Error::new(TimeoutOrCancel::Cancel),
+                    e => anyhow::Error::new(e),
+                })?;
 
             // Upload the created data dir to S3
             if self.tenant_shard_id().is_zero() {
@@ -3178,6 +4350,7 @@ impl Tenant {
             unfinished_timeline,
             &pgdata_path,
             pgdata_lsn,
+            &self.cancel,
             ctx,
         )
         .await
@@ -3350,6 +4523,9 @@ impl Tenant {
 
     /// Gathers inputs from all of the timelines to produce a sizing model input.
     ///
+    /// If given, `progress` counts the timelines whose logical sizes have been calculated, so
+    /// that it can be reported while the calculation runs.
+    ///
     /// Future is cancellation safe. Only one calculation can be running at once per tenant.
     #[instrument(skip_all, fields(tenant_id=%self.tenant_shard_id.tenant_id, shard_id=%self.tenant_shard_id.shard_slug()))]
     pub async fn gather_size_inputs(
@@ -3357,6 +4533,7 @@ impl Tenant {
         // `max_retention_period` overrides the cutoff that is used to calculate the size
         // (only if it is shorter than the real cutoff).
         max_retention_period: Option<u64>,
+        progress: Option<Arc<AtomicU64>>,
         cause: LogicalSizeCalculationCause,
         cancel: &CancellationToken,
         ctx: &RequestContext,
@@ -3366,20 +4543,15 @@ impl Tenant {
             .concurrent_tenant_size_logical_size_queries
             .inner();
 
-        // TODO: Having a single mutex block concurrent reads is not great for performance.
-        //
-        // But the only case where we need to run multiple of these at once is when we
-        // request a size for a tenant manually via API, while another background calculation
-        // is in progress (which is not a common case).
-        //
-        // See more for on the issue #2748 condenced out of the initial PR review.
-        let mut shared_cache = self.cached_logical_sizes.lock().await;
-
+        // The cache is only locked briefly to look up or insert entries, so that a manual
+        // size request via API can make progress alongside the background calculation.
+        // Concurrent requests for the same (timeline, lsn) are coalesced by the cache entries.
         size::gather_inputs(
             self,
             logical_sizes_at_once,
             max_retention_period,
-            &mut shared_cache,
+            &self.cached_logical_sizes,
+            progress,
             cause,
             cancel,
             ctx,
@@ -3390,6 +4562,9 @@ impl Tenant {
     /// Calculate synthetic tenant size and cache the result.
     /// This is periodically called by background worker.
     /// result is cached in tenant struct
+    ///
+    /// If `synthetic_size_calculation_enabled` is off for this tenant, nothing is calculated
+    /// and the previously cached size (or 0) is returned without touching the metric.
     #[instrument(skip_all, fields(tenant_id=%self.tenant_shard_id.tenant_id, shard_id=%self.tenant_shard_id.shard_slug()))]
     pub async fn calculate_synthetic_size(
         &self,
@@ -3397,7 +4572,14 @@ impl Tenant {
         cancel: &CancellationToken,
         ctx: &RequestContext,
     ) -> anyhow::Result<u64> {
-        let inputs = self.gather_size_inputs(None, cause, cancel, ctx).await?;
+        if !self.get_synthetic_size_calculation_enabled() {
+            debug!("synthetic size calculation is disabled for this tenant, skipping");
+            return Ok(self.cached_synthetic_size());
+        }
+
+        let inputs = self
+            .gather_size_inputs(None, None, cause, cancel, ctx)
+            .await?;
 
         let size = inputs.calculate()?;
 
@@ -3408,8 +4590,7 @@ impl Tenant {
 
     /// Cache given synthetic size and update the metric value
     pub fn set_cached_synthetic_size(&self, size: u64) {
-        self.cached_synthetic_tenant_size
-            .store(size, Ordering::Relaxed);
+        *self.cached_synthetic_tenant_size.lock().unwrap() = Some((size, Instant::now()));
 
         // Only shard zero should be calculating synthetic sizes
         debug_assert!(self.shard_identity.is_zero());
@@ -3420,8 +4601,45 @@ impl Tenant {
             .set(size);
     }
 
+    /// Returns the cached synthetic size, or 0 if it has not been calculated yet.
     pub fn cached_synthetic_size(&self) -> u64 {
-        self.cached_synthetic_tenant_size.load(Ordering::Relaxed)
+        self.cached_synthetic_size_with_age()
+            .map(|(size, _)| size)
+            .unwrap_or(0)
+    }
+
+    /// Returns the cached synthetic size along with how long ago it was calculated, or `None`
+    /// if it has not been calculated since this tenant was loaded.
+    pub fn cached_synthetic_size_with_age(&self) -> Option<(u64, Duration)> {
+        self.cached_synthetic_tenant_size
+            .lock()
+            .unwrap()
+            .map(|(size, at)| (size, at.elapsed()))
+    }
+
+    /// Make the `progress` passed to [`Self::gather_size_inputs`] available through
+    /// [`Self::size_calculation_progress`]. A later calculation replaces an earlier one.
+    pub(crate) fn publish_size_calculation_progress(&self, progress: &Arc<AtomicU64>) {
+        *self.size_calculation_progress.lock().unwrap() = Some(Arc::clone(progress));
+    }
+
+    /// Undo [`Self::publish_size_calculation_progress`] once the calculation is over, unless a
+    /// later calculation has replaced it in the meantime.
+    pub(crate) fn retract_size_calculation_progress(&self, progress: &Arc<AtomicU64>) {
+        let mut published = self.size_calculation_progress.lock().unwrap();
+        if published.as_ref().is_some_and(|p| Arc::ptr_eq(p, progress)) {
+            *published = None;
+        }
+    }
+
+    /// Returns how many timelines the published size calculation has processed so far, or `None`
+    /// if none is running.
+    pub(crate) fn size_calculation_progress(&self) -> Option<u64> {
+        self.size_calculation_progress
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|progress| progress.load(Ordering::Relaxed))
     }
 
     /// Flush any in-progress layers, schedule uploads, and wait for uploads to complete.
@@ -3433,18 +4651,7 @@ impl Tenant {
     /// still bounded by tenant/timeline shutdown.
     #[tracing::instrument(skip_all)]
     pub(crate) async fn flush_remote(&self) -> anyhow::Result<()> {
-        let timelines = self.timelines.lock().unwrap().clone();
-
-        async fn flush_timeline(_gate: GateGuard, timeline: Arc<Timeline>) -> anyhow::Result<()> {
-            tracing::info!(timeline_id=%timeline.timeline_id, "Flushing...");
-            timeline.freeze_and_flush().await?;
-            tracing::info!(timeline_id=%timeline.timeline_id, "Waiting for uploads...");
-            if let Some(client) = &timeline.remote_client {
-                client.wait_completion().await?;
-            }
-
-            Ok(())
-        }
+        let timelines = self.lock_timelines().clone();
 
         // We do not use a JoinSet for these tasks, because we don't want them to be
         // aborted when this function's future is cancelled: they should stay alive
@@ -3484,13 +4691,51 @@ impl Tenant {
         Ok(())
     }
 
+    /// Like [`Self::flush_remote`], but only for a single timeline: flush its in-memory data
+    /// and wait for all of its uploads to complete.
+    ///
+    /// Returns an error wrapping [`GetTimelineError`] if the timeline does not exist.
+    pub(crate) async fn flush_timeline_remote(
+        &self,
+        timeline_id: TimelineId,
+    ) -> anyhow::Result<()> {
+        let timeline = self.get_timeline(timeline_id, false)?;
+
+        let gate = timeline
+            .gate
+            .enter()
+            .map_err(|_| anyhow::anyhow!("timeline is shutting down"))?;
+
+        // Spawned for the same reason as in [`Self::flush_remote`]: if this function's future
+        // is cancelled, the task stays alive holding the gate until its I/O completes.
+        tokio::task::spawn(async move { flush_timeline(gate, timeline).await })
+            .await
+            .context("join flush task")?
+    }
+
     pub(crate) fn get_tenant_conf(&self) -> TenantConfOpt {
-        self.tenant_conf.read().unwrap().tenant_conf.clone()
+        self.read_tenant_conf().tenant_conf.clone()
+    }
+}
+
+/// Flush a timeline's in-memory layers and wait for its remote uploads to complete, holding
+/// the timeline's gate for the duration.
+async fn flush_timeline(_gate: GateGuard, timeline: Arc<Timeline>) -> anyhow::Result<()> {
+    tracing::info!(timeline_id=%timeline.timeline_id, "Flushing...");
+    timeline.freeze_and_flush().await?;
+    tracing::info!(timeline_id=%timeline.timeline_id, "Waiting for uploads...");
+    if let Some(client) = &timeline.remote_client {
+        client.wait_completion().await?;
     }
+
+    Ok(())
 }
 
 /// Create the cluster temporarily in 'initdbpath' directory inside the repository
 /// to get bootstrap data for timeline initialization.
+///
+/// The caller is expected to hold an [`INIT_DB_SEMAPHORE`] permit for as long as the
+/// directory exists.
 async fn run_initdb(
     conf: &'static PageServerConf,
     initdb_target_dir: &Utf8Path,
@@ -3507,9 +4752,7 @@ async fn run_initdb(
         initdb_bin_path, initdb_target_dir, initdb_lib_dir,
     );
 
-    let _permit = INIT_DB_SEMAPHORE.acquire().await;
-
-    let initdb_command = tokio::process::Command::new(&initdb_bin_path)
+    let mut initdb_command = tokio::process::Command::new(&initdb_bin_path)
         .args(["-D", initdb_target_dir.as_ref()])
         .args(["-U", &conf.superuser])
         .args(["-E", "utf8"])
@@ -3530,12 +4773,27 @@ async fn run_initdb(
     // initdb doesn't kill them. After we return from this function, we want the target
     // directory to be able to be cleaned up.
     // See https://github.com/neondatabase/neon/issues/6385
-    let initdb_output = initdb_command.wait_with_output().await?;
-    if !initdb_output.status.success() {
-        return Err(InitdbError::Failed(
-            initdb_output.status,
-            initdb_output.stderr,
-        ));
+    let mut stderr = initdb_command.stderr.take().expect("stderr is piped");
+    let stderr_max_bytes = conf.initdb_stderr_max_bytes;
+    let stderr_tail = async move {
+        // Only the end of the output is kept: that is where the reason of a failure usually is.
+        let mut tail = VecDeque::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = stderr.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            tail.extend(&buf[..n]);
+            if tail.len() > stderr_max_bytes {
+                tail.drain(..tail.len() - stderr_max_bytes);
+            }
+        }
+        Ok::<_, std::io::Error>(Vec::from(tail))
+    };
+    let (status, stderr_tail) = tokio::try_join!(initdb_command.wait(), stderr_tail)?;
+    if !status.success() {
+        return Err(InitdbError::Failed(status, stderr_tail));
     }
 
     // This isn't true cancellation support, see above. Still return an error to
@@ -3553,9 +4811,12 @@ impl Drop for Tenant {
     }
 }
 /// Dump contents of a layer file to stdout.
+///
+/// With `key_filter`, a verbose dump only includes the entries of keys within that range.
 pub async fn dump_layerfile_from_path(
     path: &Utf8Path,
     verbose: bool,
+    key_filter: Option<std::ops::Range<crate::repository::Key>>,
     ctx: &RequestContext,
 ) -> anyhow::Result<()> {
     use std::os::unix::fs::FileExt;
@@ -3569,12 +4830,12 @@ pub async fn dump_layerfile_from_path(
     match u16::from_be_bytes(header_buf) {
         crate::IMAGE_FILE_MAGIC => {
             ImageLayer::new_for_path(path, file)?
-                .dump(verbose, ctx)
+                .dump(verbose, key_filter.as_ref(), ctx)
                 .await?
         }
         crate::DELTA_FILE_MAGIC => {
             DeltaLayer::new_for_path(path, file)?
-                .dump(verbose, ctx)
+                .dump(verbose, key_filter.as_ref(), ctx)
                 .await?
         }
         magic => bail!("unrecognized magic identifier: {:?}", magic),
@@ -3591,6 +4852,7 @@ pub(crate) mod harness {
     use pageserver_api::models::ShardParameters;
     use pageserver_api::shard::ShardIndex;
     use std::fs;
+    use std::sync::atomic::{AtomicU64, Ordering};
     use std::sync::Arc;
     use utils::logging;
     use utils::lsn::Lsn;
@@ -3625,6 +4887,7 @@ pub(crate) mod harness {
             Self {
                 checkpoint_distance: Some(tenant_conf.checkpoint_distance),
                 checkpoint_timeout: Some(tenant_conf.checkpoint_timeout),
+                max_inmem_layer_bytes: tenant_conf.max_inmem_layer_bytes,
                 compaction_target_size: Some(tenant_conf.compaction_target_size),
                 compaction_period: Some(tenant_conf.compaction_period),
                 compaction_threshold: Some(tenant_conf.compaction_threshold),
@@ -3638,13 +4901,20 @@ pub(crate) mod harness {
                 trace_read_requests: Some(tenant_conf.trace_read_requests),
                 eviction_policy: Some(tenant_conf.eviction_policy),
                 min_resident_size_override: tenant_conf.min_resident_size_override,
+                local_disk_high_watermark_bytes: tenant_conf.local_disk_high_watermark_bytes,
                 evictions_low_residence_duration_metric_threshold: Some(
                     tenant_conf.evictions_low_residence_duration_metric_threshold,
                 ),
                 gc_feedback: Some(tenant_conf.gc_feedback),
+                gc_parallelism: Some(tenant_conf.gc_parallelism),
+                skip_orphan_timelines: Some(tenant_conf.skip_orphan_timelines),
                 heatmap_period: Some(tenant_conf.heatmap_period),
                 lazy_slru_download: Some(tenant_conf.lazy_slru_download),
                 timeline_get_throttle: Some(tenant_conf.timeline_get_throttle),
+                synthetic_size_calculation_enabled: Some(
+                    tenant_conf.synthetic_size_calculation_enabled,
+                ),
+                timeline_gc_horizon_overrides: None,
             }
         }
     }
@@ -3658,6 +4928,7 @@ pub(crate) mod harness {
         pub remote_storage: GenericRemoteStorage,
         pub remote_fs_dir: Utf8PathBuf,
         pub deletion_queue: MockDeletionQueue,
+        pub broken_events: tokio::sync::broadcast::Sender<TenantBrokenEvent>,
     }
 
     static LOG_HANDLE: OnceCell<()> = OnceCell::new();
@@ -3720,6 +4991,7 @@ pub(crate) mod harness {
                 remote_storage,
                 remote_fs_dir,
                 deletion_queue,
+                broken_events: mgr::broken_events_channel(),
             })
         }
 
@@ -3737,37 +5009,51 @@ pub(crate) mod harness {
             )
         }
 
-        #[instrument(skip_all, fields(tenant_id=%self.tenant_shard_id.tenant_id, shard_id=%self.tenant_shard_id.shard_slug()))]
         pub(crate) async fn do_try_load(
             &self,
             ctx: &RequestContext,
         ) -> anyhow::Result<Arc<Tenant>> {
-            let walredo_mgr = Arc::new(WalRedoManager::from(TestRedoManager));
+            self.do_try_load_in_mode(SpawnMode::Normal, ctx).await
+        }
+
+        #[instrument(skip_all, fields(tenant_id=%self.tenant_shard_id.tenant_id, shard_id=%self.tenant_shard_id.shard_slug()))]
+        pub(crate) async fn do_try_load_in_mode(
+            &self,
+            mode: SpawnMode,
+            ctx: &RequestContext,
+        ) -> anyhow::Result<Arc<Tenant>> {
+            let walredo_mgr = Arc::new(WalRedoManager::from(TestRedoManager::default()));
+
+            let mut attached_conf = AttachedTenantConf::try_from(LocationConf::attached_single(
+                TenantConfOpt::from(self.tenant_conf.clone()),
+                self.generation,
+                &ShardParameters::default(),
+            ))
+            .unwrap();
+            if let SpawnMode::ReadOnly = mode {
+                attached_conf.location.read_only = true;
+            }
 
             let tenant = Arc::new(Tenant::new(
                 TenantState::Loading,
                 self.conf,
-                AttachedTenantConf::try_from(LocationConf::attached_single(
-                    TenantConfOpt::from(self.tenant_conf.clone()),
-                    self.generation,
-                    &ShardParameters::default(),
-                ))
-                .unwrap(),
+                attached_conf,
                 // This is a legacy/test code path: sharding isn't supported here.
                 ShardIdentity::unsharded(),
                 Some(walredo_mgr),
                 self.tenant_shard_id,
                 Some(self.remote_storage.clone()),
                 self.deletion_queue.new_client(),
+                self.broken_events.clone(),
             ));
 
             let preload = tenant
                 .preload(&self.remote_storage, CancellationToken::new())
                 .await?;
-            tenant.attach(Some(preload), SpawnMode::Normal, ctx).await?;
+            tenant.attach(Some(preload), mode, ctx).await?;
 
             tenant.state.send_replace(TenantState::Active);
-            for timeline in tenant.timelines.lock().unwrap().values() {
+            for timeline in tenant.lock_timelines().values() {
                 timeline.set_state(TimelineState::Active);
             }
             Ok(tenant)
@@ -3779,7 +5065,11 @@ pub(crate) mod harness {
     }
 
     // Mock WAL redo manager that doesn't do much
-    pub(crate) struct TestRedoManager;
+    #[derive(Default)]
+    pub(crate) struct TestRedoManager {
+        redo_requests: AtomicU64,
+        last_redo_at: std::sync::Mutex<Option<chrono::DateTime<chrono::Utc>>>,
+    }
 
     impl TestRedoManager {
         /// # Cancel-Safety
@@ -3793,6 +5083,9 @@ pub(crate) mod harness {
             records: Vec<(Lsn, NeonWalRecord)>,
             _pg_version: u32,
         ) -> anyhow::Result<Bytes> {
+            self.redo_requests.fetch_add(1, Ordering::Relaxed);
+            *self.last_redo_at.lock().unwrap() = Some(chrono::Utc::now());
+
             let records_neon = records.iter().all(|r| apply_neon::can_apply_in_neon(&r.1));
             if records_neon {
                 // For Neon wal records, we can decode without spawning postgres, so do so.
@@ -3821,6 +5114,18 @@ pub(crate) mod harness {
                 Ok(test_img(&s))
             }
         }
+
+        pub(crate) fn status(&self) -> Option<WalRedoManagerStatus> {
+            Some(WalRedoManagerStatus {
+                last_redo_at: *self.last_redo_at.lock().unwrap(),
+                pid: None,
+            })
+        }
+
+        /// Number of redo requests served so far.
+        pub(crate) fn redo_requests(&self) -> u64 {
+            self.redo_requests.load(Ordering::Relaxed)
+        }
     }
 }
 
@@ -3830,17 +5135,28 @@ mod tests {
     use crate::keyspace::KeySpaceAccum;
     use crate::repository::{Key, Value};
     use crate::tenant::harness::*;
+    use crate::tenant::remote_timeline_client::remote_initdb_checksum_path;
+    use crate::walrecord::NeonWalRecord;
     use crate::DEFAULT_PG_VERSION;
-    use bytes::BytesMut;
+    use bytes::{Bytes, BytesMut};
     use hex_literal::hex;
     use once_cell::sync::Lazy;
     use pageserver_api::keyspace::KeySpace;
+    use pageserver_api::models::ActivatingFrom;
     use rand::{thread_rng, Rng};
     use tokio_util::sync::CancellationToken;
 
     static TEST_KEY: Lazy<Key> =
         Lazy::new(|| Key::from_slice(&hex!("010000000033333333444444445500000001")));
 
+    /// Number of redo requests served by the tenant's [`TestRedoManager`].
+    fn redo_requests(tenant: &Tenant) -> u64 {
+        match tenant.walredo_mgr.as_deref() {
+            Some(WalRedoManager::Test(mgr)) => mgr.redo_requests(),
+            _ => panic!("tenant doesn't use the test WAL redo manager"),
+        }
+    }
+
     #[tokio::test]
     async fn test_basic() -> anyhow::Result<()> {
         let (tenant, ctx) = TenantHarness::create("test_basic")?.load().await;
@@ -4088,6 +5404,309 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_timeline_gc_horizon_override() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_timeline_gc_horizon_override")?
+            .load()
+            .await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+        let sibling = tenant
+            .create_test_timeline(NEW_TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        make_some_layers(sibling.as_ref(), Lsn(0x20), &ctx).await?;
+
+        // Retain the whole history of TIMELINE_ID, but not of its sibling
+        let mut tenant_conf = tenant.tenant_specific_overrides();
+        tenant_conf.timeline_gc_horizon_overrides = Some(HashMap::from([(TIMELINE_ID, 0x1000)]));
+        tenant.set_new_tenant_config(tenant_conf)?;
+
+        tenant
+            .gc_iteration(None, 0x10, Duration::ZERO, &CancellationToken::new(), &ctx)
+            .await?;
+
+        assert_eq!(*tline.get_latest_gc_cutoff_lsn(), Lsn(0x10));
+        assert!(*sibling.get_latest_gc_cutoff_lsn() > Lsn(0x25));
+
+        // The sibling lost the data at 0x25, so it can't be branched from there anymore
+        assert!(matches!(
+            tenant
+                .branch_timeline_test(&sibling, TimelineId::generate(), Some(Lsn(0x25)), &ctx)
+                .await,
+            Err(CreateTimelineError::AncestorLsn(_))
+        ));
+
+        // The timeline with the override kept it, and its branch inherits the override
+        let branch_id = TimelineId::generate();
+        tenant
+            .branch_timeline_test(&tline, branch_id, Some(Lsn(0x25)), &ctx)
+            .await?;
+        assert_eq!(
+            tenant.get_timeline_gc_horizon_override(branch_id),
+            Some(0x1000)
+        );
+        // The inherited override is resolved at GC time, not copied into the tenant config
+        let mut tenant_conf = tenant.tenant_specific_overrides();
+        let overrides = tenant_conf.timeline_gc_horizon_overrides.as_mut().unwrap();
+        assert!(!overrides.contains_key(&branch_id));
+
+        // An override of the branch's own takes precedence
+        overrides.insert(branch_id, 0x20);
+        tenant.set_new_tenant_config(tenant_conf)?;
+        assert_eq!(
+            tenant.get_timeline_gc_horizon_override(branch_id),
+            Some(0x20)
+        );
+        assert_eq!(
+            tenant.get_timeline_gc_horizon_override(TIMELINE_ID),
+            Some(0x1000)
+        );
+        assert_eq!(
+            tenant.get_timeline_gc_horizon_override(NEW_TIMELINE_ID),
+            None
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parallel_gc() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_parallel_gc")?.load().await;
+
+        let mut timelines = Vec::new();
+        for _ in 0..4 {
+            let tline = tenant
+                .create_test_timeline(TimelineId::generate(), Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+                .await?;
+            make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+            timelines.push(tline);
+        }
+
+        let mut tenant_conf = tenant.tenant_specific_overrides();
+        tenant_conf.gc_parallelism = NonZeroUsize::new(3);
+        tenant.set_new_tenant_config(tenant_conf)?;
+
+        tenant
+            .gc_iteration(None, 0x10, Duration::ZERO, &CancellationToken::new(), &ctx)
+            .await?;
+
+        // Every timeline was collected
+        for tline in &timelines {
+            assert!(*tline.get_latest_gc_cutoff_lsn() > Lsn(0x25));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gc_cancelled_after_one_timeline() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_gc_cancelled_after_one_timeline")?
+            .load()
+            .await;
+
+        let mut timelines = Vec::new();
+        for _ in 0..2 {
+            let tline = tenant
+                .create_test_timeline(TimelineId::generate(), Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+                .await?;
+            make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+            timelines.push(tline);
+        }
+
+        // One timeline at a time, so that cancellation is checked in between them
+        let mut tenant_conf = tenant.tenant_specific_overrides();
+        tenant_conf.gc_parallelism = NonZeroUsize::new(1);
+        tenant.set_new_tenant_config(tenant_conf)?;
+
+        // Hold up GC on both timelines, so that the iteration gets stuck in the first one
+        let guards = futures::future::join_all(timelines.iter().map(|t| t.hold_gc_lock())).await;
+
+        let cancel = CancellationToken::new();
+        let gc = tenant.gc_iteration(None, 0x10, Duration::ZERO, &cancel, &ctx);
+        tokio::pin!(gc);
+        tokio::select! {
+            _ = &mut gc => panic!("GC should be waiting for the first timeline"),
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+        }
+
+        cancel.cancel();
+        drop(guards);
+        let result = gc.await?;
+
+        assert!(result.cancelled);
+        let collected = timelines
+            .iter()
+            .filter(|t| *t.get_latest_gc_cutoff_lsn() > Lsn(0x25))
+            .count();
+        assert_eq!(collected, 1);
+
+        // An uninterrupted iteration is not flagged
+        let result = tenant
+            .gc_iteration(None, 0x10, Duration::ZERO, &CancellationToken::new(), &ctx)
+            .await?;
+        assert!(!result.cancelled);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wait_no_timelines_creating() -> anyhow::Result<()> {
+        let (tenant, _ctx) = TenantHarness::create("test_wait_no_timelines_creating")?
+            .load()
+            .await;
+
+        // Nothing is being created: returns straight away
+        tenant.wait_no_timelines_creating(Duration::ZERO).await?;
+
+        let uninit_mark = tenant.create_timeline_uninit_mark(TIMELINE_ID)?;
+        let err = tenant
+            .wait_no_timelines_creating(Duration::from_millis(10))
+            .await
+            .expect_err("a timeline is being created");
+        assert!(err.to_string().contains("Timed out"), "{err}");
+
+        // Finishing the creation wakes up the waiter
+        let (res, _) = tokio::join!(
+            tenant.wait_no_timelines_creating(Duration::from_secs(10)),
+            async {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                drop(uninit_mark);
+            }
+        );
+        res?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_clear_broken_and_reactivate() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_clear_broken_and_reactivate")?
+            .load()
+            .await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        let broker_client =
+            storage_broker::connect(storage_broker::DEFAULT_ENDPOINT, Duration::from_secs(5))?;
+
+        // Not Broken: nothing to recover from
+        assert!(tenant
+            .clear_broken_and_reactivate(broker_client.clone(), &ctx)
+            .is_err());
+
+        tenant.set_broken("broken from test".to_owned()).await;
+        assert!(matches!(tenant.current_state(), TenantState::Broken { .. }));
+
+        tenant.clear_broken_and_reactivate(broker_client.clone(), &ctx)?;
+        assert_eq!(tenant.current_state(), TenantState::Active);
+        assert!(tline.is_active());
+        tenant
+            .get_timeline(TIMELINE_ID, true)
+            .expect("timeline is usable again");
+
+        // The flag was cleared along with the Broken state: recovering again is refused
+        assert!(tenant
+            .clear_broken_and_reactivate(broker_client, &ctx)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_timeline_idempotent() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_delete_timeline_idempotent")?
+            .load()
+            .await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        tenant
+            .branch_timeline_test(&tline, NEW_TIMELINE_ID, Some(Lsn(0x10)), &ctx)
+            .await?;
+
+        // A timeline that doesn't exist is only an error for the non-idempotent variant
+        let missing = TimelineId::generate();
+        assert!(matches!(
+            tenant.clone().delete_timeline(missing).await,
+            Err(DeleteTimelineError::NotFound)
+        ));
+        tenant.clone().delete_timeline_idempotent(missing).await?;
+
+        // Other errors are still reported
+        assert!(matches!(
+            tenant.clone().delete_timeline_idempotent(TIMELINE_ID).await,
+            Err(DeleteTimelineError::HasChildren(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_timeline_by_prefix() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_get_timeline_by_prefix")?
+            .load()
+            .await;
+        let first = TimelineId::from_array(hex!("ab11c3ed7b5a0c4f5e6f7a8b9c0d1e2f"));
+        let second = TimelineId::from_array(hex!("ab22c3ed7b5a0c4f5e6f7a8b9c0d1e2f"));
+        for timeline_id in [first, second] {
+            tenant
+                .create_test_timeline(timeline_id, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+                .await?;
+        }
+
+        assert_eq!(
+            tenant.get_timeline_by_prefix("ab1", true)?.timeline_id,
+            first
+        );
+        assert_eq!(
+            tenant.get_timeline_by_prefix("AB22", true)?.timeline_id,
+            second
+        );
+        assert_eq!(
+            tenant.get_timeline_by_prefix("ab", true).err(),
+            Some(GetTimelineError::Ambiguous {
+                matches: vec![first, second]
+            })
+        );
+        assert!(matches!(
+            tenant.get_timeline_by_prefix("cd", true),
+            Err(GetTimelineError::PrefixNotFound { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree_sort_timelines_with_orphans() {
+        let root = TimelineId::generate();
+        let child = TimelineId::generate();
+        let missing = TimelineId::generate();
+        let orphan = TimelineId::generate();
+        let orphan_child = TimelineId::generate();
+        let timelines = HashMap::from([
+            (root, None),
+            (child, Some(root)),
+            (orphan, Some(missing)),
+            (orphan_child, Some(orphan)),
+        ]);
+
+        let (sorted, mut orphans) =
+            tree_sort_timelines_with_orphans(timelines, |ancestor| *ancestor);
+        let sorted: Vec<_> = sorted.into_iter().map(|(id, _)| id).collect();
+        assert_eq!(sorted, vec![root, child]);
+        orphans.sort_by_key(|(id, _, _)| *id == orphan_child);
+        assert_eq!(
+            orphans,
+            vec![
+                (orphan, missing, Some(missing)),
+                (orphan_child, orphan, Some(orphan))
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn test_prohibit_branch_creation_on_pre_initdb_lsn() -> anyhow::Result<()> {
         let (tenant, ctx) =
@@ -4298,29 +5917,15 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn timeline_load_with_ancestor() -> anyhow::Result<()> {
-        const TEST_NAME: &str = "timeline_load_with_ancestor";
-        let harness = TenantHarness::create(TEST_NAME)?;
-        // create two timelines
+    async fn timeline_load_with_unreliable_remote_storage() -> anyhow::Result<()> {
+        const TEST_NAME: &str = "timeline_load_with_unreliable_remote_storage";
+        let mut harness = TenantHarness::create(TEST_NAME)?;
         {
             let (tenant, ctx) = harness.load().await;
             let tline = tenant
-                .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
-                .await?;
-
-            make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
-
-            let child_tline = tenant
-                .branch_timeline_test(&tline, NEW_TIMELINE_ID, Some(Lsn(0x40)), &ctx)
+                .create_test_timeline(TIMELINE_ID, Lsn(0x7000), DEFAULT_PG_VERSION, &ctx)
                 .await?;
-            child_tline.set_state(TimelineState::Active);
-
-            let newtline = tenant
-                .get_timeline(NEW_TIMELINE_ID, true)
-                .expect("Should have a local timeline");
-
-            make_some_layers(newtline.as_ref(), Lsn(0x60), &ctx).await?;
-
+            make_some_layers(tline.as_ref(), Lsn(0x8000), &ctx).await?;
             // so that all uploads finish & we can call harness.load() below again
             tenant
                 .shutdown(Default::default(), true)
@@ -4330,31 +5935,206 @@ mod tests {
                 .unwrap();
         }
 
-        // check that both of them are initially unloaded
-        let (tenant, _ctx) = harness.load().await;
+        // Every remote operation fails twice before it succeeds.
+        harness.remote_storage =
+            GenericRemoteStorage::unreliable_wrapper(harness.remote_storage.clone(), 2);
 
-        // check that both, child and ancestor are loaded
-        let _child_tline = tenant
-            .get_timeline(NEW_TIMELINE_ID, true)
-            .expect("cannot get child timeline loaded");
+        {
+            let (tenant, _ctx) = harness.load().await;
+            tenant
+                .get_timeline(TIMELINE_ID, true)
+                .expect("cannot load timeline");
+            tenant
+                .shutdown(Default::default(), true)
+                .instrument(harness.span())
+                .await
+                .ok()
+                .unwrap();
+        }
 
-        let _ancestor_tline = tenant
-            .get_timeline(TIMELINE_ID, true)
-            .expect("cannot get ancestor timeline loaded");
+        // With only two attempts, the index part download is given up on.
+        harness.conf = Box::leak(Box::new(PageServerConf {
+            index_part_download_max_attempts: 2,
+            ..harness.conf.clone()
+        }));
+        let (tenant, _ctx) = harness.load().await;
+        assert!(tenant.get_timeline(TIMELINE_ID, false).is_err());
 
         Ok(())
     }
 
     #[tokio::test]
-    async fn delta_layer_dumping() -> anyhow::Result<()> {
-        use storage_layer::AsLayerDesc;
-        let (tenant, ctx) = TenantHarness::create("test_layer_dumping")?.load().await;
+    async fn only_gc_iteration_persists_gc_cutoffs() -> anyhow::Result<()> {
+        let mut harness = TenantHarness::create("only_gc_iteration_persists_gc_cutoffs")?;
+        harness.tenant_conf.gc_horizon = 0x10;
+        let (tenant, ctx) = harness.load().await;
         let tline = tenant
-            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .create_test_timeline(TIMELINE_ID, Lsn(0x7000), DEFAULT_PG_VERSION, &ctx)
             .await?;
-        make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
-
-        let layer_map = tline.layers.read().await;
+        make_some_layers(tline.as_ref(), Lsn(0x8000), &ctx).await?;
+        let remote_client = tline.remote_client.as_ref().unwrap();
+
+        let index_path = harness.remote_fs_dir.join(
+            remote_timeline_client::remote_index_path(
+                &harness.tenant_shard_id,
+                &TIMELINE_ID,
+                harness.generation,
+            )
+            .get_path(),
+        );
+        let read_index_part = || -> anyhow::Result<IndexPart> {
+            Ok(IndexPart::from_s3_bytes(&std::fs::read(&index_path)?)?)
+        };
+
+        // Refreshing the GC info outside of GC, e.g. for the synthetic size, doesn't upload
+        let gc_timelines = tenant
+            .refresh_gc_info(&CancellationToken::new(), &ctx)
+            .await?;
+        assert_eq!(gc_timelines.len(), 1);
+        remote_client.wait_completion().await?;
+        assert_eq!(read_index_part()?.gc_cutoffs, None);
+
+        tenant
+            .gc_iteration(
+                Some(TIMELINE_ID),
+                0x10,
+                Duration::ZERO,
+                &CancellationToken::new(),
+                &ctx,
+            )
+            .await?;
+        remote_client.wait_completion().await?;
+        let gc_info = tline.gc_info.read().unwrap().clone();
+        assert_eq!(
+            read_index_part()?.gc_cutoffs,
+            Some(GcCutoffs {
+                horizon: gc_info.horizon_cutoff,
+                pitr: gc_info.pitr_cutoff,
+            })
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_only_attach_leaves_remote_storage_alone() -> anyhow::Result<()> {
+        let harness = TenantHarness::create("read_only_attach_leaves_remote_storage_alone")?;
+        {
+            let (tenant, ctx) = harness.load().await;
+            let tline = tenant
+                .create_test_timeline(TIMELINE_ID, Lsn(0x7000), DEFAULT_PG_VERSION, &ctx)
+                .await?;
+            make_some_layers(tline.as_ref(), Lsn(0x8000), &ctx).await?;
+            tline
+                .remote_client
+                .as_ref()
+                .unwrap()
+                .wait_completion()
+                .await?;
+            tenant
+                .shutdown(Default::default(), true)
+                .instrument(harness.span())
+                .await
+                .ok()
+                .unwrap();
+        }
+
+        // Add a layer from the future to the remote index: a normal attach would delete it,
+        // and upload an index without it.
+        let index_path = harness.remote_fs_dir.join(
+            remote_timeline_client::remote_index_path(
+                &harness.tenant_shard_id,
+                &TIMELINE_ID,
+                harness.generation,
+            )
+            .get_path(),
+        );
+        let mut index_part = IndexPart::from_s3_bytes(&std::fs::read(&index_path)?)?;
+        let future_layer = storage_layer::LayerFileName::from(storage_layer::ImageFileName {
+            key_range: Key::MIN..Key::MAX,
+            lsn: Lsn(0x10_0000),
+        });
+        let metadata = index_part.layer_metadata.values().next().unwrap().clone();
+        index_part.layer_metadata.insert(future_layer, metadata);
+        let index_bytes = index_part.to_s3_bytes()?;
+        std::fs::write(&index_path, &index_bytes)?;
+
+        let ctx = RequestContext::new(TaskKind::UnitTest, DownloadBehavior::Error);
+        let tenant = harness
+            .do_try_load_in_mode(SpawnMode::ReadOnly, &ctx)
+            .await?;
+        assert!(tenant.is_read_only());
+        let tline = tenant.get_timeline(TIMELINE_ID, true)?;
+        tline
+            .remote_client
+            .as_ref()
+            .unwrap()
+            .wait_completion()
+            .await?;
+
+        assert_eq!(std::fs::read(&index_path)?, index_bytes);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn timeline_load_with_ancestor() -> anyhow::Result<()> {
+        const TEST_NAME: &str = "timeline_load_with_ancestor";
+        let harness = TenantHarness::create(TEST_NAME)?;
+        // create two timelines
+        {
+            let (tenant, ctx) = harness.load().await;
+            let tline = tenant
+                .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+                .await?;
+
+            make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+
+            let child_tline = tenant
+                .branch_timeline_test(&tline, NEW_TIMELINE_ID, Some(Lsn(0x40)), &ctx)
+                .await?;
+            child_tline.set_state(TimelineState::Active);
+
+            let newtline = tenant
+                .get_timeline(NEW_TIMELINE_ID, true)
+                .expect("Should have a local timeline");
+
+            make_some_layers(newtline.as_ref(), Lsn(0x60), &ctx).await?;
+
+            // so that all uploads finish & we can call harness.load() below again
+            tenant
+                .shutdown(Default::default(), true)
+                .instrument(harness.span())
+                .await
+                .ok()
+                .unwrap();
+        }
+
+        // check that both of them are initially unloaded
+        let (tenant, _ctx) = harness.load().await;
+
+        // check that both, child and ancestor are loaded
+        let _child_tline = tenant
+            .get_timeline(NEW_TIMELINE_ID, true)
+            .expect("cannot get child timeline loaded");
+
+        let _ancestor_tline = tenant
+            .get_timeline(TIMELINE_ID, true)
+            .expect("cannot get ancestor timeline loaded");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn delta_layer_dumping() -> anyhow::Result<()> {
+        use storage_layer::AsLayerDesc;
+        let (tenant, ctx) = TenantHarness::create("test_layer_dumping")?.load().await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+
+        let layer_map = tline.layers.read().await;
         let level0_deltas = layer_map
             .layer_map()
             .get_level0_deltas()?
@@ -4367,7 +6147,12 @@ mod tests {
         for delta in level0_deltas {
             // Ensure we are dumping a delta layer here
             assert!(delta.layer_desc().is_delta);
-            delta.dump(true, &ctx).await.unwrap();
+            delta.dump(true, None, &ctx).await.unwrap();
+            // Restricting the dump to a single key
+            delta
+                .dump(true, Some(&(*TEST_KEY..TEST_KEY.next())), &ctx)
+                .await
+                .unwrap();
         }
 
         Ok(())
@@ -5000,4 +6785,1649 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_bootstrap_timeline_cancelled() -> anyhow::Result<()> {
+        let harness = TenantHarness::create("test_bootstrap_timeline_cancelled")?;
+        let (tenant, ctx) = harness.load().await;
+
+        // Reuse the initdb archive from the walingest test data, so that bootstrap
+        // goes through the download and extraction path rather than running initdb.
+        let source_initdb_path = format!("test_data/sk_wal_segment_from_pgbench/{INITDB_PATH}");
+        let remote_initdb_path =
+            remote_initdb_archive_path(&tenant.tenant_shard_id().tenant_id, &TIMELINE_ID);
+        let initdb_path = harness.remote_fs_dir.join(remote_initdb_path.get_path());
+        std::fs::create_dir_all(initdb_path.parent().unwrap())?;
+        std::fs::copy(source_initdb_path, initdb_path)?;
+
+        tenant.cancel.cancel();
+
+        tenant
+            .bootstrap_timeline_test(TIMELINE_ID, 15, Some(TIMELINE_ID), &ctx)
+            .await
+            .expect_err("bootstrap should fail after cancellation");
+
+        assert!(matches!(
+            tenant.get_timeline(TIMELINE_ID, false),
+            Err(GetTimelineError::NotFound { .. })
+        ));
+
+        // Neither the temporary pgdata directory nor the uninit mark should be left behind
+        let timelines_path = harness.conf.timelines_path(&tenant.tenant_shard_id);
+        let pgdata_path = path_with_suffix_extension(
+            timelines_path.join(format!("basebackup-{TIMELINE_ID}")),
+            TEMP_FILE_SUFFIX,
+        );
+        assert!(!pgdata_path.exists());
+        assert!(!harness
+            .conf
+            .timeline_uninit_mark_file_path(tenant.tenant_shard_id, TIMELINE_ID)
+            .exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_flush_timeline_remote() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_flush_timeline_remote")?
+            .load()
+            .await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+
+        tenant.flush_timeline_remote(TIMELINE_ID).await?;
+
+        let err = tenant
+            .flush_timeline_remote(NEW_TIMELINE_ID)
+            .await
+            .expect_err("flushing an unknown timeline should fail");
+        assert!(matches!(
+            err.downcast_ref::<GetTimelineError>(),
+            Some(GetTimelineError::NotFound { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_timelines_sorted() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_list_timelines_sorted")?
+            .load()
+            .await;
+        let root = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        make_some_layers(root.as_ref(), Lsn(0x20), &ctx).await?;
+
+        let branch = tenant
+            .branch_timeline_test(&root, NEW_TIMELINE_ID, Some(Lsn(0x20)), &ctx)
+            .await?;
+        let grandchild_id = TimelineId::generate();
+        tenant
+            .branch_timeline_test(&branch, grandchild_id, Some(Lsn(0x20)), &ctx)
+            .await?;
+
+        let sorted: Vec<_> = tenant
+            .list_timelines_sorted()
+            .iter()
+            .map(|t| t.timeline_id)
+            .collect();
+        assert_eq!(sorted, vec![TIMELINE_ID, NEW_TIMELINE_ID, grandchild_id]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_force_branch_below_gc_cutoff() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_force_branch_below_gc_cutoff")?
+            .load()
+            .await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+
+        tenant
+            .gc_iteration(
+                Some(TIMELINE_ID),
+                0x10,
+                Duration::ZERO,
+                &CancellationToken::new(),
+                &ctx,
+            )
+            .await?;
+        assert!(*tline.get_latest_gc_cutoff_lsn() > Lsn(0x25));
+
+        // Forcing allows branching below the GC cutoff
+        let uninit_mark = tenant.create_timeline_uninit_mark(NEW_TIMELINE_ID).unwrap();
+        let branch = tenant
+            .branch_timeline_impl(
+                &tline,
+                NEW_TIMELINE_ID,
+                Some(Lsn(0x25)),
+                true,
+                uninit_mark,
+                &ctx,
+            )
+            .await?;
+        assert_eq!(branch.get_ancestor_lsn(), Lsn(0x25));
+        assert_eq!(*branch.get_latest_gc_cutoff_lsn(), Lsn(0x25));
+
+        // ... but not below the source timeline's own ancestor LSN
+        let grandchild_id = TimelineId::generate();
+        let uninit_mark = tenant.create_timeline_uninit_mark(grandchild_id).unwrap();
+        let err = tenant
+            .branch_timeline_impl(
+                &branch,
+                grandchild_id,
+                Some(Lsn(0x20)),
+                true,
+                uninit_mark,
+                &ctx,
+            )
+            .await
+            .expect_err("branching below the ancestor lsn should fail");
+        assert!(matches!(err, CreateTimelineError::AncestorLsn(_)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compact_timeline() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_compact_timeline")?.load().await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+
+        let cancel = CancellationToken::new();
+        tenant
+            .compact_timeline(TIMELINE_ID, EnumSet::empty(), &cancel, &ctx)
+            .await?;
+
+        let err = tenant
+            .compact_timeline(NEW_TIMELINE_ID, EnumSet::empty(), &cancel, &ctx)
+            .await
+            .expect_err("compacting an unknown timeline should fail");
+        assert!(matches!(
+            err.downcast_ref::<GetTimelineError>(),
+            Some(GetTimelineError::NotFound { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wal_redo_manager_status() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_wal_redo_manager_status")?
+            .load()
+            .await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x08), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+
+        assert_eq!(redo_requests(&tenant), 0);
+        assert!(tenant
+            .wal_redo_manager_status()
+            .unwrap()
+            .last_redo_at
+            .is_none());
+
+        let mut writer = tline.writer().await;
+        writer
+            .put(
+                *TEST_KEY,
+                Lsn(0x10),
+                &Value::Image(test_img("foo at 0x10")),
+                &ctx,
+            )
+            .await?;
+        writer
+            .put(
+                *TEST_KEY,
+                Lsn(0x20),
+                &Value::WalRecord(NeonWalRecord::Postgres {
+                    will_init: false,
+                    rec: Bytes::from_static(b"record at 0x20"),
+                }),
+                &ctx,
+            )
+            .await?;
+        writer.finish_write(Lsn(0x20));
+        drop(writer);
+
+        // Reading the image alone needs no redo
+        tline.get(*TEST_KEY, Lsn(0x10), &ctx).await?;
+        assert_eq!(redo_requests(&tenant), 0);
+
+        // Reading past the WAL record goes through the redo manager
+        tline.get(*TEST_KEY, Lsn(0x20), &ctx).await?;
+        assert_eq!(redo_requests(&tenant), 1);
+        assert!(tenant
+            .wal_redo_manager_status()
+            .unwrap()
+            .last_redo_at
+            .is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_clean_up_timelines_report() -> anyhow::Result<()> {
+        let harness = TenantHarness::create("test_clean_up_timelines_report")?;
+        let (tenant, ctx) = harness.load().await;
+        tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+
+        let stale_path = harness.timeline_path(&NEW_TIMELINE_ID);
+        std::fs::create_dir_all(&stale_path)?;
+        let junk_path = harness
+            .conf
+            .timelines_path(&harness.tenant_shard_id)
+            .join("not-a-timeline");
+        std::fs::create_dir_all(&junk_path)?;
+
+        let report = tenant.clean_up_timelines(&HashSet::from([TIMELINE_ID]))?;
+        assert_eq!(report.purged, vec![stale_path.clone()]);
+        assert_eq!(report.kept_unparseable, vec![junk_path.clone()]);
+
+        assert!(!stale_path.exists());
+        assert!(junk_path.exists());
+        assert!(harness.timeline_path(&TIMELINE_ID).exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_timeline_corrupt_initdb_archive() -> anyhow::Result<()> {
+        let harness = TenantHarness::create("test_bootstrap_timeline_corrupt_initdb_archive")?;
+        let (tenant, ctx) = harness.load().await;
+
+        let source_initdb_path = format!("test_data/sk_wal_segment_from_pgbench/{INITDB_PATH}");
+        let remote_initdb_path =
+            remote_initdb_archive_path(&tenant.tenant_shard_id().tenant_id, &TIMELINE_ID);
+        let initdb_path = harness.remote_fs_dir.join(remote_initdb_path.get_path());
+        std::fs::create_dir_all(initdb_path.parent().unwrap())?;
+
+        // Store the checksum of the intact archive, but a truncated archive next to it
+        let archive = std::fs::read(source_initdb_path)?;
+        let checksum_path = harness
+            .remote_fs_dir
+            .join(remote_initdb_checksum_path(&remote_initdb_path).get_path());
+        std::fs::write(checksum_path, format!("{:08x}", crc32c::crc32c(&archive)))?;
+        std::fs::write(&initdb_path, &archive[..archive.len() / 2])?;
+
+        let err = tenant
+            .bootstrap_timeline_test(TIMELINE_ID, 15, Some(TIMELINE_ID), &ctx)
+            .await
+            .expect_err("bootstrap from a corrupt archive should fail");
+        assert!(
+            format!("{err:#}").contains("checksum mismatch"),
+            "unexpected error: {err:#}"
+        );
+
+        assert!(matches!(
+            tenant.get_timeline(TIMELINE_ID, false),
+            Err(GetTimelineError::NotFound { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_effective_config_toml() -> anyhow::Result<()> {
+        let (tenant, _ctx) = TenantHarness::create("test_effective_config_toml")?
+            .load()
+            .await;
+
+        let config_toml = tenant.effective_config_toml()?;
+        let parsed: TenantConf = toml_edit::de::from_str(&config_toml)?;
+        assert_eq!(parsed, tenant.effective_config());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_set_new_tenant_config_rejects_invalid() -> anyhow::Result<()> {
+        let (tenant, _ctx) = TenantHarness::create("test_set_new_tenant_config_rejects_invalid")?
+            .load()
+            .await;
+        let before = tenant.tenant_specific_overrides();
+
+        let invalid = TenantConfOpt {
+            compaction_threshold: Some(0),
+            ..before.clone()
+        };
+        tenant
+            .set_new_tenant_config(invalid.clone())
+            .expect_err("compaction_threshold of 0 should be rejected");
+        assert_eq!(tenant.tenant_specific_overrides(), before);
+
+        let location_conf = AttachedTenantConf {
+            tenant_conf: invalid,
+            location: tenant.read_tenant_conf().location,
+        };
+        tenant
+            .set_new_location_config(location_conf)
+            .expect_err("compaction_threshold of 0 should be rejected");
+        assert_eq!(tenant.tenant_specific_overrides(), before);
+
+        let valid = TenantConfOpt {
+            compaction_threshold: Some(1),
+            ..before
+        };
+        tenant.set_new_tenant_config(valid.clone())?;
+        assert_eq!(tenant.tenant_specific_overrides(), valid);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_branch_idempotent_ignores_pg_version() -> anyhow::Result<()> {
+        let (tenant, ctx) =
+            TenantHarness::create("test_create_branch_idempotent_ignores_pg_version")?
+                .load()
+                .await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        tenant
+            .branch_timeline_test(&tline, NEW_TIMELINE_ID, Some(Lsn(0x10)), &ctx)
+            .await?;
+
+        // Only the already-exists path is exercised, which never talks to the broker
+        let broker_client =
+            storage_broker::connect(storage_broker::DEFAULT_ENDPOINT, Duration::from_secs(5))?;
+
+        // Repeating the branch creation with a different pg_version is idempotent...
+        let branch = tenant
+            .create_timeline(
+                NEW_TIMELINE_ID,
+                Some(TIMELINE_ID),
+                Some(Lsn(0x10)),
+                None,
+                DEFAULT_PG_VERSION + 1,
+                None,
+                false,
+                broker_client.clone(),
+                &ctx,
+            )
+            .await?;
+        assert_eq!(branch.timeline_id, NEW_TIMELINE_ID);
+        assert_eq!(branch.pg_version, DEFAULT_PG_VERSION);
+
+        // ...but root timelines choose their own version, so a mismatch is a conflict
+        let res = tenant
+            .create_timeline(
+                TIMELINE_ID,
+                None,
+                None,
+                None,
+                DEFAULT_PG_VERSION + 1,
+                None,
+                false,
+                broker_client,
+                &ctx,
+            )
+            .await;
+        assert!(matches!(res, Err(CreateTimelineError::Conflict)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compaction_soft_stop() -> anyhow::Result<()> {
+        let mut harness = TenantHarness::create("test_compaction_soft_stop")?;
+        harness.tenant_conf.compaction_threshold = 2;
+        let (tenant, ctx) = harness.load().await;
+
+        let mut timelines = Vec::new();
+        for timeline_id in [TIMELINE_ID, NEW_TIMELINE_ID] {
+            let tline = tenant
+                .create_test_timeline(timeline_id, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+                .await?;
+            make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+            timelines.push(tline);
+        }
+
+        for tline in &timelines {
+            assert!(tline.level0_delta_count().await? >= 2);
+        }
+
+        // Hold up compaction of both timelines, so that the iteration gets stuck in the first one
+        let guards =
+            futures::future::join_all(timelines.iter().map(|t| t.compaction_lock.lock())).await;
+
+        let cancel = CancellationToken::new();
+        let compaction = tenant.compaction_iteration(&cancel, &ctx);
+        tokio::pin!(compaction);
+        tokio::select! {
+            _ = &mut compaction => panic!("compaction should be waiting for the first timeline"),
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+        }
+
+        tenant.set_compaction_soft_stop(true);
+        drop(guards);
+        compaction.await?;
+
+        // The timeline in progress was compacted, the other one was not touched
+        let mut compacted = 0;
+        for tline in &timelines {
+            if tline.level0_delta_count().await? == 0 {
+                compacted += 1;
+            }
+        }
+        assert_eq!(compacted, 1);
+
+        // While soft-stopped, later iterations don't compact anything...
+        tenant.compaction_iteration(&cancel, &ctx).await?;
+        let mut compacted = 0;
+        for tline in &timelines {
+            if tline.level0_delta_count().await? == 0 {
+                compacted += 1;
+            }
+        }
+        assert_eq!(compacted, 1);
+
+        // ...until the soft stop is lifted
+        tenant.set_compaction_soft_stop(false);
+        tenant.compaction_iteration(&cancel, &ctx).await?;
+        for tline in &timelines {
+            assert_eq!(tline.level0_delta_count().await?, 0);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compaction_candidates() -> anyhow::Result<()> {
+        let mut harness = TenantHarness::create("test_compaction_candidates")?;
+        harness.tenant_conf.compaction_threshold = 2;
+        let (tenant, ctx) = harness.load().await;
+
+        let quiet = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        let busy = tenant
+            .create_test_timeline(NEW_TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        make_some_layers(busy.as_ref(), Lsn(0x20), &ctx).await?;
+
+        let candidates = tenant.compaction_candidates().await?;
+        assert_eq!(
+            candidates,
+            vec![
+                (NEW_TIMELINE_ID, busy.level0_delta_count().await?),
+                (TIMELINE_ID, quiet.level0_delta_count().await?),
+            ]
+        );
+        assert!(candidates[0].1 > candidates[1].1);
+
+        // Listing the candidates doesn't compact anything
+        assert_eq!(tenant.compaction_candidates().await?, candidates);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_timelines_deferred_upload() -> anyhow::Result<()> {
+        let harness = TenantHarness::create("test_create_timelines_deferred_upload")?;
+        let (tenant, ctx) = harness.load().await;
+        tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+
+        // Activating the new timelines launches their WAL receivers, which connect lazily
+        let broker_client =
+            storage_broker::connect(storage_broker::DEFAULT_ENDPOINT, Duration::from_secs(5))?;
+
+        const N: usize = 8;
+        let mut timeline_ids = Vec::with_capacity(N);
+        let mut pending_uploads = Vec::with_capacity(N);
+        for _ in 0..N {
+            let timeline_id = TimelineId::generate();
+            let (timeline, pending_upload) = tenant
+                .create_timeline_deferred_upload(
+                    timeline_id,
+                    Some(TIMELINE_ID),
+                    Some(Lsn(0x10)),
+                    None,
+                    DEFAULT_PG_VERSION,
+                    None,
+                    false,
+                    broker_client.clone(),
+                    &ctx,
+                )
+                .await?;
+            assert!(timeline.is_active());
+            timeline_ids.push(timeline_id);
+            pending_uploads.push(pending_upload);
+        }
+
+        // Flush all of them at once
+        futures::future::try_join_all(pending_uploads.into_iter().map(|p| p.wait())).await?;
+
+        for timeline_id in timeline_ids {
+            let index_path = harness.remote_fs_dir.join(
+                remote_timeline_client::remote_index_path(
+                    &harness.tenant_shard_id,
+                    &timeline_id,
+                    harness.generation,
+                )
+                .get_path(),
+            );
+            let index_part = IndexPart::from_s3_bytes(&std::fs::read(&index_path)?)?;
+            assert_eq!(index_part.metadata.ancestor_timeline(), Some(TIMELINE_ID));
+        }
+
+        tenant
+            .shutdown(Default::default(), true)
+            .instrument(harness.span())
+            .await
+            .ok()
+            .unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_local_physical_size() -> anyhow::Result<()> {
+        use storage_layer::AsLayerDesc;
+
+        let (tenant, ctx) = TenantHarness::create("test_local_physical_size")?
+            .load()
+            .await;
+        assert_eq!(tenant.local_physical_size().await, 0);
+
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+        tline
+            .remote_client
+            .as_ref()
+            .unwrap()
+            .wait_completion()
+            .await?;
+
+        // Everything is resident, and everything has been uploaded
+        let all_layers = tline.layer_size_sum().await;
+        assert!(all_layers > 0);
+        assert_eq!(tenant.local_physical_size().await, all_layers);
+        assert_eq!(tenant.remote_size(), all_layers);
+
+        // Evicting a layer only reduces the local size
+        let layer = {
+            let guard = tline.layers.read().await;
+            let desc = guard.layer_map().iter_historic_layers().next().unwrap();
+            guard.get_from_desc(&desc)
+        };
+        layer.evict_and_wait().await?;
+        assert_eq!(
+            tenant.local_physical_size().await,
+            all_layers - layer.layer_desc().file_size
+        );
+        assert_eq!(tenant.remote_size(), all_layers);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_attach_reuses_warm_local_layers() -> anyhow::Result<()> {
+        use crate::tenant::config::SecondaryLocationConfig;
+        use pageserver_api::models::ShardParameters;
+        use std::io::Write;
+        use storage_layer::AsLayerDesc;
+
+        let mut harness = TenantHarness::create("test_attach_reuses_warm_local_layers")?;
+
+        // Write some layers in one generation, and leave them on local disk, like a secondary
+        // location that has downloaded them would.
+        let layer_names = {
+            let (tenant, ctx) = harness.load().await;
+            let tline = tenant
+                .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+                .await?;
+            make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+            tline
+                .remote_client
+                .as_ref()
+                .unwrap()
+                .wait_completion()
+                .await?;
+
+            let layer_names = tline
+                .layers
+                .read()
+                .await
+                .layer_map()
+                .iter_historic_layers()
+                .map(|desc| desc.filename())
+                .collect::<Vec<_>>();
+            tenant
+                .shutdown(Default::default(), true)
+                .instrument(harness.span())
+                .await
+                .ok()
+                .unwrap();
+            layer_names
+        };
+        assert!(layer_names.len() >= 2);
+
+        // A local file which doesn't match the index must not be used
+        let corrupt = &layer_names[0];
+        let corrupt_path = harness
+            .timeline_path(&TIMELINE_ID)
+            .join(corrupt.file_name());
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&corrupt_path)?
+            .write_all(b"garbage")?;
+
+        // Keep the generations of our location configs: without a control plane, the manager
+        // would drop them and the promoted tenant would not find the index written above.
+        harness.conf = Box::leak(Box::new(PageServerConf {
+            control_plane_api: Some("http://localhost:1234".parse().unwrap()),
+            ..harness.conf.clone()
+        }));
+        let tenant_manager = mgr::TenantManager::for_harness(&harness);
+        let ctx = RequestContext::new(TaskKind::UnitTest, DownloadBehavior::Error);
+        let tenant_conf = TenantConfOpt::from(harness.tenant_conf.clone());
+
+        // Configure the location as a warm secondary...
+        let secondary = LocationConf {
+            mode: LocationMode::Secondary(SecondaryLocationConfig { warm: true }),
+            shard: ShardIdentity::unsharded(),
+            tenant_conf: tenant_conf.clone(),
+        };
+        let attached = tenant_manager
+            .upsert_location(
+                harness.tenant_shard_id,
+                secondary,
+                None,
+                SpawnMode::Normal,
+                &ctx,
+            )
+            .instrument(harness.span())
+            .await?;
+        assert!(attached.is_none());
+
+        // ...and promote it to attached in a newer generation
+        let attached = LocationConf::attached_single(
+            tenant_conf,
+            harness.generation.next(),
+            &ShardParameters::default(),
+        );
+        let tenant = tenant_manager
+            .upsert_location(
+                harness.tenant_shard_id,
+                attached,
+                None,
+                SpawnMode::Normal,
+                &ctx,
+            )
+            .instrument(harness.span())
+            .await?
+            .expect("attached location has a tenant");
+        let mut state = tenant.state.subscribe();
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            state.wait_for(|s| *s == TenantState::Active),
+        )
+        .await??;
+        let tline = tenant.get_timeline(TIMELINE_ID, true)?;
+
+        let guard = tline.layers.read().await;
+        let resident = guard
+            .resident_layers()
+            .map(|layer| layer.layer_desc().filename())
+            .collect::<HashSet<_>>()
+            .await;
+        drop(guard);
+
+        for name in &layer_names {
+            assert_eq!(resident.contains(name), name != corrupt, "{name}");
+        }
+
+        // Neither the warm layers nor the corrupt one were downloaded: the latter is left
+        // for on-demand download.
+        assert_eq!(
+            tline
+                .remote_client
+                .as_ref()
+                .unwrap()
+                .layer_downloads_started(),
+            0
+        );
+
+        tenant
+            .shutdown(Default::default(), true)
+            .instrument(harness.span())
+            .await
+            .ok()
+            .unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_redownload_layer() -> anyhow::Result<()> {
+        use storage_layer::AsLayerDesc;
+
+        let (tenant, ctx) = TenantHarness::create("test_redownload_layer")?.load().await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+        tline
+            .remote_client
+            .as_ref()
+            .unwrap()
+            .wait_completion()
+            .await?;
+
+        let layer = {
+            let guard = tline.layers.read().await;
+            let desc = guard.layer_map().iter_historic_layers().next().unwrap();
+            guard.get_from_desc(&desc)
+        };
+        let name = layer.layer_desc().filename();
+        let path = layer.local_path().to_owned();
+
+        // Corrupt the local file, keeping its size
+        let original = std::fs::read(&path)?;
+        std::fs::write(&path, vec![0u8; original.len()])?;
+
+        tline.redownload_layer(&name).await?;
+        assert_eq!(std::fs::read(&path)?, original);
+
+        // A layer which isn't in the remote index can't be re-downloaded
+        let unknown: storage_layer::LayerFileName = "000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000000000001-0000000000000002"
+            .parse()
+            .unwrap();
+        let err = tline.redownload_layer(&unknown).await.unwrap_err();
+        assert!(
+            matches!(err, timeline::RedownloadLayerError::NotInRemoteIndex(_)),
+            "{err:#}"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_reconstruct_data_debug() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_get_reconstruct_data_debug")?
+            .load()
+            .await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x08), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+
+        let record = |lsn: Lsn| NeonWalRecord::Postgres {
+            will_init: false,
+            rec: Bytes::from(format!("record at {lsn}")),
+        };
+
+        // An image and two records on top of it, in different layers
+        let mut writer = tline.writer().await;
+        writer
+            .put(
+                *TEST_KEY,
+                Lsn(0x10),
+                &Value::Image(test_img("foo at 0x10")),
+                &ctx,
+            )
+            .await?;
+        writer
+            .put(
+                *TEST_KEY,
+                Lsn(0x20),
+                &Value::WalRecord(record(Lsn(0x20))),
+                &ctx,
+            )
+            .await?;
+        writer.finish_write(Lsn(0x20));
+        drop(writer);
+        tline.freeze_and_flush().await?;
+
+        let mut writer = tline.writer().await;
+        writer
+            .put(
+                *TEST_KEY,
+                Lsn(0x30),
+                &Value::WalRecord(record(Lsn(0x30))),
+                &ctx,
+            )
+            .await?;
+        writer.finish_write(Lsn(0x30));
+        drop(writer);
+
+        // Materialize the page, so that it is in the page cache
+        tline.get(*TEST_KEY, Lsn(0x30), &ctx).await?;
+        let redo_requests_before = redo_requests(&tenant);
+
+        let chain = tline
+            .get_reconstruct_data_debug(*TEST_KEY, Lsn(0x30), &ctx)
+            .await?;
+        assert_eq!(chain.img, Some((Lsn(0x10), test_img("foo at 0x10"))));
+        assert_eq!(
+            chain.records,
+            vec![
+                (Lsn(0x20), record(Lsn(0x20))),
+                (Lsn(0x30), record(Lsn(0x30))),
+            ]
+        );
+
+        // Older LSNs only get the records up to them
+        let chain = tline
+            .get_reconstruct_data_debug(*TEST_KEY, Lsn(0x20), &ctx)
+            .await?;
+        assert_eq!(chain.records, vec![(Lsn(0x20), record(Lsn(0x20)))]);
+
+        // WAL redo was not involved
+        assert_eq!(redo_requests(&tenant), redo_requests_before);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_synthetic_size_calculation_disabled() -> anyhow::Result<()> {
+        let mut harness = TenantHarness::create("test_synthetic_size_calculation_disabled")?;
+        harness.tenant_conf.synthetic_size_calculation_enabled = false;
+        let (tenant, ctx) = harness.load().await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+
+        tenant.set_cached_synthetic_size(1234);
+
+        let size = tenant
+            .calculate_synthetic_size(
+                LogicalSizeCalculationCause::ConsumptionMetricsSyntheticSize,
+                &CancellationToken::new(),
+                &ctx,
+            )
+            .await?;
+        assert_eq!(size, 1234, "the prior value should be returned as-is");
+        assert_eq!(tenant.cached_synthetic_size(), 1234);
+
+        let metric = TENANT_SYNTHETIC_SIZE_METRIC
+            .get_metric_with_label_values(&[&tenant.tenant_shard_id.tenant_id.to_string()])
+            .unwrap()
+            .get();
+        assert_eq!(metric, 1234, "the metric should keep its prior value");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_synthetic_size_calculation_is_not_throttled() -> anyhow::Result<()> {
+        let mut harness =
+            TenantHarness::create("test_synthetic_size_calculation_is_not_throttled")?;
+        // No budget to begin with, and a long way until the next refill
+        harness.tenant_conf.timeline_get_throttle = models::ThrottleConfig {
+            task_kinds: vec![
+                "CalculateSyntheticSize".to_string(),
+                "OndemandLogicalSizeCalculation".to_string(),
+            ],
+            initial: 0,
+            refill_interval: Duration::from_secs(3600),
+            refill_amount: NonZeroUsize::new(1).unwrap(),
+            max: 1,
+            fair: true,
+        };
+        let (tenant, ctx) = harness.load().await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+
+        // The throttle is effective for contexts that are not exempt
+        let client_ctx =
+            ctx.detached_child(TaskKind::CalculateSyntheticSize, DownloadBehavior::Download);
+        tokio::time::timeout(
+            Duration::from_millis(100),
+            tline.get(*TEST_KEY, Lsn(0x20), &client_ctx),
+        )
+        .await
+        .expect_err("non-exempt context must be throttled");
+        tenant.timeline_get_throttle.reset_stats();
+
+        let worker_ctx = crate::consumption_metrics::synthetic_size_worker_ctx(&ctx);
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            tenant.calculate_synthetic_size(
+                LogicalSizeCalculationCause::ConsumptionMetricsSyntheticSize,
+                &CancellationToken::new(),
+                &worker_ctx,
+            ),
+        )
+        .await
+        .expect("synthetic size calculation must not wait for the throttle")?;
+
+        let stats = tenant.timeline_get_throttle.reset_stats();
+        assert_eq!(stats.count_accounted, 0);
+
+        Ok(())
+    }
+
+    #[cfg(debug_assertions)]
+    #[tokio::test]
+    #[should_panic(expected = "lock order violation")]
+    async fn test_lock_order_violation_panics() {
+        let (tenant, _ctx) = TenantHarness::create("test_lock_order_violation_panics")
+            .unwrap()
+            .load()
+            .await;
+
+        // In order: fine
+        {
+            let _timelines = tenant.lock_timelines();
+            let _creating = tenant.lock_timelines_creating();
+            let _conf = tenant.read_tenant_conf();
+        }
+
+        // `timelines` must be acquired before `timelines_creating`
+        let _creating = tenant.lock_timelines_creating();
+        let _timelines = tenant.lock_timelines();
+    }
+
+    #[tokio::test]
+    async fn test_timeline_count() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_timeline_count")?.load().await;
+        assert_eq!(tenant.timeline_count(), 0);
+        assert!(!tenant.has_timeline(TIMELINE_ID));
+
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        tenant
+            .branch_timeline_test(&tline, NEW_TIMELINE_ID, Some(Lsn(0x10)), &ctx)
+            .await?;
+
+        assert_eq!(tenant.timeline_count(), 2);
+        assert_eq!(tenant.timeline_count(), tenant.list_timelines().len());
+        assert!(tenant.has_timeline(TIMELINE_ID));
+        assert!(tenant.has_timeline(NEW_TIMELINE_ID));
+        assert!(!tenant.has_timeline(TimelineId::generate()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_branch_at_timestamp_without_commits() -> anyhow::Result<()> {
+        let (tenant, ctx) =
+            TenantHarness::create("test_create_branch_at_timestamp_without_commits")?
+                .load()
+                .await;
+        tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+
+        // Both failures happen before the new timeline would be activated, which is the only
+        // part that talks to the broker
+        let broker_client =
+            storage_broker::connect(storage_broker::DEFAULT_ENDPOINT, Duration::from_secs(5))?;
+
+        // The start LSN and timestamp are mutually exclusive
+        let res = tenant
+            .create_timeline(
+                NEW_TIMELINE_ID,
+                Some(TIMELINE_ID),
+                Some(Lsn(0x10)),
+                Some(SystemTime::now()),
+                DEFAULT_PG_VERSION,
+                None,
+                false,
+                broker_client.clone(),
+                &ctx,
+            )
+            .await;
+        assert!(
+            matches!(res, Err(CreateTimelineError::AncestorLsn(_))),
+            "{:?}",
+            res.err()
+        );
+
+        // The test timeline has no CLOG, so there are no commit timestamps to resolve with
+        let res = tenant
+            .create_timeline(
+                NEW_TIMELINE_ID,
+                Some(TIMELINE_ID),
+                None,
+                Some(SystemTime::now()),
+                DEFAULT_PG_VERSION,
+                None,
+                false,
+                broker_client,
+                &ctx,
+            )
+            .await;
+        match res {
+            Err(CreateTimelineError::AncestorLsn(e)) => {
+                assert!(e.to_string().contains("no commit records"), "{e:#}")
+            }
+            res => panic!("unexpected result: {:?}", res.err()),
+        }
+        assert!(!tenant.has_timeline(NEW_TIMELINE_ID));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wait_to_become_active_total_deadline() -> anyhow::Result<()> {
+        let (tenant, _ctx) = TenantHarness::create("test_wait_to_become_active_total_deadline")?
+            .load()
+            .await;
+        tenant
+            .state
+            .send_replace(TenantState::Activating(ActivatingFrom::Attaching));
+
+        // Keep the tenant churning between non-active states more often than the timeout,
+        // which would keep resetting a per-iteration timeout forever.
+        let churn = tokio::spawn({
+            let tenant = Arc::clone(&tenant);
+            async move {
+                loop {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    tenant.state.send_modify(|state| {
+                        *state = match state {
+                            TenantState::Activating(ActivatingFrom::Attaching) => {
+                                TenantState::Activating(ActivatingFrom::Loading)
+                            }
+                            _ => TenantState::Activating(ActivatingFrom::Attaching),
+                        }
+                    });
+                }
+            }
+        });
+
+        let timeout = Duration::from_millis(100);
+        let started_at = Instant::now();
+        let res = tenant.wait_to_become_active(timeout).await;
+        let elapsed = started_at.elapsed();
+        churn.abort();
+
+        assert!(
+            matches!(res, Err(GetActiveTenantError::WaitForActiveTimeout { wait_time, .. }) if wait_time == timeout),
+            "{res:?}"
+        );
+        assert!(elapsed < Duration::from_secs(5), "waited for {elapsed:?}");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_persist_tenant_config_preserves_unknown_keys() -> anyhow::Result<()> {
+        let harness = TenantHarness::create("test_persist_tenant_config_preserves_unknown_keys")?;
+        let conf = harness.conf;
+        let tenant_shard_id = harness.tenant_shard_id;
+        let config_path = conf.tenant_location_config_path(&tenant_shard_id);
+
+        let mut location_conf = LocationConf::attached_single(
+            TenantConfOpt::default(),
+            harness.generation,
+            &models::ShardParameters::default(),
+        );
+        Tenant::persist_tenant_config(conf, &tenant_shard_id, &location_conf).await?;
+
+        // Simulate a newer version having written keys that we don't know about
+        let mut document = fs::read_to_string(&config_path)?.parse::<toml_edit::Document>()?;
+        document["future_option"] = toml_edit::value(42);
+        document["tenant_conf"]["future_knob"] = toml_edit::value("on");
+        fs::write(&config_path, document.to_string())?;
+
+        // The unknown keys neither fail loading nor leak into the parsed config
+        let loaded = Tenant::load_tenant_config(conf, &tenant_shard_id)?;
+        assert!(loaded == location_conf);
+
+        // They survive rewriting the config with a change of our own
+        location_conf.tenant_conf.gc_horizon = Some(1234);
+        Tenant::persist_tenant_config(conf, &tenant_shard_id, &location_conf).await?;
+
+        let document = fs::read_to_string(&config_path)?.parse::<toml_edit::Document>()?;
+        assert_eq!(document["future_option"].as_integer(), Some(42));
+        assert_eq!(document["tenant_conf"]["future_knob"].as_str(), Some("on"));
+        assert_eq!(
+            document["tenant_conf"]["gc_horizon"].as_integer(),
+            Some(1234)
+        );
+
+        let loaded = Tenant::read_location_config(&config_path)?;
+        assert!(loaded.conf == location_conf);
+        assert_eq!(
+            loaded.unknown.keys().collect::<Vec<_>>(),
+            ["future_option", "tenant_conf.future_knob"]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_persist_tenant_config_without_legacy() -> anyhow::Result<()> {
+        let harness = TenantHarness::create("test_persist_tenant_config_without_legacy")?;
+        let conf: &'static PageServerConf = Box::leak(Box::new(PageServerConf {
+            write_legacy_tenant_config: false,
+            ..harness.conf.clone()
+        }));
+        let tenant_shard_id = harness.tenant_shard_id;
+        let config_path = conf.tenant_location_config_path(&tenant_shard_id);
+        let legacy_config_path = conf.tenant_config_path(&tenant_shard_id);
+
+        let mut location_conf = LocationConf::attached_single(
+            TenantConfOpt::default(),
+            harness.generation,
+            &models::ShardParameters::default(),
+        );
+
+        // A legacy config written before the setting was turned off...
+        Tenant::persist_tenant_config_at(
+            &tenant_shard_id,
+            &config_path,
+            &legacy_config_path,
+            &location_conf,
+            true,
+        )
+        .await?;
+        assert!(legacy_config_path.exists());
+
+        // ...is removed rather than left behind with stale settings
+        location_conf.tenant_conf.gc_horizon = Some(1234);
+        Tenant::persist_tenant_config(conf, &tenant_shard_id, &location_conf).await?;
+
+        assert!(config_path.exists());
+        assert!(!legacy_config_path.exists());
+        let loaded = Tenant::load_tenant_config(conf, &tenant_shard_id)?;
+        assert!(loaded == location_conf);
+
+        // Without generations there is no new-style config, so the legacy one is still written
+        let legacy_location_conf = LocationConf::attached_single(
+            location_conf.tenant_conf.clone(),
+            Generation::none(),
+            &models::ShardParameters::default(),
+        );
+        fs::remove_file(&config_path)?;
+        Tenant::persist_tenant_config(conf, &tenant_shard_id, &legacy_location_conf).await?;
+
+        assert!(!config_path.exists());
+        assert!(legacy_config_path.exists());
+        let loaded = Tenant::load_tenant_config(conf, &tenant_shard_id)?;
+        assert_eq!(loaded.tenant_conf, location_conf.tenant_conf);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reload_config_from_disk() -> anyhow::Result<()> {
+        let harness = TenantHarness::create("test_reload_config_from_disk")?;
+        let (tenant, _ctx) = harness.load().await;
+        let conf = harness.conf;
+        let tenant_shard_id = harness.tenant_shard_id;
+        let config_path = conf.tenant_location_config_path(&tenant_shard_id);
+
+        let mut location_conf = LocationConf::attached_single(
+            TenantConfOpt::default(),
+            harness.generation,
+            &models::ShardParameters::default(),
+        );
+        location_conf.tenant_conf.gc_horizon = Some(1234);
+        Tenant::persist_tenant_config(conf, &tenant_shard_id, &location_conf).await?;
+        assert_ne!(tenant.get_gc_horizon(), 1234);
+
+        // Hand-edit the file the way an operator would
+        let contents = fs::read_to_string(&config_path)?;
+        assert_eq!(contents.matches("gc_horizon = 1234").count(), 1);
+        fs::write(
+            &config_path,
+            contents.replace("gc_horizon = 1234", "gc_horizon = 4321"),
+        )?;
+
+        tenant.reload_config_from_disk()?;
+        assert_eq!(tenant.get_gc_horizon(), 4321);
+
+        // A config from an older generation must not be applied
+        let mut stale_conf = LocationConf::attached_single(
+            location_conf.tenant_conf.clone(),
+            harness.generation.previous(),
+            &models::ShardParameters::default(),
+        );
+        stale_conf.tenant_conf.gc_horizon = Some(5678);
+        Tenant::persist_tenant_config(conf, &tenant_shard_id, &stale_conf).await?;
+
+        let err = tenant.reload_config_from_disk().unwrap_err();
+        assert!(
+            err.to_string().contains("refusing to downgrade generation"),
+            "{err:#}"
+        );
+        assert_eq!(tenant.get_gc_horizon(), 4321);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_initdb_stderr_is_capped() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let harness = TenantHarness::create("test_initdb_stderr_is_capped")?;
+        let pg_distrib_dir = harness.conf.workdir.join("pg_distrib");
+        let conf: &'static PageServerConf = Box::leak(Box::new(PageServerConf {
+            pg_distrib_dir: pg_distrib_dir.clone(),
+            initdb_stderr_max_bytes: 1024,
+            ..harness.conf.clone()
+        }));
+
+        // An initdb stub that spews 512KiB of stderr before failing. The environment is
+        // cleared when running initdb, so stick to shell builtins.
+        let initdb_bin_path = conf.pg_bin_dir(DEFAULT_PG_VERSION)?.join("initdb");
+        fs::create_dir_all(initdb_bin_path.parent().unwrap())?;
+        fs::write(
+            &initdb_bin_path,
+            r#"#!/bin/sh
+s=xxxxxxxx
+for _ in 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16; do s="$s$s"; done
+printf '%s' "$s" >&2
+echo "the end" >&2
+exit 3
+"#,
+        )?;
+        fs::set_permissions(&initdb_bin_path, fs::Permissions::from_mode(0o755))?;
+
+        let target_dir = harness.conf.workdir.join("initdb_target");
+        let res = run_initdb(
+            conf,
+            &target_dir,
+            DEFAULT_PG_VERSION,
+            &CancellationToken::new(),
+        )
+        .await;
+        match res {
+            Err(InitdbError::Failed(status, stderr)) => {
+                assert_eq!(status.code(), Some(3));
+                assert_eq!(stderr.len(), 1024);
+                assert!(stderr.ends_with(b"the end\n"), "{stderr:?}");
+            }
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(()) => panic!("initdb stub should have failed"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_temp_dirs_are_bounded() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut harness = TenantHarness::create("test_bootstrap_temp_dirs_are_bounded")?;
+        let pg_distrib_dir = harness.conf.workdir.join("pg_distrib");
+        harness.conf = Box::leak(Box::new(PageServerConf {
+            pg_distrib_dir,
+            ..harness.conf.clone()
+        }));
+        let conf = harness.conf;
+        let (tenant, ctx) = harness.load().await;
+
+        // An initdb stub that creates its target directory and records how many bootstrap
+        // directories exist at that point. It produces no control file, so every bootstrap
+        // fails after uploading the datadir. The environment is cleared when running initdb,
+        // so use absolute paths for anything that is not a shell builtin.
+        let counts_path = conf.workdir.join("temp_dir_counts");
+        let initdb_bin_path = conf.pg_bin_dir(DEFAULT_PG_VERSION)?.join("initdb");
+        fs::create_dir_all(initdb_bin_path.parent().unwrap())?;
+        fs::write(
+            &initdb_bin_path,
+            format!(
+                r#"#!/bin/sh
+dir="$2"
+/bin/mkdir -p "$dir"
+set -- "${{dir%/*}}"/basebackup-*.{TEMP_FILE_SUFFIX}
+echo $# >> "{counts_path}"
+/bin/sleep 0.05
+"#
+            ),
+        )?;
+        fs::set_permissions(&initdb_bin_path, fs::Permissions::from_mode(0o755))?;
+
+        let bootstraps = 3 * INIT_DB_CONCURRENCY;
+        let results = futures::future::join_all((0..bootstraps).map(|_| {
+            tenant.bootstrap_timeline_test(TimelineId::generate(), DEFAULT_PG_VERSION, None, &ctx)
+        }))
+        .await;
+        assert!(results.iter().all(|res| res.is_err()));
+
+        let counts = fs::read_to_string(&counts_path)?
+            .lines()
+            .map(|line| line.parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(counts.len(), bootstraps);
+        assert!(
+            counts.iter().all(|&count| count <= INIT_DB_CONCURRENCY),
+            "{counts:?}"
+        );
+
+        // All the temporary directories were cleaned up
+        let leftovers = fs::read_dir(conf.timelines_path(&tenant.tenant_shard_id))?
+            .filter(|entry| {
+                entry.as_ref().is_ok_and(|entry| {
+                    entry
+                        .file_name()
+                        .to_string_lossy()
+                        .starts_with("basebackup-")
+                })
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gather_size_inputs_progress() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_gather_size_inputs_progress")?
+            .load()
+            .await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+        tenant
+            .branch_timeline_test(&tline, NEW_TIMELINE_ID, Some(Lsn(0x30)), &ctx)
+            .await?;
+
+        let cancel = CancellationToken::new();
+        // the second round hits the logical size cache
+        for _ in 0..2 {
+            let progress = Arc::new(AtomicU64::new(0));
+            let done = AtomicBool::new(false);
+            let calculation = async {
+                let res = tenant
+                    .gather_size_inputs(
+                        None,
+                        Some(Arc::clone(&progress)),
+                        LogicalSizeCalculationCause::TenantSizeHandler,
+                        &cancel,
+                        &ctx,
+                    )
+                    .await;
+                done.store(true, Ordering::Relaxed);
+                res
+            };
+            let sampling = async {
+                let mut seen = Vec::new();
+                loop {
+                    let finished = done.load(Ordering::Relaxed);
+                    seen.push(progress.load(Ordering::Relaxed));
+                    if finished {
+                        break seen;
+                    }
+                    tokio::task::yield_now().await;
+                }
+            };
+            let (res, seen) = tokio::join!(calculation, sampling);
+            res?;
+
+            assert!(seen.windows(2).all(|w| w[0] <= w[1]), "{seen:?}");
+            assert_eq!(seen.last(), Some(&2));
+        }
+
+        // Published progress is reported until it is retracted, but not replaced by an older one
+        assert_eq!(tenant.size_calculation_progress(), None);
+        let older = Arc::new(AtomicU64::new(1));
+        let newer = Arc::new(AtomicU64::new(2));
+        tenant.publish_size_calculation_progress(&older);
+        assert_eq!(tenant.size_calculation_progress(), Some(1));
+        tenant.publish_size_calculation_progress(&newer);
+        tenant.retract_size_calculation_progress(&older);
+        assert_eq!(tenant.size_calculation_progress(), Some(2));
+        tenant.retract_size_calculation_progress(&newer);
+        assert_eq!(tenant.size_calculation_progress(), None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gc_unknown_target_timeline() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_gc_unknown_target_timeline")?
+            .load()
+            .await;
+        let _tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+
+        let cancel = CancellationToken::new();
+        let err = tenant
+            .gc_iteration(Some(NEW_TIMELINE_ID), 0x10, Duration::ZERO, &cancel, &ctx)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, GcError::TargetTimelineNotFound(id) if id == NEW_TIMELINE_ID),
+            "{err:?}"
+        );
+
+        let err = tenant
+            .gc_iteration_dry_run(Some(NEW_TIMELINE_ID), 0x10, Duration::ZERO, &cancel, &ctx)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, GcError::TargetTimelineNotFound(id) if id == NEW_TIMELINE_ID),
+            "{err:?}"
+        );
+
+        // An existing target is still collected
+        tenant
+            .gc_iteration(Some(TIMELINE_ID), 0x10, Duration::ZERO, &cancel, &ctx)
+            .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_estimate_gc_reclaim() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_estimate_gc_reclaim")?
+            .load()
+            .await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+
+        let mut tenant_conf = tenant.tenant_specific_overrides();
+        tenant_conf.gc_horizon = Some(0x10);
+        tenant_conf.pitr_interval = Some(Duration::ZERO);
+        tenant.set_new_tenant_config(tenant_conf)?;
+
+        async fn layer_sizes(tline: &Timeline) -> HashMap<String, u64> {
+            tline
+                .layers
+                .read()
+                .await
+                .layer_map()
+                .iter_historic_layers()
+                .map(|l| (l.filename().file_name(), l.file_size))
+                .collect()
+        }
+
+        let cutoff_before = *tline.get_latest_gc_cutoff_lsn();
+        let sizes_before = layer_sizes(&tline).await;
+
+        let estimate = tenant
+            .estimate_gc_reclaim(&CancellationToken::new(), &ctx)
+            .await?;
+        let estimated = *estimate
+            .get(&TIMELINE_ID)
+            .expect("timeline is old enough for GC");
+
+        // Estimating doesn't remove anything
+        assert_eq!(*tline.get_latest_gc_cutoff_lsn(), cutoff_before);
+        assert_eq!(layer_sizes(&tline).await, sizes_before);
+
+        // The estimate matches what a real GC with the same settings removes
+        tenant
+            .gc_iteration(
+                Some(TIMELINE_ID),
+                0x10,
+                Duration::ZERO,
+                &CancellationToken::new(),
+                &ctx,
+            )
+            .await?;
+        let sizes_after = layer_sizes(&tline).await;
+        let reclaimed: u64 = sizes_before
+            .iter()
+            .filter(|(name, _)| !sizes_after.contains_key(*name))
+            .map(|(_, size)| size)
+            .sum();
+        assert_eq!(estimated, reclaimed);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_remote_size_by_timeline() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_remote_size_by_timeline")?
+            .load()
+            .await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+        let sibling = tenant
+            .create_test_timeline(NEW_TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        for timeline in [&tline, &sibling] {
+            timeline
+                .remote_client
+                .as_ref()
+                .unwrap()
+                .wait_completion()
+                .await?;
+        }
+
+        let sizes = tenant.remote_size_by_timeline();
+        assert_eq!(sizes.len(), 2);
+        assert!(sizes[&TIMELINE_ID] > 0);
+        assert_eq!(sizes.values().sum::<u64>(), tenant.remote_size());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_size_ratios() -> anyhow::Result<()> {
+        let (tenant, ctx) = TenantHarness::create("test_size_ratios")?.load().await;
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+        tline.writer().await.update_current_logical_size(8192);
+        // no logical size: left out
+        let sibling = tenant
+            .create_test_timeline(NEW_TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await?;
+        for timeline in [&tline, &sibling] {
+            timeline
+                .remote_client
+                .as_ref()
+                .unwrap()
+                .wait_completion()
+                .await?;
+        }
+
+        let ratios = tenant.size_ratios(&ctx);
+        assert_eq!(ratios.len(), 1, "{ratios:?}");
+        let expected = tenant.remote_size_by_timeline()[&TIMELINE_ID] as f64 / 8192.0;
+        assert_eq!(ratios[&TIMELINE_ID], expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_inconsistent_index_metadata_breaks_only_that_timeline() -> anyhow::Result<()> {
+        let harness =
+            TenantHarness::create("test_inconsistent_index_metadata_breaks_only_that_timeline")?;
+        let bad_version_id = TIMELINE_ID;
+        let bad_version_child_id = NEW_TIMELINE_ID;
+        let bad_ancestor_lsn_id = TimelineId::generate();
+        let healthy_id = TimelineId::generate();
+        {
+            let (tenant, ctx) = harness.load().await;
+            let tline = tenant
+                .create_test_timeline(bad_version_id, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+                .await?;
+            make_some_layers(tline.as_ref(), Lsn(0x20), &ctx).await?;
+            tenant
+                .branch_timeline_test(&tline, bad_version_child_id, Some(Lsn(0x30)), &ctx)
+                .await?;
+            for timeline_id in [bad_ancestor_lsn_id, healthy_id] {
+                tenant
+                    .create_test_timeline(timeline_id, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+                    .await?;
+            }
+            tenant
+                .shutdown(Default::default(), true)
+                .instrument(harness.span())
+                .await
+                .ok()
+                .unwrap();
+        }
+
+        let corrupt_index = |timeline_id: TimelineId,
+                             corrupt: &dyn Fn(&TimelineMetadata) -> TimelineMetadata|
+         -> anyhow::Result<()> {
+            let index_path = harness.remote_fs_dir.join(
+                remote_timeline_client::remote_index_path(
+                    &harness.tenant_shard_id,
+                    &timeline_id,
+                    harness.generation,
+                )
+                .get_path(),
+            );
+            let mut index_part = IndexPart::from_s3_bytes(&std::fs::read(&index_path)?)?;
+            index_part.metadata = corrupt(&index_part.metadata);
+            std::fs::write(&index_path, index_part.to_s3_bytes()?)?;
+            Ok(())
+        };
+        corrupt_index(bad_version_id, &|m| {
+            TimelineMetadata::new(
+                m.disk_consistent_lsn(),
+                m.prev_record_lsn(),
+                m.ancestor_timeline(),
+                m.ancestor_lsn(),
+                m.latest_gc_cutoff_lsn(),
+                m.initdb_lsn(),
+                1,
+            )
+        })?;
+        corrupt_index(bad_ancestor_lsn_id, &|m| {
+            TimelineMetadata::new(
+                m.disk_consistent_lsn(),
+                m.prev_record_lsn(),
+                m.ancestor_timeline(),
+                Lsn(0x10),
+                m.latest_gc_cutoff_lsn(),
+                m.initdb_lsn(),
+                m.pg_version(),
+            )
+        })?;
+
+        let (tenant, _ctx) = harness.load().await;
+        assert_eq!(tenant.current_state(), TenantState::Active);
+        for timeline_id in [bad_version_id, bad_version_child_id, bad_ancestor_lsn_id] {
+            let timeline = tenant
+                .get_timeline(timeline_id, false)
+                .expect("broken timeline should still be present");
+            assert!(
+                matches!(timeline.current_state(), TimelineState::Broken { .. }),
+                "timeline {timeline_id} should be broken, got {:?}",
+                timeline.current_state()
+            );
+        }
+        tenant
+            .get_timeline(healthy_id, true)
+            .expect("healthy timeline should load");
+
+        Ok(())
+    }
 }
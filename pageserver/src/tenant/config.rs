@@ -15,9 +15,11 @@ use pageserver_api::shard::{ShardCount, ShardIdentity, ShardNumber, ShardStripeS
 use serde::de::IntoDeserializer;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
 use std::num::NonZeroU64;
 use std::time::Duration;
 use utils::generation::Generation;
+use utils::id::TimelineId;
 
 pub mod defaults {
     // FIXME: This current value is very low. I would imagine something like 1 GB or 10 GB
@@ -41,6 +43,7 @@ pub mod defaults {
     // doesn't hold a layer map write lock for non-trivial operations.
     // Relevant: https://github.com/neondatabase/neon/issues/3394
     pub const DEFAULT_GC_PERIOD: &str = "1 hr";
+    pub const DEFAULT_GC_PARALLELISM: usize = 1;
     pub const DEFAULT_IMAGE_CREATION_THRESHOLD: usize = 3;
     pub const DEFAULT_PITR_INTERVAL: &str = "7 days";
     pub const DEFAULT_WALRECEIVER_CONNECT_TIMEOUT: &str = "10 seconds";
@@ -70,6 +73,15 @@ pub(crate) enum AttachmentMode {
 pub(crate) struct AttachedLocationConfig {
     pub(crate) generation: Generation,
     pub(crate) attach_mode: AttachmentMode,
+    /// Serve reads only: don't ingest WAL, run GC or compaction, or upload anything.  Meant for
+    /// forensic analysis of a tenant's data.
+    ///
+    /// This is stricter than [`AttachmentMode::Stale`], which only hints that uploads and
+    /// deletions should be avoided and still ingests WAL.  A read-only attachment may be in
+    /// any attachment mode: its generation is still used to find the right remote index, but
+    /// the mode's hints are overridden to never upload or delete.
+    #[serde(default)]
+    pub(crate) read_only: bool,
     // TODO: add a flag to override AttachmentMode's policies under
     // disk pressure (i.e. unblock uploads under disk pressure in Stale
     // state, unblock deletions after timeout in Multi state)
@@ -114,7 +126,11 @@ impl std::fmt::Debug for LocationConf {
                     f,
                     "Attached {:?}, gen={:?}",
                     conf.attach_mode, conf.generation
-                )
+                )?;
+                if conf.read_only {
+                    write!(f, ", read-only")?;
+                }
+                Ok(())
             }
             LocationMode::Secondary(conf) => {
                 write!(f, "Secondary, warm={}", conf.warm)
@@ -128,6 +144,10 @@ impl AttachedLocationConfig {
     /// to delete layers.  This is only advisory, not required for data safety.
     /// See [`AttachmentMode`] for more context.
     pub(crate) fn may_delete_layers_hint(&self) -> bool {
+        if self.read_only {
+            return false;
+        }
+
         // TODO: add an override for disk pressure in AttachedLocationConfig,
         // and respect it here.
         match &self.attach_mode {
@@ -149,6 +169,10 @@ impl AttachedLocationConfig {
     /// This is only advisory, not required for data safety.
     /// See [`AttachmentMode`] for more context.
     pub(crate) fn may_upload_layers_hint(&self) -> bool {
+        if self.read_only {
+            return false;
+        }
+
         // TODO: add an override for disk pressure in AttachedLocationConfig,
         // and respect it here.
         match &self.attach_mode {
@@ -177,6 +201,7 @@ impl LocationConf {
             mode: LocationMode::Attached(AttachedLocationConfig {
                 generation,
                 attach_mode: AttachmentMode::Single,
+                read_only: false,
             }),
             shard: ShardIdentity::from_params(ShardNumber(0), shard_params),
             tenant_conf,
@@ -196,6 +221,7 @@ impl LocationConf {
                 self.mode = LocationMode::Attached(AttachedLocationConfig {
                     generation,
                     attach_mode: AttachmentMode::Single,
+                    read_only: false,
                 })
             }
         }
@@ -203,6 +229,9 @@ impl LocationConf {
 
     pub(crate) fn try_from(conf: &'_ models::LocationConfig) -> anyhow::Result<Self> {
         let tenant_conf = TenantConfOpt::try_from(&conf.tenant_conf)?;
+        tenant_conf
+            .validate()
+            .map_err(|e| anyhow::anyhow!("invalid tenant config: {e}"))?;
 
         fn get_generation(conf: &'_ models::LocationConfig) -> Result<Generation, anyhow::Error> {
             conf.generation
@@ -215,18 +244,21 @@ impl LocationConf {
                 LocationMode::Attached(AttachedLocationConfig {
                     generation: get_generation(conf)?,
                     attach_mode: AttachmentMode::Multi,
+                    read_only: conf.read_only,
                 })
             }
             models::LocationConfigMode::AttachedSingle => {
                 LocationMode::Attached(AttachedLocationConfig {
                     generation: get_generation(conf)?,
                     attach_mode: AttachmentMode::Single,
+                    read_only: conf.read_only,
                 })
             }
             models::LocationConfigMode::AttachedStale => {
                 LocationMode::Attached(AttachedLocationConfig {
                     generation: get_generation(conf)?,
                     attach_mode: AttachmentMode::Stale,
+                    read_only: conf.read_only,
                 })
             }
             models::LocationConfigMode::Secondary => {
@@ -273,6 +305,7 @@ impl Default for LocationConf {
             mode: LocationMode::Attached(AttachedLocationConfig {
                 generation: Generation::none(),
                 attach_mode: AttachmentMode::Single,
+                read_only: false,
             }),
             tenant_conf: TenantConfOpt::default(),
             shard: ShardIdentity::unsharded(),
@@ -280,6 +313,77 @@ impl Default for LocationConf {
     }
 }
 
+/// A [`LocationConf`] loaded from disk, together with the keys in the file that this version
+/// doesn't recognize.  Those were most likely written by a newer version, so rather than dropping
+/// them, we write them back out on the next config write: that way they survive a rollback and
+/// are still there when the newer version runs again.
+pub(crate) struct LenientLocationConf {
+    pub(crate) conf: LocationConf,
+    /// Unrecognized items, keyed by their dotted path in the document, e.g. `tenant_conf.foo`
+    pub(crate) unknown: BTreeMap<String, toml_edit::Item>,
+}
+
+impl LenientLocationConf {
+    pub(crate) fn from_document(document: toml_edit::Document) -> anyhow::Result<Self> {
+        let conf = toml_edit::de::from_document::<LocationConf>(document.clone())?;
+
+        // Whatever doesn't survive a round trip through LocationConf is unknown to us
+        let known = toml_edit::ser::to_string_pretty(&conf)?.parse::<toml_edit::Document>()?;
+        let mut unknown = BTreeMap::new();
+        collect_unknown("", document.as_table(), known.as_table(), &mut unknown);
+
+        Ok(Self { conf, unknown })
+    }
+
+    /// Add the unknown items to `document`, unless it already has a value for them.  Items whose
+    /// parent table is no longer present are dropped.
+    pub(crate) fn preserve_unknown(&self, document: &mut toml_edit::Document) {
+        'items: for (path, item) in &self.unknown {
+            let mut segments = path.split('.').collect::<Vec<_>>();
+            let Some(key) = segments.pop() else {
+                continue;
+            };
+            let mut table: &mut dyn toml_edit::TableLike = document.as_table_mut();
+            for segment in segments {
+                match table.get_mut(segment).and_then(|i| i.as_table_like_mut()) {
+                    Some(t) => table = t,
+                    None => continue 'items,
+                }
+            }
+            if !table.contains_key(key) {
+                table.insert(key, item.clone());
+            }
+        }
+    }
+}
+
+fn collect_unknown(
+    prefix: &str,
+    table: &dyn toml_edit::TableLike,
+    known: &dyn toml_edit::TableLike,
+    unknown: &mut BTreeMap<String, toml_edit::Item>,
+) {
+    for (key, item) in table.iter() {
+        let path = if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match known.get(key) {
+            None => {
+                unknown.insert(path, item.clone());
+            }
+            Some(known_item) => {
+                if let (Some(table), Some(known)) =
+                    (item.as_table_like(), known_item.as_table_like())
+                {
+                    collect_unknown(&path, table, known, unknown);
+                }
+            }
+        }
+    }
+}
+
 /// A tenant's calcuated configuration, which is the result of merging a
 /// tenant's TenantConfOpt with the global TenantConf from PageServerConf.
 ///
@@ -296,6 +400,10 @@ pub struct TenantConf {
     // eventually upload WAL after activity is stopped.
     #[serde(with = "humantime_serde")]
     pub checkpoint_timeout: Duration,
+    /// If set, the in-memory layer is also flushed once it holds this many bytes, even if
+    /// `checkpoint_distance` has not been reached yet. This caps the memory used by the
+    /// in-memory layer when ingesting huge records.
+    pub max_inmem_layer_bytes: Option<u64>,
     // Target file size, when creating image and delta layers.
     // This parameter determines L1 layer file size.
     pub compaction_target_size: u64,
@@ -336,10 +444,20 @@ pub struct TenantConf {
     pub trace_read_requests: bool,
     pub eviction_policy: EvictionPolicy,
     pub min_resident_size_override: Option<u64>,
+    /// If set, the eviction task evicts the least recently accessed layers of the tenant
+    /// whenever its resident layers exceed this many bytes, until they are back under 90%
+    /// of it. Unlike `eviction_policy`, this is checked independently of the policy's period.
+    pub local_disk_high_watermark_bytes: Option<u64>,
     // See the corresponding metric's help string.
     #[serde(with = "humantime_serde")]
     pub evictions_low_residence_duration_metric_threshold: Duration,
     pub gc_feedback: bool,
+    /// How many timelines of a tenant may be garbage collected concurrently within
+    /// a single GC iteration.
+    pub gc_parallelism: NonZeroUsize,
+    /// If true, timelines whose ancestor is missing are marked Broken when the tenant is
+    /// attached, instead of failing the whole tenant.
+    pub skip_orphan_timelines: bool,
 
     /// If non-zero, the period between uploads of a heatmap from attached tenants.  This
     /// may be disabled if a Tenant will not have secondary locations: only secondary
@@ -350,6 +468,10 @@ pub struct TenantConf {
     pub lazy_slru_download: bool,
 
     pub timeline_get_throttle: pageserver_api::models::ThrottleConfig,
+
+    /// If false, the background consumption metrics task skips calculating this tenant's
+    /// synthetic size, and the last cached value (if any) is reported unchanged.
+    pub synthetic_size_calculation_enabled: bool,
 }
 
 /// Same as TenantConf, but this struct preserves the information about
@@ -365,6 +487,10 @@ pub struct TenantConfOpt {
     #[serde(default)]
     pub checkpoint_timeout: Option<Duration>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub max_inmem_layer_bytes: Option<u64>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     pub compaction_target_size: Option<u64>,
@@ -422,6 +548,10 @@ pub struct TenantConfOpt {
     #[serde(default)]
     pub min_resident_size_override: Option<u64>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub local_disk_high_watermark_bytes: Option<u64>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(with = "humantime_serde")]
     #[serde(default)]
@@ -431,6 +561,14 @@ pub struct TenantConfOpt {
     #[serde(default)]
     pub gc_feedback: Option<bool>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub gc_parallelism: Option<NonZeroUsize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub skip_orphan_timelines: Option<bool>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(with = "humantime_serde")]
     #[serde(default)]
@@ -442,6 +580,16 @@ pub struct TenantConfOpt {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timeline_get_throttle: Option<pageserver_api::models::ThrottleConfig>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub synthetic_size_calculation_enabled: Option<bool>,
+
+    /// Per-timeline overrides of `gc_horizon`. Unlike the other settings, these have no
+    /// counterpart in [`TenantConf`]: timelines without an entry use the tenant's `gc_horizon`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub timeline_gc_horizon_overrides: Option<HashMap<TimelineId, u64>>,
 }
 
 impl TenantConfOpt {
@@ -453,6 +601,9 @@ impl TenantConfOpt {
             checkpoint_timeout: self
                 .checkpoint_timeout
                 .unwrap_or(global_conf.checkpoint_timeout),
+            max_inmem_layer_bytes: self
+                .max_inmem_layer_bytes
+                .or(global_conf.max_inmem_layer_bytes),
             compaction_target_size: self
                 .compaction_target_size
                 .unwrap_or(global_conf.compaction_target_size),
@@ -482,10 +633,17 @@ impl TenantConfOpt {
             min_resident_size_override: self
                 .min_resident_size_override
                 .or(global_conf.min_resident_size_override),
+            local_disk_high_watermark_bytes: self
+                .local_disk_high_watermark_bytes
+                .or(global_conf.local_disk_high_watermark_bytes),
             evictions_low_residence_duration_metric_threshold: self
                 .evictions_low_residence_duration_metric_threshold
                 .unwrap_or(global_conf.evictions_low_residence_duration_metric_threshold),
             gc_feedback: self.gc_feedback.unwrap_or(global_conf.gc_feedback),
+            gc_parallelism: self.gc_parallelism.unwrap_or(global_conf.gc_parallelism),
+            skip_orphan_timelines: self
+                .skip_orphan_timelines
+                .unwrap_or(global_conf.skip_orphan_timelines),
             heatmap_period: self.heatmap_period.unwrap_or(global_conf.heatmap_period),
             lazy_slru_download: self
                 .lazy_slru_download
@@ -494,7 +652,24 @@ impl TenantConfOpt {
                 .timeline_get_throttle
                 .clone()
                 .unwrap_or(global_conf.timeline_get_throttle),
+            synthetic_size_calculation_enabled: self
+                .synthetic_size_calculation_enabled
+                .unwrap_or(global_conf.synthetic_size_calculation_enabled),
+        }
+    }
+
+    /// Rejects overrides which would break compaction or GC once applied.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.checkpoint_distance == Some(0) {
+            return Err("checkpoint_distance must be greater than 0".to_string());
+        }
+        if self.compaction_threshold == Some(0) {
+            return Err("compaction_threshold must be at least 1".to_string());
         }
+        if self.image_creation_threshold == Some(0) {
+            return Err("image_creation_threshold must be at least 1".to_string());
+        }
+        Ok(())
     }
 }
 
@@ -505,6 +680,7 @@ impl Default for TenantConf {
             checkpoint_distance: DEFAULT_CHECKPOINT_DISTANCE,
             checkpoint_timeout: humantime::parse_duration(DEFAULT_CHECKPOINT_TIMEOUT)
                 .expect("cannot parse default checkpoint timeout"),
+            max_inmem_layer_bytes: None,
             compaction_target_size: DEFAULT_COMPACTION_TARGET_SIZE,
             compaction_period: humantime::parse_duration(DEFAULT_COMPACTION_PERIOD)
                 .expect("cannot parse default compaction period"),
@@ -526,14 +702,19 @@ impl Default for TenantConf {
             trace_read_requests: false,
             eviction_policy: EvictionPolicy::NoEviction,
             min_resident_size_override: None,
+            local_disk_high_watermark_bytes: None,
             evictions_low_residence_duration_metric_threshold: humantime::parse_duration(
                 DEFAULT_EVICTIONS_LOW_RESIDENCE_DURATION_METRIC_THRESHOLD,
             )
             .expect("cannot parse default evictions_low_residence_duration_metric_threshold"),
             gc_feedback: false,
+            gc_parallelism: NonZeroUsize::new(DEFAULT_GC_PARALLELISM)
+                .expect("cannot parse default gc parallelism"),
+            skip_orphan_timelines: false,
             heatmap_period: Duration::ZERO,
             lazy_slru_download: false,
             timeline_get_throttle: crate::tenant::throttle::Config::disabled(),
+            synthetic_size_calculation_enabled: true,
         }
     }
 }
@@ -587,6 +768,7 @@ impl From<TenantConfOpt> for models::TenantConfig {
         Self {
             checkpoint_distance: value.checkpoint_distance,
             checkpoint_timeout: value.checkpoint_timeout.map(humantime),
+            max_inmem_layer_bytes: value.max_inmem_layer_bytes,
             compaction_target_size: value.compaction_target_size,
             compaction_period: value.compaction_period.map(humantime),
             compaction_threshold: value.compaction_threshold,
@@ -600,13 +782,18 @@ impl From<TenantConfOpt> for models::TenantConfig {
             trace_read_requests: value.trace_read_requests,
             eviction_policy: value.eviction_policy,
             min_resident_size_override: value.min_resident_size_override,
+            local_disk_high_watermark_bytes: value.local_disk_high_watermark_bytes,
             evictions_low_residence_duration_metric_threshold: value
                 .evictions_low_residence_duration_metric_threshold
                 .map(humantime),
             gc_feedback: value.gc_feedback,
+            gc_parallelism: value.gc_parallelism,
+            skip_orphan_timelines: value.skip_orphan_timelines,
             heatmap_period: value.heatmap_period.map(humantime),
             lazy_slru_download: value.lazy_slru_download,
             timeline_get_throttle: value.timeline_get_throttle.map(ThrottleConfig::from),
+            synthetic_size_calculation_enabled: value.synthetic_size_calculation_enabled,
+            timeline_gc_horizon_overrides: value.timeline_gc_horizon_overrides,
         }
     }
 }
@@ -665,4 +852,63 @@ mod tests {
             Some(Duration::from_secs(5))
         );
     }
+
+    #[test]
+    fn read_only_location_conf() {
+        let location_config: models::LocationConfig = serde_json::from_str(
+            r#"{"mode": "AttachedSingle", "generation": 1, "read_only": true, "tenant_conf": {}}"#,
+        )
+        .unwrap();
+        let location_conf = LocationConf::try_from(&location_config).unwrap();
+        let LocationMode::Attached(attached_conf) = &location_conf.mode else {
+            panic!("expected an attached location, got {location_conf:?}");
+        };
+        assert!(attached_conf.read_only);
+        assert!(!attached_conf.may_delete_layers_hint());
+        assert!(!attached_conf.may_upload_layers_hint());
+
+        // Location configs written before read-only mode existed are not read-only
+        let location_config: models::LocationConfig = serde_json::from_str(
+            r#"{"mode": "AttachedSingle", "generation": 1, "tenant_conf": {}}"#,
+        )
+        .unwrap();
+        assert!(!location_config.read_only);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(TenantConfOpt::default().validate(), Ok(()));
+
+        let valid = TenantConfOpt {
+            checkpoint_distance: Some(1),
+            compaction_threshold: Some(1),
+            image_creation_threshold: Some(1),
+            ..TenantConfOpt::default()
+        };
+        assert_eq!(valid.validate(), Ok(()));
+
+        let invalid = [
+            TenantConfOpt {
+                checkpoint_distance: Some(0),
+                ..TenantConfOpt::default()
+            },
+            TenantConfOpt {
+                compaction_threshold: Some(0),
+                ..TenantConfOpt::default()
+            },
+            TenantConfOpt {
+                image_creation_threshold: Some(0),
+                ..TenantConfOpt::default()
+            },
+        ];
+        for conf in invalid {
+            assert!(conf.validate().is_err(), "{conf:?} should be rejected");
+        }
+
+        let location_config: models::LocationConfig = serde_json::from_str(
+            r#"{"mode": "AttachedSingle", "generation": 1, "tenant_conf": {"compaction_threshold": 0}}"#,
+        )
+        .unwrap();
+        assert!(LocationConf::try_from(&location_config).is_err());
+    }
 }
@@ -22,7 +22,7 @@ use super::{
     remote_timeline_client::{FAILED_REMOTE_OP_RETRIES, FAILED_UPLOAD_WARN_THRESHOLD},
     span,
     timeline::delete::DeleteTimelineFlow,
-    tree_sort_timelines, DeleteTimelineError, Tenant, TenantPreload,
+    DeleteTimelineError, Tenant, TenantPreload,
 };
 
 #[derive(Debug, thiserror::Error)]
@@ -124,20 +124,23 @@ async fn schedule_ordered_timeline_deletions(
 ) -> Result<Vec<(Arc<tokio::sync::Mutex<DeleteTimelineFlow>>, TimelineId)>, DeleteTenantError> {
     // Tenant is stopping at this point. We know it will be deleted.
     // No new timelines should be created.
-    // Tree sort timelines to delete from leafs to the root.
+    // Tree sort timelines to delete from leafs to the root. This includes timelines whose
+    // ancestor was not loaded (see `skip_orphan_timelines`), which are listed last.
     // NOTE: by calling clone we release the mutex which creates a possibility for a race: pending deletion
     // can complete and remove timeline from the map in between our call to clone
     // and `DeleteTimelineFlow::run`, so `run` wont find timeline in `timelines` map.
     // timelines.lock is currently synchronous so we cant hold it across await point.
     // So just ignore NotFound error if we get it from `run`.
     // Beware: in case it becomes async and we try to hold it here, `run` also locks it, which can create a deadlock.
-    let timelines = tenant.timelines.lock().unwrap().clone();
-    let sorted =
-        tree_sort_timelines(timelines, |t| t.get_ancestor_timeline_id()).context("tree sort")?;
+    let sorted = tenant.list_timelines_sorted();
 
     let mut already_running_deletions = vec![];
 
-    for (timeline_id, _) in sorted.into_iter().rev() {
+    for timeline_id in sorted
+        .into_iter()
+        .rev()
+        .map(|timeline| timeline.timeline_id)
+    {
         let span = tracing::info_span!("timeline_delete", %timeline_id);
         let res = DeleteTimelineFlow::run(tenant, timeline_id, true)
             .instrument(span)
@@ -604,7 +607,7 @@ impl DeleteTenantFlow {
                             tracing::warn!("Tenant transitioned to secondary mode while deleting!");
                             break;
                         }
-                        TenantsMapRemoveResult::Occupied(TenantSlot::InProgress(_)) => {
+                        TenantsMapRemoveResult::Occupied(TenantSlot::InProgress(..)) => {
                             unreachable!("TenantsMap::remove handles InProgress separately, should never return it here");
                         }
                         TenantsMapRemoveResult::Vacant => {
@@ -632,3 +635,70 @@ impl DeleteTenantFlow {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use utils::lsn::Lsn;
+
+    use super::*;
+    use crate::context::DownloadBehavior;
+    use crate::tenant::harness::{TenantHarness, TIMELINE_ID};
+    use crate::tenant::remote_timeline_client::remote_timeline_path;
+    use crate::DEFAULT_PG_VERSION;
+
+    #[tokio::test]
+    async fn deletes_orphan_timelines() -> anyhow::Result<()> {
+        let mut harness = TenantHarness::create("deletes_orphan_timelines")?;
+        let orphan_id = TimelineId::generate();
+        let orphan_child_id = TimelineId::generate();
+        {
+            let (tenant, ctx) = harness.load().await;
+            let root = tenant
+                .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+                .await?;
+            let orphan = tenant
+                .branch_timeline_test(&root, orphan_id, Some(Lsn(0x10)), &ctx)
+                .await?;
+            tenant
+                .branch_timeline_test(&orphan, orphan_child_id, Some(Lsn(0x10)), &ctx)
+                .await?;
+            for timeline in tenant.list_timelines() {
+                timeline
+                    .remote_client
+                    .as_ref()
+                    .unwrap()
+                    .wait_completion()
+                    .await?;
+            }
+            tenant
+                .shutdown(Default::default(), true)
+                .instrument(harness.span())
+                .await
+                .ok()
+                .unwrap();
+        }
+
+        // Lose the root timeline, so that its descendants are orphans on the next attach
+        std::fs::remove_dir_all(
+            harness
+                .remote_fs_dir
+                .join(remote_timeline_path(&harness.tenant_shard_id, &TIMELINE_ID).get_path()),
+        )?;
+        harness.tenant_conf.skip_orphan_timelines = true;
+        let ctx = RequestContext::new(TaskKind::UnitTest, DownloadBehavior::Error);
+        let tenant = harness.do_try_load(&ctx).await?;
+        let mut loaded = tenant.list_timeline_ids();
+        loaded.sort();
+        let mut orphans = vec![orphan_id, orphan_child_id];
+        orphans.sort();
+        assert_eq!(loaded, orphans);
+
+        let already_running = schedule_ordered_timeline_deletions(&tenant)
+            .instrument(harness.span())
+            .await?;
+        assert!(already_running.is_empty());
+        assert!(tenant.list_timeline_ids().is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+//! Debug-build checks for the order in which a [`Tenant`](super::Tenant)'s locks are acquired.
+//!
+//! The locks are ranked by [`TenantLock`]: while holding one of them, a thread may only acquire
+//! locks of a higher rank. The `Tenant::lock_*` helpers record the locks held by the current
+//! thread and panic on an out-of-order acquisition, so that code prone to deadlocks fails in
+//! tests rather than hanging in production. In release builds the checks compile to nothing.
+
+use std::ops::{Deref, DerefMut};
+
+/// The locks of a [`Tenant`](super::Tenant), in the order in which they must be acquired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum TenantLock {
+    /// An async mutex which is held across await points, possibly moving between threads, so it
+    /// is only checked on acquisition and not tracked while held. Being the first in the order,
+    /// that is enough: no other lock may be held when acquiring it.
+    GcCs,
+    Timelines,
+    TimelinesCreating,
+    TenantConf,
+}
+
+#[cfg(debug_assertions)]
+thread_local! {
+    /// Locks currently held by this thread, in acquisition order.
+    static HELD: std::cell::RefCell<Vec<TenantLock>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// Panics if `lock` may not be acquired while holding the locks this thread holds.
+pub(crate) fn check(lock: TenantLock) {
+    #[cfg(debug_assertions)]
+    {
+        let held = HELD.with(|held| held.borrow().clone());
+        if let Some(violated) = held.iter().find(|held| **held > lock) {
+            panic!(
+                "lock order violation: acquiring {lock:?} while holding {violated:?} (held: {held:?})"
+            );
+        }
+    }
+    #[cfg(not(debug_assertions))]
+    let _ = lock;
+}
+
+/// Records a lock as held by this thread until dropped.
+pub(crate) struct Held {
+    #[cfg(debug_assertions)]
+    lock: TenantLock,
+}
+
+fn acquire(lock: TenantLock) -> Held {
+    check(lock);
+    #[cfg(debug_assertions)]
+    HELD.with(|held| held.borrow_mut().push(lock));
+    Held {
+        #[cfg(debug_assertions)]
+        lock,
+    }
+}
+
+#[cfg(debug_assertions)]
+impl Drop for Held {
+    fn drop(&mut self) {
+        // Guards need not be released in the reverse order of acquisition.
+        let _ = HELD.try_with(|held| {
+            let mut held = held.borrow_mut();
+            if let Some(pos) = held.iter().rposition(|held| *held == self.lock) {
+                held.remove(pos);
+            }
+        });
+    }
+}
+
+/// A lock guard which counts as held for the lock order checks until dropped.
+pub(crate) struct OrderedGuard<G> {
+    // Declared before `_held`, so that the lock is released before it stops counting as held.
+    guard: G,
+    _held: Held,
+}
+
+/// Checks that `lock` may be acquired, then acquires it with `f`.
+pub(crate) fn ordered<G>(lock: TenantLock, f: impl FnOnce() -> G) -> OrderedGuard<G> {
+    let _held = acquire(lock);
+    OrderedGuard { guard: f(), _held }
+}
+
+impl<G: Deref> Deref for OrderedGuard<G> {
+    type Target = G::Target;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<G: DerefMut> DerefMut for OrderedGuard<G> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_order_acquisitions_pass() {
+        let _timelines = acquire(TenantLock::Timelines);
+        let creating = acquire(TenantLock::TimelinesCreating);
+        let _conf = acquire(TenantLock::TenantConf);
+        // Re-acquiring a lock of the same rank, e.g. another read of the config, is allowed.
+        let _conf2 = acquire(TenantLock::TenantConf);
+
+        // Releasing out of order forgets the right lock.
+        drop(creating);
+        assert_eq!(
+            HELD.with(|held| held.borrow().clone()),
+            vec![
+                TenantLock::Timelines,
+                TenantLock::TenantConf,
+                TenantLock::TenantConf
+            ]
+        );
+    }
+
+    #[test]
+    fn released_locks_are_forgotten() {
+        drop(acquire(TenantLock::TenantConf));
+        check(TenantLock::GcCs);
+        let _timelines = acquire(TenantLock::Timelines);
+    }
+
+    #[test]
+    #[should_panic(expected = "lock order violation")]
+    fn out_of_order_acquisition_panics() {
+        let _conf = acquire(TenantLock::TenantConf);
+        let _timelines = acquire(TenantLock::Timelines);
+    }
+
+    #[test]
+    #[should_panic(expected = "lock order violation")]
+    fn gc_cs_must_be_acquired_first() {
+        let _timelines = acquire(TenantLock::Timelines);
+        check(TenantLock::GcCs);
+    }
+}
@@ -219,6 +219,29 @@ impl TimelineMetadata {
         self.body.pg_version
     }
 
+    /// Sanity checks for metadata that came from outside this process, e.g. from a
+    /// remote `IndexPart`: the checksum only protects against torn writes, not against
+    /// a well-formed index carrying nonsensical values.
+    pub fn check_consistency(&self) -> anyhow::Result<()> {
+        #[allow(clippy::manual_range_patterns)]
+        match self.body.pg_version {
+            14 | 15 | 16 => {}
+            v => anyhow::bail!("unsupported pg_version {v}"),
+        }
+
+        match (self.body.ancestor_timeline, self.body.ancestor_lsn) {
+            (Some(ancestor_id), lsn) if !lsn.is_valid() => {
+                anyhow::bail!("ancestor timeline {ancestor_id} is set, but ancestor_lsn is {lsn}")
+            }
+            (None, lsn) if lsn.is_valid() => {
+                anyhow::bail!("ancestor_lsn is {lsn}, but there is no ancestor timeline")
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
     // Checksums make it awkward to build a valid instance by hand.  This helper
     // provides a TimelineMetadata with a valid checksum in its header.
     #[cfg(test)]
@@ -358,6 +381,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_metadata_check_consistency() {
+        let metadata = |ancestor_timeline, ancestor_lsn, pg_version| {
+            TimelineMetadata::new(
+                Lsn(0x200),
+                Some(Lsn(0x100)),
+                ancestor_timeline,
+                ancestor_lsn,
+                Lsn(0),
+                Lsn(0),
+                pg_version,
+            )
+        };
+
+        metadata(None, Lsn(0), crate::DEFAULT_PG_VERSION)
+            .check_consistency()
+            .expect("root timeline metadata is consistent");
+        metadata(Some(TIMELINE_ID), Lsn(0x100), crate::DEFAULT_PG_VERSION)
+            .check_consistency()
+            .expect("branch metadata is consistent");
+
+        let err = metadata(None, Lsn(0), 1).check_consistency().unwrap_err();
+        assert!(
+            err.to_string().contains("unsupported pg_version 1"),
+            "{err}"
+        );
+
+        let err = metadata(Some(TIMELINE_ID), Lsn(0), crate::DEFAULT_PG_VERSION)
+            .check_consistency()
+            .unwrap_err();
+        assert!(err.to_string().contains("ancestor_lsn is 0/0"), "{err}");
+
+        let err = metadata(None, Lsn(0x100), crate::DEFAULT_PG_VERSION)
+            .check_consistency()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("there is no ancestor timeline"),
+            "{err}"
+        );
+    }
+
     #[test]
     fn test_metadata_bincode_serde() {
         let original_metadata = TimelineMetadata::new(
@@ -5,15 +5,16 @@ use camino::{Utf8DirEntry, Utf8Path, Utf8PathBuf};
 use futures::stream::StreamExt;
 use itertools::Itertools;
 use pageserver_api::key::Key;
-use pageserver_api::models::ShardParameters;
+use pageserver_api::models::{ShardParameters, TenantSlotCounts};
 use pageserver_api::shard::{ShardCount, ShardIdentity, ShardNumber, TenantShardId};
 use rand::{distributions::Alphanumeric, Rng};
 use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap};
+use std::num::NonZeroUsize;
 use std::ops::Deref;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::fs;
 use utils::timeout::{timeout_cancellable, TimeoutCancellableError};
 
@@ -23,7 +24,6 @@ use tokio::task::JoinSet;
 use tokio_util::sync::CancellationToken;
 use tracing::*;
 
-use remote_storage::GenericRemoteStorage;
 use utils::{completion, crashsafe};
 
 use crate::config::PageServerConf;
@@ -68,7 +68,10 @@ pub(crate) enum TenantSlot {
     Secondary(Arc<SecondaryTenant>),
     /// In this state, other administrative operations acting on the TenantId should
     /// block, or return a retry indicator equivalent to HTTP 503.
-    InProgress(utils::completion::Barrier),
+    ///
+    /// The `Instant` records when the slot entered this state, so that tenants stuck
+    /// mid-transition can be spotted.
+    InProgress(utils::completion::Barrier, Instant),
 }
 
 impl std::fmt::Debug for TenantSlot {
@@ -76,7 +79,7 @@ impl std::fmt::Debug for TenantSlot {
         match self {
             Self::Attached(tenant) => write!(f, "Attached({})", tenant.current_state()),
             Self::Secondary(_) => write!(f, "Secondary"),
-            Self::InProgress(_) => write!(f, "InProgress"),
+            Self::InProgress(..) => write!(f, "InProgress"),
         }
     }
 }
@@ -87,7 +90,7 @@ impl TenantSlot {
         match self {
             Self::Attached(t) => Some(t),
             Self::Secondary(_) => None,
-            Self::InProgress(_) => None,
+            Self::InProgress(..) => None,
         }
     }
 }
@@ -121,6 +124,10 @@ pub(crate) enum ShardSelector {
     First,
     /// Pick the shard that holds this key
     Page(Key),
+    /// Pick the highest-numbered shard we find for the TenantId, e.g. for diagnostic tools
+    /// checking that all of a tenant's shards are present.
+    #[allow(dead_code)]
+    Last,
 }
 
 impl TenantsMap {
@@ -144,9 +151,19 @@ impl TenantsMap {
         selector: ShardSelector,
     ) -> Option<TenantShardId> {
         let mut want_shard = None;
+        let mut last_shard: Option<TenantShardId> = None;
         match self {
             TenantsMap::Initializing => None,
             TenantsMap::Open(m) | TenantsMap::ShuttingDown(m) => {
+                // Fast path for the common case of an unsharded tenant: its only slot
+                // satisfies any selector, so there is no need to hash the key.
+                let mut slots = m.range(TenantShardId::tenant_range(*tenant_id));
+                if let Some((tenant_shard_id, TenantSlot::Attached(_))) = slots.next() {
+                    if tenant_shard_id.shard_count.is_unsharded() && slots.next().is_none() {
+                        return Some(*tenant_shard_id);
+                    }
+                }
+
                 for slot in m.range(TenantShardId::tenant_range(*tenant_id)) {
                     // Ignore all slots that don't contain an attached tenant
                     let tenant = match &slot.1 {
@@ -171,12 +188,18 @@ impl TenantsMap {
                                 return Some(*slot.0);
                             }
                         }
+                        ShardSelector::Last => {
+                            if last_shard.map_or(true, |l| slot.0.shard_number >= l.shard_number) {
+                                last_shard = Some(*slot.0);
+                            }
+                        }
                         _ => continue,
                     }
                 }
 
-                // Fall through: we didn't find an acceptable shard
-                None
+                // Fall through: we didn't find an acceptable shard, unless we were
+                // looking for the last one.
+                last_shard
             }
         }
     }
@@ -191,7 +214,7 @@ impl TenantsMap {
             TenantsMap::Initializing => TenantsMapRemoveResult::Vacant,
             TenantsMap::Open(m) | TenantsMap::ShuttingDown(m) => match m.entry(tenant_shard_id) {
                 Entry::Occupied(entry) => match entry.get() {
-                    TenantSlot::InProgress(barrier) => {
+                    TenantSlot::InProgress(barrier, _) => {
                         TenantsMapRemoveResult::InProgress(barrier.clone())
                     }
                     _ => TenantsMapRemoveResult::Occupied(entry.remove()),
@@ -207,6 +230,54 @@ impl TenantsMap {
             TenantsMap::Open(m) | TenantsMap::ShuttingDown(m) => m.len(),
         }
     }
+
+    /// Iterate over all slots in the map.  Yields nothing while the map is initializing.
+    fn iter(&self) -> impl Iterator<Item = (&TenantShardId, &TenantSlot)> {
+        let m = match self {
+            TenantsMap::Initializing => None,
+            TenantsMap::Open(m) | TenantsMap::ShuttingDown(m) => Some(m),
+        };
+        m.into_iter().flat_map(|m| m.iter())
+    }
+
+    /// Iterate over tenants in [`TenantSlot::Attached`] slots.  Yields nothing while the map
+    /// is initializing.
+    pub(crate) fn iter_attached(&self) -> impl Iterator<Item = (&TenantShardId, &Arc<Tenant>)> {
+        self.iter()
+            .filter_map(|(id, slot)| slot.get_attached().map(|t| (id, t)))
+    }
+
+    /// Attached tenants whose state is anything but [`TenantState::Active`], e.g. because they
+    /// are still attaching or waiting to be activated.  Empty while the map is initializing.
+    fn not_yet_active(&self) -> Vec<(TenantShardId, TenantState)> {
+        self.iter_attached()
+            .filter_map(|(id, tenant)| match tenant.current_state() {
+                TenantState::Active => None,
+                state => Some((*id, state)),
+            })
+            .collect()
+    }
+
+    /// Iterate over tenants in [`TenantSlot::Secondary`] slots.  Yields nothing while the map
+    /// is initializing.
+    pub(crate) fn iter_secondary(
+        &self,
+    ) -> impl Iterator<Item = (&TenantShardId, &Arc<SecondaryTenant>)> {
+        self.iter().filter_map(|(id, slot)| match slot {
+            TenantSlot::Secondary(s) => Some((id, s)),
+            TenantSlot::Attached(_) | TenantSlot::InProgress(..) => None,
+        })
+    }
+
+    /// Slots in [`TenantSlot::InProgress`], with how long they have been in that state.
+    fn in_progress(&self) -> Vec<(TenantShardId, Duration)> {
+        self.iter()
+            .filter_map(|(id, slot)| match slot {
+                TenantSlot::InProgress(_, since) => Some((*id, since.elapsed())),
+                TenantSlot::Attached(_) | TenantSlot::Secondary(_) => None,
+            })
+            .collect()
+    }
 }
 
 /// This is "safe" in that that it won't leave behind a partially deleted directory
@@ -215,9 +286,50 @@ impl TenantsMap {
 ///
 /// This is pageserver-specific, as it relies on future processes after a crash to check
 /// for TEMP_FILE_SUFFIX when loading things.
-async fn safe_remove_tenant_dir_all(path: impl AsRef<Utf8Path>) -> std::io::Result<()> {
+///
+/// The contents are removed in batches, checking `cancel` in between: if it fires, the
+/// renamed directory is left in place for a later startup to clean up, and `Ok(false)` is
+/// returned.
+async fn safe_remove_tenant_dir_all(
+    path: impl AsRef<Utf8Path>,
+    cancel: &CancellationToken,
+) -> std::io::Result<bool> {
     let tmp_path = safe_rename_tenant_dir(path).await?;
-    fs::remove_dir_all(tmp_path).await
+    let cancel = cancel.clone();
+    tokio::task::spawn_blocking(move || {
+        remove_dir_all_batched(&tmp_path, REMOVE_DIR_BATCH_SIZE, &cancel)
+    })
+    .await
+    .map_err(std::io::Error::other)?
+}
+
+/// Number of directory entries removed by [`remove_dir_all_batched`] between checks for cancellation.
+const REMOVE_DIR_BATCH_SIZE: usize = 1000;
+
+/// Blocking, recursive removal of `path`, checking `cancel` after every `batch_size` removed
+/// entries.  Returns `Ok(false)` if cancelled before `path` was completely removed.
+fn remove_dir_all_batched(
+    path: &Utf8Path,
+    batch_size: usize,
+    cancel: &CancellationToken,
+) -> std::io::Result<bool> {
+    // Visit directories after their contents, so that they are empty by the time we remove them.
+    let mut removed = 0;
+    for entry in walkdir::WalkDir::new(path).contents_first(true) {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            std::fs::remove_dir(entry.path())?;
+        } else {
+            std::fs::remove_file(entry.path())?;
+        }
+
+        removed += 1;
+        if removed % batch_size == 0 && cancel.is_cancelled() {
+            info!("Cancelled removing directory {path} after {removed} entries, leaving the rest for cleanup on next startup");
+            return Ok(false);
+        }
+    }
+    Ok(true)
 }
 
 async fn safe_rename_tenant_dir(path: impl AsRef<Utf8Path>) -> std::io::Result<Utf8PathBuf> {
@@ -242,9 +354,229 @@ async fn safe_rename_tenant_dir(path: impl AsRef<Utf8Path>) -> std::io::Result<U
     Ok(tmp_path)
 }
 
+/// Remove a tenant directory that was already renamed to a temporary path in the background.
+fn spawn_tenant_files_delete(tmp_path: Utf8PathBuf) {
+    // Although we are cleaning up the tenant, this task is not meant to be bound by the lifetime of the tenant in memory.
+    // After a tenant is detached, there are no more task_mgr tasks for that tenant_id.
+    let task_tenant_id = None;
+    task_mgr::spawn(
+        task_mgr::BACKGROUND_RUNTIME.handle(),
+        TaskKind::MgmtRequest,
+        task_tenant_id,
+        None,
+        "tenant_files_delete",
+        false,
+        async move {
+            fs::remove_dir_all(tmp_path.as_path())
+                .await
+                .with_context(|| format!("tenant directory {:?} deletion", tmp_path))
+        },
+    );
+}
+
+/// A tenant directory detached with `preserve_local` is renamed to
+/// `tenants/<tenant_shard_id>___quarantine` instead of being deleted, so that re-attaching the
+/// tenant to this pageserver within [`PageServerConf::tenant_quarantine_ttl`] can reuse its local
+/// layers rather than downloading them all again.
+const TENANT_QUARANTINE_SUFFIX: &str = "___quarantine";
+
+/// File in a quarantined tenant directory that holds the time of quarantining, in seconds since
+/// the UNIX epoch.
+const TENANT_QUARANTINE_MARKER: &str = "___quarantined_at";
+
+fn tenant_quarantine_path(
+    conf: &'static PageServerConf,
+    tenant_shard_id: &TenantShardId,
+) -> Utf8PathBuf {
+    conf.tenants_path()
+        .join(format!("{tenant_shard_id}{TENANT_QUARANTINE_SUFFIX}"))
+}
+
+async fn read_quarantined_at(quarantine_path: &Utf8Path) -> std::io::Result<SystemTime> {
+    let content = fs::read_to_string(quarantine_path.join(TENANT_QUARANTINE_MARKER)).await?;
+    let secs = content
+        .trim()
+        .parse::<u64>()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    Ok(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Move the directory of a detached tenant, already renamed to `tmp_path`, into quarantine.
+/// Only the most recent detach of a tenant is kept: an older quarantined directory is deleted.
+async fn quarantine_tenant_dir(
+    conf: &'static PageServerConf,
+    tenant_shard_id: TenantShardId,
+    tmp_path: &Utf8Path,
+) -> std::io::Result<Utf8PathBuf> {
+    let quarantine_path = tenant_quarantine_path(conf, &tenant_shard_id);
+    match safe_rename_tenant_dir(&quarantine_path).await {
+        Ok(old_quarantine_path) => spawn_tenant_files_delete(old_quarantine_path),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    fs::write(tmp_path.join(TENANT_QUARANTINE_MARKER), now.to_string()).await?;
+    fs::rename(tmp_path, &quarantine_path).await?;
+    fs::File::open(conf.tenants_path())
+        .await?
+        .sync_all()
+        .await?;
+
+    schedule_quarantine_expiry(conf, quarantine_path.clone());
+    Ok(quarantine_path)
+}
+
+/// If `tenant_shard_id` was detached with `preserve_local` less than
+/// [`PageServerConf::tenant_quarantine_ttl`] ago, move its quarantined directory back into place.
+/// Returns whether it did.
+async fn restore_quarantined_tenant_dir(
+    conf: &'static PageServerConf,
+    tenant_shard_id: TenantShardId,
+) -> std::io::Result<bool> {
+    let tenant_path = conf.tenant_path(&tenant_shard_id);
+    let quarantine_path = tenant_quarantine_path(conf, &tenant_shard_id);
+    if tenant_path.exists() {
+        return Ok(false);
+    }
+
+    let quarantined_at = match read_quarantined_at(&quarantine_path).await {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    let age = quarantined_at.elapsed().unwrap_or_default();
+    if age >= conf.tenant_quarantine_ttl {
+        // Its expiry will clean it up
+        return Ok(false);
+    }
+
+    match fs::rename(&quarantine_path, &tenant_path).await {
+        Ok(()) => {}
+        // Expired and removed in the meantime
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    }
+    fs::File::open(conf.tenants_path())
+        .await?
+        .sync_all()
+        .await?;
+    match fs::remove_file(tenant_path.join(TENANT_QUARANTINE_MARKER)).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    info!(
+        "Restored local tenant directory from quarantine, detached {}s ago",
+        age.as_secs()
+    );
+    Ok(true)
+}
+
+/// Delete the quarantined directory at `quarantine_path` if it has expired.  Otherwise, returns
+/// how long until it expires.
+async fn remove_quarantined_tenant_dir_if_expired(
+    conf: &'static PageServerConf,
+    quarantine_path: &Utf8Path,
+    cancel: &CancellationToken,
+) -> std::io::Result<Option<Duration>> {
+    let quarantined_at = match read_quarantined_at(quarantine_path).await {
+        Ok(t) => t,
+        // Restored
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && !quarantine_path.exists() => {
+            return Ok(None)
+        }
+        Err(e) => {
+            warn!("Removing quarantined tenant directory {quarantine_path} with unreadable {TENANT_QUARANTINE_MARKER}: {e}");
+            UNIX_EPOCH
+        }
+    };
+    if let Ok(remaining) =
+        (quarantined_at + conf.tenant_quarantine_ttl).duration_since(SystemTime::now())
+    {
+        return Ok(Some(remaining));
+    }
+
+    match safe_remove_tenant_dir_all(quarantine_path, cancel).await {
+        Ok(true) => info!("Removed expired quarantined tenant directory {quarantine_path}"),
+        // Moved out of the way, but the rest is left for the next startup
+        Ok(false) => {}
+        // Restored concurrently
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    Ok(None)
+}
+
+/// Remove the quarantined directory at `quarantine_path` once it expires, unless it is restored
+/// before that.  If we shut down first, the next startup takes over.
+fn schedule_quarantine_expiry(conf: &'static PageServerConf, quarantine_path: Utf8PathBuf) {
+    task_mgr::spawn(
+        task_mgr::BACKGROUND_RUNTIME.handle(),
+        TaskKind::MgmtRequest,
+        None,
+        None,
+        "tenant_quarantine_expiry",
+        false,
+        async move {
+            let cancel = task_mgr::shutdown_token();
+            // A newer detach may replace the directory and extend its expiry: check again after sleeping
+            while let Some(remaining) =
+                remove_quarantined_tenant_dir_if_expired(conf, &quarantine_path, &cancel)
+                    .await
+                    .with_context(|| {
+                        format!("remove quarantined tenant directory {quarantine_path}")
+                    })?
+            {
+                tokio::select! {
+                    _ = tokio::time::sleep(remaining) => {}
+                    _ = cancel.cancelled() => break,
+                }
+            }
+            Ok(())
+        },
+    );
+}
+
 static TENANTS: Lazy<std::sync::RwLock<TenantsMap>> =
     Lazy::new(|| std::sync::RwLock::new(TenantsMap::Initializing));
 
+/// How many [`TenantBrokenEvent`]s a slow subscriber may fall behind before it starts missing them.
+const TENANT_BROKEN_EVENTS_CAPACITY: usize = 256;
+
+/// Creates the channel for [`TenantBrokenEvent`]s. The sender goes into [`TenantSharedResources`],
+/// so that it is owned by the [`TenantManager`] and every [`Tenant`] it spawns can publish to it.
+pub fn broken_events_channel() -> tokio::sync::broadcast::Sender<TenantBrokenEvent> {
+    tokio::sync::broadcast::channel(TENANT_BROKEN_EVENTS_CAPACITY).0
+}
+
+/// Published whenever an attached tenant enters [`TenantState::Broken`], so that interested
+/// parties (e.g. the control plane client) don't have to poll tenant states to notice.
+#[derive(Debug, Clone)]
+pub struct TenantBrokenEvent {
+    pub tenant_shard_id: TenantShardId,
+    pub reason: String,
+    pub at: SystemTime,
+}
+
+/// Called by the tenant's state watcher on every transition into Broken.
+pub(super) fn notify_tenant_broken(
+    broken_events: &tokio::sync::broadcast::Sender<TenantBrokenEvent>,
+    tenant_shard_id: TenantShardId,
+    reason: String,
+) {
+    // An error only means that nobody is subscribed right now
+    let _ = broken_events.send(TenantBrokenEvent {
+        tenant_shard_id,
+        reason,
+        at: SystemTime::now(),
+    });
+}
+
 /// The TenantManager is responsible for storing and mutating the collection of all tenants
 /// that this pageserver process has state for.  Every Tenant and SecondaryTenant instance
 /// lives inside the TenantManager.
@@ -344,6 +676,15 @@ fn load_tenant_config(
         return Ok(None);
     }
 
+    if tenant_dir_path
+        .file_name()
+        .is_some_and(|name| name.ends_with(TENANT_QUARANTINE_SUFFIX))
+    {
+        info!("Found quarantined tenant directory, keeping it until it expires: {tenant_dir_path}");
+        schedule_quarantine_expiry(conf, tenant_dir_path);
+        return Ok(None);
+    }
+
     // This case happens if we crash during attachment before writing a config into the dir
     let is_empty = tenant_dir_path
         .is_empty_dir()
@@ -508,6 +849,7 @@ pub async fn init_tenant_mgr(
                         conf,
                         tenant_shard_id,
                         format!("{}", e),
+                        resources.broken_events.clone(),
                     )),
                 );
                 continue;
@@ -563,7 +905,8 @@ pub async fn init_tenant_mgr(
                         // plane tells us so.
                         // (https://github.com/neondatabase/neon/issues/5377)
                         info!(tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(), "Detaching tenant, control plane omitted it in re-attach response");
-                        if let Err(e) = safe_remove_tenant_dir_all(&tenant_dir_path).await {
+                        if let Err(e) = safe_remove_tenant_dir_all(&tenant_dir_path, &cancel).await
+                        {
                             error!(tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(),
                                 "Failed to remove detached tenant directory '{tenant_dir_path}': {e:?}",
                             );
@@ -657,6 +1000,15 @@ pub(crate) fn tenant_spawn(
         "Cannot load tenant, ignore mark found at {tenant_ignore_mark:?}"
     );
 
+    // Read-only attachments persist as such in the location config, so they also come back
+    // read-only after a restart.
+    let mode = if location_conf.location.read_only {
+        SpawnMode::ReadOnly
+    } else {
+        mode
+    };
+
+    let broken_events = resources.broken_events.clone();
     let tenant = match Tenant::spawn(
         conf,
         tenant_shard_id,
@@ -671,7 +1023,7 @@ pub(crate) fn tenant_spawn(
         Ok(tenant) => tenant,
         Err(e) => {
             error!("Failed to spawn tenant {tenant_shard_id}, reason: {e:#}");
-            Tenant::create_broken_tenant(conf, tenant_shard_id, format!("{e:#}"))
+            Tenant::create_broken_tenant(conf, tenant_shard_id, format!("{e:#}"), broken_events)
         }
     };
 
@@ -746,7 +1098,7 @@ async fn shutdown_all_tenants0(tenants: &std::sync::RwLock<TenantsMap>) {
 
                             shutdown_state.insert(tenant_shard_id, TenantSlot::Secondary(state));
                         }
-                        TenantSlot::InProgress(notify) => {
+                        TenantSlot::InProgress(notify, _) => {
                             // InProgress tenants are not visible in TenantsMap::ShuttingDown: we will
                             // wait for their notifications to fire in this function.
                             join_set.spawn(async move {
@@ -825,6 +1177,8 @@ async fn shutdown_all_tenants0(tenants: &std::sync::RwLock<TenantsMap>) {
 pub(crate) enum SetNewTenantConfigError {
     #[error(transparent)]
     GetTenant(#[from] GetTenantError),
+    #[error("Invalid tenant config: {0}")]
+    Invalid(String),
     #[error(transparent)]
     Persist(anyhow::Error),
     #[error(transparent)]
@@ -849,6 +1203,11 @@ pub(crate) async fn set_new_tenant_config(
         )));
     }
 
+    // Validate before persisting, so that an invalid config doesn't outlive a restart.
+    new_tenant_conf
+        .validate()
+        .map_err(SetNewTenantConfigError::Invalid)?;
+
     // This is a legacy API that only operates on attached tenants: the preferred
     // API to use is the location_config/ endpoint, which lets the caller provide
     // the full LocationConf.
@@ -861,7 +1220,9 @@ pub(crate) async fn set_new_tenant_config(
     Tenant::persist_tenant_config(conf, &tenant_shard_id, &location_conf)
         .await
         .map_err(SetNewTenantConfigError::Persist)?;
-    tenant.set_new_tenant_config(new_tenant_conf);
+    tenant
+        .set_new_tenant_config(new_tenant_conf)
+        .map_err(SetNewTenantConfigError::Other)?;
     Ok(())
 }
 
@@ -890,6 +1251,39 @@ impl TenantManager {
         self.conf
     }
 
+    /// Change how many tenants may warm up concurrently during startup, see
+    /// [`PageServerConf::concurrent_tenant_warmup`].  Lowering the limit below the number of
+    /// warmups in progress doesn't interrupt them, it only delays new warmups.
+    pub(crate) fn set_warmup_concurrency(&self, n: usize) -> anyhow::Result<()> {
+        let n = NonZeroUsize::new(n).context("warmup concurrency must be at least 1")?;
+        self.conf.concurrent_tenant_warmup.set_permits(n);
+        info!("Set tenant warmup concurrency to {n}");
+        Ok(())
+    }
+
+    /// See [`Tenant::set_compaction_soft_stop`].
+    pub(crate) fn set_compaction_soft_stop(
+        &self,
+        tenant_shard_id: TenantShardId,
+        stop: bool,
+    ) -> Result<(), GetTenantError> {
+        let tenant = self.get_attached_tenant_shard(tenant_shard_id, false)?;
+        tenant.set_compaction_soft_stop(stop);
+        info!(%tenant_shard_id, stop, "Set compaction soft stop");
+        Ok(())
+    }
+
+    /// Subscribe to [`TenantBrokenEvent`]s for all tenants. Events published before subscribing
+    /// are not delivered.
+    pub fn subscribe_broken_events(&self) -> tokio::sync::broadcast::Receiver<TenantBrokenEvent> {
+        self.resources.broken_events.subscribe()
+    }
+
+    /// For code paths that build their own [`TenantSharedResources`], such as the legacy `/load` API.
+    pub(crate) fn broken_events_sender(&self) -> tokio::sync::broadcast::Sender<TenantBrokenEvent> {
+        self.resources.broken_events.clone()
+    }
+
     /// Gets the attached tenant from the in-memory data, erroring if it's absent, in secondary mode, or is not fitting to the query.
     /// `active_only = true` allows to query only tenants that are ready for operations, erroring on other kinds of tenants.
     pub(crate) fn get_attached_tenant_shard(
@@ -916,7 +1310,7 @@ impl TenantManager {
                     }
                 }
             },
-            Some(TenantSlot::InProgress(_)) => Err(GetTenantError::NotActive(tenant_shard_id)),
+            Some(TenantSlot::InProgress(..)) => Err(GetTenantError::NotActive(tenant_shard_id)),
             None | Some(TenantSlot::Secondary(_)) => {
                 Err(GetTenantError::NotFound(tenant_shard_id.tenant_id))
             }
@@ -962,6 +1356,11 @@ impl TenantManager {
         debug_assert_current_span_has_tenant_id();
         info!("configuring tenant location to state {new_location_config:?}");
 
+        // Reject an invalid config before it is persisted below
+        new_location_config.tenant_conf.validate().map_err(|e| {
+            UpsertLocationError::BadRequest(anyhow::anyhow!("invalid tenant config: {e}"))
+        })?;
+
         enum FastPathModified {
             Attached(Arc<Tenant>),
             Secondary(Arc<SecondaryTenant>),
@@ -977,14 +1376,22 @@ impl TenantManager {
             match (&new_location_config.mode, peek_slot) {
                 (LocationMode::Attached(attach_conf), Some(TenantSlot::Attached(tenant))) => {
                     match attach_conf.generation.cmp(&tenant.generation) {
+                        Ordering::Equal if attach_conf.read_only != tenant.is_read_only() => {
+                            // Switching to or from read-only mode changes which background work
+                            // the tenant runs (e.g. WAL ingest): fall through to the general case
+                            // of replacing the `Tenant` object.
+                            None
+                        }
                         Ordering::Equal => {
                             // A transition from Attached to Attached in the same generation, we may
                             // take our fast path and just provide the updated configuration
                             // to the tenant.
-                            tenant.set_new_location_config(
-                                AttachedTenantConf::try_from(new_location_config.clone())
-                                    .map_err(UpsertLocationError::BadRequest)?,
-                            );
+                            tenant
+                                .set_new_location_config(
+                                    AttachedTenantConf::try_from(new_location_config.clone())
+                                        .map_err(UpsertLocationError::BadRequest)?,
+                                )
+                                .map_err(UpsertLocationError::BadRequest)?;
 
                             Some(FastPathModified::Attached(tenant.clone()))
                         }
@@ -1027,8 +1434,8 @@ impl TenantManager {
                 // still, and have been requested to go stale as part of a migration.  If
                 // the caller set `flush`, then flush to remote storage.
                 if let LocationMode::Attached(AttachedLocationConfig {
-                    generation: _,
                     attach_mode: AttachmentMode::Stale,
+                    ..
                 }) = &new_location_config.mode
                 {
                     if let Some(flush_timeout) = flush {
@@ -1111,10 +1518,14 @@ impl TenantManager {
                 spawn_mode = SpawnMode::Normal;
             }
             Some(TenantSlot::Secondary(state)) => {
+                // The layers the secondary downloaded stay where they are: secondary and attached
+                // locations share the directory layout, so when we are promoted to attached, the
+                // timeline load below uses every local layer whose size matches the freshly
+                // downloaded index, instead of downloading it again.
                 info!("Shutting down secondary tenant");
                 state.shutdown().await;
             }
-            Some(TenantSlot::InProgress(_)) => {
+            Some(TenantSlot::InProgress(..)) => {
                 // This should never happen: acquire_slot should error out
                 // if the contents of a slot were InProgress.
                 return Err(UpsertLocationError::Other(anyhow::anyhow!(
@@ -1129,6 +1540,14 @@ impl TenantManager {
         let tenant_path = self.conf.tenant_path(&tenant_shard_id);
         let timelines_path = self.conf.timelines_path(&tenant_shard_id);
 
+        // If the tenant was recently detached from here with `preserve_local`, pick up its
+        // local layers again instead of downloading them.
+        if let LocationMode::Attached(_) = &new_location_config.mode {
+            if let Err(e) = restore_quarantined_tenant_dir(self.conf, tenant_shard_id).await {
+                warn!("Failed to restore quarantined tenant directory, attaching without it: {e}");
+            }
+        }
+
         // Directory structure is the same for attached and secondary modes:
         // create it if it doesn't exist.  Timeline load/creation expects the
         // timelines/ subdir to already exist.
@@ -1204,7 +1623,7 @@ impl TenantManager {
                 //
                 // We must shut it down inline here.
                 match new_slot {
-                    TenantSlot::InProgress(_) => {
+                    TenantSlot::InProgress(..) => {
                         // Unreachable because we never insert an InProgress
                         unreachable!()
                     }
@@ -1309,6 +1728,29 @@ impl TenantManager {
         Ok(())
     }
 
+    pub(crate) fn get_slot_counts(&self) -> TenantSlotCounts {
+        let locked = self.tenants.read().unwrap();
+        let attached = locked.iter_attached().count();
+        let secondary = locked.iter_secondary().count();
+        TenantSlotCounts {
+            attached,
+            secondary,
+            in_progress: locked.len() - attached - secondary,
+        }
+    }
+
+    /// Attached tenant shards that have not become Active yet, for orchestration to wait for
+    /// the startup warmup to complete.  Empty while the tenant map is still initializing.
+    pub(crate) fn list_not_yet_active(&self) -> Vec<(TenantShardId, TenantState)> {
+        self.tenants.read().unwrap().not_yet_active()
+    }
+
+    /// Tenant shards whose slot is in the middle of a transition (e.g. attach, detach or a
+    /// location config change), with how long they have been in that state.
+    pub(crate) fn list_in_progress(&self) -> Vec<(TenantShardId, Duration)> {
+        self.tenants.read().unwrap().in_progress()
+    }
+
     pub(crate) fn get_attached_active_tenant_shards(&self) -> Vec<Arc<Tenant>> {
         let locked = self.tenants.read().unwrap();
         match &*locked {
@@ -1474,7 +1916,7 @@ impl TenantManager {
         let parent = match parent_slot_guard.get_old_value() {
             Some(TenantSlot::Attached(t)) => t,
             Some(TenantSlot::Secondary(_)) => anyhow::bail!("Tenant location in secondary mode"),
-            Some(TenantSlot::InProgress(_)) => {
+            Some(TenantSlot::InProgress(..)) => {
                 // tenant_map_acquire_slot never returns InProgress, if a slot was InProgress
                 // it would return an error.
                 unreachable!()
@@ -1495,7 +1937,7 @@ impl TenantManager {
         // Take a snapshot of where the parent's WAL ingest had got to: we will wait for
         // child shards to reach this point.
         let mut target_lsns = HashMap::new();
-        for timeline in parent.timelines.lock().unwrap().clone().values() {
+        for timeline in parent.lock_timelines().clone().values() {
             target_lsns.insert(timeline.timeline_id, timeline.get_last_record_lsn());
         }
 
@@ -1512,6 +1954,7 @@ impl TenantManager {
                 mode: LocationMode::Attached(AttachedLocationConfig {
                     generation: parent_generation,
                     attach_mode: AttachmentMode::Single,
+                    read_only: false,
                 }),
                 shard: child_shard_identity,
                 tenant_conf: parent_tenant_conf.clone(),
@@ -1547,7 +1990,7 @@ impl TenantManager {
                     continue;
                 }
 
-                let timelines = t.timelines.lock().unwrap().clone();
+                let timelines = t.lock_timelines().clone();
                 for timeline in timelines.values() {
                     let Some(target_lsn) = target_lsns.get(&timeline.timeline_id) else {
                         continue;
@@ -1626,7 +2069,7 @@ impl TenantManager {
         let parent_path = self.conf.tenant_path(parent_shard.get_tenant_shard_id());
         let (parent_timelines, parent_layers) = {
             let mut parent_layers = Vec::new();
-            let timelines = parent_shard.timelines.lock().unwrap().clone();
+            let timelines = parent_shard.lock_timelines().clone();
             let parent_timelines = timelines.keys().cloned().collect::<Vec<_>>();
             for timeline in timelines.values() {
                 let timeline_layers = timeline
@@ -1784,7 +2227,7 @@ pub(crate) fn get_tenant(
                 }
             }
         },
-        Some(TenantSlot::InProgress(_)) => Err(GetTenantError::NotActive(tenant_shard_id)),
+        Some(TenantSlot::InProgress(..)) => Err(GetTenantError::NotActive(tenant_shard_id)),
         None | Some(TenantSlot::Secondary(_)) => {
             Err(GetTenantError::NotFound(tenant_shard_id.tenant_id))
         }
@@ -1863,7 +2306,7 @@ pub(crate) async fn get_active_tenant_with_timeout(
                     tenant_shard_id,
                 )))
             }
-            Some(TenantSlot::InProgress(barrier)) => {
+            Some(TenantSlot::InProgress(barrier, _)) => {
                 (WaitFor::Barrier(barrier.clone()), tenant_shard_id)
             }
             None => {
@@ -1936,10 +2379,15 @@ pub(crate) enum TenantStateError {
     Other(#[from] anyhow::Error),
 }
 
+/// Detach a tenant and remove its local directory.
+///
+/// With `preserve_local`, the local directory is quarantined instead, for a re-attach to this
+/// pageserver to pick up, see [`TENANT_QUARANTINE_SUFFIX`].
 pub(crate) async fn detach_tenant(
     conf: &'static PageServerConf,
     tenant_shard_id: TenantShardId,
     detach_ignored: bool,
+    preserve_local: bool,
     deletion_queue_client: &DeletionQueueClient,
 ) -> Result<(), TenantStateError> {
     let tmp_path = detach_tenant0(
@@ -1950,22 +2398,20 @@ pub(crate) async fn detach_tenant(
         deletion_queue_client,
     )
     .await?;
-    // Although we are cleaning up the tenant, this task is not meant to be bound by the lifetime of the tenant in memory.
-    // After a tenant is detached, there are no more task_mgr tasks for that tenant_id.
-    let task_tenant_id = None;
-    task_mgr::spawn(
-        task_mgr::BACKGROUND_RUNTIME.handle(),
-        TaskKind::MgmtRequest,
-        task_tenant_id,
-        None,
-        "tenant_files_delete",
-        false,
-        async move {
-            fs::remove_dir_all(tmp_path.as_path())
-                .await
-                .with_context(|| format!("tenant directory {:?} deletion", tmp_path))
-        },
-    );
+
+    if preserve_local {
+        match quarantine_tenant_dir(conf, tenant_shard_id, &tmp_path).await {
+            Ok(quarantine_path) => {
+                info!("Quarantined local tenant directory at {quarantine_path}");
+                return Ok(());
+            }
+            Err(e) => {
+                warn!("Failed to quarantine local tenant directory, deleting it instead: {e}");
+            }
+        }
+    }
+
+    spawn_tenant_files_delete(tmp_path);
     Ok(())
 }
 
@@ -2021,9 +2467,7 @@ pub(crate) async fn load_tenant(
     conf: &'static PageServerConf,
     tenant_id: TenantId,
     generation: Generation,
-    broker_client: storage_broker::BrokerClientChannel,
-    remote_storage: Option<GenericRemoteStorage>,
-    deletion_queue_client: DeletionQueueClient,
+    resources: TenantSharedResources,
     ctx: &RequestContext,
 ) -> Result<(), TenantMapInsertError> {
     // This is a legacy API (replaced by `/location_conf`).  It does not support sharding
@@ -2042,12 +2486,6 @@ pub(crate) async fn load_tenant(
         })?;
     }
 
-    let resources = TenantSharedResources {
-        broker_client,
-        remote_storage,
-        deletion_queue_client,
-    };
-
     let mut location_conf =
         Tenant::load_tenant_config(conf, &tenant_shard_id).map_err(TenantMapInsertError::Other)?;
     location_conf.attach_in_generation(generation);
@@ -2130,7 +2568,7 @@ pub(crate) async fn list_tenants(
                 Some((*id, tenant.current_state(), tenant.generation()))
             }
             TenantSlot::Secondary(_) => None,
-            TenantSlot::InProgress(_) => None,
+            TenantSlot::InProgress(..) => None,
         })
         .collect())
 }
@@ -2278,7 +2716,7 @@ impl SlotGuard {
         let replaced = {
             let mut locked = TENANTS.write().unwrap();
 
-            if let TenantSlot::InProgress(_) = new_value {
+            if let TenantSlot::InProgress(..) = new_value {
                 // It is never expected to try and upsert InProgress via this path: it should
                 // only be written via the tenant_map_acquire_slot path.  If we hit this it's a bug.
                 return Err(TenantSlotUpsertError::InternalError(
@@ -2311,7 +2749,7 @@ impl SlotGuard {
 
         // Sanity check: on an upsert we should always be replacing an InProgress marker
         match replaced {
-            Some(TenantSlot::InProgress(_)) => {
+            Some(TenantSlot::InProgress(..)) => {
                 // Expected case: we find our InProgress in the map: nothing should have
                 // replaced it because the code that acquires slots will not grant another
                 // one for the same TenantId.
@@ -2361,7 +2799,7 @@ impl SlotGuard {
         match self.old_value.as_ref() {
             Some(TenantSlot::Attached(tenant)) => tenant.gate.close_complete(),
             Some(TenantSlot::Secondary(secondary_tenant)) => secondary_tenant.gate.close_complete(),
-            Some(TenantSlot::InProgress(_)) => {
+            Some(TenantSlot::InProgress(..)) => {
                 // A SlotGuard cannot be constructed for a slot that was already InProgress
                 unreachable!()
             }
@@ -2408,7 +2846,7 @@ impl Drop for SlotGuard {
         use std::collections::btree_map::Entry;
         match m.entry(self.tenant_shard_id) {
             Entry::Occupied(mut entry) => {
-                if !matches!(entry.get(), TenantSlot::InProgress(_)) {
+                if !matches!(entry.get(), TenantSlot::InProgress(..)) {
                     METRICS.unexpected_errors.inc();
                     error!(tenant_shard_id=%self.tenant_shard_id, "Unexpected contents of TenantSlot during drop, this is a bug.  Contents: {:?}", entry.get());
                 }
@@ -2508,7 +2946,7 @@ fn tenant_map_acquire_slot_impl(
             }
             _ => {
                 let (completion, barrier) = utils::completion::channel();
-                v.insert(TenantSlot::InProgress(barrier));
+                v.insert(TenantSlot::InProgress(barrier, Instant::now()));
                 tracing::debug!("Vacant, inserted InProgress");
                 Ok(SlotGuard::new(*tenant_shard_id, None, completion))
             }
@@ -2516,7 +2954,7 @@ fn tenant_map_acquire_slot_impl(
         Entry::Occupied(mut o) => {
             // Apply mode-driven checks
             match (o.get(), mode) {
-                (TenantSlot::InProgress(_), _) => {
+                (TenantSlot::InProgress(..), _) => {
                     tracing::debug!("Occupied, failing for InProgress");
                     Err(TenantSlotError::InProgress)
                 }
@@ -2544,7 +2982,7 @@ fn tenant_map_acquire_slot_impl(
                 _ => {
                     // Happy case: the slot was not in any state that violated our mode
                     let (completion, barrier) = utils::completion::channel();
-                    let old_value = o.insert(TenantSlot::InProgress(barrier));
+                    let old_value = o.insert(TenantSlot::InProgress(barrier, Instant::now()));
                     tracing::debug!("Occupied, replaced with InProgress");
                     Ok(SlotGuard::new(
                         *tenant_shard_id,
@@ -2600,7 +3038,7 @@ where
             secondary_state.shutdown().await;
             None
         }
-        Some(TenantSlot::InProgress(_)) => {
+        Some(TenantSlot::InProgress(..)) => {
             // Acquiring a slot guarantees its old value was not InProgress
             unreachable!();
         }
@@ -2633,7 +3071,7 @@ where
 }
 
 use {
-    crate::repository::GcResult, pageserver_api::models::TimelineGcRequest,
+    crate::repository::GcResult, crate::tenant::GcError, pageserver_api::models::TimelineGcRequest,
     utils::http::error::ApiError,
 };
 
@@ -2641,9 +3079,10 @@ pub(crate) async fn immediate_gc(
     tenant_shard_id: TenantShardId,
     timeline_id: TimelineId,
     gc_req: TimelineGcRequest,
+    dry_run: bool,
     cancel: CancellationToken,
     ctx: &RequestContext,
-) -> Result<tokio::sync::oneshot::Receiver<Result<GcResult, anyhow::Error>>, ApiError> {
+) -> Result<tokio::sync::oneshot::Receiver<Result<GcResult, GcError>>, ApiError> {
     let guard = TENANTS.read().unwrap();
 
     let tenant = guard
@@ -2657,7 +3096,9 @@ pub(crate) async fn immediate_gc(
     let pitr = tenant.get_pitr_interval();
 
     // Run in task_mgr to avoid race with tenant_detach operation
-    let ctx = ctx.detached_child(TaskKind::GarbageCollector, DownloadBehavior::Download);
+    let ctx = ctx
+        .detached_child(TaskKind::GarbageCollector, DownloadBehavior::Download)
+        .with_throttle_exempt(true);
     let (task_done, wait_task_done) = tokio::sync::oneshot::channel();
     // TODO: spawning is redundant now, need to hold the gate
     task_mgr::spawn(
@@ -2671,12 +3112,17 @@ pub(crate) async fn immediate_gc(
             fail::fail_point!("immediate_gc_task_pre");
 
             #[allow(unused_mut)]
-            let mut result = tenant
-                .gc_iteration(Some(timeline_id), gc_horizon, pitr, &cancel, &ctx)
-                .instrument(info_span!("manual_gc", tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(), %timeline_id))
-                .await;
-                // FIXME: `gc_iteration` can return an error for multiple reasons; we should handle it
-                // better once the types support it.
+            let mut result = if dry_run {
+                tenant
+                    .gc_iteration_dry_run(Some(timeline_id), gc_horizon, pitr, &cancel, &ctx)
+                    .instrument(info_span!("manual_gc_dry_run", tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(), %timeline_id))
+                    .await
+            } else {
+                tenant
+                    .gc_iteration(Some(timeline_id), gc_horizon, pitr, &cancel, &ctx)
+                    .instrument(info_span!("manual_gc", tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(), %timeline_id))
+                    .await
+            };
 
             #[cfg(feature = "testing")]
             {
@@ -2717,10 +3163,43 @@ pub(crate) async fn immediate_gc(
     Ok(wait_task_done)
 }
 
+#[cfg(test)]
+impl TenantManager {
+    /// A [`TenantManager`] sharing the harness' configuration and resources, including the channel
+    /// that harness tenants publish their [`TenantBrokenEvent`]s on.
+    ///
+    /// All managers share the global [`TENANTS`] map, which is opened here if nothing has
+    /// initialized it yet, so that slots can be acquired through this manager.
+    pub(crate) fn for_harness(h: &crate::tenant::harness::TenantHarness) -> Self {
+        {
+            let mut locked = TENANTS.write().unwrap();
+            if matches!(&*locked, TenantsMap::Initializing) {
+                *locked = TenantsMap::Open(BTreeMap::new());
+            }
+        }
+
+        TenantManager {
+            conf: h.conf,
+            tenants: &TENANTS,
+            resources: TenantSharedResources {
+                broker_client: storage_broker::connect(
+                    storage_broker::DEFAULT_ENDPOINT,
+                    Duration::from_secs(5),
+                )
+                .unwrap(),
+                remote_storage: Some(h.remote_storage.clone()),
+                deletion_queue_client: h.deletion_queue.new_client(),
+                broken_events: h.broken_events.clone(),
+            },
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeMap;
     use std::sync::Arc;
+    use std::time::{Duration, Instant};
     use tracing::Instrument;
 
     use crate::tenant::mgr::TenantSlot;
@@ -2795,4 +3274,407 @@ mod tests {
         remove_tenant_from_memory_task.await.unwrap().unwrap();
         shutdown_task.await.unwrap();
     }
+
+    #[tokio::test]
+    async fn iter_attached_and_secondary() {
+        use pageserver_api::models::SecondaryLocationConfig;
+        use pageserver_api::shard::{ShardIdentity, TenantShardId};
+
+        use crate::tenant::secondary::SecondaryTenant;
+
+        let h = TenantHarness::create("iter_attached_and_secondary").unwrap();
+        let (t, _ctx) = h.load().await;
+        let attached_id = t.tenant_shard_id();
+
+        let secondary_id = TenantShardId::unsharded(utils::id::TenantId::generate());
+        let secondary = SecondaryTenant::new(
+            secondary_id,
+            ShardIdentity::unsharded(),
+            Default::default(),
+            &SecondaryLocationConfig { warm: false },
+        );
+
+        let in_progress_id = TenantShardId::unsharded(utils::id::TenantId::generate());
+        let (_completion, barrier) = utils::completion::channel();
+
+        let slots = BTreeMap::from([
+            (attached_id, TenantSlot::Attached(t.clone())),
+            (secondary_id, TenantSlot::Secondary(secondary)),
+            (
+                in_progress_id,
+                TenantSlot::InProgress(barrier, Instant::now()),
+            ),
+        ]);
+
+        for map in [
+            TenantsMap::Open(slots.clone()),
+            TenantsMap::ShuttingDown(slots),
+        ] {
+            let attached: Vec<_> = map.iter_attached().map(|(id, _)| *id).collect();
+            assert_eq!(attached, vec![attached_id]);
+            let secondary: Vec<_> = map.iter_secondary().map(|(id, _)| *id).collect();
+            assert_eq!(secondary, vec![secondary_id]);
+        }
+
+        let map = TenantsMap::Initializing;
+        assert_eq!(map.iter_attached().count(), 0);
+        assert_eq!(map.iter_secondary().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn not_yet_active() {
+        use pageserver_api::models::TenantState;
+
+        let h = TenantHarness::create("not_yet_active").unwrap();
+        let (t, _ctx) = h.load().await;
+        let id = t.tenant_shard_id();
+
+        let slots = BTreeMap::from([(id, TenantSlot::Attached(t.clone()))]);
+
+        // harness loads it to active
+        let map = TenantsMap::Open(slots.clone());
+        assert_eq!(map.not_yet_active(), vec![]);
+
+        t.state
+            .send_replace(TenantState::broken_from_reason("test".to_owned()));
+        let not_yet_active = map.not_yet_active();
+        assert_eq!(not_yet_active.len(), 1);
+        assert_eq!(not_yet_active[0].0, id);
+        assert!(
+            matches!(not_yet_active[0].1, TenantState::Broken { .. }),
+            "{not_yet_active:?}"
+        );
+
+        let map = TenantsMap::Initializing;
+        assert_eq!(map.not_yet_active(), vec![]);
+    }
+
+    #[test]
+    fn in_progress_reports_age() {
+        use pageserver_api::shard::TenantShardId;
+
+        let id = TenantShardId::unsharded(utils::id::TenantId::generate());
+        let (_completion, barrier) = utils::completion::channel();
+        let offset = Duration::from_millis(10);
+        let since = Instant::now()
+            .checked_sub(offset)
+            .expect("monotonic clock is past its epoch");
+
+        let map = TenantsMap::Open(BTreeMap::from([(
+            id,
+            TenantSlot::InProgress(barrier, since),
+        )]));
+        let in_progress = map.in_progress();
+        assert_eq!(in_progress.len(), 1);
+        assert_eq!(in_progress[0].0, id);
+        assert!(in_progress[0].1 >= offset, "{in_progress:?}");
+
+        let map = TenantsMap::Initializing;
+        assert_eq!(map.in_progress(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn resolve_last_shard() {
+        use pageserver_api::models::SecondaryLocationConfig;
+        use pageserver_api::shard::{ShardCount, ShardIdentity, ShardNumber, TenantShardId};
+
+        use super::ShardSelector;
+        use crate::tenant::secondary::SecondaryTenant;
+
+        let h = TenantHarness::create("resolve_last_shard").unwrap();
+        let (t, _ctx) = h.load().await;
+        let tenant_id = t.tenant_shard_id().tenant_id;
+
+        let shard = |n| TenantShardId {
+            tenant_id,
+            shard_number: ShardNumber(n),
+            shard_count: ShardCount::new(4),
+        };
+
+        // All 4 shards present
+        let mut slots: BTreeMap<_, _> = (0..4)
+            .map(|n| (shard(n), TenantSlot::Attached(t.clone())))
+            .collect();
+        // A shard of another tenant must not be picked up
+        let other = TenantShardId::unsharded(utils::id::TenantId::generate());
+        slots.insert(other, TenantSlot::Attached(t.clone()));
+        let map = TenantsMap::Open(slots.clone());
+        assert_eq!(
+            map.resolve_attached_shard(&tenant_id, ShardSelector::Last),
+            Some(shard(3))
+        );
+
+        // A gap in the shard numbers does not matter: we return the highest present
+        slots.remove(&shard(2));
+        let map = TenantsMap::Open(slots.clone());
+        assert_eq!(
+            map.resolve_attached_shard(&tenant_id, ShardSelector::Last),
+            Some(shard(3))
+        );
+
+        // Secondary slots are skipped
+        let secondary = SecondaryTenant::new(
+            shard(3),
+            ShardIdentity::unsharded(),
+            Default::default(),
+            &SecondaryLocationConfig { warm: false },
+        );
+        slots.insert(shard(3), TenantSlot::Secondary(secondary));
+        let map = TenantsMap::Open(slots.clone());
+        assert_eq!(
+            map.resolve_attached_shard(&tenant_id, ShardSelector::Last),
+            Some(shard(1))
+        );
+
+        // Without any attached shard of the tenant, there is nothing to return
+        slots.retain(|id, slot| {
+            id.tenant_id != tenant_id || matches!(slot, TenantSlot::Secondary(_))
+        });
+        let map = TenantsMap::Open(slots);
+        assert_eq!(
+            map.resolve_attached_shard(&tenant_id, ShardSelector::Last),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_unsharded_shard() {
+        use pageserver_api::key::Key;
+        use pageserver_api::shard::{ShardCount, ShardNumber, TenantShardId};
+
+        use super::ShardSelector;
+
+        let h = TenantHarness::create("resolve_unsharded_shard").unwrap();
+        let (t, _ctx) = h.load().await;
+        let tenant_id = t.tenant_shard_id().tenant_id;
+        let unsharded = TenantShardId::unsharded(tenant_id);
+
+        let selectors = [
+            ShardSelector::First,
+            ShardSelector::Zero,
+            ShardSelector::Page(Key::MIN),
+            ShardSelector::Last,
+        ];
+
+        let mut slots = BTreeMap::from([(unsharded, TenantSlot::Attached(t.clone()))]);
+        let map = TenantsMap::Open(slots.clone());
+        for selector in selectors {
+            assert_eq!(
+                map.resolve_attached_shard(&tenant_id, selector),
+                Some(unsharded)
+            );
+        }
+
+        // While splitting, the parent coexists with its children: the fast path must not hide them
+        let child = TenantShardId {
+            tenant_id,
+            shard_number: ShardNumber(1),
+            shard_count: ShardCount::new(2),
+        };
+        slots.insert(child, TenantSlot::Attached(t.clone()));
+        let map = TenantsMap::Open(slots);
+        assert_eq!(
+            map.resolve_attached_shard(&tenant_id, ShardSelector::First),
+            Some(unsharded)
+        );
+        assert_eq!(
+            map.resolve_attached_shard(&tenant_id, ShardSelector::Last),
+            Some(child)
+        );
+    }
+
+    #[test]
+    fn remove_dir_all_batched_cancellation() {
+        use super::remove_dir_all_batched;
+        use tokio_util::sync::CancellationToken;
+
+        let temp_dir = camino_tempfile::tempdir().unwrap();
+        let populate = |name: &str| {
+            let dir = temp_dir.path().join(name);
+            std::fs::create_dir_all(dir.join("timelines")).unwrap();
+            for i in 0..10 {
+                std::fs::write(dir.join("timelines").join(format!("layer-{i}")), b"").unwrap();
+            }
+            dir
+        };
+
+        // Without cancellation, everything is removed
+        let dir = populate("complete");
+        assert!(remove_dir_all_batched(&dir, 4, &CancellationToken::new()).unwrap());
+        assert!(!dir.exists());
+
+        // With cancellation, we stop after the first batch and leave the rest in place
+        let dir = populate("cancelled");
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        assert!(!remove_dir_all_batched(&dir, 4, &cancel).unwrap());
+        assert!(dir.exists());
+        let remaining = std::fs::read_dir(dir.join("timelines")).unwrap().count();
+        assert_eq!(remaining, 6);
+    }
+
+    #[tokio::test]
+    async fn safe_remove_tenant_dir_all_reports_cancellation() {
+        use super::{safe_remove_tenant_dir_all, REMOVE_DIR_BATCH_SIZE};
+        use tokio_util::sync::CancellationToken;
+
+        let temp_dir = camino_tempfile::tempdir().unwrap();
+        let populate = |name: &str| {
+            let dir = temp_dir.path().join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            for i in 0..REMOVE_DIR_BATCH_SIZE {
+                std::fs::write(dir.join(format!("layer-{i}")), b"").unwrap();
+            }
+            dir
+        };
+
+        let dir = populate("complete");
+        assert!(safe_remove_tenant_dir_all(&dir, &CancellationToken::new())
+            .await
+            .unwrap());
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+
+        // A cancelled removal moves the directory aside, but must not claim to have removed it
+        let dir = populate("cancelled");
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        assert!(!safe_remove_tenant_dir_all(&dir, &cancel).await.unwrap());
+        assert!(!dir.exists());
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn raising_warmup_concurrency_admits_waiting_tenant() {
+        use super::TenantState;
+        use crate::context::{DownloadBehavior, RequestContext};
+        use crate::task_mgr::TaskKind;
+        use crate::tenant::config::{AttachedTenantConf, LocationConf, TenantConfOpt};
+        use crate::tenant::{SpawnMode, Tenant};
+        use crate::InitializationOrder;
+        use pageserver_api::models::ShardParameters;
+        use pageserver_api::shard::ShardIdentity;
+
+        let h = TenantHarness::create("raising_warmup_concurrency_admits_waiting_tenant").unwrap();
+        let tenant_manager = super::TenantManager::for_harness(&h);
+        let ctx = RequestContext::new(TaskKind::UnitTest, DownloadBehavior::Error);
+
+        // Pretend that all the configured warmups are already in progress
+        let warmup = &h.conf.concurrent_tenant_warmup;
+        let permits = warmup.initial_permits().get();
+        let _in_progress = warmup.inner().acquire_many(permits as u32).await.unwrap();
+
+        let (_barrier_done, background_jobs_can_start) = utils::completion::channel();
+        let tenant = Tenant::spawn(
+            h.conf,
+            h.tenant_shard_id,
+            tenant_manager.resources.clone(),
+            AttachedTenantConf::try_from(LocationConf::attached_single(
+                TenantConfOpt::from(h.tenant_conf.clone()),
+                h.generation,
+                &ShardParameters::default(),
+            ))
+            .unwrap(),
+            ShardIdentity::unsharded(),
+            Some(InitializationOrder {
+                initial_tenant_load_remote: None,
+                initial_tenant_load: None,
+                background_jobs_can_start,
+            }),
+            &super::TENANTS,
+            SpawnMode::Normal,
+            &ctx,
+        )
+        .unwrap();
+
+        // The tenant waits for a warmup permit...
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(tenant.current_state(), TenantState::Attaching);
+
+        // ...which raising the limit makes available without any warmup finishing
+        tenant_manager.set_warmup_concurrency(permits + 1).unwrap();
+        let mut state = tenant.state.subscribe();
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            state.wait_for(|s| *s == TenantState::Active),
+        )
+        .await
+        .expect("tenant warms up after raising the limit")
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn broken_event_is_published() {
+        use super::TenantState;
+
+        let h = TenantHarness::create("broken_event_is_published").unwrap();
+
+        // The harness tenant publishes on the same channel that the manager owns
+        let tenant_manager = super::TenantManager::for_harness(&h);
+        let mut events = tenant_manager.subscribe_broken_events();
+
+        let (t, _ctx) = h.load().await;
+
+        t.state
+            .send_replace(TenantState::broken_from_reason("test failure".to_string()));
+
+        let event = tokio::time::timeout(Duration::from_secs(10), events.recv())
+            .await
+            .expect("broken event for tenant")
+            .unwrap();
+        assert_eq!(event.tenant_shard_id, t.tenant_shard_id());
+        assert_eq!(event.reason, "test failure");
+    }
+
+    #[tokio::test]
+    async fn quarantined_tenant_dir_is_restored() {
+        use super::{
+            quarantine_tenant_dir, remove_quarantined_tenant_dir_if_expired,
+            restore_quarantined_tenant_dir, safe_rename_tenant_dir, tenant_quarantine_path,
+            TENANT_QUARANTINE_MARKER,
+        };
+        use tokio_util::sync::CancellationToken;
+
+        let h = TenantHarness::create("quarantined_tenant_dir_is_restored").unwrap();
+        let conf = h.conf;
+        let id = h.tenant_shard_id;
+        let tenant_path = conf.tenant_path(&id);
+        let layer_path = conf.timelines_path(&id).join("some-layer");
+        std::fs::write(&layer_path, b"layer").unwrap();
+        let cancel = CancellationToken::new();
+
+        // Detaching with preserve_local moves the directory aside...
+        let tmp_path = safe_rename_tenant_dir(&tenant_path).await.unwrap();
+        let quarantine_path = quarantine_tenant_dir(conf, id, &tmp_path).await.unwrap();
+        assert_eq!(quarantine_path, tenant_quarantine_path(conf, &id));
+        assert!(!tenant_path.exists());
+        assert!(!tmp_path.exists());
+
+        // ...where it stays until it expires...
+        assert!(
+            remove_quarantined_tenant_dir_if_expired(conf, &quarantine_path, &cancel)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        // ...unless the tenant is attached again, which gets the local layers back
+        assert!(restore_quarantined_tenant_dir(conf, id).await.unwrap());
+        assert_eq!(std::fs::read(&layer_path).unwrap(), b"layer");
+        assert!(!tenant_path.join(TENANT_QUARANTINE_MARKER).exists());
+        assert!(!quarantine_path.exists());
+
+        // An expired quarantine is not restored, but removed
+        let tmp_path = safe_rename_tenant_dir(&tenant_path).await.unwrap();
+        let quarantine_path = quarantine_tenant_dir(conf, id, &tmp_path).await.unwrap();
+        std::fs::write(quarantine_path.join(TENANT_QUARANTINE_MARKER), "0").unwrap();
+        assert!(!restore_quarantined_tenant_dir(conf, id).await.unwrap());
+        assert!(!tenant_path.exists());
+        assert!(
+            remove_quarantined_tenant_dir_if_expired(conf, &quarantine_path, &cancel)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(!quarantine_path.exists());
+    }
 }
@@ -192,6 +192,7 @@ pub(crate) use download::download_initdb_tar_zst;
 use pageserver_api::shard::{ShardIndex, TenantShardId};
 use scopeguard::ScopeGuard;
 use tokio_util::sync::CancellationToken;
+pub(crate) use upload::copy_initdb_archive;
 pub(crate) use upload::upload_initdb_dir;
 use utils::backoff::{
     self, exponential_backoff, DEFAULT_BASE_BACKOFF_SECONDS, DEFAULT_MAX_BACKOFF_SECONDS,
@@ -233,7 +234,7 @@ use crate::{
 
 use utils::id::{TenantId, TimelineId};
 
-use self::index::IndexPart;
+use self::index::{GcCutoffs, IndexPart};
 
 use super::storage_layer::{Layer, LayerFileName, ResidentLayer};
 use super::upload_queue::SetDeletedFlagProgress;
@@ -258,6 +259,9 @@ pub(crate) const INITDB_PATH: &str = "initdb.tar.zst";
 
 pub(crate) const INITDB_PRESERVED_PATH: &str = "initdb-preserved.tar.zst";
 
+/// Suffix of the object holding the crc32c checksum of an initdb archive, next to the archive.
+pub(crate) const INITDB_CHECKSUM_SUFFIX: &str = ".crc32c";
+
 /// Default buffer size when interfacing with [`tokio::fs::File`].
 pub(crate) const BUFFER_SIZE: usize = 32 * 1024;
 
@@ -438,6 +442,18 @@ impl RemoteTimelineClient {
         }
     }
 
+    /// The layers of the projected index, i.e. the index as it will look once all currently
+    /// scheduled operations have completed. `None` if the upload queue is not initialized.
+    pub(crate) fn projected_layer_metadata(
+        &self,
+    ) -> Option<HashMap<LayerFileName, LayerFileMetadata>> {
+        match &*self.upload_queue.lock().unwrap() {
+            UploadQueue::Uninitialized => None,
+            UploadQueue::Initialized(q) => Some(q.latest_files.clone()),
+            UploadQueue::Stopped(q) => Some(q.upload_queue_for_deletion.latest_files.clone()),
+        }
+    }
+
     fn update_remote_physical_size_gauge(&self, current_remote_index_part: Option<&IndexPart>) {
         let size: u64 = if let Some(current_remote_index_part) = current_remote_index_part {
             current_remote_index_part
@@ -456,6 +472,14 @@ impl RemoteTimelineClient {
         self.metrics.remote_physical_size_get()
     }
 
+    /// How many layer downloads this client has started.
+    #[cfg(test)]
+    pub(crate) fn layer_downloads_started(&self) -> u64 {
+        self.metrics
+            .get_calls_started_value(&RemoteOpFileKind::Layer, &RemoteOpKind::Download)
+            .unwrap_or(0)
+    }
+
     //
     // Download operations.
     //
@@ -481,6 +505,7 @@ impl RemoteTimelineClient {
             &self.tenant_shard_id,
             &self.timeline_id,
             self.generation,
+            self.conf.index_part_download_max_attempts,
             cancel,
         )
         .measure_remote_op(
@@ -594,6 +619,27 @@ impl RemoteTimelineClient {
         Ok(())
     }
 
+    ///
+    /// Launch an index-file upload operation in the background, if the given
+    /// GC cutoffs differ from the ones last scheduled for upload.
+    ///
+    /// The cutoffs are persisted so that a restarted pageserver can restore them
+    /// before its first GC iteration, see [`IndexPart::gc_cutoffs`].
+    pub(crate) fn schedule_index_upload_for_gc_cutoffs(
+        self: &Arc<Self>,
+        gc_cutoffs: GcCutoffs,
+    ) -> anyhow::Result<()> {
+        let mut guard = self.upload_queue.lock().unwrap();
+        let upload_queue = guard.initialized_mut()?;
+
+        if upload_queue.latest_gc_cutoffs != Some(gc_cutoffs) {
+            upload_queue.latest_gc_cutoffs = Some(gc_cutoffs);
+            self.schedule_index_upload(upload_queue, upload_queue.latest_metadata.clone());
+        }
+
+        Ok(())
+    }
+
     /// Launch an index-file upload operation in the background (internal function)
     fn schedule_index_upload(
         self: &Arc<Self>,
@@ -608,11 +654,12 @@ impl RemoteTimelineClient {
 
         let disk_consistent_lsn = upload_queue.latest_metadata.disk_consistent_lsn();
 
-        let index_part = IndexPart::new(
+        let mut index_part = IndexPart::new(
             upload_queue.latest_files.clone(),
             disk_consistent_lsn,
             metadata,
         );
+        index_part.gc_cutoffs = upload_queue.latest_gc_cutoffs;
         let op = UploadOp::UploadMetadata(index_part, disk_consistent_lsn);
         self.metric_begin(&op);
         upload_queue.queued_operations.push_back(op);
@@ -1093,12 +1140,13 @@ impl RemoteTimelineClient {
         let layer_deletion_count = layers.len();
         self.deletion_queue_client.push_immediate(layers).await?;
 
-        // Delete the initdb.tar.zst, which is not always present, but deletion attempts of
-        // inexistant objects are not considered errors.
+        // Delete the initdb.tar.zst and its checksum, which are not always present, but deletion
+        // attempts of inexistant objects are not considered errors.
         let initdb_path =
             remote_initdb_archive_path(&self.tenant_shard_id.tenant_id, &self.timeline_id);
+        let initdb_checksum_path = remote_initdb_checksum_path(&initdb_path);
         self.deletion_queue_client
-            .push_immediate(vec![initdb_path])
+            .push_immediate(vec![initdb_path, initdb_checksum_path])
             .await?;
 
         // Do not delete index part yet, it is needed for possible retry. If we remove it first
@@ -1147,7 +1195,11 @@ impl RemoteTimelineClient {
                 if p == &latest_index {
                     return false;
                 }
-                if p.object_name() == Some(INITDB_PRESERVED_PATH) {
+                // Keep the preserved initdb archive, along with its checksum
+                if p
+                    .object_name()
+                    .is_some_and(|n| n.starts_with(INITDB_PRESERVED_PATH))
+                {
                     return false;
                 }
                 true
@@ -1611,6 +1663,7 @@ impl RemoteTimelineClient {
                         latest_files: initialized.latest_files.clone(),
                         latest_files_changes_since_metadata_upload_scheduled: 0,
                         latest_metadata: initialized.latest_metadata.clone(),
+                        latest_gc_cutoffs: initialized.latest_gc_cutoffs,
                         projected_remote_consistent_lsn: None,
                         visible_remote_consistent_lsn: initialized
                             .visible_remote_consistent_lsn
@@ -1724,6 +1777,33 @@ pub fn remote_initdb_preserved_archive_path(
     .expect("Failed to construct path")
 }
 
+pub fn remote_initdb_checksum_path(archive_path: &RemotePath) -> RemotePath {
+    RemotePath::from_string(&format!(
+        "{}{INITDB_CHECKSUM_SUFFIX}",
+        archive_path.get_path()
+    ))
+    .expect("Failed to construct path")
+}
+
+/// Calculates the crc32c checksum of an initdb archive, leaving the file rewound to its start.
+async fn initdb_archive_checksum(file: &mut tokio::fs::File) -> std::io::Result<u32> {
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    file.seek(std::io::SeekFrom::Start(0)).await?;
+    let mut checksum = 0;
+    let mut buf = vec![0; BUFFER_SIZE];
+    loop {
+        let read = file.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        checksum = crc32c::crc32c_append(checksum, &buf[..read]);
+    }
+    file.seek(std::io::SeekFrom::Start(0)).await?;
+
+    Ok(checksum)
+}
+
 pub fn remote_index_path(
     tenant_shard_id: &TenantShardId,
     timeline_id: &TimelineId,
@@ -2296,4 +2376,50 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn gc_cutoffs_round_trip() -> anyhow::Result<()> {
+        let test_state = TestSetup::new("gc_cutoffs_round_trip").await.unwrap();
+        let span = test_state.span();
+        let _guard = span.enter();
+
+        let client = test_state.timeline.remote_client.as_ref().unwrap();
+
+        let download_index_part = |client: Arc<RemoteTimelineClient>| async move {
+            match client
+                .download_index_file(&CancellationToken::new())
+                .await
+                .unwrap()
+            {
+                MaybeDeletedIndexPart::IndexPart(index_part) => index_part,
+                MaybeDeletedIndexPart::Deleted(_) => panic!("unexpectedly got deleted index part"),
+            }
+        };
+
+        // A freshly created timeline has no cutoffs persisted yet
+        let initial_index_part = download_index_part(client.clone()).await;
+        assert_eq!(initial_index_part.gc_cutoffs, None);
+
+        let gc_cutoffs = GcCutoffs {
+            horizon: Lsn(0x30),
+            pitr: Lsn(0x20),
+        };
+        client.schedule_index_upload_for_gc_cutoffs(gc_cutoffs)?;
+        client.wait_completion().await?;
+
+        let index_part = download_index_part(client.clone()).await;
+        assert_eq!(index_part.gc_cutoffs, Some(gc_cutoffs));
+
+        // A client loading the index carries the cutoffs over into its own index uploads
+        let generation = test_state.harness.generation;
+        let reloaded = test_state.build_client(generation);
+        reloaded.init_upload_queue(&index_part)?;
+        reloaded.schedule_index_upload_for_metadata_update(&index_part.metadata)?;
+        reloaded.wait_completion().await?;
+
+        let reloaded_index_part = download_index_part(reloaded).await;
+        assert_eq!(reloaded_index_part.gc_cutoffs, Some(gc_cutoffs));
+
+        Ok(())
+    }
 }
@@ -10,7 +10,7 @@ use anyhow::{anyhow, Context};
 use camino::{Utf8Path, Utf8PathBuf};
 use pageserver_api::shard::TenantShardId;
 use tokio::fs::{self, File, OpenOptions};
-use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 use tokio_util::io::StreamReader;
 use tokio_util::sync::CancellationToken;
 use tracing::warn;
@@ -23,15 +23,15 @@ use crate::tenant::storage_layer::LayerFileName;
 use crate::tenant::Generation;
 use crate::virtual_file::on_fatal_io_error;
 use crate::TEMP_FILE_SUFFIX;
-use remote_storage::{DownloadError, GenericRemoteStorage, ListingMode};
+use remote_storage::{DownloadError, GenericRemoteStorage, ListingMode, RemotePath};
 use utils::crashsafe::path_with_suffix_extension;
 use utils::id::TimelineId;
 
 use super::index::{IndexPart, LayerFileMetadata};
 use super::{
-    parse_remote_index_path, remote_index_path, remote_initdb_archive_path,
-    remote_initdb_preserved_archive_path, FAILED_DOWNLOAD_WARN_THRESHOLD, FAILED_REMOTE_OP_RETRIES,
-    INITDB_PATH,
+    initdb_archive_checksum, parse_remote_index_path, remote_index_path,
+    remote_initdb_archive_path, remote_initdb_checksum_path, remote_initdb_preserved_archive_path,
+    FAILED_DOWNLOAD_WARN_THRESHOLD, FAILED_REMOTE_OP_RETRIES, INITDB_PATH,
 };
 
 ///
@@ -225,11 +225,12 @@ async fn do_download_index_part(
     tenant_shard_id: &TenantShardId,
     timeline_id: &TimelineId,
     index_generation: Generation,
+    max_attempts: u32,
     cancel: &CancellationToken,
 ) -> Result<IndexPart, DownloadError> {
     let remote_path = remote_index_path(tenant_shard_id, timeline_id, index_generation);
 
-    let index_part_bytes = download_retry_forever(
+    let index_part_bytes = backoff::retry(
         || async {
             let download = storage.download(&remote_path, cancel).await?;
 
@@ -242,10 +243,15 @@ async fn do_download_index_part(
 
             Ok(bytes)
         },
+        DownloadError::is_permanent,
+        FAILED_DOWNLOAD_WARN_THRESHOLD,
+        max_attempts.saturating_sub(1),
         &format!("download {remote_path:?}"),
         cancel,
     )
-    .await?;
+    .await
+    .ok_or_else(|| DownloadError::Cancelled)
+    .and_then(|x| x)?;
 
     let index_part: IndexPart = serde_json::from_slice(&index_part_bytes)
         .with_context(|| format!("deserialize index part file at {remote_path:?}"))
@@ -259,12 +265,15 @@ async fn do_download_index_part(
 ///
 /// In this function we probe for the most recent index in a generation <= our current generation.
 /// See "Finding the remote indices for timelines" in docs/rfcs/025-generation-numbers.md
+///
+/// Each index part download is attempted at most `max_attempts` times.
 #[tracing::instrument(skip_all, fields(generation=?my_generation))]
 pub(super) async fn download_index_part(
     storage: &GenericRemoteStorage,
     tenant_shard_id: &TenantShardId,
     timeline_id: &TimelineId,
     my_generation: Generation,
+    max_attempts: u32,
     cancel: &CancellationToken,
 ) -> Result<IndexPart, DownloadError> {
     debug_assert_current_span_has_tenant_and_timeline_id();
@@ -276,6 +285,7 @@ pub(super) async fn download_index_part(
             tenant_shard_id,
             timeline_id,
             my_generation,
+            max_attempts,
             cancel,
         )
         .await;
@@ -285,8 +295,15 @@ pub(super) async fn download_index_part(
     // index in our generation.
     //
     // This is an optimization to avoid doing the listing for the general case below.
-    let res =
-        do_download_index_part(storage, tenant_shard_id, timeline_id, my_generation, cancel).await;
+    let res = do_download_index_part(
+        storage,
+        tenant_shard_id,
+        timeline_id,
+        my_generation,
+        max_attempts,
+        cancel,
+    )
+    .await;
     match res {
         Ok(index_part) => {
             tracing::debug!(
@@ -311,6 +328,7 @@ pub(super) async fn download_index_part(
         tenant_shard_id,
         timeline_id,
         my_generation.previous(),
+        max_attempts,
         cancel,
     )
     .await;
@@ -352,7 +370,15 @@ pub(super) async fn download_index_part(
     match max_previous_generation {
         Some(g) => {
             tracing::debug!("Found index_part in generation {g:?}");
-            do_download_index_part(storage, tenant_shard_id, timeline_id, g, cancel).await
+            do_download_index_part(
+                storage,
+                tenant_shard_id,
+                timeline_id,
+                g,
+                max_attempts,
+                cancel,
+            )
+            .await
         }
         None => {
             // Migration from legacy pre-generation state: we have a generation but no prior
@@ -363,6 +389,7 @@ pub(super) async fn download_index_part(
                 tenant_shard_id,
                 timeline_id,
                 Generation::none(),
+                max_attempts,
                 cancel,
             )
             .await
@@ -396,44 +423,54 @@ pub(crate) async fn download_initdb_tar_zst(
         "{INITDB_PATH}.download-{timeline_id}.{TEMP_FILE_SUFFIX}"
     ));
 
-    let file = download_retry(
-        || async {
-            let file = OpenOptions::new()
-                .create(true)
-                .truncate(true)
-                .read(true)
-                .write(true)
-                .open(&temp_path)
-                .await
-                .with_context(|| format!("tempfile creation {temp_path}"))
-                .map_err(DownloadError::Other)?;
-
-            let download = match storage.download(&remote_path, cancel).await {
-                Ok(dl) => dl,
-                Err(DownloadError::NotFound) => {
-                    storage.download(&remote_preserved_path, cancel).await?
-                }
-                Err(other) => Err(other)?,
-            };
-            let mut download = tokio_util::io::StreamReader::new(download.download_stream);
-            let mut writer = tokio::io::BufWriter::with_capacity(super::BUFFER_SIZE, file);
-
-            tokio::io::copy_buf(&mut download, &mut writer).await?;
+    let download_and_verify = async {
+        let (mut file, archive_path) = download_retry(
+            || async {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .truncate(true)
+                    .read(true)
+                    .write(true)
+                    .open(&temp_path)
+                    .await
+                    .with_context(|| format!("tempfile creation {temp_path}"))
+                    .map_err(DownloadError::Other)?;
+
+                let (download, archive_path) = match storage.download(&remote_path, cancel).await {
+                    Ok(dl) => (dl, &remote_path),
+                    Err(DownloadError::NotFound) => (
+                        storage.download(&remote_preserved_path, cancel).await?,
+                        &remote_preserved_path,
+                    ),
+                    Err(other) => Err(other)?,
+                };
+                let mut download = tokio_util::io::StreamReader::new(download.download_stream);
+                let mut writer = tokio::io::BufWriter::with_capacity(super::BUFFER_SIZE, file);
+
+                tokio::io::copy_buf(&mut download, &mut writer).await?;
+
+                let mut file = writer.into_inner();
+
+                file.seek(std::io::SeekFrom::Start(0))
+                    .await
+                    .with_context(|| format!("rewinding initdb.tar.zst at: {remote_path:?}"))
+                    .map_err(DownloadError::Other)?;
+
+                Ok((file, archive_path.clone()))
+            },
+            &format!("download {remote_path}"),
+            cancel,
+        )
+        .await?;
 
-            let mut file = writer.into_inner();
+        // Verify outside of the retries: a mismatching archive is corrupt in remote storage, and
+        // downloading it again won't help.
+        verify_initdb_archive_checksum(storage, &archive_path, &mut file, cancel).await?;
 
-            file.seek(std::io::SeekFrom::Start(0))
-                .await
-                .with_context(|| format!("rewinding initdb.tar.zst at: {remote_path:?}"))
-                .map_err(DownloadError::Other)?;
+        Ok::<_, DownloadError>(file)
+    };
 
-            Ok(file)
-        },
-        &format!("download {remote_path}"),
-        cancel,
-    )
-    .await
-    .map_err(|e| {
+    let file = download_and_verify.await.map_err(|e| {
         // Do a best-effort attempt at deleting the temporary file upon encountering an error.
         // We don't have async here nor do we want to pile on any extra errors.
         if let Err(e) = std::fs::remove_file(&temp_path) {
@@ -447,6 +484,67 @@ pub(crate) async fn download_initdb_tar_zst(
     Ok((temp_path, file))
 }
 
+/// Downloads the checksum uploaded next to the initdb archive at `archive_path`.
+///
+/// Returns `None` for archives which were uploaded before we started checksumming them.
+pub(super) async fn download_initdb_checksum(
+    storage: &GenericRemoteStorage,
+    archive_path: &RemotePath,
+    cancel: &CancellationToken,
+) -> Result<Option<u32>, DownloadError> {
+    let checksum_path = remote_initdb_checksum_path(archive_path);
+
+    let download = match storage.download(&checksum_path, cancel).await {
+        Ok(download) => download,
+        Err(DownloadError::NotFound) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let mut checksum = String::new();
+    StreamReader::new(download.download_stream)
+        .read_to_string(&mut checksum)
+        .await
+        .with_context(|| format!("read {checksum_path}"))
+        .map_err(DownloadError::Other)?;
+
+    u32::from_str_radix(checksum.trim(), 16)
+        .map(Some)
+        .with_context(|| format!("parse {checksum_path}"))
+        .map_err(DownloadError::Other)
+}
+
+async fn verify_initdb_archive_checksum(
+    storage: &GenericRemoteStorage,
+    archive_path: &RemotePath,
+    file: &mut File,
+    cancel: &CancellationToken,
+) -> Result<(), DownloadError> {
+    let expected = download_retry(
+        || download_initdb_checksum(storage, archive_path, cancel),
+        &format!("download checksum of {archive_path}"),
+        cancel,
+    )
+    .await?;
+
+    let Some(expected) = expected else {
+        tracing::info!("initdb archive {archive_path} has no checksum, skipping verification");
+        return Ok(());
+    };
+
+    let actual = initdb_archive_checksum(file)
+        .await
+        .with_context(|| format!("checksum downloaded initdb archive {archive_path}"))
+        .map_err(DownloadError::Other)?;
+
+    if actual != expected {
+        return Err(DownloadError::Other(anyhow!(
+            "initdb archive {archive_path} checksum mismatch: expected {expected:08x}, got {actual:08x}"
+        )));
+    }
+
+    Ok(())
+}
+
 /// Helper function to handle retries for a download operation.
 ///
 /// Remote operations can fail due to rate limits (S3), spurious network
@@ -4,10 +4,9 @@ use anyhow::{bail, Context};
 use camino::Utf8Path;
 use fail::fail_point;
 use pageserver_api::shard::TenantShardId;
-use std::io::{ErrorKind, SeekFrom};
+use std::io::ErrorKind;
 use std::time::SystemTime;
 use tokio::fs::{self, File};
-use tokio::io::AsyncSeekExt;
 use tokio_util::sync::CancellationToken;
 use utils::backoff;
 
@@ -15,13 +14,14 @@ use super::Generation;
 use crate::{
     config::PageServerConf,
     tenant::remote_timeline_client::{
-        index::IndexPart, remote_index_path, remote_initdb_archive_path,
-        remote_initdb_preserved_archive_path, remote_path,
+        index::IndexPart, initdb_archive_checksum, remote_index_path, remote_initdb_archive_path,
+        remote_initdb_checksum_path, remote_initdb_preserved_archive_path, remote_path,
     },
 };
-use remote_storage::{GenericRemoteStorage, TimeTravelError};
+use remote_storage::{GenericRemoteStorage, RemotePath, TimeTravelError};
 use utils::id::{TenantId, TimelineId};
 
+use super::download::download_initdb_checksum;
 use super::index::LayerFileMetadata;
 
 use tracing::info;
@@ -131,16 +131,87 @@ pub(crate) async fn upload_initdb_dir(
 ) -> anyhow::Result<()> {
     tracing::trace!("uploading initdb dir");
 
-    // We might have read somewhat into the file already in the prior retry attempt
-    initdb_tar_zst.seek(SeekFrom::Start(0)).await?;
+    // We might have read somewhat into the file already in the prior retry attempt,
+    // calculating the checksum rewinds it.
+    let checksum = initdb_archive_checksum(&mut initdb_tar_zst)
+        .await
+        .context("checksum initdb archive")?;
 
     let file = tokio_util::io::ReaderStream::with_capacity(initdb_tar_zst, super::BUFFER_SIZE);
 
     let remote_path = remote_initdb_archive_path(tenant_id, timeline_id);
+    delete_initdb_checksum(storage, &remote_path, cancel)
+        .await
+        .with_context(|| format!("delete initdb checksum for '{tenant_id} / {timeline_id}'"))?;
     storage
         .upload_storage_object(file, size as usize, &remote_path, cancel)
         .await
-        .with_context(|| format!("upload initdb dir for '{tenant_id} / {timeline_id}'"))
+        .with_context(|| format!("upload initdb dir for '{tenant_id} / {timeline_id}'"))?;
+
+    upload_initdb_checksum(storage, &remote_path, checksum, cancel)
+        .await
+        .with_context(|| format!("upload initdb checksum for '{tenant_id} / {timeline_id}'"))
+}
+
+/// Deletes the checksum of an initdb archive which is about to be overwritten.
+///
+/// The checksum is only uploaded once its archive has been, so if we crash in between, the
+/// archive is left without a checksum rather than with the checksum of a previous archive.
+async fn delete_initdb_checksum(
+    storage: &GenericRemoteStorage,
+    archive_path: &RemotePath,
+    cancel: &CancellationToken,
+) -> anyhow::Result<()> {
+    storage
+        .delete(&remote_initdb_checksum_path(archive_path), cancel)
+        .await
+}
+
+async fn upload_initdb_checksum(
+    storage: &GenericRemoteStorage,
+    archive_path: &RemotePath,
+    checksum: u32,
+    cancel: &CancellationToken,
+) -> anyhow::Result<()> {
+    let checksum_bytes = bytes::Bytes::from(format!("{checksum:08x}"));
+    let checksum_size = checksum_bytes.len();
+
+    storage
+        .upload_storage_object(
+            futures::stream::once(futures::future::ready(Ok(checksum_bytes))),
+            checksum_size,
+            &remote_initdb_checksum_path(archive_path),
+            cancel,
+        )
+        .await
+}
+
+/// Copies an initdb archive to another location, along with its checksum if it has one.
+pub(crate) async fn copy_initdb_archive(
+    storage: &GenericRemoteStorage,
+    source_path: &RemotePath,
+    dest_path: &RemotePath,
+    cancel: &CancellationToken,
+) -> anyhow::Result<()> {
+    delete_initdb_checksum(storage, dest_path, cancel)
+        .await
+        .with_context(|| format!("delete initdb checksum for {dest_path}"))?;
+    storage
+        .copy_object(source_path, dest_path, cancel)
+        .await
+        .with_context(|| format!("copy initdb archive from {source_path} to {dest_path}"))?;
+
+    // Archives uploaded before we started checksumming them have no checksum to copy.
+    if let Some(checksum) = download_initdb_checksum(storage, source_path, cancel)
+        .await
+        .with_context(|| format!("download initdb checksum for {source_path}"))?
+    {
+        upload_initdb_checksum(storage, dest_path, checksum, cancel)
+            .await
+            .with_context(|| format!("upload initdb checksum for {dest_path}"))?;
+    }
+
+    Ok(())
 }
 
 pub(crate) async fn preserve_initdb_archive(
@@ -151,8 +222,7 @@ pub(crate) async fn preserve_initdb_archive(
 ) -> anyhow::Result<()> {
     let source_path = remote_initdb_archive_path(tenant_id, timeline_id);
     let dest_path = remote_initdb_preserved_archive_path(tenant_id, timeline_id);
-    storage
-        .copy_object(&source_path, &dest_path, cancel)
+    copy_initdb_archive(storage, &source_path, &dest_path, cancel)
         .await
         .with_context(|| format!("backing up initdb archive for '{tenant_id} / {timeline_id}'"))
 }
@@ -176,6 +176,11 @@ impl JobGenerator<UploadPending, WriteInProgress, WriteComplete, UploadCommand>
                 return;
             }
 
+            // Read-only attachments never upload anything.
+            if tenant.is_read_only() {
+                return;
+            }
+
             // Create an entry in self.tenants if one doesn't already exist: this will later be updated
             // with the completion time in on_completion.
             let state = self
@@ -368,7 +373,7 @@ async fn upload_tenant_heatmap(
         timelines: Vec::new(),
         generation,
     };
-    let timelines = tenant.timelines.lock().unwrap().clone();
+    let timelines = tenant.lock_timelines().clone();
 
     // Ensure that Tenant::shutdown waits for any upload in flight: this is needed because otherwise
     // when we delete a tenant, we might race with an upload in flight and end up leaving a heatmap behind
@@ -1,11 +1,12 @@
 use std::cmp;
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
 use anyhow::{bail, Context};
 use tokio::sync::oneshot::error::RecvError;
-use tokio::sync::Semaphore;
+use tokio::sync::{OnceCell, Semaphore};
 use tokio_util::sync::CancellationToken;
 
 use crate::context::RequestContext;
@@ -20,6 +21,13 @@ use tracing::*;
 
 use tenant_size_model::{Segment, StorageModel};
 
+/// Logical sizes at (timeline, lsn) points, shared between size calculations of a tenant.
+///
+/// Each entry is initialized at most once: concurrent calculations needing the same point
+/// wait on the same cell instead of calculating the size again. A failed calculation leaves
+/// the cell uninitialized, so that the next calculation retries it.
+pub(super) type LogicalSizeCache = HashMap<(TimelineId, Lsn), Arc<OnceCell<u64>>>;
+
 /// Inputs to the actual tenant sizing model
 ///
 /// Implements [`serde::Serialize`] but is not meant to be part of the public API, instead meant to
@@ -108,11 +116,16 @@ pub struct TimelineInputs {
 ///
 /// Until gc_horizon_cutoff > `Timeline::last_record_lsn` for any of the tenant's timelines, the
 /// tenant size will be zero.
+///
+/// If given, `progress` is incremented whenever all logical sizes of a timeline are known, so it
+/// ends up at the number of active timelines once the inputs are complete.
+#[allow(clippy::too_many_arguments)]
 pub(super) async fn gather_inputs(
     tenant: &Tenant,
     limit: &Arc<Semaphore>,
     max_retention_period: Option<u64>,
-    logical_size_cache: &mut HashMap<(TimelineId, Lsn), u64>,
+    logical_size_cache: &Mutex<LogicalSizeCache>,
+    progress: Option<Arc<AtomicU64>>,
     cause: LogicalSizeCalculationCause,
     cancel: &CancellationToken,
     ctx: &RequestContext,
@@ -312,6 +325,7 @@ pub(super) async fn gather_inputs(
         &mut segments,
         limit,
         logical_size_cache,
+        progress.as_deref(),
         cause,
         ctx,
     )
@@ -332,7 +346,8 @@ async fn fill_logical_sizes(
     timelines: &[Arc<Timeline>],
     segments: &mut [SegmentMeta],
     limit: &Arc<Semaphore>,
-    logical_size_cache: &mut HashMap<(TimelineId, Lsn), u64>,
+    logical_size_cache: &Mutex<LogicalSizeCache>,
+    progress: Option<&AtomicU64>,
     cause: LogicalSizeCalculationCause,
     ctx: &RequestContext,
 ) -> anyhow::Result<()> {
@@ -347,6 +362,11 @@ async fn fill_logical_sizes(
     // require new lsns to be inspected.
     let mut sizes_needed = HashMap::<(TimelineId, Lsn), Option<u64>>::new();
 
+    // calculations still running per timeline, for reporting progress
+    let mut pending_per_timeline = HashMap::<TimelineId, usize>::from_iter(
+        timelines.iter().map(|timeline| (timeline.timeline_id, 0)),
+    );
+
     // with joinset, on drop, all of the tasks will just be de-scheduled, which we can use to
     // our advantage with `?` error handling.
     let mut joinset = tokio::task::JoinSet::new();
@@ -362,20 +382,37 @@ async fn fill_logical_sizes(
         let lsn = Lsn(seg.segment.lsn);
 
         if let Entry::Vacant(e) = sizes_needed.entry((timeline_id, lsn)) {
-            let cached_size = logical_size_cache.get(&(timeline_id, lsn)).cloned();
+            // Only hold the lock for the lookup: the calculation itself happens on our own
+            // clone of the cell, which other calculations may be initializing concurrently.
+            let cell = Arc::clone(
+                logical_size_cache
+                    .lock()
+                    .unwrap()
+                    .entry((timeline_id, lsn))
+                    .or_default(),
+            );
+            let cached_size = cell.get().cloned();
             if cached_size.is_none() {
                 let timeline = Arc::clone(timeline_hash.get(&timeline_id).unwrap());
                 let parallel_size_calcs = Arc::clone(limit);
                 let ctx = ctx.attached_child();
                 joinset.spawn(
-                    calculate_logical_size(parallel_size_calcs, timeline, lsn, cause, ctx)
+                    calculate_logical_size(parallel_size_calcs, timeline, lsn, cell, cause, ctx)
                         .in_current_span(),
                 );
+                *pending_per_timeline.get_mut(&timeline_id).unwrap() += 1;
             }
             e.insert(cached_size);
         }
     }
 
+    // timelines which were fully cached are done already
+    pending_per_timeline.retain(|_, pending| *pending > 0);
+    if let Some(progress) = progress {
+        let done = timelines.len() - pending_per_timeline.len();
+        progress.fetch_add(done as u64, Ordering::Relaxed);
+    }
+
     // Perform the size lookups
     let mut have_any_error = false;
     while let Some(res) = joinset.join_next().await {
@@ -407,14 +444,28 @@ async fn fill_logical_sizes(
             Ok(Ok(TimelineAtLsnSizeResult(timeline, lsn, Ok(size)))) => {
                 debug!(timeline_id=%timeline.timeline_id, %lsn, size, "size calculated");
 
-                logical_size_cache.insert((timeline.timeline_id, lsn), size);
                 sizes_needed.insert((timeline.timeline_id, lsn), Some(size));
+
+                if let Entry::Occupied(mut e) = pending_per_timeline.entry(timeline.timeline_id) {
+                    *e.get_mut() -= 1;
+                    if *e.get() == 0 {
+                        e.remove();
+                        if let Some(progress) = progress {
+                            progress.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
             }
         }
     }
 
     // prune any keys not needed anymore; we record every used key and added key.
-    logical_size_cache.retain(|key, _| sizes_needed.contains_key(key));
+    // A concurrent calculation which still needs a pruned key holds its own clone of the
+    // cell, so it is not affected beyond the key having to be recalculated next time.
+    logical_size_cache
+        .lock()
+        .unwrap()
+        .retain(|key, _| sizes_needed.contains_key(key));
 
     if have_any_error {
         // we cannot complete this round, because we are missing data.
@@ -475,17 +526,34 @@ async fn calculate_logical_size(
     limit: Arc<tokio::sync::Semaphore>,
     timeline: Arc<crate::tenant::Timeline>,
     lsn: utils::lsn::Lsn,
+    cell: Arc<OnceCell<u64>>,
     cause: LogicalSizeCalculationCause,
     ctx: RequestContext,
 ) -> Result<TimelineAtLsnSizeResult, RecvError> {
-    let _permit = tokio::sync::Semaphore::acquire_owned(limit)
-        .await
-        .expect("global semaphore should not had been closed");
-
-    let size_res = timeline
-        .spawn_ondemand_logical_size_calculation(lsn, cause, ctx)
-        .instrument(info_span!("spawn_ondemand_logical_size_calculation"))
-        .await?;
+    // If another calculation is already initializing the cell, this waits for it instead of
+    // calculating the same size again. The permit is only taken by whoever does the work.
+    let res = cell
+        .get_or_try_init(|| async {
+            let _permit = tokio::sync::Semaphore::acquire_owned(limit)
+                .await
+                .expect("global semaphore should not had been closed");
+
+            match timeline
+                .spawn_ondemand_logical_size_calculation(lsn, cause, ctx)
+                .instrument(info_span!("spawn_ondemand_logical_size_calculation"))
+                .await
+            {
+                Ok(size_res) => size_res.map_err(Ok),
+                Err(recv_error) => Err(Err(recv_error)),
+            }
+        })
+        .await;
+
+    let size_res = match res {
+        Ok(size) => Ok(*size),
+        Err(Ok(calculation_error)) => Err(calculation_error),
+        Err(Err(recv_error)) => return Err(recv_error),
+    };
     Ok(TimelineAtLsnSizeResult(timeline, lsn, size_res))
 }
 
@@ -241,7 +241,12 @@ impl AsLayerDesc for DeltaLayer {
 }
 
 impl DeltaLayer {
-    pub(crate) async fn dump(&self, verbose: bool, ctx: &RequestContext) -> Result<()> {
+    pub(crate) async fn dump(
+        &self,
+        verbose: bool,
+        key_filter: Option<&Range<Key>>,
+        ctx: &RequestContext,
+    ) -> Result<()> {
         self.desc.dump();
 
         if !verbose {
@@ -250,7 +255,7 @@ impl DeltaLayer {
 
         let inner = self.load(LayerAccessKind::Dump, ctx).await?;
 
-        inner.dump(ctx).await
+        inner.dump(key_filter, ctx).await
     }
 
     fn temp_path_for(
@@ -1006,20 +1011,28 @@ impl DeltaLayerInner {
         Ok(all_keys)
     }
 
-    pub(super) async fn dump(&self, ctx: &RequestContext) -> anyhow::Result<()> {
+    /// With `key_filter`, only the entries of keys within that range are dumped.
+    pub(super) async fn dump(
+        &self,
+        key_filter: Option<&Range<Key>>,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
         println!(
             "index_start_blk: {}, root {}",
             self.index_start_blk, self.index_root_blk
         );
 
-        let file = &self.file;
-        let tree_reader = DiskBtreeReader::<_, DELTA_KEY_SIZE>::new(
-            self.index_start_blk,
-            self.index_root_blk,
-            file,
-        );
+        // The index spans all keys of the layer, so only dump it along with all of the entries
+        if key_filter.is_none() {
+            let file = &self.file;
+            let tree_reader = DiskBtreeReader::<_, DELTA_KEY_SIZE>::new(
+                self.index_start_blk,
+                self.index_root_blk,
+                file,
+            );
 
-        tree_reader.dump().await?;
+            tree_reader.dump().await?;
+        }
 
         let keys = self.load_keys(ctx).await?;
 
@@ -1045,6 +1058,9 @@ impl DeltaLayerInner {
 
         for entry in keys {
             let DeltaEntry { key, lsn, val, .. } = entry;
+            if key_filter.is_some_and(|range| !range.contains(&key)) {
+                continue;
+            }
             let desc = match dump_blob(&val, ctx).await {
                 Ok(desc) => desc,
                 Err(err) => {
@@ -166,18 +166,31 @@ impl std::fmt::Debug for ImageLayerInner {
 }
 
 impl ImageLayerInner {
-    pub(super) async fn dump(&self, ctx: &RequestContext) -> anyhow::Result<()> {
+    /// With `key_filter`, only the entries of keys within that range are dumped.
+    pub(super) async fn dump(
+        &self,
+        key_filter: Option<&Range<Key>>,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
         let file = &self.file;
         let tree_reader =
             DiskBtreeReader::<_, KEY_SIZE>::new(self.index_start_blk, self.index_root_blk, file);
 
-        tree_reader.dump().await?;
+        // The index spans all keys of the layer, so only dump it along with all of the entries
+        let mut search_key = [0u8; KEY_SIZE];
+        match key_filter {
+            Some(range) => range.start.write_to_byte_slice(&mut search_key),
+            None => tree_reader.dump().await?,
+        }
 
         tree_reader
             .visit(
-                &[0u8; KEY_SIZE],
+                &search_key,
                 VisitDirection::Forwards,
                 |key, value| {
+                    if key_filter.is_some_and(|range| Key::from_slice(key) >= range.end) {
+                        return false;
+                    }
                     println!("key: {} offset {}", hex::encode(key), value);
                     true
                 },
@@ -203,7 +216,12 @@ impl AsLayerDesc for ImageLayer {
 }
 
 impl ImageLayer {
-    pub(crate) async fn dump(&self, verbose: bool, ctx: &RequestContext) -> Result<()> {
+    pub(crate) async fn dump(
+        &self,
+        verbose: bool,
+        key_filter: Option<&Range<Key>>,
+        ctx: &RequestContext,
+    ) -> Result<()> {
         self.desc.dump();
 
         if !verbose {
@@ -212,7 +230,7 @@ impl ImageLayer {
 
         let inner = self.load(LayerAccessKind::Dump, ctx).await?;
 
-        inner.dump(ctx).await?;
+        inner.dump(key_filter, ctx).await?;
 
         Ok(())
     }
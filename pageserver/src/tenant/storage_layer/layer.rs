@@ -342,14 +342,21 @@ impl Layer {
     }
 
     /// Traditional debug dumping facility
+    ///
+    /// With `key_filter`, a verbose dump only includes the entries of keys within that range.
     #[allow(unused)]
-    pub(crate) async fn dump(&self, verbose: bool, ctx: &RequestContext) -> anyhow::Result<()> {
+    pub(crate) async fn dump(
+        &self,
+        verbose: bool,
+        key_filter: Option<&Range<Key>>,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
         self.0.desc.dump();
 
         if verbose {
             // for now, unconditionally download everything, even if that might not be wanted.
             let l = self.0.get_or_maybe_download(true, Some(ctx)).await?;
-            l.dump(&self.0, ctx).await?
+            l.dump(&self.0, key_filter, ctx).await?
         }
 
         Ok(())
@@ -1384,11 +1391,16 @@ impl DownloadedLayer {
         }
     }
 
-    async fn dump(&self, owner: &Arc<LayerInner>, ctx: &RequestContext) -> anyhow::Result<()> {
+    async fn dump(
+        &self,
+        owner: &Arc<LayerInner>,
+        key_filter: Option<&Range<Key>>,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
         use LayerKind::*;
         match self.get(owner, ctx).await? {
-            Delta(d) => d.dump(ctx).await?,
-            Image(i) => i.dump(ctx).await?,
+            Delta(d) => d.dump(key_filter, ctx).await?,
+            Image(i) => i.dump(key_filter, ctx).await?,
         }
 
         Ok(())
@@ -305,7 +305,8 @@ async fn gc_loop(tenant: Arc<Tenant>, cancel: CancellationToken) {
         // GC might require downloading, to find the cutoff LSN that corresponds to the
         // cutoff specified as time.
         let ctx =
-            RequestContext::todo_child(TaskKind::GarbageCollector, DownloadBehavior::Download);
+            RequestContext::todo_child(TaskKind::GarbageCollector, DownloadBehavior::Download)
+                .with_throttle_exempt(true);
         let mut first = true;
         loop {
             tokio::select! {
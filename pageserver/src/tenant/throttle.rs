@@ -131,6 +131,9 @@ where
     }
 
     pub async fn throttle(&self, ctx: &RequestContext, key_count: usize) {
+        if ctx.throttle_exempt() {
+            return;
+        }
         let inner = self.inner.load_full(); // clones the `Inner` Arc
         if !inner.task_kinds.contains(ctx.task_kind()) {
             return;
@@ -160,3 +163,48 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use super::*;
+    use crate::context::{DownloadBehavior, RequestContextBuilder};
+
+    struct NoMetric;
+
+    impl Metric for NoMetric {
+        fn observe_throttling(&self, _observation: &Observation) {}
+    }
+
+    #[tokio::test]
+    async fn exempt_context_is_not_throttled() {
+        // No budget to begin with, and a long way until the next refill
+        let throttle = Throttle::new(
+            Config {
+                task_kinds: vec!["PageRequestHandler".to_string()],
+                initial: 0,
+                refill_interval: Duration::from_secs(3600),
+                refill_amount: NonZeroUsize::new(1).unwrap(),
+                max: 1,
+                fair: true,
+            },
+            NoMetric,
+        );
+
+        let client = RequestContextBuilder::new(TaskKind::PageRequestHandler)
+            .download_behavior(DownloadBehavior::Download)
+            .build();
+        let exempt = client.with_throttle_exempt(true);
+
+        tokio::time::timeout(Duration::from_secs(10), throttle.throttle(&exempt, 1))
+            .await
+            .expect("exempt context must not wait for the throttle");
+        tokio::time::timeout(Duration::from_millis(100), throttle.throttle(&client, 1))
+            .await
+            .expect_err("client context must be throttled");
+
+        let stats = throttle.reset_stats();
+        assert_eq!(stats.count_accounted, 0);
+    }
+}
@@ -19,7 +19,7 @@ use pageserver_api::{
     keyspace::KeySpaceAccum,
     models::{
         DownloadRemoteLayersTaskInfo, DownloadRemoteLayersTaskSpawnRequest, EvictionPolicy,
-        LayerMapInfo, TimelineState,
+        LayerDiscrepancy, LayerMapInfo, TimelineState,
     },
     reltag::BlockNumber,
     shard::{ShardIdentity, TenantShardId},
@@ -37,6 +37,7 @@ use utils::{bin_ser::BeSer, sync::gate::Gate};
 
 use std::ops::{Deref, Range};
 use std::pin::pin;
+use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering as AtomicOrdering;
 use std::sync::{Arc, Mutex, RwLock, Weak};
 use std::time::{Duration, Instant, SystemTime};
@@ -79,6 +80,7 @@ use crate::config::PageServerConf;
 use crate::keyspace::{KeyPartitioning, KeySpace, KeySpaceRandomAccum};
 use crate::metrics::{
     TimelineMetrics, MATERIALIZED_PAGE_CACHE_HIT, MATERIALIZED_PAGE_CACHE_HIT_DIRECT,
+    TIMELINE_STATE_METRIC,
 };
 use crate::pgdatadir_mapping::CalculateLogicalSizeError;
 use crate::tenant::config::TenantConfOpt;
@@ -330,6 +332,9 @@ pub struct Timeline {
 
     eviction_task_timeline_state: tokio::sync::Mutex<EvictionTaskTimelineState>,
 
+    /// Number of live eviction pins, see [`Timeline::pin_eviction`].
+    eviction_pins: Arc<AtomicUsize>,
+
     /// Load or creation time information about the disk_consistent_lsn and when the loading
     /// happened. Used for consumption metrics.
     pub(crate) loaded_at: (Lsn, SystemTime),
@@ -343,16 +348,16 @@ pub struct Timeline {
 
     /// Make sure we only have one running compaction at a time in tests.
     ///
-    /// Must only be taken in two places:
+    /// Must only be taken in two places (and by tests which need to hold up compaction):
     /// - [`Timeline::compact`] (this file)
     /// - [`delete::delete_local_timeline_directory`]
     ///
     /// Timeline deletion will acquire both compaction and gc locks in whatever order.
-    compaction_lock: tokio::sync::Mutex<()>,
+    pub(crate) compaction_lock: tokio::sync::Mutex<()>,
 
     /// Make sure we only have one running gc at a time.
     ///
-    /// Must only be taken in two places:
+    /// Must only be taken in two places (and by tests, see [`Timeline::hold_gc_lock`]):
     /// - [`Timeline::gc`] (this file)
     /// - [`delete::delete_local_timeline_directory`]
     ///
@@ -365,6 +370,16 @@ pub struct Timeline {
     >,
 }
 
+/// What WAL redo would be given to reconstruct a key at an LSN, see
+/// [`Timeline::get_reconstruct_data_debug`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub(crate) struct ReconstructChain {
+    /// The image the records are applied on, if any, and its LSN.
+    pub(crate) img: Option<(Lsn, Bytes)>,
+    /// The records to apply, oldest first.
+    pub(crate) records: Vec<(Lsn, crate::walrecord::NeonWalRecord)>,
+}
+
 pub struct WalReceiverInfo {
     pub wal_source_connconf: PgConnectionConfig,
     pub last_received_msg_lsn: Lsn,
@@ -375,6 +390,7 @@ pub struct WalReceiverInfo {
 /// Information about how much history needs to be retained, needed by
 /// Garbage Collection.
 ///
+#[derive(Clone)]
 pub struct GcInfo {
     /// Specific LSNs that are needed.
     ///
@@ -498,6 +514,21 @@ pub(crate) enum GetReadyAncestorError {
     Other(#[from] anyhow::Error),
 }
 
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum RedownloadLayerError {
+    #[error("layer {0} is not in the remote index")]
+    NotInRemoteIndex(LayerFileName),
+
+    #[error("layer {0} is not in the layer map")]
+    NotInLayerMap(LayerFileName),
+
+    #[error("Cancelled")]
+    Cancelled,
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
 #[derive(Clone, Copy)]
 pub enum LogicalSizeCalculationCause {
     Initial,
@@ -726,6 +757,35 @@ impl Timeline {
         res
     }
 
+    /// Collect the base image and WAL records that [`Self::get`] would hand to WAL redo to
+    /// reconstruct `key` at `lsn`, without running WAL redo.
+    ///
+    /// Meant for debugging: the page cache is not consulted, so the whole chain stored in the
+    /// layers is returned, and the timeline's get throttle doesn't apply.
+    pub(crate) async fn get_reconstruct_data_debug(
+        &self,
+        key: Key,
+        lsn: Lsn,
+        ctx: &RequestContext,
+    ) -> Result<ReconstructChain, PageReconstructError> {
+        if !lsn.is_valid() {
+            return Err(PageReconstructError::Other(anyhow::anyhow!("Invalid LSN")));
+        }
+
+        let mut reconstruct_state = ValueReconstructState {
+            records: Vec::new(),
+            img: None,
+        };
+        self.get_reconstruct_data(key, lsn, &mut reconstruct_state, ctx)
+            .await?;
+
+        // The traversal collects the records newest first, see `reconstruct_value`
+        let ValueReconstructState { mut records, img } = reconstruct_state;
+        records.reverse();
+
+        Ok(ReconstructChain { img, records })
+    }
+
     pub(crate) const MAX_GET_VECTORED_KEYS: u64 = 32;
 
     /// Look up multiple page versions at a given LSN
@@ -961,6 +1021,18 @@ impl Timeline {
         size
     }
 
+    /// Like [`Self::layer_size_sum`], but only counts the layers which are currently
+    /// present on local disk.
+    pub(crate) async fn resident_layer_size_sum(&self) -> u64 {
+        let guard = self.layers.read().await;
+        let sizes: Vec<u64> = guard
+            .resident_layers()
+            .map(|layer| layer.layer_desc().file_size)
+            .collect()
+            .await;
+        sizes.into_iter().sum()
+    }
+
     pub(crate) fn resident_physical_size(&self) -> u64 {
         self.metrics.resident_physical_size_get()
     }
@@ -1218,7 +1290,11 @@ impl Timeline {
             // Logical size is only maintained accurately on shard zero.
             self.spawn_initial_logical_size_computation_task(ctx);
         }
-        self.launch_wal_receiver(ctx, broker_client);
+        if self.tenant_conf.read().unwrap().location.read_only {
+            info!("not launching WAL receiver, tenant is attached read-only");
+        } else {
+            self.launch_wal_receiver(ctx, broker_client);
+        }
         self.set_state(TimelineState::Active);
         self.launch_eviction_task(background_jobs_can_start);
     }
@@ -1414,6 +1490,66 @@ impl Timeline {
         }
     }
 
+    /// Number of level-0 delta layers, which is what compaction compares against the
+    /// compaction threshold.
+    pub(crate) async fn level0_delta_count(&self) -> anyhow::Result<usize> {
+        let guard = self.layers.read().await;
+        Ok(guard.layer_map().get_level0_deltas()?.len())
+    }
+
+    /// Compare the historic layers in the layer map against the projected remote index.
+    ///
+    /// This is a read-only diagnostic. Layers which are being created or removed concurrently
+    /// may show up as transient discrepancies, because the upload queue is updated after the
+    /// layer map. Without remote storage there is nothing to compare against, and the result
+    /// is empty.
+    pub(crate) async fn audit_layer_consistency(&self) -> Vec<LayerDiscrepancy> {
+        let Some(remote_client) = self.remote_client.as_ref() else {
+            return Vec::new();
+        };
+
+        let guard = self.layers.read().await;
+        let Some(mut index_layers) = remote_client.projected_layer_metadata() else {
+            return Vec::new();
+        };
+
+        let mut discrepancies = Vec::new();
+        for desc in guard.layer_map().iter_historic_layers() {
+            let layer_file_name = desc.filename();
+            let local = guard.get_from_desc(&desc).metadata();
+            match index_layers.remove(&layer_file_name) {
+                None => discrepancies.push(LayerDiscrepancy::NotInIndex {
+                    layer_file_name: layer_file_name.file_name(),
+                    generation: local.generation,
+                }),
+                Some(remote)
+                    if remote.file_size() != local.file_size()
+                        || remote.generation != local.generation =>
+                {
+                    discrepancies.push(LayerDiscrepancy::MetadataMismatch {
+                        layer_file_name: layer_file_name.file_name(),
+                        local_size: local.file_size(),
+                        remote_size: remote.file_size(),
+                        local_generation: local.generation,
+                        remote_generation: remote.generation,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        drop(guard);
+
+        discrepancies.extend(index_layers.into_iter().map(|(layer_file_name, remote)| {
+            LayerDiscrepancy::NotInLayerMap {
+                layer_file_name: layer_file_name.file_name(),
+                generation: remote.generation,
+            }
+        }));
+
+        discrepancies.sort_by(|a, b| a.layer_file_name().cmp(b.layer_file_name()));
+        discrepancies
+    }
+
     #[instrument(skip_all, fields(tenant_id = %self.tenant_shard_id.tenant_id, shard_id = %self.tenant_shard_id.shard_slug(), timeline_id = %self.timeline_id))]
     pub(crate) async fn download_layer(
         &self,
@@ -1451,6 +1587,72 @@ impl Timeline {
             Err(EvictionError::Downloaded) => Ok(Some(false)),
         }
     }
+
+    /// Replace the local copy of a layer with a fresh download from remote storage, e.g. after
+    /// the local file was found to be corrupt.
+    ///
+    /// Fails if the layer is not in the remote index, or if the index entry does not describe
+    /// the layer in the layer map.
+    #[instrument(skip_all, fields(tenant_id = %self.tenant_shard_id.tenant_id, shard_id = %self.tenant_shard_id.shard_slug(), timeline_id = %self.timeline_id, layer = %layer_file_name))]
+    pub(crate) async fn redownload_layer(
+        &self,
+        layer_file_name: &LayerFileName,
+    ) -> Result<(), RedownloadLayerError> {
+        /// How often to retry the eviction when it loses a race against a download.
+        const MAX_EVICTION_ATTEMPTS: usize = 3;
+
+        let _gate = self
+            .gate
+            .enter()
+            .map_err(|_| RedownloadLayerError::Cancelled)?;
+
+        let remote_client = self
+            .remote_client
+            .as_ref()
+            .context("timeline has no remote storage configured")?;
+
+        let Some(remote) = remote_client
+            .projected_layer_metadata()
+            .and_then(|mut index| index.remove(layer_file_name))
+        else {
+            return Err(RedownloadLayerError::NotInRemoteIndex(
+                layer_file_name.clone(),
+            ));
+        };
+
+        let Some(layer) = self.find_layer(&layer_file_name.file_name()).await else {
+            return Err(RedownloadLayerError::NotInLayerMap(layer_file_name.clone()));
+        };
+
+        let local = layer.metadata();
+        if local != remote {
+            return Err(RedownloadLayerError::Other(anyhow::anyhow!(
+                "layer {layer_file_name} metadata differs from the remote index: {local:?} vs {remote:?}"
+            )));
+        }
+
+        // A download racing with the eviction wins and keeps the possibly corrupt local file,
+        // so the eviction has to be retried until the file is actually gone.
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match layer.evict_and_wait().await {
+                Ok(()) | Err(EvictionError::NotFound) => break,
+                Err(EvictionError::Downloaded) if attempt < MAX_EVICTION_ATTEMPTS => continue,
+                Err(EvictionError::Downloaded) => {
+                    return Err(RedownloadLayerError::Other(anyhow::anyhow!(
+                        "layer {layer_file_name} kept being downloaded while evicting it, gave up after {attempt} attempts"
+                    )));
+                }
+            }
+        }
+
+        layer.download().await?;
+
+        info!("re-downloaded layer from remote storage");
+
+        Ok(())
+    }
 }
 
 /// Number of times we will compute partition within a checkpoint distance.
@@ -1507,6 +1709,21 @@ impl Timeline {
             .unwrap_or(self.conf.default_tenant_conf.eviction_policy)
     }
 
+    fn get_max_inmem_layer_bytes(&self) -> Option<u64> {
+        let tenant_conf = self.tenant_conf.read().unwrap().tenant_conf.clone();
+        tenant_conf
+            .max_inmem_layer_bytes
+            .or(self.conf.default_tenant_conf.max_inmem_layer_bytes)
+    }
+
+    fn get_local_disk_high_watermark_bytes(&self) -> Option<u64> {
+        let tenant_conf = self.tenant_conf.read().unwrap().tenant_conf.clone();
+        tenant_conf.local_disk_high_watermark_bytes.or(self
+            .conf
+            .default_tenant_conf
+            .local_disk_high_watermark_bytes)
+    }
+
     fn get_evictions_low_residence_duration_metric_threshold(
         tenant_conf: &TenantConfOpt,
         default_tenant_conf: &TenantConf,
@@ -1571,7 +1788,8 @@ impl Timeline {
         cancel: CancellationToken,
     ) -> Arc<Self> {
         let disk_consistent_lsn = metadata.disk_consistent_lsn();
-        let (state, _) = watch::channel(state);
+        let (state, rx) = watch::channel(state);
+        tokio::spawn(count_timeline_state(rx, &TIMELINE_STATE_METRIC));
 
         let (layer_flush_start_tx, _) = tokio::sync::watch::channel(0);
         let (layer_flush_done_tx, _) = tokio::sync::watch::channel((0, Ok(())));
@@ -1672,6 +1890,7 @@ impl Timeline {
                 eviction_task_timeline_state: tokio::sync::Mutex::new(
                     EvictionTaskTimelineState::default(),
                 ),
+                eviction_pins: Arc::new(AtomicUsize::new(0)),
                 delete_progress: Arc::new(tokio::sync::Mutex::new(DeleteTimelineFlow::default())),
 
                 cancel,
@@ -1952,7 +2171,16 @@ impl Timeline {
 
         guard.initialize_local_layers(loaded_layers, disk_consistent_lsn + 1);
 
-        if let Some(rtc) = self.remote_client.as_ref() {
+        // A read-only tenant leaves remote storage as it is: the future layers stay in the index.
+        let read_only = self.tenant_conf.read().unwrap().location.read_only;
+        if read_only && !needs_cleanup.is_empty() {
+            info!(
+                "not deleting {} future layers, tenant is attached read-only",
+                needs_cleanup.len()
+            );
+        }
+
+        if let Some(rtc) = self.remote_client.as_ref().filter(|_| !read_only) {
             rtc.schedule_layer_file_deletion(&needs_cleanup)?;
             rtc.schedule_index_upload_for_file_changes()?;
             // This barrier orders above DELETEs before any later operations.
@@ -2085,10 +2313,12 @@ impl Timeline {
             .expect("initial logical size calculation task must be spawned exactly once per Timeline object");
 
         let self_clone = Arc::clone(self);
-        let background_ctx = ctx.detached_child(
-            TaskKind::InitialLogicalSizeCalculation,
-            DownloadBehavior::Download,
-        );
+        let background_ctx = ctx
+            .detached_child(
+                TaskKind::InitialLogicalSizeCalculation,
+                DownloadBehavior::Download,
+            )
+            .with_throttle_exempt(true);
         task_mgr::spawn(
             task_mgr::BACKGROUND_RUNTIME.handle(),
             task_mgr::TaskKind::InitialLogicalSizeCalculation,
@@ -2263,10 +2493,12 @@ impl Timeline {
         // we should stop the size calculation work and return an error.
         // That would require restructuring this function's API to
         // return the result directly, instead of a Receiver for the result.
-        let ctx = ctx.detached_child(
-            TaskKind::OndemandLogicalSizeCalculation,
-            DownloadBehavior::Download,
-        );
+        let ctx = ctx
+            .detached_child(
+                TaskKind::OndemandLogicalSizeCalculation,
+                DownloadBehavior::Download,
+            )
+            .with_throttle_exempt(true);
         task_mgr::spawn(
             task_mgr::BACKGROUND_RUNTIME.handle(),
             task_mgr::TaskKind::OndemandLogicalSizeCalculation,
@@ -3401,6 +3633,10 @@ impl Timeline {
     }
 
     // Is it time to create a new image layer for the given partition?
+    //
+    // The image creation threshold applies to each partition on its own: only partitions where
+    // enough deltas have stacked up since their last image get a new one, so that a few hot key
+    // ranges don't cause images to be created for the whole keyspace.
     async fn time_for_new_image_layer(&self, partition: &KeySpace, lsn: Lsn) -> bool {
         let threshold = self.get_image_creation_threshold();
 
@@ -4357,6 +4593,9 @@ impl Timeline {
     /// The 'pitr' duration is used to calculate a 'pitr_cutoff', which can be used to determine
     /// whether a record is needed for PITR.
     ///
+    /// The calculated information is returned. With `dry_run`, it is not stored
+    /// in the 'gc_info' field.
+    ///
     /// NOTE: This function holds a short-lived lock to protect the 'gc_info'
     /// field, so that the three values passed as argument are stored
     /// atomically. But the caller is responsible for ensuring that no new
@@ -4370,9 +4609,10 @@ impl Timeline {
         retain_lsns: Vec<Lsn>,
         cutoff_horizon: Lsn,
         pitr: Duration,
+        dry_run: bool,
         cancel: &CancellationToken,
         ctx: &RequestContext,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<GcInfo> {
         // First, calculate pitr_cutoff_timestamp and then convert it to LSN.
         //
         // Some unit tests depend on garbage-collection working even when
@@ -4420,14 +4660,18 @@ impl Timeline {
             cutoff_horizon
         };
 
-        // Grab the lock and update the values
-        *self.gc_info.write().unwrap() = GcInfo {
+        let gc_info = GcInfo {
             retain_lsns,
             horizon_cutoff: cutoff_horizon,
             pitr_cutoff,
         };
 
-        Ok(())
+        // Grab the lock and update the values
+        if !dry_run {
+            *self.gc_info.write().unwrap() = gc_info.clone();
+        }
+
+        Ok(gc_info)
     }
 
     /// Garbage collect layer files on a timeline that are no longer needed.
@@ -4436,6 +4680,24 @@ impl Timeline {
     /// within a layer file. We can only remove the whole file if it's fully
     /// obsolete.
     pub(super) async fn gc(&self) -> anyhow::Result<GcResult> {
+        self.gc_impl(None).await
+    }
+
+    /// Hold up [`Self::gc`] on this timeline for as long as the guard is held.
+    #[cfg(test)]
+    pub(super) async fn hold_gc_lock(&self) -> tokio::sync::MutexGuard<'_, ()> {
+        self.gc_lock.lock().await
+    }
+
+    /// Report which layer files [`Self::gc`] would remove with the given `gc_info`, without
+    /// removing them or otherwise modifying the timeline.
+    ///
+    /// The names of the layer files are returned in [`GcResult::layers_to_remove`].
+    pub(super) async fn gc_dry_run(&self, gc_info: &GcInfo) -> anyhow::Result<GcResult> {
+        self.gc_impl(Some(gc_info)).await
+    }
+
+    async fn gc_impl(&self, dry_run_gc_info: Option<&GcInfo>) -> anyhow::Result<GcResult> {
         // this is most likely the background tasks, but it might be the spawned task from
         // immediate_gc
         let cancel = crate::task_mgr::shutdown_token();
@@ -4453,8 +4715,16 @@ impl Timeline {
             anyhow::bail!("timeline is Stopping");
         }
 
+        let dry_run = dry_run_gc_info.is_some();
         let (horizon_cutoff, pitr_cutoff, retain_lsns) = {
-            let gc_info = self.gc_info.read().unwrap();
+            let stored_gc_info;
+            let gc_info = match dry_run_gc_info {
+                Some(gc_info) => gc_info,
+                None => {
+                    stored_gc_info = self.gc_info.read().unwrap();
+                    &*stored_gc_info
+                }
+            };
 
             let horizon_cutoff = min(gc_info.horizon_cutoff, self.get_disk_consistent_lsn());
             let pitr_cutoff = gc_info.pitr_cutoff;
@@ -4465,14 +4735,16 @@ impl Timeline {
         let new_gc_cutoff = Lsn::min(horizon_cutoff, pitr_cutoff);
 
         let res = self
-            .gc_timeline(horizon_cutoff, pitr_cutoff, retain_lsns, new_gc_cutoff)
+            .gc_timeline(horizon_cutoff, pitr_cutoff, retain_lsns, new_gc_cutoff, dry_run)
             .instrument(
-                info_span!("gc_timeline", timeline_id = %self.timeline_id, cutoff = %new_gc_cutoff),
+                info_span!("gc_timeline", timeline_id = %self.timeline_id, cutoff = %new_gc_cutoff, dry_run),
             )
             .await?;
 
-        // only record successes
-        timer.stop_and_record();
+        // only record successes, and don't let dry runs skew the timings
+        if !dry_run {
+            timer.stop_and_record();
+        }
 
         Ok(res)
     }
@@ -4483,6 +4755,7 @@ impl Timeline {
         pitr_cutoff: Lsn,
         retain_lsns: Vec<Lsn>,
         new_gc_cutoff: Lsn,
+        dry_run: bool,
     ) -> anyhow::Result<GcResult> {
         let now = SystemTime::now();
         let mut result: GcResult = GcResult::default();
@@ -4501,17 +4774,21 @@ impl Timeline {
         // for details. This will block until the old value is no longer in use.
         //
         // The GC cutoff should only ever move forwards.
-        let waitlist = {
-            let write_guard = self.latest_gc_cutoff_lsn.lock_for_write();
-            ensure!(
-                *write_guard <= new_gc_cutoff,
-                "Cannot move GC cutoff LSN backwards (was {}, new {})",
-                *write_guard,
-                new_gc_cutoff
-            );
-            write_guard.store_and_unlock(new_gc_cutoff)
-        };
-        waitlist.wait().await;
+        //
+        // A dry run doesn't remove anything, so it leaves the cutoff where it is.
+        if !dry_run {
+            let waitlist = {
+                let write_guard = self.latest_gc_cutoff_lsn.lock_for_write();
+                ensure!(
+                    *write_guard <= new_gc_cutoff,
+                    "Cannot move GC cutoff LSN backwards (was {}, new {})",
+                    *write_guard,
+                    new_gc_cutoff
+                );
+                write_guard.store_and_unlock(new_gc_cutoff)
+            };
+            waitlist.wait().await;
+        }
 
         info!("GC starting");
 
@@ -4621,6 +4898,23 @@ impl Timeline {
             );
             layers_to_remove.push(l);
         }
+
+        if dry_run {
+            result.bytes_to_remove = Some(layers_to_remove.iter().map(|l| l.file_size).sum());
+            let layers_to_remove = layers_to_remove
+                .iter()
+                .map(|l| l.filename().file_name())
+                .collect::<Vec<_>>();
+            info!(
+                "GC dry run would remove {} layers, cutoff {}",
+                layers_to_remove.len(),
+                new_gc_cutoff
+            );
+            result.layers_to_remove = Some(layers_to_remove);
+            result.elapsed = now.elapsed()?;
+            return Ok(result);
+        }
+
         self.wanted_image_layers
             .lock()
             .unwrap()
@@ -5156,7 +5450,9 @@ impl<'a> TimelineWriter<'a> {
         //    account for how writes are distributed across shards: we expect each node to consume
         //    1/count of the LSN on average.
         // 2. The size of the currently open layer.
-        // 3. The time since the last roll. It helps safekeepers to regard pageserver as caught
+        // 3. The size of the currently open layer reaching `max_inmem_layer_bytes`, which
+        //    may be set lower than the checkpoint distance to cap memory usage.
+        // 4. The time since the last roll. It helps safekeepers to regard pageserver as caught
         //    up and suspend activity.
         if distance
             >= self.get_checkpoint_distance() as i128 * self.shard_identity.count.count() as i128
@@ -5175,6 +5471,16 @@ impl<'a> TimelineWriter<'a> {
                 lsn, state.current_size, proposed_open_layer_size
             );
 
+            OpenLayerAction::Roll
+        } else if let Some(max_inmem_layer_bytes) = self
+            .get_max_inmem_layer_bytes()
+            .filter(|max| state.current_size > 0 && proposed_open_layer_size >= *max)
+        {
+            info!(
+                "Will roll layer at {} with layer size {} due to max in-memory layer size ({})",
+                lsn, state.current_size, max_inmem_layer_bytes
+            );
+
             OpenLayerAction::Roll
         } else if distance > 0
             && state.cached_last_freeze_ts.elapsed() >= self.get_checkpoint_timeout()
@@ -5263,12 +5569,38 @@ fn rename_to_backup(path: &Utf8Path) -> anyhow::Result<()> {
     bail!("couldn't find an unused backup number for {:?}", path)
 }
 
+/// Reflect the state of a timeline in the per-state counts of `metric`, until the timeline
+/// (and with it, the sending half of `rx`) is dropped.
+async fn count_timeline_state(
+    mut rx: watch::Receiver<TimelineState>,
+    metric: &'static metrics::UIntGaugeVec,
+) {
+    loop {
+        let state: &'static str = (&*rx.borrow_and_update()).into();
+        let current = metric.with_label_values(&[state]);
+        current.inc();
+
+        let changed = rx.changed().await;
+        current.dec();
+        if changed.is_err() {
+            // timeline has been dropped
+            break;
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use utils::{id::TimelineId, lsn::Lsn};
 
     use crate::tenant::{
-        harness::TenantHarness, storage_layer::Layer, timeline::EvictionError, Timeline,
+        harness::TenantHarness,
+        storage_layer::Layer,
+        timeline::{
+            eviction_task::{evict_to_local_disk_watermark, local_disk_low_watermark},
+            EvictionError,
+        },
+        Timeline,
     };
 
     #[tokio::test]
@@ -5311,6 +5643,523 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn local_disk_watermark_evicts_least_recently_accessed() {
+        use crate::repository::{Key, Value};
+        use crate::tenant::harness::test_img;
+        use std::ops::ControlFlow;
+        use tokio_util::sync::CancellationToken;
+
+        let harness =
+            TenantHarness::create("local_disk_watermark_evicts_least_recently_accessed").unwrap();
+
+        let ctx = any_context();
+        let tenant = harness.do_try_load(&ctx).await.unwrap();
+        let timeline = tenant
+            .create_test_timeline(TimelineId::generate(), Lsn(0x10), 14, &ctx)
+            .await
+            .unwrap();
+
+        // a few more layers, each one created (and thus accessed) after the previous one
+        let key = Key::from_hex("010000000033333333444444445500000001").unwrap();
+        let mut lsn = Lsn(0x20);
+        for _ in 0..3 {
+            let mut writer = timeline.writer().await;
+            writer
+                .put(
+                    key,
+                    lsn,
+                    &Value::Image(test_img(&format!("foo at {lsn}"))),
+                    &ctx,
+                )
+                .await
+                .unwrap();
+            writer.finish_write(lsn);
+            drop(writer);
+            timeline.freeze_and_flush().await.unwrap();
+            lsn += 0x10;
+        }
+
+        let resident = timeline
+            .get_local_layers_for_disk_usage_eviction()
+            .await
+            .resident_layers;
+        assert!(resident.len() >= 4, "{resident:?}");
+        let resident_size: u64 = resident.iter().map(|c| c.layer.get_file_size()).sum();
+        let newest = resident
+            .iter()
+            .max_by_key(|c| c.last_activity_ts)
+            .unwrap()
+            .layer
+            .get_name();
+
+        let cancel = CancellationToken::new();
+
+        // at the watermark: nothing to do
+        let ControlFlow::Continue(stats) =
+            evict_to_local_disk_watermark(&tenant, resident_size, &cancel).await
+        else {
+            panic!("not cancelled");
+        };
+        assert_eq!(stats.candidates, 0, "{stats:?}");
+
+        // past the watermark: evict until under the low watermark, oldest first
+        let high_watermark = resident_size - 1;
+        let ControlFlow::Continue(stats) =
+            evict_to_local_disk_watermark(&tenant, high_watermark, &cancel).await
+        else {
+            panic!("not cancelled");
+        };
+        assert!(stats.evicted > 0, "{stats:?}");
+        assert_eq!(stats.evicted, stats.candidates, "{stats:?}");
+
+        let resident = timeline
+            .get_local_layers_for_disk_usage_eviction()
+            .await
+            .resident_layers;
+        let resident_size: u64 = resident.iter().map(|c| c.layer.get_file_size()).sum();
+        assert!(
+            resident_size <= local_disk_low_watermark(high_watermark),
+            "{resident_size} {high_watermark}"
+        );
+        assert!(
+            resident.iter().any(|c| c.layer.get_name() == newest),
+            "most recently accessed layer should have stayed resident"
+        );
+    }
+
+    #[tokio::test]
+    async fn local_disk_watermark_is_checked_once_per_tenant() {
+        use std::ops::ControlFlow;
+        use tokio_util::sync::CancellationToken;
+
+        let harness =
+            TenantHarness::create("local_disk_watermark_is_checked_once_per_tenant").unwrap();
+
+        let ctx = any_context();
+        let tenant = harness.do_try_load(&ctx).await.unwrap();
+        let first = tenant
+            .create_test_timeline(TimelineId::generate(), Lsn(0x10), 14, &ctx)
+            .await
+            .unwrap();
+        let second = tenant
+            .create_test_timeline(TimelineId::generate(), Lsn(0x10), 14, &ctx)
+            .await
+            .unwrap();
+        let cancel = CancellationToken::new();
+
+        // without a watermark, the eviction task leaves the layers alone
+        let ControlFlow::Continue(stats) =
+            first.local_disk_watermark_iteration(&tenant, &cancel).await
+        else {
+            panic!("not cancelled");
+        };
+        assert!(stats.is_none(), "{stats:?}");
+
+        // writing past the configured watermark makes the first task to check evict the layers
+        // of both timelines...
+        let mut tenant_conf = tenant.tenant_specific_overrides();
+        tenant_conf.local_disk_high_watermark_bytes = Some(1);
+        tenant.set_new_tenant_config(tenant_conf).unwrap();
+
+        let ControlFlow::Continue(Some(stats)) =
+            first.local_disk_watermark_iteration(&tenant, &cancel).await
+        else {
+            panic!("should have checked the watermark");
+        };
+        assert!(stats.evicted > 0, "{stats:?}");
+        for timeline in [&first, &second] {
+            let resident = timeline
+                .get_local_layers_for_disk_usage_eviction()
+                .await
+                .resident_layers;
+            assert!(resident.is_empty(), "{resident:?}");
+        }
+
+        // ...and the other timelines' tasks don't repeat the check within the period
+        let ControlFlow::Continue(stats) = second
+            .local_disk_watermark_iteration(&tenant, &cancel)
+            .await
+        else {
+            panic!("not cancelled");
+        };
+        assert!(stats.is_none(), "{stats:?}");
+    }
+
+    #[tokio::test]
+    async fn pinned_timeline_is_not_evicted() {
+        use std::ops::ControlFlow;
+        use tokio_util::sync::CancellationToken;
+
+        let harness = TenantHarness::create("pinned_timeline_is_not_evicted").unwrap();
+
+        let ctx = any_context();
+        let tenant = harness.do_try_load(&ctx).await.unwrap();
+        let pinned = tenant
+            .create_test_timeline(TimelineId::generate(), Lsn(0x10), 14, &ctx)
+            .await
+            .unwrap();
+        let sibling = tenant
+            .create_test_timeline(TimelineId::generate(), Lsn(0x10), 14, &ctx)
+            .await
+            .unwrap();
+
+        async fn resident_layers(timeline: &Timeline) -> usize {
+            timeline
+                .get_local_layers_for_disk_usage_eviction()
+                .await
+                .resident_layers
+                .len()
+        }
+        let pinned_layers = resident_layers(&pinned).await;
+        assert!(pinned_layers > 0);
+        assert!(resident_layers(&sibling).await > 0);
+
+        let cancel = CancellationToken::new();
+        let guard = pinned.pin_eviction();
+        // nested pins are fine
+        drop(pinned.pin_eviction());
+
+        // a zero watermark evicts everything that can be evicted
+        let ControlFlow::Continue(stats) = evict_to_local_disk_watermark(&tenant, 0, &cancel).await
+        else {
+            panic!("not cancelled");
+        };
+        assert!(stats.evicted > 0, "{stats:?}");
+        assert_eq!(resident_layers(&pinned).await, pinned_layers);
+        assert_eq!(resident_layers(&sibling).await, 0);
+
+        drop(guard);
+        let ControlFlow::Continue(stats) = evict_to_local_disk_watermark(&tenant, 0, &cancel).await
+        else {
+            panic!("not cancelled");
+        };
+        assert_eq!(stats.evicted, pinned_layers, "{stats:?}");
+        assert_eq!(resident_layers(&pinned).await, 0);
+    }
+
+    #[tokio::test]
+    async fn gc_dry_run_reports_layers_without_removing_them() {
+        use crate::repository::{Key, Value};
+        use crate::tenant::harness::test_img;
+        use pageserver_api::keyspace::{KeyPartitioning, KeySpace};
+        use std::collections::HashSet;
+        use std::time::Duration;
+        use tokio_util::sync::CancellationToken;
+
+        let harness =
+            TenantHarness::create("gc_dry_run_reports_layers_without_removing_them").unwrap();
+
+        let ctx = any_context();
+        let tenant = harness.do_try_load(&ctx).await.unwrap();
+        let timeline_id = TimelineId::generate();
+        let timeline = tenant
+            .create_test_timeline(timeline_id, Lsn(0x10), 14, &ctx)
+            .await
+            .unwrap();
+
+        let key = Key {
+            field1: 0x10,
+            field2: 0,
+            field3: 0,
+            field4: 0,
+            field5: 0,
+            field6: 0,
+        };
+        let write = |lsn: Lsn| {
+            let timeline = &timeline;
+            let ctx = &ctx;
+            async move {
+                let mut writer = timeline.writer().await;
+                writer
+                    .put(key, lsn, &Value::Image(test_img(&format!("{lsn}"))), ctx)
+                    .await
+                    .unwrap();
+                writer.finish_write(lsn);
+                drop(writer);
+                timeline.freeze_and_flush().await.unwrap();
+            }
+        };
+        let layer_names = || {
+            let timeline = &timeline;
+            async move {
+                timeline
+                    .layers
+                    .read()
+                    .await
+                    .layer_map()
+                    .iter_historic_layers()
+                    .map(|desc| desc.filename().file_name())
+                    .collect::<HashSet<_>>()
+            }
+        };
+
+        // A delta layer which is covered by a later image layer below the GC cutoff, and so
+        // garbage, followed by more recent layers which are not.
+        write(Lsn(0x20)).await;
+        let garbage = layer_names().await;
+        write(Lsn(0x30)).await;
+        let partitioning = KeyPartitioning {
+            parts: vec![KeySpace {
+                ranges: vec![key..key.next()],
+            }],
+        };
+        timeline
+            .create_image_layers(&partitioning, Lsn(0x30), true, &ctx)
+            .await
+            .unwrap();
+        write(Lsn(0x40)).await;
+
+        let layers_before = layer_names().await;
+        let cutoff_before = *timeline.get_latest_gc_cutoff_lsn();
+
+        let result = tenant
+            .gc_iteration_dry_run(
+                Some(timeline_id),
+                0,
+                Duration::ZERO,
+                &CancellationToken::new(),
+                &ctx,
+            )
+            .await
+            .unwrap();
+
+        let reported = result
+            .layers_to_remove
+            .clone()
+            .expect("dry run reports the layers")
+            .into_iter()
+            .collect::<HashSet<_>>();
+        assert!(!reported.is_empty(), "{result:?}");
+        assert!(reported.is_subset(&garbage), "{reported:?} vs {garbage:?}");
+        assert_eq!(result.layers_removed, 0);
+
+        // Nothing was removed, from the layer map or from disk
+        assert_eq!(layer_names().await, layers_before);
+        let timeline_path = harness.timeline_path(&timeline_id);
+        for name in &layers_before {
+            assert!(timeline_path.join(name).exists(), "{name}");
+        }
+        assert_eq!(*timeline.get_latest_gc_cutoff_lsn(), cutoff_before);
+
+        // A real GC run removes exactly the reported layers
+        let result = tenant
+            .gc_iteration(
+                Some(timeline_id),
+                0,
+                Duration::ZERO,
+                &CancellationToken::new(),
+                &ctx,
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.layers_removed, reported.len() as u64);
+        let layers_after = layer_names().await;
+        assert_eq!(
+            layers_before
+                .difference(&layers_after)
+                .cloned()
+                .collect::<HashSet<_>>(),
+            reported
+        );
+    }
+
+    #[tokio::test]
+    async fn image_layers_only_for_partitions_over_threshold() {
+        use crate::repository::{Key, Value};
+        use crate::tenant::harness::test_img;
+        use crate::tenant::storage_layer::AsLayerDesc;
+        use pageserver_api::keyspace::{KeyPartitioning, KeySpace};
+
+        let harness =
+            TenantHarness::create("image_layers_only_for_partitions_over_threshold").unwrap();
+
+        let ctx = any_context();
+        let tenant = harness.do_try_load(&ctx).await.unwrap();
+        let timeline = tenant
+            .create_test_timeline(TimelineId::generate(), Lsn(0x10), 14, &ctx)
+            .await
+            .unwrap();
+        let threshold = timeline.get_image_creation_threshold();
+
+        let key_range = |field1| {
+            let start = Key {
+                field1,
+                field2: 0,
+                field3: 0,
+                field4: 0,
+                field5: 0,
+                field6: 0,
+            };
+            start..start.add(10)
+        };
+        let hot = key_range(0x10);
+        let cold = key_range(0x20);
+
+        // The cold range is written once, the hot one gets a new delta layer per write
+        let mut lsn = Lsn(0x10);
+        for range in std::iter::once(&cold).chain(std::iter::repeat(&hot).take(threshold)) {
+            lsn += 0x10;
+            let mut writer = timeline.writer().await;
+            let mut key = range.start;
+            while key < range.end {
+                writer
+                    .put(key, lsn, &Value::Image(test_img(&format!("{lsn}"))), &ctx)
+                    .await
+                    .unwrap();
+                key = key.next();
+            }
+            writer.finish_write(lsn);
+            drop(writer);
+            timeline.freeze_and_flush().await.unwrap();
+        }
+
+        let partitioning = KeyPartitioning {
+            parts: vec![
+                KeySpace {
+                    ranges: vec![hot.clone()],
+                },
+                KeySpace {
+                    ranges: vec![cold.clone()],
+                },
+            ],
+        };
+        let image_layers = timeline
+            .create_image_layers(&partitioning, lsn, false, &ctx)
+            .await
+            .unwrap();
+
+        // Only the hot partition got an image layer
+        assert_eq!(image_layers.len(), 1, "{image_layers:?}");
+        let desc = image_layers[0].layer_desc();
+        assert!(!desc.is_delta);
+        assert_eq!(desc.key_range.end, hot.end);
+        assert!(desc.key_range.end <= cold.start);
+    }
+
+    #[tokio::test]
+    async fn layer_audit_reports_layers_missing_from_index() {
+        use crate::tenant::storage_layer::AsLayerDesc;
+        use pageserver_api::models::LayerDiscrepancy;
+
+        let harness =
+            TenantHarness::create("layer_audit_reports_layers_missing_from_index").unwrap();
+
+        let ctx = any_context();
+        let tenant = harness.do_try_load(&ctx).await.unwrap();
+        let timeline = tenant
+            .create_test_timeline(TimelineId::generate(), Lsn(0x10), 14, &ctx)
+            .await
+            .unwrap();
+
+        let remote_client = timeline.remote_client.as_ref().unwrap();
+        remote_client.wait_completion().await.unwrap();
+        assert_eq!(timeline.audit_layer_consistency().await, Vec::new());
+
+        // unlink a layer from the index, leaving it in the layer map
+        let layer = find_some_layer(&timeline).await;
+        let layer_file_name = layer.layer_desc().filename();
+        remote_client
+            .schedule_layer_file_deletion(&[layer_file_name.clone()])
+            .unwrap();
+
+        assert_eq!(
+            timeline.audit_layer_consistency().await,
+            vec![LayerDiscrepancy::NotInIndex {
+                layer_file_name: layer_file_name.file_name(),
+                generation: layer.metadata().generation,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn max_inmem_layer_bytes_freezes_before_checkpoint_distance() {
+        use crate::repository::{Key, Value};
+        use bytes::Bytes;
+
+        let mut harness =
+            TenantHarness::create("max_inmem_layer_bytes_freezes_before_checkpoint_distance")
+                .unwrap();
+        harness.tenant_conf.max_inmem_layer_bytes = Some(128 * 1024);
+
+        let ctx = any_context();
+        let tenant = harness.do_try_load(&ctx).await.unwrap();
+        let timeline = tenant
+            .create_test_timeline(TimelineId::generate(), Lsn(0x10), 14, &ctx)
+            .await
+            .unwrap();
+        assert!(timeline.get_checkpoint_distance() > 1024 * 1024);
+        let frozen_at_start = timeline.last_freeze_at.load();
+
+        // a single batch of 1MiB, far below the checkpoint distance
+        let key = Key::from_hex("010000000033333333444444445500000001").unwrap();
+        let batch = (1..=16u64)
+            .map(|i| {
+                let value = Value::Image(Bytes::from(vec![0u8; 64 * 1024]));
+                (key, Lsn(0x10 + i * 0x10), value)
+            })
+            .collect::<Vec<_>>();
+        let last_lsn = batch.last().unwrap().1;
+
+        let mut writer = timeline.writer().await;
+        writer.put_batch(batch, &ctx).await.unwrap();
+        writer.finish_write(last_lsn);
+        drop(writer);
+
+        let frozen_at = timeline.last_freeze_at.load();
+        assert!(
+            frozen_at > frozen_at_start && frozen_at < last_lsn,
+            "expected a freeze in the middle of the batch, last freeze at {frozen_at}"
+        );
+    }
+
+    #[tokio::test]
+    async fn timeline_state_metric_follows_transitions() {
+        use pageserver_api::models::TimelineState;
+        use std::time::Duration;
+        use tokio::sync::watch;
+
+        // a private, unregistered metric, so that other tests' timelines don't interfere
+        let metric: &'static _ = Box::leak(Box::new(
+            metrics::UIntGaugeVec::new(
+                metrics::opts!("test_timeline_states_count", "Count of timelines per state"),
+                &["state"],
+            )
+            .unwrap(),
+        ));
+        let wait_for = |expected: [u64; 3]| async move {
+            let counts = || {
+                ["Loading", "Active", "Broken"]
+                    .map(|state| metric.with_label_values(&[state]).get())
+            };
+            tokio::time::timeout(Duration::from_secs(10), async {
+                while counts() != expected {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .unwrap_or_else(|_| panic!("expected {expected:?}, got {:?}", counts()));
+        };
+
+        let (tx, rx) = watch::channel(TimelineState::Loading);
+        let task = tokio::spawn(super::count_timeline_state(rx, metric));
+        wait_for([1, 0, 0]).await;
+
+        tx.send_replace(TimelineState::Active);
+        wait_for([0, 1, 0]).await;
+
+        tx.send_replace(TimelineState::Broken {
+            reason: "test".to_string(),
+            backtrace: String::new(),
+        });
+        wait_for([0, 0, 1]).await;
+
+        // dropping the timeline removes it from the counts
+        drop(tx);
+        task.await.unwrap();
+        wait_for([0, 0, 0]).await;
+    }
+
     fn any_context() -> crate::context::RequestContext {
         use crate::context::*;
         use crate::task_mgr::*;
@@ -218,7 +218,7 @@ async fn remove_timeline_from_tenant(
     _: &DeletionGuard, // using it as a witness
 ) -> anyhow::Result<()> {
     // Remove the timeline from the map.
-    let mut timelines = tenant.timelines.lock().unwrap();
+    let mut timelines = tenant.lock_timelines();
     let children_exist = timelines
         .iter()
         .any(|(_, entry)| entry.get_ancestor_timeline_id() == Some(timeline_id));
@@ -350,7 +350,7 @@ impl DeleteTimelineFlow {
         // We meed to do this because when console retries delete request we shouldnt answer with 404
         // because 404 means successful deletion.
         {
-            let mut locked = tenant.timelines.lock().unwrap();
+            let mut locked = tenant.lock_timelines();
             locked.insert(timeline_id, Arc::clone(&timeline));
         }
 
@@ -387,7 +387,7 @@ impl DeleteTimelineFlow {
         // T2: do a full deletion, acquire and drop `Timeline::delete_progress`
         // T1: acquire deletion lock, do another `DeleteTimelineFlow::run`
         // For more context see this discussion: `https://github.com/neondatabase/neon/pull/4552#discussion_r1253437346`
-        let timelines = tenant.timelines.lock().unwrap();
+        let timelines = tenant.lock_timelines();
 
         let timeline = match timelines.get(&timeline_id) {
             Some(t) => t,
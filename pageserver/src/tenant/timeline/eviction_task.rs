@@ -12,11 +12,22 @@
 //!
 //! Items with parentheses are not (yet) touched by this task.
 //!
+//! Besides the time-based [`EvictionPolicy`], the task also enforces the tenant's
+//! `local_disk_high_watermark_bytes`: whenever the tenant's resident layers exceed it, the least
+//! recently accessed layers are evicted until the tenant is back under the low watermark. This
+//! check runs once per tenant every [`LOCAL_DISK_WATERMARK_CHECK_PERIOD`], regardless of the
+//! policy's period.
+//!
+//! Neither kind of eviction touches timelines pinned with [`Timeline::pin_eviction`].
+//!
 //! See write-up on restart on-demand download spike: <https://gist.github.com/problame/2265bf7b8dc398be834abfead36c76b5>
 use std::{
     collections::HashMap,
     ops::ControlFlow,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     time::{Duration, SystemTime},
 };
 
@@ -27,6 +38,7 @@ use tracing::{debug, error, info, info_span, instrument, warn, Instrument};
 
 use crate::{
     context::{DownloadBehavior, RequestContext},
+    disk_usage_eviction_task::EvictionLayer,
     pgdatadir_mapping::CollectKeySpaceError,
     task_mgr::{self, TaskKind, BACKGROUND_RUNTIME},
     tenant::{
@@ -38,6 +50,14 @@ use utils::completion;
 
 use super::Timeline;
 
+/// How often the tenant's resident size is compared against `local_disk_high_watermark_bytes`,
+/// if it is set.
+const LOCAL_DISK_WATERMARK_CHECK_PERIOD: Duration = Duration::from_secs(10);
+
+/// Once the high watermark is exceeded, layers are evicted until the tenant's resident size is at
+/// most this percentage of it, so that we do not start evicting again right after the next flush.
+const LOCAL_DISK_LOW_WATERMARK_PERCENT: u64 = 90;
+
 #[derive(Default)]
 pub struct EvictionTaskTimelineState {
     last_layer_access_imitation: Option<tokio::time::Instant>,
@@ -46,9 +66,39 @@ pub struct EvictionTaskTimelineState {
 #[derive(Default)]
 pub struct EvictionTaskTenantState {
     last_layer_access_imitation: Option<Instant>,
+    last_local_disk_watermark_check: Option<Instant>,
+}
+
+/// Keeps the eviction task away from a timeline's layers while held, see
+/// [`Timeline::pin_eviction`].
+pub(crate) struct EvictionPinGuard {
+    pins: Arc<AtomicUsize>,
+}
+
+impl Drop for EvictionPinGuard {
+    fn drop(&mut self) {
+        self.pins.fetch_sub(1, Ordering::Relaxed);
+    }
 }
 
 impl Timeline {
+    /// Pin this timeline's layers against the eviction task until the returned guard is
+    /// dropped, e.g. while an operation that reads all of them is in progress. Pins nest.
+    ///
+    /// Eviction passes that start while the timeline is pinned skip it; a pass that was
+    /// already underway may still evict some layers. Disk usage based eviction, which runs
+    /// under disk pressure, ignores pins.
+    pub(crate) fn pin_eviction(&self) -> EvictionPinGuard {
+        self.eviction_pins.fetch_add(1, Ordering::Relaxed);
+        EvictionPinGuard {
+            pins: Arc::clone(&self.eviction_pins),
+        }
+    }
+
+    fn is_eviction_pinned(&self) -> bool {
+        self.eviction_pins.load(Ordering::Relaxed) > 0
+    }
+
     pub(super) fn launch_eviction_task(
         self: &Arc<Self>,
         background_tasks_can_start: Option<&completion::Barrier>,
@@ -94,21 +144,81 @@ impl Timeline {
         }
 
         let ctx = RequestContext::new(TaskKind::Eviction, DownloadBehavior::Warn);
+        let mut next_policy_iteration = Instant::now();
         loop {
-            let policy = self.get_eviction_policy();
-            let cf = self.eviction_iteration(&policy, &cancel, &ctx).await;
-
-            match cf {
-                ControlFlow::Break(()) => break,
-                ControlFlow::Continue(sleep_until) => {
-                    if tokio::time::timeout_at(sleep_until, cancel.cancelled())
+            if self.get_local_disk_high_watermark_bytes().is_some() {
+                // not active (yet) if this fails: shutdown is noticed through `cancel`, so just
+                // try again later
+                if let Ok(tenant) = crate::tenant::mgr::get_tenant(self.tenant_shard_id, true) {
+                    if self
+                        .local_disk_watermark_iteration(&tenant, &cancel)
                         .await
-                        .is_ok()
+                        .is_break()
                     {
                         break;
                     }
                 }
             }
+
+            if Instant::now() >= next_policy_iteration {
+                let policy = self.get_eviction_policy();
+                let cf = self.eviction_iteration(&policy, &cancel, &ctx).await;
+
+                match cf {
+                    ControlFlow::Break(()) => break,
+                    ControlFlow::Continue(sleep_until) => next_policy_iteration = sleep_until,
+                }
+            }
+
+            // Wake up early if we need to keep an eye on the tenant's resident size.
+            let sleep_until = match self.get_local_disk_high_watermark_bytes() {
+                Some(_) => {
+                    next_policy_iteration.min(Instant::now() + LOCAL_DISK_WATERMARK_CHECK_PERIOD)
+                }
+                None => next_policy_iteration,
+            };
+            if tokio::time::timeout_at(sleep_until, cancel.cancelled())
+                .await
+                .is_ok()
+            {
+                break;
+            }
+        }
+    }
+
+    /// Enforces `local_disk_high_watermark_bytes`, if set. Returns the stats of the check, or
+    /// `None` if there was nothing to do for this timeline.
+    ///
+    /// The watermark applies to the whole tenant, while this task is timeline-scoped. Like the
+    /// synthetic size imitation, one of the tenant's timelines draws the short straw and checks all
+    /// of them, and the others skip the check until [`LOCAL_DISK_WATERMARK_CHECK_PERIOD`] has
+    /// passed.
+    pub(super) async fn local_disk_watermark_iteration(
+        self: &Arc<Self>,
+        tenant: &Tenant,
+        cancel: &CancellationToken,
+    ) -> ControlFlow<(), Option<LocalDiskWatermarkEvictionStats>> {
+        let Some(high_watermark) = self.get_local_disk_high_watermark_bytes() else {
+            return ControlFlow::Continue(None);
+        };
+
+        if self.remote_client.is_none() {
+            // eviction_iteration_threshold complains about this already
+            return ControlFlow::Continue(None);
+        }
+
+        let mut state = tenant.eviction_task_tenant_state.lock().await;
+        match state.last_local_disk_watermark_check {
+            Some(ts) if ts.elapsed() < LOCAL_DISK_WATERMARK_CHECK_PERIOD => {
+                ControlFlow::Continue(None)
+            }
+            _ => match evict_to_local_disk_watermark(tenant, high_watermark, cancel).await {
+                ControlFlow::Break(()) => ControlFlow::Break(()),
+                ControlFlow::Continue(stats) => {
+                    state.last_local_disk_watermark_check = Some(Instant::now());
+                    ControlFlow::Continue(Some(stats))
+                }
+            },
         }
     }
 
@@ -207,6 +317,11 @@ impl Timeline {
             return ControlFlow::Continue(());
         }
 
+        if self.is_eviction_pinned() {
+            debug!("timeline is pinned against eviction, skipping");
+            return ControlFlow::Continue(());
+        }
+
         let mut js = tokio::task::JoinSet::new();
         {
             let guard = self.layers.read().await;
@@ -488,12 +603,13 @@ impl Timeline {
             .eviction_task_immitated_concurrent_logical_size_queries
             .inner();
 
-        let mut throwaway_cache = HashMap::new();
+        let throwaway_cache = std::sync::Mutex::new(HashMap::new());
         let gather = crate::tenant::size::gather_inputs(
             tenant,
             limit,
             None,
-            &mut throwaway_cache,
+            &throwaway_cache,
+            None,
             LogicalSizeCalculationCause::EvictionTaskImitation,
             cancel,
             ctx,
@@ -516,3 +632,113 @@ impl Timeline {
         }
     }
 }
+
+#[derive(Debug, Default)]
+pub(crate) struct LocalDiskWatermarkEvictionStats {
+    pub(crate) resident_size: u64,
+    pub(crate) candidates: usize,
+    pub(crate) evicted: usize,
+    pub(crate) evicted_bytes: u64,
+    pub(crate) errors: usize,
+    pub(crate) not_evictable: usize,
+}
+
+/// The resident size that [`evict_to_local_disk_watermark`] evicts down to.
+pub(crate) fn local_disk_low_watermark(high_watermark: u64) -> u64 {
+    (u128::from(high_watermark) * u128::from(LOCAL_DISK_LOW_WATERMARK_PERCENT) / 100) as u64
+}
+
+/// If the tenant's resident layers exceed `high_watermark` bytes, evicts the least recently
+/// accessed ones until the resident size is at most [`LOCAL_DISK_LOW_WATERMARK_PERCENT`] of
+/// `high_watermark`.
+///
+/// Victims are ordered by the same access stats that the time-based policy and the disk usage
+/// based eviction use. Layers of pinned timelines count towards the resident size, but are
+/// never evicted.
+pub(crate) async fn evict_to_local_disk_watermark(
+    tenant: &Tenant,
+    high_watermark: u64,
+    cancel: &CancellationToken,
+) -> ControlFlow<(), LocalDiskWatermarkEvictionStats> {
+    let mut stats = LocalDiskWatermarkEvictionStats::default();
+
+    let mut candidates = Vec::new();
+    for tl in tenant.list_timelines() {
+        if !tl.is_active() {
+            continue;
+        }
+        let info = tl.get_local_layers_for_disk_usage_eviction().await;
+        stats.resident_size += info
+            .resident_layers
+            .iter()
+            .map(|c| c.layer.get_file_size())
+            .sum::<u64>();
+        if !tl.is_eviction_pinned() {
+            candidates.extend(info.resident_layers);
+        }
+
+        if cancel.is_cancelled() {
+            return ControlFlow::Break(());
+        }
+    }
+
+    if stats.resident_size <= high_watermark {
+        return ControlFlow::Continue(stats);
+    }
+
+    let low_watermark = local_disk_low_watermark(high_watermark);
+
+    // least recently accessed first
+    candidates.sort_unstable_by_key(|c| c.last_activity_ts);
+
+    let mut js = tokio::task::JoinSet::new();
+    let mut planned_size = stats.resident_size;
+    for candidate in candidates {
+        if planned_size <= low_watermark {
+            break;
+        }
+        let EvictionLayer::Attached(layer) = candidate.layer else {
+            unreachable!("only attached layers are collected from timelines")
+        };
+        let file_size = layer.layer_desc().file_size;
+        planned_size -= file_size;
+        stats.candidates += 1;
+        js.spawn(async move { (file_size, layer.evict_and_wait().await) });
+    }
+
+    let join_all = async move {
+        while let Some(next) = js.join_next().await {
+            match next {
+                Ok((file_size, Ok(()))) => {
+                    stats.evicted += 1;
+                    stats.evicted_bytes += file_size;
+                }
+                Ok((_, Err(EvictionError::NotFound | EvictionError::Downloaded))) => {
+                    stats.not_evictable += 1;
+                }
+                Err(je) if je.is_cancelled() => unreachable!("not used"),
+                Err(je) if je.is_panic() => {
+                    /* already logged */
+                    stats.errors += 1;
+                }
+                Err(je) => tracing::error!("unknown JoinError: {je:?}"),
+            }
+        }
+        stats
+    };
+
+    tokio::select! {
+        stats = join_all => {
+            if stats.errors > 0 || stats.not_evictable > 0 {
+                warn!(high_watermark, low_watermark, stats=?stats, "local disk watermark eviction complete");
+            } else {
+                info!(high_watermark, low_watermark, stats=?stats, "local disk watermark eviction complete");
+            }
+            ControlFlow::Continue(stats)
+        }
+        _ = cancel.cancelled() => {
+            // just drop the joinset to "abort"
+            ControlFlow::Break(())
+        }
+    }
+}
@@ -64,7 +64,7 @@ impl<'t> UninitializedTimeline<'t> {
             "new timeline {tenant_shard_id}/{timeline_id} has invalid disk_consistent_lsn"
         );
 
-        let mut timelines = self.owning_tenant.timelines.lock().unwrap();
+        let mut timelines = self.owning_tenant.lock_timelines();
         match timelines.entry(timeline_id) {
             Entry::Occupied(_) => anyhow::bail!(
                 "Found freshly initialized timeline {tenant_shard_id}/{timeline_id} in the tenant map"
@@ -199,11 +199,8 @@ impl<'t> TimelineUninitMark<'t> {
     ) -> Result<Self, TimelineExclusionError> {
         // Lock order: this is the only place we take both locks.  During drop() we only
         // lock creating_timelines
-        let timelines = owning_tenant.timelines.lock().unwrap();
-        let mut creating_timelines: std::sync::MutexGuard<
-            '_,
-            std::collections::HashSet<TimelineId>,
-        > = owning_tenant.timelines_creating.lock().unwrap();
+        let timelines = owning_tenant.lock_timelines();
+        let mut creating_timelines = owning_tenant.lock_timelines_creating();
 
         if let Some(existing) = timelines.get(&timeline_id) {
             Err(TimelineExclusionError::AlreadyExists(existing.clone()))
@@ -265,9 +262,10 @@ impl Drop for TimelineUninitMark<'_> {
         }
 
         self.owning_tenant
-            .timelines_creating
-            .lock()
-            .unwrap()
+            .lock_timelines_creating()
             .remove(&self.timeline_id);
+        self.owning_tenant
+            .timelines_creating_removed
+            .notify_waiters();
     }
 }
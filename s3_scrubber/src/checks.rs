@@ -10,7 +10,7 @@ use utils::id::TimelineId;
 
 use crate::cloud_admin_api::BranchData;
 use crate::metadata_stream::stream_listing;
-use crate::{download_object_with_retries, RootTarget, TenantShardTimelineId};
+use crate::{download_object_with_retries, RootTarget, S3Target, TenantShardTimelineId};
 use futures_util::{pin_mut, StreamExt};
 use pageserver::tenant::remote_timeline_client::parse_remote_index_path;
 use pageserver::tenant::storage_layer::LayerFileName;
@@ -71,6 +71,7 @@ pub(crate) fn branch_cleanup_and_check_errors(
     match s3_data {
         Some(s3_data) => {
             result.garbage_keys.extend(s3_data.keys_to_remove);
+            result.errors.extend(s3_data.initdb_errors);
 
             match s3_data.blob_data {
                 BlobDataParseResult::Parsed {
@@ -242,6 +243,8 @@ impl TenantObjectListing {
 pub(crate) struct S3TimelineBlobData {
     pub(crate) blob_data: BlobDataParseResult,
     pub(crate) keys_to_remove: Vec<String>,
+    /// initdb archives which don't match their checksum
+    pub(crate) initdb_errors: Vec<String>,
 }
 
 #[derive(Debug)]
@@ -284,6 +287,8 @@ pub(crate) async fn list_timeline_blobs(
 
     let mut index_parts: Vec<ObjectIdentifier> = Vec::new();
     let mut initdb_archive: bool = false;
+    let mut initdb_archives = HashSet::new();
+    let mut initdb_checksums = Vec::new();
 
     let stream = stream_listing(s3_client, &timeline_dir_target);
     pin_mut!(stream);
@@ -297,9 +302,18 @@ pub(crate) async fn list_timeline_blobs(
                 tracing::info!("Index key {key}");
                 index_parts.push(obj)
             }
-            Some("initdb.tar.zst") => {
+            Some(name @ "initdb.tar.zst") => {
                 tracing::info!("initdb archive {key}");
                 initdb_archive = true;
+                initdb_archives.insert(name);
+            }
+            Some(name @ "initdb-preserved.tar.zst") => {
+                tracing::info!("preserved initdb archive {key}");
+                initdb_archives.insert(name);
+            }
+            Some(name @ ("initdb.tar.zst.crc32c" | "initdb-preserved.tar.zst.crc32c")) => {
+                tracing::info!("initdb archive checksum {key}");
+                initdb_checksums.push(name.trim_end_matches(".crc32c").to_string());
             }
             Some(maybe_layer_name) => match parse_layer_object_name(maybe_layer_name) {
                 Ok((new_layer, gen)) => {
@@ -322,6 +336,17 @@ pub(crate) async fn list_timeline_blobs(
         }
     }
 
+    // A checksum is only uploaded after its archive, so one without an archive is a leftover
+    // of an interrupted deletion, and archives uploaded before checksumming have none.
+    let mut initdb_errors = Vec::new();
+    for archive_name in initdb_checksums {
+        if initdb_archives.contains(archive_name.as_str()) {
+            initdb_errors.extend(
+                verify_initdb_archive(s3_client, &timeline_dir_target, &archive_name).await?,
+            );
+        }
+    }
+
     if index_parts.is_empty() && s3_layers.is_empty() && initdb_archive {
         tracing::info!(
             "Timeline is empty apart from initdb archive: expected post-deletion state."
@@ -329,6 +354,7 @@ pub(crate) async fn list_timeline_blobs(
         return Ok(S3TimelineBlobData {
             blob_data: BlobDataParseResult::Relic,
             keys_to_remove: Vec::new(),
+            initdb_errors,
         });
     }
 
@@ -375,6 +401,7 @@ pub(crate) async fn list_timeline_blobs(
                         s3_layers,
                     },
                     keys_to_remove,
+                    initdb_errors,
                 })
             }
             Err(index_parse_error) => errors.push(format!(
@@ -396,5 +423,43 @@ pub(crate) async fn list_timeline_blobs(
     Ok(S3TimelineBlobData {
         blob_data: BlobDataParseResult::Incorrect(errors),
         keys_to_remove,
+        initdb_errors,
     })
 }
+
+/// Downloads an initdb archive and compares it with the crc32c checksum stored next to it,
+/// returning an error message if they don't match.
+async fn verify_initdb_archive(
+    s3_client: &Client,
+    timeline_dir_target: &S3Target,
+    archive_name: &str,
+) -> anyhow::Result<Option<String>> {
+    let archive_key = format!("{}{archive_name}", timeline_dir_target.prefix_in_bucket);
+    let checksum_key = format!("{archive_key}.crc32c");
+
+    let checksum =
+        download_object_with_retries(s3_client, &timeline_dir_target.bucket_name, &checksum_key)
+            .await
+            .with_context(|| format!("{checksum_key} download"))?;
+    let Some(expected) = std::str::from_utf8(&checksum)
+        .ok()
+        .and_then(|checksum| u32::from_str_radix(checksum.trim(), 16).ok())
+    else {
+        return Ok(Some(format!(
+            "initdb archive checksum {checksum_key} is malformed"
+        )));
+    };
+
+    let archive =
+        download_object_with_retries(s3_client, &timeline_dir_target.bucket_name, &archive_key)
+            .await
+            .with_context(|| format!("{archive_key} download"))?;
+    let actual = crc32c::crc32c(&archive);
+    if actual != expected {
+        return Ok(Some(format!(
+            "initdb archive {archive_key} has checksum {actual:08x}, expected {expected:08x}"
+        )));
+    }
+
+    Ok(None)
+}
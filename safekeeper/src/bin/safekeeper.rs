@@ -28,16 +28,16 @@ use utils::pid_file;
 use metrics::set_build_info_metric;
 use safekeeper::defaults::{
     DEFAULT_HEARTBEAT_TIMEOUT, DEFAULT_HTTP_LISTEN_ADDR, DEFAULT_MAX_OFFLOADER_LAG_BYTES,
-    DEFAULT_PG_LISTEN_ADDR,
+    DEFAULT_MAX_RECEIVE_WAL_INFLIGHT_BYTES, DEFAULT_PG_LISTEN_ADDR,
+    DEFAULT_PG_LISTEN_ADDR_TENANT_ONLY, DEFAULT_WAL_REMOVAL_BATCH_SIZE,
 };
 use safekeeper::wal_service;
 use safekeeper::GlobalTimelines;
 use safekeeper::SafeKeeperConf;
-use safekeeper::{broker, WAL_SERVICE_RUNTIME};
+use safekeeper::{broker, ConfiguredRuntimes};
 use safekeeper::{control_file, BROKER_RUNTIME};
 use safekeeper::{http, WAL_REMOVER_RUNTIME};
-use safekeeper::{remove_wal, WAL_BACKUP_RUNTIME};
-use safekeeper::{wal_backup, HTTP_RUNTIME};
+use safekeeper::{remove_wal, wal_backup, HTTP_RUNTIME};
 use storage_broker::DEFAULT_ENDPOINT;
 use utils::auth::{JwtAuth, Scope, SwappableJwtAuth};
 use utils::{
@@ -89,7 +89,9 @@ struct Args {
     #[arg(short, long, default_value = DEFAULT_PG_LISTEN_ADDR)]
     listen_pg: String,
     /// Listen endpoint for receiving/sending WAL in the form host:port allowing
-    /// only tenant scoped auth tokens. Pointless if auth is disabled.
+    /// only tenant scoped auth tokens. Pointless if auth is disabled. If not
+    /// specified but --pg-tenant-only-auth-public-key-path is given,
+    /// 127.0.0.1:5455 is used.
     #[arg(long, default_value = None, verbatim_doc_comment)]
     listen_pg_tenant_only: Option<String>,
     /// Listen http endpoint for management and metrics in the form host:port.
@@ -134,9 +136,17 @@ struct Args {
     /// Safekeeper won't be elected for WAL offloading if it is lagging for more than this value in bytes
     #[arg(long, default_value_t = DEFAULT_MAX_OFFLOADER_LAG_BYTES)]
     max_offloader_lag: u64,
+    /// Safekeeper stops reading WAL from compute when this many bytes are
+    /// received but not yet fsynced.
+    #[arg(long, default_value_t = DEFAULT_MAX_RECEIVE_WAL_INFLIGHT_BYTES)]
+    max_receive_wal_inflight_bytes: u64,
     /// Number of max parallel WAL segments to be offloaded to remote storage.
     #[arg(long, default_value = "5")]
     wal_backup_parallel_jobs: usize,
+    /// Limit on the total rate of WAL offloading to remote storage, in bytes per
+    /// second, shared by all timelines. Unlimited by default.
+    #[arg(long)]
+    wal_backup_max_bytes_per_sec: Option<u64>,
     /// Disable WAL backup to s3. When disabled, safekeeper removes WAL ignoring
     /// WAL backup horizon.
     #[arg(long)]
@@ -170,6 +180,18 @@ struct Args {
     /// still needed for existing replication connection.
     #[arg(long)]
     walsenders_keep_horizon: bool,
+    /// Max number of WAL segments of a timeline removed at once; the rest are
+    /// removed in the following iterations to avoid IO spikes.
+    #[arg(long, default_value_t = DEFAULT_WAL_REMOVAL_BATCH_SIZE)]
+    wal_removal_batch_size: usize,
+    /// Number of worker threads of the WAL service runtime. Defaults to the
+    /// number of CPU cores.
+    #[arg(long)]
+    wal_service_worker_threads: Option<usize>,
+    /// Number of worker threads of the WAL backup runtime. Defaults to the
+    /// number of CPU cores.
+    #[arg(long)]
+    wal_backup_worker_threads: Option<usize>,
 }
 
 // Like PathBufValueParser, but allows empty string.
@@ -254,6 +276,14 @@ async fn main() -> anyhow::Result<()> {
             ))
         }
     };
+    // Tenant only auth is pointless without the listener it applies to.
+    if args.pg_tenant_only_auth_public_key_path.is_some() && args.listen_pg_tenant_only.is_none() {
+        info!(
+            "tenant only auth is configured without --listen-pg-tenant-only, listening on {}",
+            DEFAULT_PG_LISTEN_ADDR_TENANT_ONLY
+        );
+        args.listen_pg_tenant_only = Some(DEFAULT_PG_LISTEN_ADDR_TENANT_ONLY.to_string());
+    }
     let pg_tenant_only_auth = match args.pg_tenant_only_auth_public_key_path.as_ref() {
         None => {
             info!("pg tenant only auth is disabled");
@@ -293,14 +323,21 @@ async fn main() -> anyhow::Result<()> {
         peer_recovery_enabled: args.peer_recovery,
         remote_storage: args.remote_storage,
         max_offloader_lag_bytes: args.max_offloader_lag,
+        max_receive_wal_inflight_bytes: args.max_receive_wal_inflight_bytes,
         wal_backup_enabled: !args.disable_wal_backup,
         backup_parallel_jobs: args.wal_backup_parallel_jobs,
+        wal_backup_max_bytes_per_sec: args.wal_backup_max_bytes_per_sec,
         pg_auth,
         pg_tenant_only_auth,
         http_auth,
         current_thread_runtime: args.current_thread_runtime,
         walsenders_keep_horizon: args.walsenders_keep_horizon,
+        wal_removal_batch_size: args.wal_removal_batch_size,
+        wal_service_worker_threads: args.wal_service_worker_threads,
+        wal_backup_worker_threads: args.wal_backup_worker_threads,
     };
+    conf.validate()
+        .context("invalid safekeeper configuration")?;
 
     // initialize sentry if SENTRY_DSN is provided
     let _sentry_guard = init_sentry(
@@ -375,10 +412,21 @@ async fn start_safekeeper(conf: SafeKeeperConf) -> Result<()> {
     let current_thread_rt = conf
         .current_thread_runtime
         .then(|| Handle::try_current().expect("no runtime in main"));
+    let (wal_service_rt, wal_backup_rt) = match &current_thread_rt {
+        Some(rt) => (rt.clone(), rt.clone()),
+        None => {
+            // Runtimes must not be dropped from async context, and they are
+            // needed until the process exits anyway.
+            let runtimes: &'static ConfiguredRuntimes =
+                Box::leak(Box::new(ConfiguredRuntimes::new(&conf)?));
+            (
+                runtimes.wal_service.handle().clone(),
+                runtimes.wal_backup.handle().clone(),
+            )
+        }
+    };
     let conf_ = conf.clone();
-    let wal_backup_handle = current_thread_rt
-        .as_ref()
-        .unwrap_or_else(|| WAL_BACKUP_RUNTIME.handle())
+    let wal_backup_handle = wal_backup_rt
         .spawn(wal_backup::wal_backup_launcher_task_main(
             conf_,
             wal_backup_launcher_rx,
@@ -395,9 +443,7 @@ async fn start_safekeeper(conf: SafeKeeperConf) -> Result<()> {
         info!("running in current thread runtime");
     }
 
-    let wal_service_handle = current_thread_rt
-        .as_ref()
-        .unwrap_or_else(|| WAL_SERVICE_RUNTIME.handle())
+    let wal_service_handle = wal_service_rt
         .spawn(wal_service::task_main(
             conf_,
             pg_listener,
@@ -409,9 +455,7 @@ async fn start_safekeeper(conf: SafeKeeperConf) -> Result<()> {
 
     if let Some(pg_listener_tenant_only) = pg_listener_tenant_only {
         let conf_ = conf.clone();
-        let wal_service_handle = current_thread_rt
-            .as_ref()
-            .unwrap_or_else(|| WAL_SERVICE_RUNTIME.handle())
+        let wal_service_handle = wal_service_rt
             .spawn(wal_service::task_main(
                 conf_,
                 pg_listener_tenant_only,
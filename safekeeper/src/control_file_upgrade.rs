@@ -1,9 +1,11 @@
 //! Code to deal with safekeeper control file upgrades
 use crate::{
+    control_file::SK_FORMAT_VERSION,
     safekeeper::{AcceptorState, PgUuid, ServerInfo, Term, TermHistory, TermLsn},
     state::{PersistedPeers, TimelinePersistentState},
 };
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, Result};
+use bytes::Bytes;
 use pq_proto::SystemId;
 use serde::{Deserialize, Serialize};
 use tracing::*;
@@ -266,6 +268,163 @@ pub fn upgrade_control_file(buf: &[u8], version: u32) -> Result<TimelinePersiste
     bail!("unsupported safekeeper control file version {}", version)
 }
 
+/// Serializes `state` in the on-disk format of control file version `target_version`,
+/// to be read by an older safekeeper binary, e.g. after a rollback. Like the buffer
+/// passed to [`upgrade_control_file`], the result excludes the magic, version and
+/// checksum. Fails if `state` has fields which can't be represented in the target
+/// version, i.e. if upgrading the result again wouldn't give back `state`.
+pub fn downgrade_control_file(
+    state: &TimelinePersistentState,
+    target_version: u32,
+) -> Result<Bytes> {
+    // Fields which didn't exist before version 5, and which upgrades from those
+    // versions fill with defaults.
+    let ensure_pre_v5_representable = || -> Result<()> {
+        ensure!(
+            state.timeline_start_lsn == Lsn(0) && state.local_start_lsn == Lsn(0),
+            "timeline_start_lsn {} and local_start_lsn {} can't be represented in control file version {target_version}",
+            state.timeline_start_lsn,
+            state.local_start_lsn
+        );
+        ensure!(
+            state.remote_consistent_lsn == Lsn(0),
+            "remote_consistent_lsn {} can't be represented in control file version {target_version}",
+            state.remote_consistent_lsn
+        );
+        ensure!(
+            state.peers.0.is_empty(),
+            "peers can't be represented in control file version {target_version}"
+        );
+        Ok(())
+    };
+
+    let buf = match target_version {
+        1 => {
+            ensure_pre_v5_representable()?;
+            ensure!(
+                state.backup_lsn == Lsn(0),
+                "backup_lsn {} can't be represented in control file version {target_version}",
+                state.backup_lsn
+            );
+            // Version 1 only stored the epoch, which upgrades turn into a single term
+            // history entry starting at Lsn(0).
+            let epoch = match state.acceptor_state.term_history.0.as_slice() {
+                [TermLsn { term, lsn: Lsn(0) }] => *term,
+                _ => bail!(
+                    "term history {:?} can't be represented in control file version {target_version}",
+                    state.acceptor_state.term_history
+                ),
+            };
+            SafeKeeperStateV1 {
+                acceptor_state: AcceptorStateV1 {
+                    term: state.acceptor_state.term,
+                    epoch,
+                },
+                server: ServerInfoV2 {
+                    pg_version: state.server.pg_version,
+                    system_id: state.server.system_id,
+                    tenant_id: state.tenant_id,
+                    timeline_id: state.timeline_id,
+                    wal_seg_size: state.server.wal_seg_size,
+                },
+                proposer_uuid: state.proposer_uuid,
+                commit_lsn: state.commit_lsn,
+                truncate_lsn: state.peer_horizon_lsn,
+                // Not carried over by upgrades.
+                wal_start_lsn: Lsn(0),
+            }
+            .ser()?
+        }
+        2 => {
+            ensure_pre_v5_representable()?;
+            ensure!(
+                state.backup_lsn == Lsn(0),
+                "backup_lsn {} can't be represented in control file version {target_version}",
+                state.backup_lsn
+            );
+            SafeKeeperStateV2 {
+                acceptor_state: state.acceptor_state.clone(),
+                server: ServerInfoV2 {
+                    pg_version: state.server.pg_version,
+                    system_id: state.server.system_id,
+                    tenant_id: state.tenant_id,
+                    timeline_id: state.timeline_id,
+                    wal_seg_size: state.server.wal_seg_size,
+                },
+                proposer_uuid: state.proposer_uuid,
+                commit_lsn: state.commit_lsn,
+                truncate_lsn: state.peer_horizon_lsn,
+                wal_start_lsn: Lsn(0),
+            }
+            .ser()?
+        }
+        3 => {
+            ensure_pre_v5_representable()?;
+            ensure!(
+                state.backup_lsn == Lsn(0),
+                "backup_lsn {} can't be represented in control file version {target_version}",
+                state.backup_lsn
+            );
+            SafeKeeperStateV3 {
+                acceptor_state: state.acceptor_state.clone(),
+                server: ServerInfoV3 {
+                    pg_version: state.server.pg_version,
+                    system_id: state.server.system_id,
+                    tenant_id: state.tenant_id,
+                    timeline_id: state.timeline_id,
+                    wal_seg_size: state.server.wal_seg_size,
+                },
+                proposer_uuid: state.proposer_uuid,
+                commit_lsn: state.commit_lsn,
+                truncate_lsn: state.peer_horizon_lsn,
+                wal_start_lsn: Lsn(0),
+            }
+            .ser()?
+        }
+        4 => {
+            ensure_pre_v5_representable()?;
+            ensure!(
+                state.backup_lsn == Lsn::INVALID,
+                "backup_lsn {} can't be represented in control file version {target_version}",
+                state.backup_lsn
+            );
+            SafeKeeperStateV4 {
+                tenant_id: state.tenant_id,
+                timeline_id: state.timeline_id,
+                acceptor_state: state.acceptor_state.clone(),
+                server: state.server.clone(),
+                proposer_uuid: state.proposer_uuid,
+                commit_lsn: state.commit_lsn,
+                // Upgrades don't read these, backup progress is recomputed instead.
+                s3_wal_lsn: Lsn(0),
+                peer_horizon_lsn: state.peer_horizon_lsn,
+                remote_consistent_lsn: Lsn(0),
+                peers: PersistedPeers(vec![]),
+            }
+            .ser()?
+        }
+        // Versions 5 and 6 share the current layout, upgrades only patch up values
+        // which were missing at the time.
+        5 => {
+            ensure!(
+                state.timeline_start_lsn != Lsn(0),
+                "zero timeline_start_lsn can't be represented in control file version {target_version}"
+            );
+            state.ser()?
+        }
+        6 => {
+            ensure!(
+                state.server.pg_version != 0,
+                "zero pg_version can't be represented in control file version {target_version}"
+            );
+            state.ser()?
+        }
+        SK_FORMAT_VERSION => state.ser()?,
+        _ => bail!("unsupported safekeeper control file version {target_version}"),
+    };
+    Ok(Bytes::from(buf))
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -506,4 +665,172 @@ mod tests {
 
         assert_eq!(state, deser);
     }
+
+    /// A state which versions before 5 can represent: none of the fields added later
+    /// are set, and the term history is what upgrades from version 1 produce.
+    fn pre_v5_state() -> TimelinePersistentState {
+        let tenant_id = TenantId::from_str("cf0480929707ee75372337efaa5ecf96").unwrap();
+        let timeline_id = TimelineId::from_str("112ded66422aa5e953e5440fa5427ac4").unwrap();
+        TimelinePersistentState {
+            tenant_id,
+            timeline_id,
+            acceptor_state: AcceptorState {
+                term: 42,
+                term_history: TermHistory(vec![TermLsn {
+                    term: 41,
+                    lsn: Lsn(0),
+                }]),
+            },
+            server: ServerInfo {
+                pg_version: 140005,
+                system_id: 0x1234567887654321,
+                wal_seg_size: 0x12345678,
+            },
+            proposer_uuid: {
+                let mut arr = timeline_id.as_arr();
+                arr.reverse();
+                arr
+            },
+            timeline_start_lsn: Lsn(0),
+            local_start_lsn: Lsn(0),
+            commit_lsn: Lsn(1234567800),
+            backup_lsn: Lsn(0),
+            peer_horizon_lsn: Lsn(9999999),
+            remote_consistent_lsn: Lsn(0),
+            peers: PersistedPeers(vec![]),
+        }
+    }
+
+    fn current_state() -> TimelinePersistentState {
+        TimelinePersistentState {
+            acceptor_state: AcceptorState {
+                term: 42,
+                term_history: TermHistory(vec![
+                    TermLsn {
+                        term: 41,
+                        lsn: Lsn(0x1),
+                    },
+                    TermLsn {
+                        term: 42,
+                        lsn: Lsn(1234567000),
+                    },
+                ]),
+            },
+            timeline_start_lsn: Lsn(0x1),
+            local_start_lsn: Lsn(0x1),
+            backup_lsn: Lsn(1234567300),
+            remote_consistent_lsn: Lsn(1234560000),
+            peers: PersistedPeers(vec![(
+                NodeId(1),
+                PersistedPeerInfo {
+                    backup_lsn: Lsn(1234567000),
+                    term: 42,
+                    flush_lsn: Lsn(1234567800 - 8),
+                    commit_lsn: Lsn(1234567600),
+                },
+            )]),
+            ..pre_v5_state()
+        }
+    }
+
+    fn upgrade(buf: &[u8], version: u32) -> TimelinePersistentState {
+        if version == SK_FORMAT_VERSION {
+            TimelinePersistentState::des(buf).unwrap()
+        } else {
+            upgrade_control_file(buf, version).unwrap()
+        }
+    }
+
+    #[test]
+    fn downgrade_then_upgrade() {
+        for version in 1..=SK_FORMAT_VERSION {
+            let state = if version < 5 {
+                pre_v5_state()
+            } else {
+                current_state()
+            };
+            let buf = downgrade_control_file(&state, version).unwrap();
+            assert_eq!(upgrade(&buf, version), state, "version {version}");
+        }
+    }
+
+    #[test]
+    fn upgrade_then_downgrade() {
+        // Upgrading loses the information newer versions don't keep, after that
+        // downgrading and upgrading again must be lossless.
+        let tenant_id = TenantId::from_str("cf0480929707ee75372337efaa5ecf96").unwrap();
+        let timeline_id = TimelineId::from_str("112ded66422aa5e953e5440fa5427ac4").unwrap();
+        let v1 = SafeKeeperStateV1 {
+            acceptor_state: AcceptorStateV1 {
+                term: 42,
+                epoch: 43,
+            },
+            server: ServerInfoV2 {
+                pg_version: 14,
+                system_id: 0x1234567887654321,
+                tenant_id,
+                timeline_id,
+                wal_seg_size: 0x12345678,
+            },
+            proposer_uuid: timeline_id.as_arr(),
+            commit_lsn: Lsn(1234567800),
+            truncate_lsn: Lsn(123456780),
+            wal_start_lsn: Lsn(1234567800 - 8),
+        };
+        let v4 = SafeKeeperStateV4 {
+            tenant_id,
+            timeline_id,
+            acceptor_state: current_state().acceptor_state,
+            server: current_state().server,
+            proposer_uuid: timeline_id.as_arr(),
+            commit_lsn: Lsn(1234567800),
+            s3_wal_lsn: Lsn(1234567300),
+            peer_horizon_lsn: Lsn(9999999),
+            remote_consistent_lsn: Lsn(1234560000),
+            peers: current_state().peers,
+        };
+        for (version, buf) in [
+            (1, v1.ser().unwrap()),
+            (4, v4.ser().unwrap()),
+            (5, current_state().ser().unwrap()),
+            (6, current_state().ser().unwrap()),
+            (SK_FORMAT_VERSION, current_state().ser().unwrap()),
+        ] {
+            let upgraded = upgrade(&buf, version);
+            let downgraded = downgrade_control_file(&upgraded, version).unwrap();
+            assert_eq!(upgrade(&downgraded, version), upgraded, "version {version}");
+        }
+    }
+
+    #[test]
+    fn downgrade_unrepresentable() {
+        // Fields added in version 5 and later.
+        for version in 1..5 {
+            assert!(downgrade_control_file(&current_state(), version).is_err());
+        }
+        // Version 1 has no term history, only the epoch.
+        let state = TimelinePersistentState {
+            acceptor_state: current_state().acceptor_state,
+            ..pre_v5_state()
+        };
+        assert!(downgrade_control_file(&state, 1).is_err());
+        downgrade_control_file(&state, 2).unwrap();
+        // Versions 5 and 6 read zero values as "unknown".
+        let state = TimelinePersistentState {
+            timeline_start_lsn: Lsn(0),
+            ..current_state()
+        };
+        assert!(downgrade_control_file(&state, 5).is_err());
+        let state = TimelinePersistentState {
+            server: ServerInfo {
+                pg_version: 0,
+                ..current_state().server
+            },
+            ..current_state()
+        };
+        assert!(downgrade_control_file(&state, 6).is_err());
+
+        assert!(downgrade_control_file(&current_state(), 0).is_err());
+        assert!(downgrade_control_file(&current_state(), SK_FORMAT_VERSION + 1).is_err());
+    }
 }
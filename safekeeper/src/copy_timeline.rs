@@ -17,7 +17,7 @@ use crate::{
     state::TimelinePersistentState,
     timeline::{Timeline, TimelineError},
     wal_backup::copy_s3_segments,
-    wal_storage::{wal_file_paths, WalReader},
+    wal_storage::{check_segments_on_disk, wal_file_paths, WalReader},
     GlobalTimelines, SafeKeeperConf,
 };
 
@@ -117,6 +117,18 @@ pub async fn handle_request(request: Request) -> Result<()> {
     assert!(first_ondisk_segment <= last_segment);
     assert!(first_ondisk_segment >= first_segment);
 
+    // Segments which are not fully backed up must be read from disk, make sure
+    // none of them is gone before we start copying anything.
+    let first_local_segment = first_ondisk_segment.max(backup_lsn.segment_number(wal_seg_size));
+    if first_local_segment <= last_segment {
+        check_segments_on_disk(
+            &conf.timeline_dir(&request.source.ttid),
+            wal_seg_size,
+            first_local_segment..=last_segment,
+        )
+        .await?;
+    }
+
     copy_s3_segments(
         wal_seg_size,
         &request.source.ttid,
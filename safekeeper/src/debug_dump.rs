@@ -187,18 +187,25 @@ pub async fn build(args: Args) -> Result<Response> {
     let start_time = Utc::now();
     let timelines_count = GlobalTimelines::timelines_count();
 
-    let ptrs_snapshot = if args.tenant_id.is_some() && args.timeline_id.is_some() {
+    // Per-timeline state is only collected when the response is serialized, so
+    // narrowing down the list here is enough to skip non-matching timelines.
+    let ptrs_snapshot = match (args.tenant_id, args.timeline_id) {
         // If both tenant_id and timeline_id are specified, we can just get the
         // timeline directly, without taking a snapshot of the whole list.
-        let ttid = TenantTimelineId::new(args.tenant_id.unwrap(), args.timeline_id.unwrap());
-        if let Ok(tli) = GlobalTimelines::get(ttid) {
-            vec![tli]
-        } else {
-            vec![]
+        (Some(tenant_id), Some(timeline_id)) => {
+            let ttid = TenantTimelineId::new(tenant_id, timeline_id);
+            if let Ok(tli) = GlobalTimelines::get(ttid) {
+                vec![tli]
+            } else {
+                vec![]
+            }
         }
-    } else {
+        (Some(tenant_id), None) => GlobalTimelines::get_all_for_tenant(tenant_id)
+            .into_iter()
+            .filter(|tli| !tli.is_cancelled())
+            .collect(),
         // Otherwise, take a snapshot of the whole list.
-        GlobalTimelines::get_all()
+        (None, _) => GlobalTimelines::get_all(),
     };
 
     let mut timelines = Vec::new();
@@ -107,6 +107,10 @@ pub struct TimelineStatus {
     pub backup_lsn: Lsn,
     pub peer_horizon_lsn: Lsn,
     pub remote_consistent_lsn: Lsn,
+    /// Min LSN of pageserver walsenders, flush_lsn if there are none. Absent in statuses of
+    /// safekeepers that predate it, which peers fetch during a rolling upgrade.
+    #[serde(default)]
+    pub walsenders_horizon_lsn: Option<Lsn>,
     pub peers: Vec<PeerInfo>,
     pub walsenders: Vec<WalSenderState>,
     pub walreceivers: Vec<WalReceiverState>,
@@ -161,6 +165,7 @@ async fn timeline_status_handler(request: Request<Body>) -> Result<Response<Body
         backup_lsn: inmem.backup_lsn,
         peer_horizon_lsn: inmem.peer_horizon_lsn,
         remote_consistent_lsn: inmem.remote_consistent_lsn,
+        walsenders_horizon_lsn: Some(tli.get_walsenders().horizon_lsn(flush_lsn)),
         peers: tli.get_peers(conf).await,
         walsenders: tli.get_walsenders().get_all(),
         walreceivers: tli.get_walreceivers().get_all(),
@@ -485,6 +490,31 @@ async fn patch_control_file_handler(
     json_response(StatusCode::OK, response)
 }
 
+async fn peer_horizon_lsn_handler(mut request: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permission(&request, None)?;
+
+    let ttid = TenantTimelineId::new(
+        parse_request_param(&request, "tenant_id")?,
+        parse_request_param(&request, "timeline_id")?,
+    );
+
+    let tli = GlobalTimelines::get(ttid).map_err(ApiError::from)?;
+
+    let peer_horizon_request: patch_control_file::PeerHorizonRequest =
+        json_request(&mut request).await?;
+    let response = patch_control_file::handle_peer_horizon_request(tli, peer_horizon_request)
+        .await
+        .map_err(|e| {
+            if e.is::<patch_control_file::PeerHorizonAheadError>() {
+                ApiError::BadRequest(e)
+            } else {
+                ApiError::InternalServerError(e)
+            }
+        })?;
+
+    json_response(StatusCode::OK, response)
+}
+
 /// Safekeeper http router.
 pub fn make_router(conf: SafeKeeperConf) -> RouterBuilder<hyper::Body, ApiError> {
     let mut router = endpoint::make_router();
@@ -550,6 +580,10 @@ pub fn make_router(conf: SafeKeeperConf) -> RouterBuilder<hyper::Body, ApiError>
             "/v1/tenant/:tenant_id/timeline/:timeline_id/control_file",
             |r| request_span(r, patch_control_file_handler),
         )
+        .put(
+            "/v1/tenant/:tenant_id/timeline/:timeline_id/control_file/peer_horizon_lsn",
+            |r| request_span(r, peer_horizon_lsn_handler),
+        )
         // for tests
         .post("/v1/record_safekeeper_info/:tenant_id/:timeline_id", |r| {
             request_span(r, record_safekeeper_info)
@@ -60,6 +60,9 @@ struct AppendResult {
     state: TimelinePersistentState,
     // info about new record in the WAL
     inserted_wal: InsertedWAL,
+    // LSNs of the timeline after append
+    flush_lsn: Lsn,
+    commit_lsn: Lsn,
 }
 
 /// Handles command to craft logical message WAL record with given
@@ -81,9 +84,12 @@ pub async fn handle_json_ctrl<IO: AsyncRead + AsyncWrite + Unpin>(
     }
 
     let inserted_wal = append_logical_message(&tli, append_request).await?;
+    let (inmem, state) = tli.get_state().await;
     let response = AppendResult {
-        state: tli.get_state().await.1,
+        state,
         inserted_wal,
+        flush_lsn: tli.get_flush_lsn().await,
+        commit_lsn: inmem.commit_lsn,
     };
     let response_data = serde_json::to_vec(&response)
         .with_context(|| format!("Response {response:?} is not a json array"))?;
@@ -1,4 +1,5 @@
 #![deny(clippy::undocumented_unsafe_blocks)]
+use anyhow::{ensure, Context};
 use camino::Utf8PathBuf;
 use once_cell::sync::Lazy;
 use remote_storage::RemoteStorageConfig;
@@ -43,11 +44,14 @@ use utils::auth::JwtAuth;
 pub mod defaults {
     pub use safekeeper_api::{
         DEFAULT_HTTP_LISTEN_ADDR, DEFAULT_HTTP_LISTEN_PORT, DEFAULT_PG_LISTEN_ADDR,
-        DEFAULT_PG_LISTEN_PORT,
+        DEFAULT_PG_LISTEN_ADDR_TENANT_ONLY, DEFAULT_PG_LISTEN_PORT,
+        DEFAULT_PG_LISTEN_PORT_TENANT_ONLY,
     };
 
     pub const DEFAULT_HEARTBEAT_TIMEOUT: &str = "5000ms";
     pub const DEFAULT_MAX_OFFLOADER_LAG_BYTES: u64 = 128 * (1 << 20);
+    pub const DEFAULT_WAL_REMOVAL_BATCH_SIZE: usize = 100;
+    pub const DEFAULT_MAX_RECEIVE_WAL_INFLIGHT_BYTES: u64 = 128 * (1 << 20);
 }
 
 #[derive(Debug, Clone)]
@@ -72,13 +76,26 @@ pub struct SafeKeeperConf {
     pub peer_recovery_enabled: bool,
     pub remote_storage: Option<RemoteStorageConfig>,
     pub max_offloader_lag_bytes: u64,
+    /// Max amount of WAL received from compute but not yet fsynced; reading
+    /// from the socket pauses when it is reached.
+    pub max_receive_wal_inflight_bytes: u64,
     pub backup_parallel_jobs: usize,
+    /// Upper bound on the aggregate WAL upload rate to remote storage, across all
+    /// timelines and parallel jobs. Unlimited if None.
+    pub wal_backup_max_bytes_per_sec: Option<u64>,
     pub wal_backup_enabled: bool,
     pub pg_auth: Option<Arc<JwtAuth>>,
     pub pg_tenant_only_auth: Option<Arc<JwtAuth>>,
     pub http_auth: Option<Arc<SwappableJwtAuth>>,
     pub current_thread_runtime: bool,
     pub walsenders_keep_horizon: bool,
+    /// Max number of WAL segments removed from a timeline in one pass of the
+    /// WAL removal task, the rest is left for the following passes.
+    pub wal_removal_batch_size: usize,
+    /// Number of worker threads of the WAL service runtime, tokio default if None.
+    pub wal_service_worker_threads: Option<usize>,
+    /// Number of worker threads of the WAL backup runtime, tokio default if None.
+    pub wal_backup_worker_threads: Option<usize>,
 }
 
 impl SafeKeeperConf {
@@ -91,9 +108,68 @@ impl SafeKeeperConf {
             .join(ttid.timeline_id.to_string())
     }
 
+    /// Address advertised to peers and computes: `advertise_pg_addr` if set,
+    /// `listen_pg_addr` otherwise.
+    pub fn effective_advertise_pg_addr(&self) -> &str {
+        self.advertise_pg_addr
+            .as_deref()
+            .unwrap_or(&self.listen_pg_addr)
+    }
+
+    /// Address to advertise to clients which are allowed to use only tenant
+    /// scoped tokens: `listen_pg_addr_tenant_only` if set, falling back to
+    /// [`Self::effective_advertise_pg_addr`].
+    pub fn effective_advertise_pg_addr_tenant_only(&self) -> &str {
+        self.listen_pg_addr_tenant_only
+            .as_deref()
+            .unwrap_or_else(|| self.effective_advertise_pg_addr())
+    }
+
     pub fn is_wal_backup_enabled(&self) -> bool {
         self.remote_storage.is_some() && self.wal_backup_enabled
     }
+
+    /// Check invariants between config values which can't be expressed by
+    /// their types. Should be called once at startup.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.max_offloader_lag_bytes > 0,
+            "max_offloader_lag_bytes must be positive"
+        );
+        ensure!(
+            self.heartbeat_timeout <= self.broker_keepalive_interval,
+            "heartbeat_timeout ({:?}) must not exceed broker_keepalive_interval ({:?})",
+            self.heartbeat_timeout,
+            self.broker_keepalive_interval
+        );
+        ensure!(
+            self.max_receive_wal_inflight_bytes > 0
+                && self.max_receive_wal_inflight_bytes <= u32::MAX as u64,
+            "max_receive_wal_inflight_bytes must be in (0, {}]",
+            u32::MAX
+        );
+        ensure!(
+            self.backup_parallel_jobs >= 1,
+            "backup_parallel_jobs must be at least 1"
+        );
+        ensure!(
+            self.wal_removal_batch_size >= 1,
+            "wal_removal_batch_size must be at least 1"
+        );
+        ensure!(
+            self.wal_backup_max_bytes_per_sec != Some(0),
+            "wal_backup_max_bytes_per_sec must be positive"
+        );
+        ensure!(
+            self.wal_service_worker_threads != Some(0) && self.wal_backup_worker_threads != Some(0),
+            "runtime worker thread counts must be positive"
+        );
+        ensure!(
+            !self.wal_backup_enabled || self.remote_storage.is_some(),
+            "WAL backup is enabled, but remote storage is not configured"
+        );
+        Ok(())
+    }
 }
 
 impl SafeKeeperConf {
@@ -114,27 +190,194 @@ impl SafeKeeperConf {
                 .expect("failed to parse default broker endpoint"),
             broker_keepalive_interval: Duration::from_secs(5),
             peer_recovery_enabled: true,
-            wal_backup_enabled: true,
+            wal_backup_enabled: false,
             backup_parallel_jobs: 1,
+            wal_backup_max_bytes_per_sec: None,
             pg_auth: None,
             pg_tenant_only_auth: None,
             http_auth: None,
             heartbeat_timeout: Duration::new(5, 0),
             max_offloader_lag_bytes: defaults::DEFAULT_MAX_OFFLOADER_LAG_BYTES,
+            max_receive_wal_inflight_bytes: defaults::DEFAULT_MAX_RECEIVE_WAL_INFLIGHT_BYTES,
             current_thread_runtime: false,
             walsenders_keep_horizon: false,
+            wal_removal_batch_size: defaults::DEFAULT_WAL_REMOVAL_BATCH_SIZE,
+            wal_service_worker_threads: None,
+            wal_backup_worker_threads: None,
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use remote_storage::RemoteStorageKind;
+
+    #[test]
+    fn effective_advertise_pg_addr() {
+        // Nothing set: fall back to the listen address.
+        let conf = SafeKeeperConf {
+            listen_pg_addr: "127.0.0.1:5454".to_string(),
+            ..SafeKeeperConf::dummy()
+        };
+        assert_eq!(conf.effective_advertise_pg_addr(), "127.0.0.1:5454");
+        assert_eq!(
+            conf.effective_advertise_pg_addr_tenant_only(),
+            "127.0.0.1:5454"
+        );
+
+        // Advertise address set.
+        let conf = SafeKeeperConf {
+            listen_pg_addr: "127.0.0.1:5454".to_string(),
+            advertise_pg_addr: Some("sk-1.local:5454".to_string()),
+            ..SafeKeeperConf::dummy()
+        };
+        assert_eq!(conf.effective_advertise_pg_addr(), "sk-1.local:5454");
+        assert_eq!(
+            conf.effective_advertise_pg_addr_tenant_only(),
+            "sk-1.local:5454"
+        );
+
+        // Only the tenant only address set.
+        let conf = SafeKeeperConf {
+            listen_pg_addr: "127.0.0.1:5454".to_string(),
+            listen_pg_addr_tenant_only: Some("127.0.0.1:5455".to_string()),
+            ..SafeKeeperConf::dummy()
+        };
+        assert_eq!(conf.effective_advertise_pg_addr(), "127.0.0.1:5454");
+        assert_eq!(
+            conf.effective_advertise_pg_addr_tenant_only(),
+            "127.0.0.1:5455"
+        );
+
+        // Both advertise and tenant only addresses set.
+        let conf = SafeKeeperConf {
+            listen_pg_addr: "127.0.0.1:5454".to_string(),
+            advertise_pg_addr: Some("sk-1.local:5454".to_string()),
+            listen_pg_addr_tenant_only: Some("sk-1.local:5455".to_string()),
+            ..SafeKeeperConf::dummy()
+        };
+        assert_eq!(conf.effective_advertise_pg_addr(), "sk-1.local:5454");
+        assert_eq!(
+            conf.effective_advertise_pg_addr_tenant_only(),
+            "sk-1.local:5455"
+        );
+    }
+
+    #[test]
+    fn validate_dummy() {
+        SafeKeeperConf::dummy().validate().unwrap();
+    }
+
+    #[test]
+    fn validate_max_offloader_lag_bytes() {
+        let conf = SafeKeeperConf {
+            max_offloader_lag_bytes: 0,
+            ..SafeKeeperConf::dummy()
+        };
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_heartbeat_timeout() {
+        let conf = SafeKeeperConf {
+            heartbeat_timeout: Duration::from_secs(10),
+            broker_keepalive_interval: Duration::from_secs(5),
+            ..SafeKeeperConf::dummy()
+        };
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_max_receive_wal_inflight_bytes() {
+        let conf = SafeKeeperConf {
+            max_receive_wal_inflight_bytes: 0,
+            ..SafeKeeperConf::dummy()
+        };
+        assert!(conf.validate().is_err());
+
+        let conf = SafeKeeperConf {
+            max_receive_wal_inflight_bytes: u32::MAX as u64 + 1,
+            ..SafeKeeperConf::dummy()
+        };
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_backup_parallel_jobs() {
+        let conf = SafeKeeperConf {
+            backup_parallel_jobs: 0,
+            ..SafeKeeperConf::dummy()
+        };
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_wal_removal_batch_size() {
+        let conf = SafeKeeperConf {
+            wal_removal_batch_size: 0,
+            ..SafeKeeperConf::dummy()
+        };
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_wal_backup_max_bytes_per_sec() {
+        let conf = SafeKeeperConf {
+            wal_backup_max_bytes_per_sec: Some(0),
+            ..SafeKeeperConf::dummy()
+        };
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_wal_backup_without_remote_storage() {
+        let conf = SafeKeeperConf {
+            wal_backup_enabled: true,
+            remote_storage: None,
+            ..SafeKeeperConf::dummy()
+        };
+        assert!(conf.validate().is_err());
+
+        let conf = SafeKeeperConf {
+            wal_backup_enabled: true,
+            remote_storage: Some(RemoteStorageConfig {
+                storage: RemoteStorageKind::LocalFs(Utf8PathBuf::from("/tmp/remote_storage")),
+                timeout: RemoteStorageConfig::DEFAULT_TIMEOUT,
+            }),
+            ..SafeKeeperConf::dummy()
+        };
+        conf.validate().unwrap();
+    }
+}
+
+/// Tokio runtimes whose size is configurable in [`SafeKeeperConf`], so they
+/// can't be lazily initialized statics like the rest.
+pub struct ConfiguredRuntimes {
+    pub wal_service: Runtime,
+    pub wal_backup: Runtime,
+}
+
+impl ConfiguredRuntimes {
+    pub fn new(conf: &SafeKeeperConf) -> anyhow::Result<Self> {
+        let build = |thread_name: &str, worker_threads: Option<usize>| {
+            let mut builder = tokio::runtime::Builder::new_multi_thread();
+            builder.thread_name(thread_name).enable_all();
+            if let Some(worker_threads) = worker_threads {
+                builder.worker_threads(worker_threads);
+            }
+            builder
+                .build()
+                .with_context(|| format!("Failed to create {thread_name} runtime"))
+        };
+        Ok(ConfiguredRuntimes {
+            wal_service: build("WAL service worker", conf.wal_service_worker_threads)?,
+            wal_backup: build("WAL backup worker", conf.wal_backup_worker_threads)?,
+        })
+    }
+}
+
 // Tokio runtimes.
-pub static WAL_SERVICE_RUNTIME: Lazy<Runtime> = Lazy::new(|| {
-    tokio::runtime::Builder::new_multi_thread()
-        .thread_name("WAL service worker")
-        .enable_all()
-        .build()
-        .expect("Failed to create WAL service runtime")
-});
 
 pub static HTTP_RUNTIME: Lazy<Runtime> = Lazy::new(|| {
     tokio::runtime::Builder::new_multi_thread()
@@ -162,14 +405,6 @@ pub static WAL_REMOVER_RUNTIME: Lazy<Runtime> = Lazy::new(|| {
         .expect("Failed to create broker runtime")
 });
 
-pub static WAL_BACKUP_RUNTIME: Lazy<Runtime> = Lazy::new(|| {
-    tokio::runtime::Builder::new_multi_thread()
-        .thread_name("WAL backup worker")
-        .enable_all()
-        .build()
-        .expect("Failed to create WAL backup runtime")
-});
-
 pub static METRICS_SHIFTER_RUNTIME: Lazy<Runtime> = Lazy::new(|| {
     tokio::runtime::Builder::new_multi_thread()
         .thread_name("metric shifter")
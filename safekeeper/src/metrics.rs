@@ -11,8 +11,8 @@ use futures::Future;
 use metrics::{
     core::{AtomicU64, Collector, Desc, GenericCounter, GenericGaugeVec, Opts},
     proto::MetricFamily,
-    register_int_counter, register_int_counter_pair_vec, register_int_counter_vec, Gauge,
-    IntCounter, IntCounterPairVec, IntCounterVec, IntGaugeVec,
+    register_int_counter, register_int_counter_pair_vec, register_int_counter_vec,
+    register_int_gauge_vec, Gauge, IntCounter, IntCounterPairVec, IntCounterVec, IntGaugeVec,
 };
 use once_cell::sync::Lazy;
 
@@ -140,6 +140,14 @@ pub static BROKER_ITERATION_TIMELINES: Lazy<Histogram> = Lazy::new(|| {
     )
     .expect("Failed to register safekeeper_broker_iteration_timelines histogram vec")
 });
+pub static PEER_RECOVERY_LAG_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "safekeeper_peer_recovery_lag_bytes",
+        "Distance between donor flush_lsn and local flush_lsn during peer recovery",
+        &["tenant_id", "timeline_id"]
+    )
+    .expect("Failed to register safekeeper_peer_recovery_lag_bytes gauge vec")
+});
 
 pub const LABEL_UNKNOWN: &str = "unknown";
 
@@ -312,6 +320,7 @@ pub struct FullTimelineInfo {
     pub persisted_state: TimelinePersistentState,
 
     pub flush_lsn: Lsn,
+    pub walsenders_horizon_lsn: Lsn,
 
     pub wal_storage: WalStorageMetrics,
 }
@@ -325,6 +334,7 @@ pub struct TimelineCollector {
     epoch_start_lsn: GenericGaugeVec<AtomicU64>,
     peer_horizon_lsn: GenericGaugeVec<AtomicU64>,
     remote_consistent_lsn: GenericGaugeVec<AtomicU64>,
+    walsenders_horizon_lsn: GenericGaugeVec<AtomicU64>,
     ps_last_received_lsn: GenericGaugeVec<AtomicU64>,
     feedback_last_time_seconds: GenericGaugeVec<AtomicU64>,
     timeline_active: GenericGaugeVec<AtomicU64>,
@@ -410,6 +420,16 @@ impl TimelineCollector {
         .unwrap();
         descs.extend(remote_consistent_lsn.desc().into_iter().cloned());
 
+        let walsenders_horizon_lsn = GenericGaugeVec::new(
+            Opts::new(
+                "safekeeper_walsenders_horizon_lsn",
+                "Min LSN of pageserver walsenders, flush_lsn if there are none",
+            ),
+            &["tenant_id", "timeline_id"],
+        )
+        .unwrap();
+        descs.extend(walsenders_horizon_lsn.desc().into_iter().cloned());
+
         let ps_last_received_lsn = GenericGaugeVec::new(
             Opts::new(
                 "safekeeper_ps_last_received_lsn",
@@ -536,6 +556,7 @@ impl TimelineCollector {
             epoch_start_lsn,
             peer_horizon_lsn,
             remote_consistent_lsn,
+            walsenders_horizon_lsn,
             ps_last_received_lsn,
             feedback_last_time_seconds,
             timeline_active,
@@ -568,6 +589,7 @@ impl Collector for TimelineCollector {
         self.epoch_start_lsn.reset();
         self.peer_horizon_lsn.reset();
         self.remote_consistent_lsn.reset();
+        self.walsenders_horizon_lsn.reset();
         self.ps_last_received_lsn.reset();
         self.feedback_last_time_seconds.reset();
         self.timeline_active.reset();
@@ -622,6 +644,9 @@ impl Collector for TimelineCollector {
             self.remote_consistent_lsn
                 .with_label_values(labels)
                 .set(tli.mem_state.remote_consistent_lsn.into());
+            self.walsenders_horizon_lsn
+                .with_label_values(labels)
+                .set(tli.walsenders_horizon_lsn.into());
             self.timeline_active
                 .with_label_values(labels)
                 .set(tli.timeline_is_active as u64);
@@ -677,6 +702,7 @@ impl Collector for TimelineCollector {
         mfs.extend(self.epoch_start_lsn.collect());
         mfs.extend(self.peer_horizon_lsn.collect());
         mfs.extend(self.remote_consistent_lsn.collect());
+        mfs.extend(self.walsenders_horizon_lsn.collect());
         mfs.extend(self.ps_last_received_lsn.collect());
         mfs.extend(self.feedback_last_time_seconds.collect());
         mfs.extend(self.timeline_active.collect());
@@ -3,6 +3,7 @@ use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tracing::info;
+use utils::lsn::Lsn;
 
 use crate::{state::TimelinePersistentState, timeline::Timeline};
 
@@ -14,6 +15,21 @@ pub struct Request {
     pub apply_fields: Vec<String>,
 }
 
+/// Request to reset peer_horizon_lsn, e.g. after split-brain.
+#[derive(Deserialize, Debug, Clone)]
+pub struct PeerHorizonRequest {
+    pub peer_horizon_lsn: Lsn,
+}
+
+/// [`PeerHorizonRequest`] is refused because WAL beyond flush_lsn doesn't exist on this
+/// safekeeper.
+#[derive(thiserror::Error, Debug)]
+#[error("peer_horizon_lsn {peer_horizon_lsn} is ahead of flush_lsn {flush_lsn}")]
+pub struct PeerHorizonAheadError {
+    pub peer_horizon_lsn: Lsn,
+    pub flush_lsn: Lsn,
+}
+
 #[derive(Serialize)]
 pub struct Response {
     pub old_control_file: TimelinePersistentState,
@@ -24,7 +40,7 @@ pub struct Response {
 /// fields from the request and persist the new state on disk.
 pub async fn handle_request(tli: Arc<Timeline>, request: Request) -> anyhow::Result<Response> {
     let response = tli
-        .map_control_file(|state| {
+        .map_control_file(|state, _flush_lsn| {
             let old_control_file = state.clone();
             let new_control_file = state_apply_diff(&old_control_file, &request)?;
 
@@ -44,6 +60,51 @@ pub async fn handle_request(tli: Arc<Timeline>, request: Request) -> anyhow::Res
     Ok(response)
 }
 
+/// Set peer_horizon_lsn to the given value and persist it. Refuses to move it
+/// beyond flush_lsn with [`PeerHorizonAheadError`].
+pub async fn handle_peer_horizon_request(
+    tli: Arc<Timeline>,
+    request: PeerHorizonRequest,
+) -> anyhow::Result<Response> {
+    let response = tli
+        .map_control_file(|state, flush_lsn| {
+            let old_control_file = state.clone();
+            let new_control_file =
+                state_set_peer_horizon_lsn(&old_control_file, request.peer_horizon_lsn, flush_lsn)?;
+
+            info!(
+                "resetting peer_horizon_lsn from {} to {}, flush_lsn {}",
+                old_control_file.peer_horizon_lsn, new_control_file.peer_horizon_lsn, flush_lsn
+            );
+            *state = new_control_file.clone();
+
+            Ok(Response {
+                old_control_file,
+                new_control_file,
+            })
+        })
+        .await?;
+
+    Ok(response)
+}
+
+fn state_set_peer_horizon_lsn(
+    state: &TimelinePersistentState,
+    peer_horizon_lsn: Lsn,
+    flush_lsn: Lsn,
+) -> anyhow::Result<TimelinePersistentState> {
+    if peer_horizon_lsn > flush_lsn {
+        return Err(PeerHorizonAheadError {
+            peer_horizon_lsn,
+            flush_lsn,
+        }
+        .into());
+    }
+    let mut new_state = state.clone();
+    new_state.peer_horizon_lsn = peer_horizon_lsn;
+    Ok(new_state)
+}
+
 fn state_apply_diff(
     state: &TimelinePersistentState,
     request: &Request,
@@ -83,3 +144,38 @@ fn json_apply_diff(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control_file::{FileStorage, Storage};
+    use crate::SafeKeeperConf;
+    use utils::id::TenantTimelineId;
+
+    #[tokio::test]
+    async fn test_set_peer_horizon_lsn() {
+        let conf = SafeKeeperConf {
+            workdir: camino_tempfile::tempdir().unwrap().into_path(),
+            ..SafeKeeperConf::dummy()
+        };
+        let ttid = TenantTimelineId::generate();
+        let timeline_dir = conf.timeline_dir(&ttid);
+        tokio::fs::create_dir_all(&timeline_dir).await.unwrap();
+
+        let mut state = TimelinePersistentState::empty();
+        state.peer_horizon_lsn = Lsn(0x3000);
+        let mut storage = FileStorage::create_new(timeline_dir, &conf, state.clone()).unwrap();
+        let flush_lsn = Lsn(0x2000);
+
+        // Above flush_lsn is refused.
+        let err = state_set_peer_horizon_lsn(&state, Lsn(0x2001), flush_lsn).unwrap_err();
+        assert!(err.is::<PeerHorizonAheadError>(), "{err}");
+
+        // At or below is fine and survives reload.
+        let new_state = state_set_peer_horizon_lsn(&state, Lsn(0x1000), flush_lsn).unwrap();
+        storage.persist(&new_state).await.unwrap();
+        let reloaded = FileStorage::load_control_file_conf(&conf, &ttid).unwrap();
+        assert_eq!(reloaded.peer_horizon_lsn, Lsn(0x1000));
+        assert_eq!(reloaded, new_state);
+    }
+}
@@ -21,6 +21,7 @@ use postgres_backend::QueryError;
 use pq_proto::BeMessage;
 use serde::Deserialize;
 use serde::Serialize;
+use std::cmp::min;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::io::AsyncRead;
@@ -29,6 +30,7 @@ use tokio::sync::mpsc::channel;
 use tokio::sync::mpsc::error::TryRecvError;
 use tokio::sync::mpsc::Receiver;
 use tokio::sync::mpsc::Sender;
+use tokio::sync::Semaphore;
 use tokio::task;
 use tokio::task::JoinHandle;
 use tokio::time::Duration;
@@ -161,6 +163,44 @@ impl Drop for WalReceiverGuard {
 pub const MSG_QUEUE_SIZE: usize = 256;
 pub const REPLY_QUEUE_SIZE: usize = 16;
 
+/// Limits the amount of WAL read from the socket but not yet fsynced by
+/// WalAcceptor, so that slow disk makes us stop reading from compute instead
+/// of buffering without bound.
+pub struct InflightWal {
+    sem: Semaphore,
+    limit: u32,
+}
+
+impl InflightWal {
+    pub fn new(limit: u64) -> Arc<InflightWal> {
+        let limit = u32::try_from(limit).expect("validated in SafeKeeperConf");
+        Arc::new(InflightWal {
+            sem: Semaphore::new(limit as usize),
+            limit,
+        })
+    }
+
+    /// Number of permits taken by a message with `bytes` of WAL. Messages
+    /// larger than the limit take the whole budget instead of waiting forever.
+    fn permits(&self, bytes: usize) -> u32 {
+        min(bytes, self.limit as usize) as u32
+    }
+
+    /// Wait until `bytes` more of WAL fit into the limit and account them.
+    async fn acquire(&self, bytes: usize) {
+        self.sem
+            .acquire_many(self.permits(bytes))
+            .await
+            .expect("semaphore is never closed")
+            .forget();
+    }
+
+    /// Return permits previously taken with acquire, once WAL is fsynced.
+    fn release(&self, permits: u32) {
+        self.sem.add_permits(permits as usize);
+    }
+}
+
 impl SafekeeperPostgresHandler {
     /// Wrapper around handle_start_wal_push_guts handling result. Error is
     /// handled here while we're still in walreceiver ttid span; with API
@@ -191,6 +231,7 @@ impl SafekeeperPostgresHandler {
         // [1] https://github.com/neondatabase/neon/pull/1318
         let (msg_tx, msg_rx) = channel(MSG_QUEUE_SIZE);
         let (reply_tx, reply_rx) = channel(REPLY_QUEUE_SIZE);
+        let inflight = InflightWal::new(self.conf.max_receive_wal_inflight_bytes);
         let mut acceptor_handle: Option<JoinHandle<anyhow::Result<()>>> = None;
 
         // Concurrently receive and send data; replies are not synchronized with
@@ -203,6 +244,7 @@ impl SafekeeperPostgresHandler {
             pgb_reader: &mut pgb_reader,
             peer_addr,
             acceptor_handle: &mut acceptor_handle,
+            inflight,
         };
         let res = tokio::select! {
             // todo: add read|write .context to these errors
@@ -248,6 +290,7 @@ struct NetworkReader<'a, IO> {
     // WalAcceptor is spawned when we learn server info from walproposer and
     // create timeline; handle is put here.
     acceptor_handle: &'a mut Option<JoinHandle<anyhow::Result<()>>>,
+    inflight: Arc<InflightWal>,
 }
 
 impl<'a, IO: AsyncRead + AsyncWrite + Unpin> NetworkReader<'a, IO> {
@@ -284,10 +327,11 @@ impl<'a, IO: AsyncRead + AsyncWrite + Unpin> NetworkReader<'a, IO> {
             msg_rx,
             reply_tx,
             Some(self.conn_id),
+            Some(self.inflight.clone()),
         ));
 
         // Forward all messages to WalAcceptor
-        read_network_loop(self.pgb_reader, msg_tx, next_msg).await
+        read_network_loop(self.pgb_reader, msg_tx, next_msg, &self.inflight).await
     }
 }
 
@@ -305,8 +349,13 @@ async fn read_network_loop<IO: AsyncRead + AsyncWrite + Unpin>(
     pgb_reader: &mut PostgresBackendReader<IO>,
     msg_tx: Sender<ProposerAcceptorMessage>,
     mut next_msg: ProposerAcceptorMessage,
+    inflight: &InflightWal,
 ) -> Result<(), CopyStreamHandlerEnd> {
     loop {
+        // Don't read further while too much WAL is waiting for fsync.
+        if let ProposerAcceptorMessage::AppendRequest(append_request) = &next_msg {
+            inflight.acquire(append_request.wal_data.len()).await;
+        }
         if msg_tx.send(next_msg).await.is_err() {
             return Ok(()); // chan closed, WalAcceptor terminated
         }
@@ -347,6 +396,7 @@ pub struct WalAcceptor {
     msg_rx: Receiver<ProposerAcceptorMessage>,
     reply_tx: Sender<AcceptorProposerMessage>,
     conn_id: Option<ConnectionId>,
+    inflight: Option<Arc<InflightWal>>,
 }
 
 impl WalAcceptor {
@@ -355,11 +405,15 @@ impl WalAcceptor {
     /// message processing is encountered.
     ///
     /// conn_id None means WalAcceptor is used by recovery initiated at this safekeeper.
+    ///
+    /// If inflight is given, permits taken by the sender for AppendRequests are
+    /// returned once their WAL is fsynced.
     pub fn spawn(
         tli: Arc<Timeline>,
         msg_rx: Receiver<ProposerAcceptorMessage>,
         reply_tx: Sender<AcceptorProposerMessage>,
         conn_id: Option<ConnectionId>,
+        inflight: Option<Arc<InflightWal>>,
     ) -> JoinHandle<anyhow::Result<()>> {
         task::spawn(async move {
             let mut wa = WalAcceptor {
@@ -367,6 +421,7 @@ impl WalAcceptor {
                 msg_rx,
                 reply_tx,
                 conn_id,
+                inflight,
             };
 
             let span_ttid = wa.tli.ttid; // satisfy borrow checker
@@ -414,7 +469,11 @@ impl WalAcceptor {
                 // Note: this will need to be rewritten if we want to read non-AppendRequest messages here.
                 // Otherwise, we might end up in a situation where we read a message, but don't
                 // process it.
+                let mut unflushed_permits = 0;
                 while let ProposerAcceptorMessage::AppendRequest(append_request) = next_msg {
+                    if let Some(inflight) = &self.inflight {
+                        unflushed_permits += inflight.permits(append_request.wal_data.len());
+                    }
                     let noflush_msg = ProposerAcceptorMessage::NoFlushAppendRequest(append_request);
 
                     if let Some(reply) = self.tli.process_msg(&noflush_msg).await? {
@@ -436,9 +495,14 @@ impl WalAcceptor {
                 }
 
                 // flush all written WAL to the disk
-                self.tli
+                let reply = self
+                    .tli
                     .process_msg(&ProposerAcceptorMessage::FlushWAL)
-                    .await?
+                    .await?;
+                if let Some(inflight) = &self.inflight {
+                    inflight.release(unflushed_permits);
+                }
+                reply
             } else {
                 // process message other than AppendRequest
                 self.tli.process_msg(&next_msg).await?
@@ -470,3 +534,205 @@ impl Drop for ComputeConnectionGuard {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use bytes::BufMut;
+    use postgres_backend::AuthType;
+    use postgres_ffi::{encode_logical_message, WAL_SEGMENT_SIZE};
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+    use crate::safekeeper::{ProposerElected, Term, TermHistory, TermLsn};
+    use crate::SafeKeeperConf;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_inflight_wal_backpressure() {
+        let inflight = InflightWal::new(1000);
+        let pulled = Arc::new(AtomicUsize::new(0));
+
+        // Network reader pulling 100 byte messages as fast as it can.
+        let reader = tokio::spawn({
+            let inflight = inflight.clone();
+            let pulled = pulled.clone();
+            async move {
+                loop {
+                    inflight.acquire(100).await;
+                    pulled.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        });
+
+        // Storage doesn't fsync anything: reader stops at the limit.
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(pulled.load(Ordering::SeqCst), 10);
+
+        // fsync of 3 messages lets exactly 3 more in.
+        inflight.release(3 * inflight.permits(100));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(pulled.load(Ordering::SeqCst), 13);
+
+        reader.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_inflight_wal_oversized_message() {
+        let inflight = InflightWal::new(1000);
+
+        // Message larger than the limit takes the whole budget, but doesn't hang.
+        inflight.acquire(5000).await;
+        let permits = inflight.permits(5000);
+        assert_eq!(permits, 1000);
+
+        let next = tokio::spawn({
+            let inflight = inflight.clone();
+            async move { inflight.acquire(1).await }
+        });
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(!next.is_finished());
+
+        inflight.release(permits);
+        next.await.unwrap();
+    }
+
+    /// CopyData frame carrying an AppendRequest of `wal` at `begin_lsn`, as
+    /// walproposer sends it.
+    fn append_request_frame(term: Term, begin_lsn: Lsn, wal: &[u8]) -> Vec<u8> {
+        let mut msg = BytesMut::new();
+        msg.put_u64_le('a' as u64);
+        msg.put_u64_le(term);
+        msg.put_u64_le(begin_lsn.0); // epoch_start_lsn
+        msg.put_u64_le(begin_lsn.0);
+        msg.put_u64_le(begin_lsn.0 + wal.len() as u64);
+        msg.put_u64_le(0); // commit_lsn
+        msg.put_u64_le(0); // truncate_lsn
+        msg.put_slice(&[0u8; 16]); // proposer_uuid
+        msg.put_slice(wal);
+
+        let mut frame = BytesMut::new();
+        frame.put_u8(b'd');
+        frame.put_u32(4 + msg.len() as u32);
+        frame.put_slice(&msg);
+        frame.to_vec()
+    }
+
+    /// Streams WAL through the network reader into a WalAcceptor whose storage
+    /// is stuck: the reader stops pulling from the socket at the limit, and
+    /// continues once WalAcceptor has fsynced and returned the permits.
+    #[tokio::test]
+    async fn test_receive_wal_backpressure_round_trip() {
+        const MESSAGES: usize = 10;
+        const TERM: Term = 1;
+        let start_lsn = Lsn(0x016B_9188);
+
+        let workdir = camino_tempfile::tempdir().unwrap();
+        let conf = SafeKeeperConf {
+            workdir: workdir.path().to_owned(),
+            no_sync: true,
+            peer_recovery_enabled: false,
+            ..SafeKeeperConf::dummy()
+        };
+        let ttid = TenantTimelineId::generate();
+        let (wal_backup_launcher_tx, _wal_backup_launcher_rx) = channel(100);
+        let tli = Arc::new(
+            Timeline::create_empty(
+                &conf,
+                ttid,
+                wal_backup_launcher_tx,
+                ServerInfo {
+                    pg_version: 160000,
+                    wal_seg_size: WAL_SEGMENT_SIZE as u32,
+                    system_id: 0,
+                },
+                Lsn::INVALID,
+                Lsn::INVALID,
+            )
+            .unwrap(),
+        );
+        tli.init_new(&mut tli.write_shared_state().await, &conf)
+            .await
+            .unwrap();
+        tli.process_msg(&ProposerAcceptorMessage::Elected(ProposerElected {
+            term: TERM,
+            start_streaming_at: start_lsn,
+            term_history: TermHistory(vec![TermLsn::from((TERM, start_lsn))]),
+            timeline_start_lsn: start_lsn,
+        }))
+        .await
+        .unwrap();
+
+        let wal = encode_logical_message("prefix", "message");
+        let end_lsn = start_lsn + (MESSAGES * wal.len()) as u64;
+        let frames: Vec<Vec<u8>> = (0..MESSAGES)
+            .map(|i| append_request_frame(TERM, start_lsn + (i * wal.len()) as u64, &wal))
+            .collect();
+
+        // Only three messages fit into the limit. With a socket buffer of a
+        // single message, the sender stalls soon after the reader stops.
+        let inflight = InflightWal::new(3 * wal.len() as u64);
+        let (mut compute, socket) = tokio::io::duplex(frames[0].len());
+        let mut pgb = PostgresBackend::new_from_io(
+            socket,
+            "127.0.0.1:5432".parse().unwrap(),
+            AuthType::Trust,
+            None,
+        )
+        .unwrap();
+        let mut pgb_reader = pgb.split().unwrap();
+
+        // Storage doesn't make progress while we hold the timeline lock.
+        let stuck_storage = tli.write_shared_state().await;
+
+        let (msg_tx, msg_rx) = channel(MSG_QUEUE_SIZE);
+        let (reply_tx, mut reply_rx) = channel(REPLY_QUEUE_SIZE);
+        let acceptor = WalAcceptor::spawn(
+            tli.clone(),
+            msg_rx,
+            reply_tx,
+            Some(1),
+            Some(inflight.clone()),
+        );
+        let reader = tokio::spawn({
+            let inflight = inflight.clone();
+            async move {
+                let first_msg = read_message(&mut pgb_reader).await?;
+                read_network_loop(&mut pgb_reader, msg_tx, first_msg, &inflight).await
+            }
+        });
+        let sender = tokio::spawn(async move {
+            for frame in frames {
+                compute.write_all(&frame).await.unwrap();
+            }
+            compute
+        });
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(!sender.is_finished(), "reader kept pulling past the limit");
+        assert_eq!(inflight.sem.available_permits(), 0);
+
+        // Once WAL gets fsynced, the permits come back and everything is received.
+        drop(stuck_storage);
+        let compute = tokio::time::timeout(Duration::from_secs(10), sender)
+            .await
+            .expect("sender finishes once storage catches up")
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while tli.get_flush_lsn().await < end_lsn {
+                reply_rx.recv().await.expect("WalAcceptor replies");
+            }
+        })
+        .await
+        .expect("all WAL is flushed");
+        assert_eq!(tli.get_flush_lsn().await, end_lsn);
+
+        drop(compute);
+        drop(reply_rx);
+        assert!(matches!(
+            reader.await.unwrap(),
+            Err(CopyStreamHandlerEnd::EOF)
+        ));
+        acceptor.await.unwrap().unwrap();
+    }
+}
@@ -1,6 +1,7 @@
 //! This module implements pulling WAL from peer safekeepers if compute can't
 //! provide it, i.e. safekeeper lags too much.
 
+use std::cmp::max;
 use std::time::SystemTime;
 use std::{fmt, pin::pin, sync::Arc};
 
@@ -17,12 +18,17 @@ use tokio::{
 use tokio_postgres::replication::ReplicationStream;
 use tokio_postgres::types::PgLsn;
 use tracing::*;
-use utils::{id::NodeId, lsn::Lsn, postgres_client::wal_stream_connection_config};
+use utils::{
+    id::{NodeId, TenantTimelineId},
+    lsn::Lsn,
+    postgres_client::wal_stream_connection_config,
+};
 
 use crate::receive_wal::{WalAcceptor, REPLY_QUEUE_SIZE};
 use crate::safekeeper::{AppendRequest, AppendRequestHeader};
 use crate::{
     http::routes::TimelineStatus,
+    metrics::PEER_RECOVERY_LAG_BYTES,
     receive_wal::MSG_QUEUE_SIZE,
     safekeeper::{
         AcceptorProposerMessage, ProposerAcceptorMessage, ProposerElected, Term, TermHistory,
@@ -45,12 +51,21 @@ pub async fn recovery_main(tli: Arc<Timeline>, conf: SafeKeeperConf) {
         }
     };
 
+    let ttid = tli.ttid;
     select! {
         _ = recovery_main_loop(tli, conf) => { unreachable!() }
         _ = cancellation_rx.changed() => {
             info!("stopped");
         }
     }
+    // Timeline is being removed, don't leave its lag behind.
+    remove_recovery_lag_metric(&ttid);
+}
+
+/// Drop the per timeline recovery lag gauge, if it was ever set.
+fn remove_recovery_lag_metric(ttid: &TenantTimelineId) {
+    let _ = PEER_RECOVERY_LAG_BYTES
+        .remove_label_values(&[&ttid.tenant_id.to_string(), &ttid.timeline_id.to_string()]);
 }
 
 /// Result of Timeline::recovery_needed, contains donor(s) if recovery needed and
@@ -102,6 +117,7 @@ pub struct Donor {
     pub flush_lsn: Lsn,
     pub pg_connstr: String,
     pub http_connstr: String,
+    pub availability_zone: Option<String>,
 }
 
 impl From<&PeerInfo> for Donor {
@@ -112,17 +128,31 @@ impl From<&PeerInfo> for Donor {
             flush_lsn: p.flush_lsn,
             pg_connstr: p.pg_connstr.clone(),
             http_connstr: p.http_connstr.clone(),
+            availability_zone: p.availability_zone.clone(),
         }
     }
 }
 
+/// Move donors in our availability zone to the front to avoid cross-AZ
+/// traffic, keeping the original order otherwise. If AZ is unknown, or no
+/// donor matches, order is left as is.
+fn prefer_same_az_donors(donors: &mut [Donor], my_az: Option<&str>) {
+    if let Some(my_az) = my_az {
+        donors.sort_by_key(|d| d.availability_zone.as_deref() != Some(my_az));
+    }
+}
+
 const CHECK_INTERVAL_MS: u64 = 2000;
 
 /// Check regularly whether we need to start recovery.
 async fn recovery_main_loop(tli: Arc<Timeline>, conf: SafeKeeperConf) {
     let check_duration = Duration::from_millis(CHECK_INTERVAL_MS);
     loop {
-        let recovery_needed_info = tli.recovery_needed(conf.heartbeat_timeout).await;
+        let mut recovery_needed_info = tli.recovery_needed(conf.heartbeat_timeout).await;
+        prefer_same_az_donors(
+            &mut recovery_needed_info.donors,
+            conf.availability_zone.as_deref(),
+        );
         match recovery_needed_info.donors.first() {
             Some(donor) => {
                 info!(
@@ -137,6 +167,9 @@ async fn recovery_main_loop(tli: Arc<Timeline>, conf: SafeKeeperConf) {
                     Err(e) => warn!("recovery failed: {:#}", e),
                     Ok(msg) => info!("recovery finished: {}", msg),
                 }
+                // Lag is meaningful only while recovery is running; if it is
+                // still needed, the next attempt will set it again.
+                remove_recovery_lag_metric(&tli.ttid);
             }
             None => {
                 trace!(
@@ -288,7 +321,7 @@ async fn recovery_stream(
     // As in normal walreceiver, do networking and writing to disk in parallel.
     let (msg_tx, msg_rx) = channel(MSG_QUEUE_SIZE);
     let (reply_tx, reply_rx) = channel(REPLY_QUEUE_SIZE);
-    let wa = WalAcceptor::spawn(tli.clone(), msg_rx, reply_tx, None);
+    let wa = WalAcceptor::spawn(tli.clone(), msg_rx, reply_tx, None, None);
 
     let res = tokio::select! {
         r = network_io(physical_stream, msg_tx, donor.clone(), tli.clone(), conf.clone()) => r,
@@ -327,6 +360,12 @@ async fn network_io(
 ) -> anyhow::Result<Option<String>> {
     let mut physical_stream = pin!(physical_stream);
     let mut last_received_lsn = Lsn::INVALID;
+    // Highest flush_lsn of the donor we know about, to report recovery lag.
+    let mut donor_flush_lsn = donor.flush_lsn;
+    let lag_gauge = PEER_RECOVERY_LAG_BYTES.with_label_values(&[
+        &tli.ttid.tenant_id.to_string(),
+        &tli.ttid.timeline_id.to_string(),
+    ]);
     // tear down connection if no data arrives withing this period
     let no_data_timeout = Duration::from_millis(30000);
 
@@ -341,6 +380,7 @@ async fn network_io(
 
         match msg {
             ReplicationMessage::XLogData(xlog_data) => {
+                donor_flush_lsn = max(donor_flush_lsn, Lsn(xlog_data.wal_end()));
                 let ar_hdr = AppendRequestHeader {
                     term: donor.term,
                     epoch_start_lsn: Lsn::INVALID, // unused
@@ -368,8 +408,11 @@ async fn network_io(
                 {
                     return Ok(None); // chan closed, WalAcceptor terminated
                 }
+                let local_flush_lsn = tli.get_flush_lsn().await;
+                lag_gauge.set(donor_flush_lsn.0.saturating_sub(local_flush_lsn.0) as i64);
             }
-            ReplicationMessage::PrimaryKeepAlive(_) => {
+            ReplicationMessage::PrimaryKeepAlive(keepalive) => {
+                donor_flush_lsn = max(donor_flush_lsn, Lsn(keepalive.wal_end()));
                 // keepalive means nothing is being streamed for a while. Check whether we need to stop.
                 let recovery_needed_info = tli.recovery_needed(conf.heartbeat_timeout).await;
                 // do current donors still contain one we currently connected to?
@@ -430,3 +473,48 @@ async fn read_replies(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn donor(sk_id: u64, availability_zone: Option<&str>) -> Donor {
+        Donor {
+            sk_id: NodeId(sk_id),
+            term: 1,
+            flush_lsn: Lsn::INVALID,
+            pg_connstr: String::new(),
+            http_connstr: String::new(),
+            availability_zone: availability_zone.map(str::to_owned),
+        }
+    }
+
+    fn ids(donors: &[Donor]) -> Vec<u64> {
+        donors.iter().map(|d| d.sk_id.0).collect()
+    }
+
+    #[test]
+    fn test_prefer_same_az_donors() {
+        let all = vec![
+            donor(1, Some("az-a")),
+            donor(2, Some("az-b")),
+            donor(3, None),
+            donor(4, Some("az-b")),
+        ];
+
+        // Same AZ donors go first, the rest keep their order.
+        let mut donors = all.clone();
+        prefer_same_az_donors(&mut donors, Some("az-b"));
+        assert_eq!(ids(&donors), vec![2, 4, 1, 3]);
+
+        // No donor in our AZ: fall back to any.
+        let mut donors = all.clone();
+        prefer_same_az_donors(&mut donors, Some("az-c"));
+        assert_eq!(ids(&donors), vec![1, 2, 3, 4]);
+
+        // Our AZ is unknown.
+        let mut donors = all;
+        prefer_same_az_donors(&mut donors, None);
+        assert_eq!(ids(&donors), vec![1, 2, 3, 4]);
+    }
+}
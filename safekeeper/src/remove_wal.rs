@@ -29,12 +29,17 @@ pub async fn task_main(conf: SafeKeeperConf) -> anyhow::Result<()> {
                 if let Err(e) = tli.maybe_persist_control_file().await {
                     warn!("failed to persist control file: {e}");
                 }
-                if let Err(e) = tli.remove_old_wal(conf.wal_backup_enabled).await {
+                if let Err(e) = tli
+                    .remove_old_wal(conf.wal_backup_enabled, conf.wal_removal_batch_size)
+                    .await
+                {
                     error!("failed to remove WAL: {}", e);
                 }
             }
             .instrument(info_span!("WAL removal", ttid = %ttid))
             .await;
+            // Let other tasks run between removal batches of different timelines.
+            tokio::task::yield_now().await;
         }
 
         let elapsed = now.elapsed();
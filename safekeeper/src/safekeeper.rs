@@ -1017,8 +1017,12 @@ mod tests {
             Ok(())
         }
 
-        fn remove_up_to(&self, _segno_up_to: XLogSegNo) -> BoxFuture<'static, anyhow::Result<()>> {
-            Box::pin(async { Ok(()) })
+        fn remove_up_to(
+            &self,
+            _segno_up_to: XLogSegNo,
+            _max_segments: usize,
+        ) -> BoxFuture<'static, anyhow::Result<usize>> {
+            Box::pin(async { Ok(0) })
         }
 
         fn get_metrics(&self) -> crate::metrics::WalStorageMetrics {
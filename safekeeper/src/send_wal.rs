@@ -151,6 +151,12 @@ impl WalSenders {
             .min()
     }
 
+    /// Get LSN below which WAL is pinned by walsenders, i.e. the laggard's LSN,
+    /// or `flush_lsn` if there are no active pageserver walsenders.
+    pub fn horizon_lsn(self: &Arc<WalSenders>, flush_lsn: Lsn) -> Lsn {
+        self.laggard_lsn().unwrap_or(flush_lsn)
+    }
+
     /// Get aggregated pageserver feedback.
     pub fn get_ps_feedback(self: &Arc<WalSenders>) -> PageserverFeedback {
         self.mutex.lock().agg_ps_feedback
@@ -815,4 +821,19 @@ mod tests {
         assert_eq!(wss.agg_ps_feedback.current_timeline_size, 4);
         assert_eq!(wss.agg_ps_feedback.last_received_lsn, Lsn(84));
     }
+
+    #[test]
+    fn test_horizon_lsn() {
+        let walsenders = WalSenders::new();
+        assert_eq!(walsenders.horizon_lsn(Lsn(100)), Lsn(100));
+
+        {
+            let mut wss = walsenders.mutex.lock();
+            push_feedback(&mut wss, ps_feedback(8, Lsn(42)));
+            push_feedback(&mut wss, ps_feedback(4, Lsn(84)));
+            // standby doesn't hold the horizon
+            push_feedback(&mut wss, hs_feedback(1, 42));
+        }
+        assert_eq!(walsenders.horizon_lsn(Lsn(100)), Lsn(42));
+    }
 }
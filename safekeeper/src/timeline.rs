@@ -61,6 +61,7 @@ pub struct PeerInfo {
     ts: Instant,
     pub pg_connstr: String,
     pub http_connstr: String,
+    pub availability_zone: Option<String>,
 }
 
 impl PeerInfo {
@@ -74,6 +75,7 @@ impl PeerInfo {
             local_start_lsn: Lsn(sk_info.local_start_lsn),
             pg_connstr: sk_info.safekeeper_connstr.clone(),
             http_connstr: sk_info.http_connstr.clone(),
+            availability_zone: sk_info.availability_zone.clone(),
             ts,
         }
     }
@@ -262,10 +264,7 @@ impl SharedState {
             commit_lsn: self.sk.state.inmem.commit_lsn.0,
             remote_consistent_lsn: self.sk.state.inmem.remote_consistent_lsn.0,
             peer_horizon_lsn: self.sk.state.inmem.peer_horizon_lsn.0,
-            safekeeper_connstr: conf
-                .advertise_pg_addr
-                .to_owned()
-                .unwrap_or(conf.listen_pg_addr.clone()),
+            safekeeper_connstr: conf.effective_advertise_pg_addr().to_owned(),
             http_connstr: conf.listen_http_addr.to_owned(),
             backup_lsn: self.sk.state.inmem.backup_lsn.0,
             local_start_lsn: self.sk.state.local_start_lsn.0,
@@ -843,7 +842,9 @@ impl Timeline {
 
     /// Delete WAL segments from disk that are no longer needed. This is determined
     /// based on pageserver's remote_consistent_lsn and local backup_lsn/peer_lsn.
-    pub async fn remove_old_wal(&self, wal_backup_enabled: bool) -> Result<()> {
+    /// At most `batch_size` segments are deleted per call, so a big backlog is
+    /// removed over several calls.
+    pub async fn remove_old_wal(&self, wal_backup_enabled: bool, batch_size: usize) -> Result<()> {
         if self.is_cancelled() {
             bail!(TimelineError::Cancelled(self.ttid));
         }
@@ -867,11 +868,19 @@ impl Timeline {
             }
 
             // release the lock before removing
-            shared_state.sk.wal_store.remove_up_to(horizon_segno - 1)
+            shared_state
+                .sk
+                .wal_store
+                .remove_up_to(horizon_segno - 1, batch_size)
         };
 
         // delete old WAL files
-        remover.await?;
+        let n_remaining = remover.await?;
+        if n_remaining > 0 {
+            // Don't advance last_removed_segno until everything below the
+            // horizon is gone, the next call will continue from here.
+            return Ok(());
+        }
 
         // update last_removed_segno
         let mut shared_state = self.write_shared_state().await;
@@ -912,6 +921,7 @@ impl Timeline {
                 mem_state: state.sk.state.inmem.clone(),
                 persisted_state: state.sk.state.clone(),
                 flush_lsn: state.sk.wal_store.flush_lsn(),
+                walsenders_horizon_lsn: self.walsenders.horizon_lsn(state.sk.wal_store.flush_lsn()),
                 wal_storage: state.sk.wal_store.get_metrics(),
             })
         } else {
@@ -943,15 +953,17 @@ impl Timeline {
         }
     }
 
-    /// Apply a function to the control file state and persist it.
+    /// Apply a function to the control file state and persist it. The function
+    /// also gets current flush_lsn to validate the change against.
     pub async fn map_control_file<T>(
         &self,
-        f: impl FnOnce(&mut TimelinePersistentState) -> Result<T>,
+        f: impl FnOnce(&mut TimelinePersistentState, Lsn) -> Result<T>,
     ) -> Result<T> {
         let mut state = self.write_shared_state().await;
+        let flush_lsn = state.sk.wal_store.flush_lsn();
         let mut persistent_state = state.sk.state.start_change();
         // If f returns error, we abort the change and don't persist anything.
-        let res = f(&mut persistent_state)?;
+        let res = f(&mut persistent_state, flush_lsn)?;
         // If persisting fails, we abort the change and return error.
         state.sk.state.finish_change(&persistent_state).await?;
         Ok(res)
@@ -12,7 +12,7 @@ use std::cmp::min;
 use std::collections::{HashMap, HashSet};
 use std::num::NonZeroU32;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use postgres_ffi::v14::xlog_utils::XLogSegNoOffsetToRecPtr;
@@ -24,7 +24,7 @@ use tokio::fs::File;
 use tokio::select;
 use tokio::sync::mpsc::{self, Receiver, Sender};
 use tokio::sync::watch;
-use tokio::time::sleep;
+use tokio::time::{sleep, Instant};
 use tracing::*;
 
 use utils::{id::TenantTimelineId, lsn::Lsn};
@@ -180,6 +180,58 @@ fn get_configured_remote_storage() -> &'static GenericRemoteStorage {
         .unwrap()
 }
 
+/// Limits the aggregate upload rate of all backup tasks, `None` if unlimited.
+static BACKUP_RATE_LIMITER: OnceCell<Option<Arc<TokenBucket>>> = OnceCell::new();
+
+/// Token bucket rate limiter for WAL uploads. The bucket holds at most one second
+/// worth of tokens, so bursts are bounded by the configured rate.
+struct TokenBucket {
+    bytes_per_sec: u64,
+    state: Mutex<TokenBucketState>,
+}
+
+struct TokenBucketState {
+    /// Negative if callers took more than was available and have to wait for the refill.
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(bytes_per_sec: u64) -> Self {
+        TokenBucket {
+            bytes_per_sec,
+            state: Mutex::new(TokenBucketState {
+                tokens: bytes_per_sec as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes `amount` tokens, going into debt if there are not enough of them, and
+    /// returns how long the caller has to wait until the debt is repaid. Later callers
+    /// inherit the debt, so waiters are served roughly in order.
+    fn reserve(&self, amount: u64) -> Duration {
+        let rate = self.bytes_per_sec as f64;
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let refill = now.duration_since(state.refilled_at).as_secs_f64() * rate;
+        state.tokens = (state.tokens + refill).min(rate) - amount as f64;
+        state.refilled_at = now;
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / rate)
+        }
+    }
+
+    async fn acquire(&self, amount: u64) {
+        let wait = self.reserve(amount);
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+    }
+}
+
 const CHECK_TASKS_INTERVAL_MSEC: u64 = 1000;
 
 /// Sits on wal_backup_launcher_rx and starts/stops per timeline wal backup
@@ -201,6 +253,10 @@ pub async fn wal_backup_launcher_task_main(
             .as_ref()
             .map(|c| GenericRemoteStorage::from_config(c).expect("failed to create remote storage"))
     });
+    BACKUP_RATE_LIMITER.get_or_init(|| {
+        conf.wal_backup_max_bytes_per_sec
+            .map(|rate| Arc::new(TokenBucket::new(rate)))
+    });
 
     // Presence in this map means launcher is aware s3 offloading is needed for
     // the timeline, but task is started only if it makes sense for to offload
@@ -513,9 +569,27 @@ async fn backup_object(
 
     let cancel = CancellationToken::new();
 
-    storage
-        .upload_storage_object(file, size, target_file, &cancel)
-        .await
+    match BACKUP_RATE_LIMITER.get().cloned().flatten() {
+        Some(limiter) => {
+            let file = file.then(move |chunk| {
+                let limiter = Arc::clone(&limiter);
+                async move {
+                    if let Ok(bytes) = &chunk {
+                        limiter.acquire(bytes.len() as u64).await;
+                    }
+                    chunk
+                }
+            });
+            storage
+                .upload_storage_object(file, size, target_file, &cancel)
+                .await
+        }
+        None => {
+            storage
+                .upload_storage_object(file, size, target_file, &cancel)
+                .await
+        }
+    }
 }
 
 pub async fn read_object(
@@ -667,3 +741,40 @@ pub async fn copy_s3_segments(
     );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn token_bucket_delays_uploads() {
+        let bucket = Arc::new(TokenBucket::new(1000));
+
+        // A full bucket lets one second worth of bytes through right away.
+        let started_at = Instant::now();
+        bucket.acquire(1000).await;
+        assert_eq!(started_at.elapsed(), Duration::ZERO);
+
+        // After that, the rate is bounded.
+        bucket.acquire(500).await;
+        assert_eq!(started_at.elapsed(), Duration::from_millis(500));
+
+        // Concurrent acquirers share the bucket, so the aggregate rate is bounded too.
+        let started_at = Instant::now();
+        let jobs: Vec<_> = (0..4)
+            .map(|_| {
+                let bucket = Arc::clone(&bucket);
+                tokio::spawn(async move { bucket.acquire(250).await })
+            })
+            .collect();
+        for job in jobs {
+            job.await.unwrap();
+        }
+        assert_eq!(started_at.elapsed(), Duration::from_secs(1));
+
+        // Idle time refills the bucket, but not beyond its capacity.
+        sleep(Duration::from_secs(10)).await;
+        assert_eq!(bucket.reserve(1000), Duration::ZERO);
+        assert_eq!(bucket.reserve(1000), Duration::from_secs(1));
+    }
+}
@@ -16,6 +16,7 @@ use postgres_ffi::{dispatch_pgversion, XLogSegNo, PG_TLI};
 use remote_storage::RemotePath;
 use std::cmp::{max, min};
 use std::io::{self, SeekFrom};
+use std::ops::RangeInclusive;
 use std::pin::Pin;
 use tokio::fs::{self, remove_file, File, OpenOptions};
 use tokio::io::{AsyncRead, AsyncWriteExt};
@@ -47,9 +48,15 @@ pub trait Storage {
     /// Durably store WAL on disk, up to the last written WAL record.
     async fn flush_wal(&mut self) -> Result<()>;
 
-    /// Remove all segments <= given segno. Returns function doing that as we
-    /// want to perform it without timeline lock.
-    fn remove_up_to(&self, segno_up_to: XLogSegNo) -> BoxFuture<'static, anyhow::Result<()>>;
+    /// Remove segments <= given segno, oldest first, but not more than
+    /// `max_segments` of them. Returns function doing that as we want to
+    /// perform it without timeline lock; it resolves to the number of segments
+    /// <= segno which are still left on disk.
+    fn remove_up_to(
+        &self,
+        segno_up_to: XLogSegNo,
+        max_segments: usize,
+    ) -> BoxFuture<'static, anyhow::Result<usize>>;
 
     /// Release resources associated with the storage -- technically, close FDs.
     /// Currently we don't remove timelines until restart (#3146), so need to
@@ -428,7 +435,10 @@ impl Storage for PhysicalStorage {
         let segno = end_pos.segment_number(self.wal_seg_size);
 
         // Remove all segments after the given LSN.
-        remove_segments_from_disk(&self.timeline_dir, self.wal_seg_size, |x| x > segno).await?;
+        remove_segments_from_disk(&self.timeline_dir, self.wal_seg_size, usize::MAX, |x| {
+            x > segno
+        })
+        .await?;
 
         let (mut file, is_partial) = self.open_or_create(segno).await?;
 
@@ -452,11 +462,18 @@ impl Storage for PhysicalStorage {
         Ok(())
     }
 
-    fn remove_up_to(&self, segno_up_to: XLogSegNo) -> BoxFuture<'static, anyhow::Result<()>> {
+    fn remove_up_to(
+        &self,
+        segno_up_to: XLogSegNo,
+        max_segments: usize,
+    ) -> BoxFuture<'static, anyhow::Result<usize>> {
         let timeline_dir = self.timeline_dir.clone();
         let wal_seg_size = self.wal_seg_size;
         Box::pin(async move {
-            remove_segments_from_disk(&timeline_dir, wal_seg_size, |x| x <= segno_up_to).await
+            remove_segments_from_disk(&timeline_dir, wal_seg_size, max_segments, |x| {
+                x <= segno_up_to
+            })
+            .await
         })
     }
 
@@ -470,15 +487,16 @@ impl Storage for PhysicalStorage {
     }
 }
 
-/// Remove all WAL segments in timeline_dir that match the given predicate.
+/// Remove WAL segments in timeline_dir that match the given predicate, lowest
+/// segno first, at most `max_segments` of them. Returns the number of matching
+/// segments left on disk.
 async fn remove_segments_from_disk(
     timeline_dir: &Utf8Path,
     wal_seg_size: usize,
+    max_segments: usize,
     remove_predicate: impl Fn(XLogSegNo) -> bool,
-) -> Result<()> {
-    let mut n_removed = 0;
-    let mut min_removed = u64::MAX;
-    let mut max_removed = u64::MIN;
+) -> Result<usize> {
+    let mut to_remove = Vec::new();
 
     let mut entries = fs::read_dir(timeline_dir).await?;
     while let Some(entry) = entries.next_entry().await? {
@@ -492,22 +510,33 @@ async fn remove_segments_from_disk(
             }
             let (segno, _) = XLogFromFileName(fname_str, wal_seg_size);
             if remove_predicate(segno) {
-                remove_file(entry_path).await?;
-                n_removed += 1;
-                min_removed = min(min_removed, segno);
-                max_removed = max(max_removed, segno);
-                REMOVED_WAL_SEGMENTS.inc();
+                to_remove.push((segno, entry_path));
             }
         }
     }
+    to_remove.sort_unstable_by_key(|(segno, _)| *segno);
+
+    let n_remaining = to_remove.len().saturating_sub(max_segments);
+    to_remove.truncate(max_segments);
+
+    let mut n_removed = 0;
+    let mut min_removed = u64::MAX;
+    let mut max_removed = u64::MIN;
+    for (segno, entry_path) in to_remove {
+        remove_file(entry_path).await?;
+        n_removed += 1;
+        min_removed = min(min_removed, segno);
+        max_removed = max(max_removed, segno);
+        REMOVED_WAL_SEGMENTS.inc();
+    }
 
     if n_removed > 0 {
         info!(
-            "removed {} WAL segments [{}; {}]",
-            n_removed, min_removed, max_removed
+            "removed {} WAL segments [{}; {}], {} more left to remove",
+            n_removed, min_removed, max_removed, n_remaining
         );
     }
-    Ok(())
+    Ok(n_remaining)
 }
 
 pub struct WalReader {
@@ -742,6 +771,29 @@ async fn write_zeroes(file: &mut File, mut count: usize) -> Result<()> {
     Ok(())
 }
 
+/// Check that every segment in `segnos` exists in timeline_dir, either full or
+/// .partial. Errors out naming the first missing one.
+pub async fn check_segments_on_disk(
+    timeline_dir: &Utf8Path,
+    wal_seg_size: usize,
+    segnos: RangeInclusive<XLogSegNo>,
+) -> Result<()> {
+    for segno in segnos {
+        let (wal_file_path, wal_file_partial_path) =
+            wal_file_paths(timeline_dir, segno, wal_seg_size)?;
+        if !fs::try_exists(&wal_file_path).await? && !fs::try_exists(&wal_file_partial_path).await?
+        {
+            bail!(
+                "WAL segment {} ({}) is missing in {}",
+                segno,
+                XLogFileName(PG_TLI, segno, wal_seg_size),
+                timeline_dir
+            );
+        }
+    }
+    Ok(())
+}
+
 /// Helper returning full path to WAL segment file and its .partial brother.
 pub fn wal_file_paths(
     timeline_dir: &Utf8Path,
@@ -753,3 +805,85 @@ pub fn wal_file_paths(
     let wal_file_partial_path = timeline_dir.join(wal_file_name + ".partial");
     Ok((wal_file_path, wal_file_partial_path))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use postgres_ffi::WAL_SEGMENT_SIZE;
+
+    #[tokio::test]
+    async fn remove_segments_in_batches() {
+        let timeline_dir = camino_tempfile::tempdir().unwrap();
+        for segno in 1..=500 {
+            let fname = XLogFileName(PG_TLI, segno, WAL_SEGMENT_SIZE);
+            File::create(timeline_dir.path().join(fname)).await.unwrap();
+        }
+        let partial = XLogFileName(PG_TLI, 501, WAL_SEGMENT_SIZE) + ".partial";
+        File::create(timeline_dir.path().join(&partial))
+            .await
+            .unwrap();
+
+        let mut passes = 0;
+        loop {
+            let remaining =
+                remove_segments_from_disk(timeline_dir.path(), WAL_SEGMENT_SIZE, 100, |x| x <= 500)
+                    .await
+                    .unwrap();
+            passes += 1;
+            assert_eq!(remaining, 500 - passes * 100);
+            if remaining == 0 {
+                break;
+            }
+        }
+        assert_eq!(passes, 5);
+
+        // Only the segment above the horizon is left.
+        let mut entries = fs::read_dir(timeline_dir.path()).await.unwrap();
+        let mut left = Vec::new();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            left.push(entry.file_name().into_string().unwrap());
+        }
+        assert_eq!(left, vec![partial]);
+    }
+
+    #[tokio::test]
+    async fn check_segments_detects_gap() {
+        let timeline_dir = camino_tempfile::tempdir().unwrap();
+        for segno in 1..=4 {
+            let fname = XLogFileName(PG_TLI, segno, WAL_SEGMENT_SIZE);
+            File::create(timeline_dir.path().join(fname)).await.unwrap();
+        }
+        let partial = XLogFileName(PG_TLI, 5, WAL_SEGMENT_SIZE) + ".partial";
+        File::create(timeline_dir.path().join(partial))
+            .await
+            .unwrap();
+
+        check_segments_on_disk(timeline_dir.path(), WAL_SEGMENT_SIZE, 1..=5)
+            .await
+            .unwrap();
+
+        // Remove a segment in the middle, and one after it.
+        for segno in [3, 4] {
+            let fname = XLogFileName(PG_TLI, segno, WAL_SEGMENT_SIZE);
+            fs::remove_file(timeline_dir.path().join(fname))
+                .await
+                .unwrap();
+        }
+        let err = check_segments_on_disk(timeline_dir.path(), WAL_SEGMENT_SIZE, 1..=5)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(&XLogFileName(PG_TLI, 3, WAL_SEGMENT_SIZE)),
+            "{err}"
+        );
+
+        // Ranges not touching the gap are fine.
+        check_segments_on_disk(timeline_dir.path(), WAL_SEGMENT_SIZE, 1..=2)
+            .await
+            .unwrap();
+        check_segments_on_disk(timeline_dir.path(), WAL_SEGMENT_SIZE, 5..=5)
+            .await
+            .unwrap();
+    }
+}
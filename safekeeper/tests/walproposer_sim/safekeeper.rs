@@ -165,17 +165,22 @@ pub fn run_server(os: NodeOs, disk: Arc<SafekeeperDisk>) -> Result<()> {
         heartbeat_timeout: Duration::from_secs(0),
         remote_storage: None,
         max_offloader_lag_bytes: 0,
+        max_receive_wal_inflight_bytes: u32::MAX as u64,
         wal_backup_enabled: false,
         listen_pg_addr_tenant_only: None,
         advertise_pg_addr: None,
         availability_zone: None,
         peer_recovery_enabled: false,
         backup_parallel_jobs: 0,
+        wal_backup_max_bytes_per_sec: None,
         pg_auth: None,
         pg_tenant_only_auth: None,
         http_auth: None,
         current_thread_runtime: false,
         walsenders_keep_horizon: false,
+        wal_removal_batch_size: 100,
+        wal_service_worker_threads: None,
+        wal_backup_worker_threads: None,
     };
 
     let mut global = GlobalMap::new(disk, conf.clone())?;
@@ -259,10 +259,14 @@ impl wal_storage::Storage for DiskWALStorage {
         Ok(())
     }
 
-    /// Remove all segments <= given segno. Returns function doing that as we
+    /// Remove segments <= given segno. Returns function doing that as we
     /// want to perform it without timeline lock.
-    fn remove_up_to(&self, _segno_up_to: XLogSegNo) -> BoxFuture<'static, anyhow::Result<()>> {
-        Box::pin(async move { Ok(()) })
+    fn remove_up_to(
+        &self,
+        _segno_up_to: XLogSegNo,
+        _max_segments: usize,
+    ) -> BoxFuture<'static, anyhow::Result<usize>> {
+        Box::pin(async move { Ok(0) })
     }
 
     /// Release resources associated with the storage -- technically, close FDs.
@@ -3513,6 +3513,7 @@ class SafekeeperTimelineStatus:
     backup_lsn: Lsn
     peer_horizon_lsn: Lsn
     remote_consistent_lsn: Lsn
+    walsenders_horizon_lsn: Lsn
     walreceivers: List[Walreceiver]
 
 
@@ -3589,6 +3590,18 @@ class SafekeeperHttpClient(requests.Session):
         assert isinstance(res_json, dict)
         return res_json
 
+    def reset_peer_horizon_lsn(
+        self, tenant_id: TenantId, timeline_id: TimelineId, peer_horizon_lsn: Lsn
+    ) -> Dict[str, Any]:
+        res = self.put(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/control_file/peer_horizon_lsn",
+            json={"peer_horizon_lsn": str(peer_horizon_lsn)},
+        )
+        res.raise_for_status()
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
     def pull_timeline(self, body: Dict[str, Any]) -> Dict[str, Any]:
         res = self.post(f"http://localhost:{self.port}/v1/pull_timeline", json=body)
         res.raise_for_status()
@@ -3650,6 +3663,7 @@ class SafekeeperHttpClient(requests.Session):
             backup_lsn=Lsn(resj["backup_lsn"]),
             peer_horizon_lsn=Lsn(resj["peer_horizon_lsn"]),
             remote_consistent_lsn=Lsn(resj["remote_consistent_lsn"]),
+            walsenders_horizon_lsn=Lsn(resj["walsenders_horizon_lsn"]),
             walreceivers=walreceivers,
         )
 
@@ -263,10 +263,14 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         )
         self.verbose_error(res)
 
-    def tenant_detach(self, tenant_id: TenantId, detach_ignored=False, timeout_secs=None):
+    def tenant_detach(
+        self, tenant_id: TenantId, detach_ignored=False, timeout_secs=None, preserve_local=False
+    ):
         params = {}
         if detach_ignored:
             params["detach_ignored"] = "true"
+        if preserve_local:
+            params["preserve_local"] = "true"
 
         kwargs = {}
         if timeout_secs is not None:
@@ -339,6 +343,59 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         self.verbose_error(res)
         return TenantConfig.from_json(res.json())
 
+    def tenant_config_effective_toml(self, tenant_id: Union[TenantId, TenantShardId]) -> str:
+        """
+        Returns the tenant's effective config, serialized as TOML
+        """
+        res = self.get(f"http://localhost:{self.port}/v1/tenant/{tenant_id}/config/effective_toml")
+        self.verbose_error(res)
+        return res.text
+
+    def tenant_config_reload(self, tenant_id: Union[TenantId, TenantShardId]):
+        """
+        Re-reads the tenant's config file from disk and applies it to the running tenant
+        """
+        res = self.post(f"http://localhost:{self.port}/v1/tenant/{tenant_id}/config/reload")
+        self.verbose_error(res)
+
+    def tenant_remote_size(self, tenant_id: Union[TenantId, TenantShardId]) -> Dict[str, int]:
+        """
+        Returns the remote physical size of each timeline, keyed by timeline id
+        """
+        res = self.get(f"http://localhost:{self.port}/v1/tenant/{tenant_id}/remote_size")
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
+    def tenant_size_ratio(self, tenant_id: Union[TenantId, TenantShardId]) -> Dict[str, float]:
+        """
+        Returns the physical to logical size ratio of each timeline, keyed by timeline id
+        """
+        res = self.get(f"http://localhost:{self.port}/v1/tenant/{tenant_id}/size_ratio")
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
+    def tenant_gc_reclaim_estimate(
+        self, tenant_id: Union[TenantId, TenantShardId]
+    ) -> Dict[str, int]:
+        """
+        Returns the bytes a GC iteration would reclaim from each timeline, keyed by timeline id
+        """
+        res = self.get(f"http://localhost:{self.port}/v1/tenant/{tenant_id}/gc_reclaim_estimate")
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
+    def tenant_warmup_concurrency(self, concurrency: int):
+        res = self.put(
+            f"http://localhost:{self.port}/v1/tenant_warmup_concurrency", json=concurrency
+        )
+        self.verbose_error(res)
+
     def tenant_heatmap_upload(self, tenant_id: Union[TenantId, TenantShardId]):
         res = self.post(f"http://localhost:{self.port}/v1/tenant/{tenant_id}/heatmap_upload")
         self.verbose_error(res)
@@ -372,6 +429,16 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
     def tenant_size(self, tenant_id: Union[TenantId, TenantShardId]) -> int:
         return self.tenant_size_and_modelinputs(tenant_id)[0]
 
+    def tenant_size_progress(self, tenant_id: Union[TenantId, TenantShardId]) -> Dict[str, Any]:
+        """
+        Returns how many timelines a running tenant_size calculation has processed, out of how many
+        """
+        res = self.get(f"http://localhost:{self.port}/v1/tenant/{tenant_id}/synthetic_size/progress")
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
     def tenant_size_and_modelinputs(
         self, tenant_id: Union[TenantId, TenantShardId]
     ) -> Tuple[int, Dict[str, Any]]:
@@ -449,11 +516,15 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         ancestor_timeline_id: Optional[TimelineId] = None,
         ancestor_start_lsn: Optional[Lsn] = None,
         existing_initdb_timeline_id: Optional[TimelineId] = None,
+        ancestor_start_timestamp: Optional[datetime] = None,
         **kwargs,
     ) -> Dict[Any, Any]:
         body: Dict[str, Any] = {
             "new_timeline_id": str(new_timeline_id),
             "ancestor_start_lsn": str(ancestor_start_lsn) if ancestor_start_lsn else None,
+            "ancestor_start_timestamp": f"{ancestor_start_timestamp.isoformat()}Z"
+            if ancestor_start_timestamp
+            else None,
             "ancestor_timeline_id": str(ancestor_timeline_id) if ancestor_timeline_id else None,
             "existing_initdb_timeline_id": str(existing_initdb_timeline_id)
             if existing_initdb_timeline_id
@@ -523,18 +594,29 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         tenant_id: Union[TenantId, TenantShardId],
         timeline_id: TimelineId,
         gc_horizon: Optional[int],
+        dry_run: bool = False,
     ) -> dict[str, Any]:
         """
         Unlike most handlers, this will wait for the layers to be actually
         complete registering themselves to the deletion queue.
+
+        With `dry_run`, nothing is removed, and the names of the layers that
+        would have been removed are returned in `layers_to_remove`.
+
+        If the GC run was cancelled before it completed, `cancelled` is true
+        and the returned counters are partial.
         """
         self.is_testing_enabled_or_skip()
 
         log.info(
-            f"Requesting GC: tenant {tenant_id}, timeline {timeline_id}, gc_horizon {repr(gc_horizon)}"
+            f"Requesting GC: tenant {tenant_id}, timeline {timeline_id}, gc_horizon {repr(gc_horizon)}, dry_run {dry_run}"
         )
+        query = {}
+        if dry_run:
+            query["dry_run"] = "true"
         res = self.put(
             f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/do_gc",
+            params=query,
             json={"gc_horizon": gc_horizon},
         )
         log.info(f"Got GC request response code: {res.status_code}")
@@ -624,6 +706,20 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         res_json = res.json()
         assert res_json is None
 
+    def timeline_flush(
+        self,
+        tenant_id: Union[TenantId, TenantShardId],
+        timeline_id: TimelineId,
+    ):
+        log.info(f"Requesting flush: tenant {tenant_id}, timeline {timeline_id}")
+        res = self.post(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/flush",
+        )
+        log.info(f"Got flush request response code: {res.status_code}")
+        self.verbose_error(res)
+        res_json = res.json()
+        assert res_json is None
+
     def timeline_spawn_download_remote_layers(
         self,
         tenant_id: Union[TenantId, TenantShardId],
@@ -750,6 +846,19 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         self.verbose_error(res)
         return LayerMapInfo.from_json(res.json())
 
+    def layer_audit(
+        self,
+        tenant_id: Union[TenantId, TenantShardId],
+        timeline_id: TimelineId,
+    ) -> List[Dict[str, Any]]:
+        res = self.get(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/layer_audit",
+        )
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, list)
+        return res_json
+
     def download_layer(
         self, tenant_id: Union[TenantId, TenantShardId], timeline_id: TimelineId, layer_name: str
     ):
@@ -779,6 +888,16 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
 
         assert res.status_code in (200, 304)
 
+    def redownload_layer(
+        self, tenant_id: Union[TenantId, TenantShardId], timeline_id: TimelineId, layer_name: str
+    ):
+        res = self.post(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/layer/{layer_name}/redownload",
+        )
+        self.verbose_error(res)
+
+        assert res.status_code == 200
+
     def evict_all_layers(self, tenant_id: Union[TenantId, TenantShardId], timeline_id: TimelineId):
         info = self.layer_map_info(tenant_id, timeline_id)
         for layer in info.historic_layers:
@@ -168,14 +168,19 @@ def test_fully_custom_config(positive_env: NeonEnv):
         "evictions_low_residence_duration_metric_threshold": "2days",
         "gc_feedback": True,
         "gc_horizon": 23 * (1024 * 1024),
+        "gc_parallelism": 4,
         "gc_period": "2h 13m",
         "heatmap_period": "10m",
         "image_creation_threshold": 7,
         "pitr_interval": "1m",
         "lagging_wal_timeout": "23m",
         "lazy_slru_download": True,
+        "local_disk_high_watermark_bytes": 23 * (1024 * 1024),
+        "max_inmem_layer_bytes": 23 * (1024 * 1024),
         "max_lsn_wal_lag": 230000,
         "min_resident_size_override": 23,
+        "skip_orphan_timelines": True,
+        "synthetic_size_calculation_enabled": False,
         "timeline_get_throttle": {
             "task_kinds": ["PageRequestHandler"],
             "fair": True,
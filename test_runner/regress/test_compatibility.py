@@ -288,14 +288,17 @@ def check_neon_works(env: NeonEnv, test_output_dir: Path, sql_dump_path: Path, r
         # Allow the error as we might be running the old pageserver binary
         log.info(f"Got allowed error: '{e}'")
 
-    # Delete all files from local_fs_remote_storage except initdb-preserved.tar.zst,
-    # the file is required for `timeline_create` with `existing_initdb_timeline_id`.
+    # Delete all files from local_fs_remote_storage except initdb-preserved.tar.zst and its
+    # checksum, the file is required for `timeline_create` with `existing_initdb_timeline_id`.
     #
     # TODO: switch to Path.walk() in Python 3.12
     # for dirpath, _dirnames, filenames in (repo_dir / "local_fs_remote_storage").walk():
     for dirpath, _dirnames, filenames in os.walk(repo_dir / "local_fs_remote_storage"):
         for filename in filenames:
-            if filename != "initdb-preserved.tar.zst" and filename != "initdb.tar.zst":
+            if filename.removesuffix(".crc32c") not in (
+                "initdb-preserved.tar.zst",
+                "initdb.tar.zst",
+            ):
                 (Path(dirpath) / filename).unlink()
 
     timeline_delete_wait_completed(pageserver_http, tenant_id, timeline_id)
@@ -10,10 +10,14 @@ from fixtures.neon_fixtures import NeonEnvBuilder, WalCraft
     "wal_type",
     [
         "simple",
+        "aborted_transaction",
+        "toasted_row_insert",
         "last_wal_record_xlog_switch",
         "last_wal_record_xlog_switch_ends_on_page_boundary",
         "last_wal_record_crossing_segment",
         "wal_record_crossing_segment_followed_by_small_one",
+        "wal_record_spanning_three_segments",
+        "full_page_image_insert",
     ],
 )
 def test_crafted_wal_end(neon_env_builder: NeonEnvBuilder, wal_type: str):
@@ -22,7 +26,11 @@ def test_crafted_wal_end(neon_env_builder: NeonEnvBuilder, wal_type: str):
 
     endpoint = env.endpoints.create("test_crafted_wal_end")
     wal_craft = WalCraft(env)
-    endpoint.config(wal_craft.postgres_config())
+    config_lines = wal_craft.postgres_config()
+    if wal_type == "full_page_image_insert":
+        # The crafter refuses to run unless full page images are written
+        config_lines.append("full_page_writes=on")
+    endpoint.config(config_lines)
     endpoint.start()
     res = endpoint.safe_psql_many(
         queries=[
@@ -1,10 +1,11 @@
 import time
 from datetime import datetime, timedelta, timezone
 
+import pytest
 from fixtures.log_helper import log
 from fixtures.neon_fixtures import NeonEnvBuilder, wait_for_last_flush_lsn
-from fixtures.pageserver.http import PageserverApiException
-from fixtures.types import Lsn
+from fixtures.pageserver.http import PageserverApiException, TimelineCreate406
+from fixtures.types import Lsn, TimelineId
 from fixtures.utils import query_scalar
 
 
@@ -107,6 +108,53 @@ def test_lsn_mapping(neon_env_builder: NeonEnvBuilder):
         assert Lsn(result["lsn"]) >= last_flush_lsn
 
 
+# Test branching at a timestamp instead of an LSN
+def test_branch_at_timestamp(neon_env_builder: NeonEnvBuilder):
+    env = neon_env_builder.init_start()
+
+    timeline_id = env.neon_cli.create_branch("test_branch_at_timestamp")
+    endpoint_main = env.endpoints.create_start("test_branch_at_timestamp")
+
+    cur = endpoint_main.connect().cursor()
+    cur.execute("CREATE TABLE foo (x integer)")
+    tbl = []
+    for i in range(100):
+        cur.execute("INSERT INTO foo VALUES(%s)", (i,))
+        # Get the timestamp at UTC
+        after_timestamp = query_scalar(cur, "SELECT clock_timestamp()").replace(tzinfo=None)
+        tbl.append([i, after_timestamp])
+        time.sleep(0.005)
+
+    wait_for_last_flush_lsn(env, endpoint_main, env.initial_tenant, timeline_id)
+
+    client = env.pageserver.http_client()
+    for i in [10, 50, 99]:
+        branch_timeline_id = TimelineId.generate()
+        client.timeline_create(
+            env.pg_version,
+            env.initial_tenant,
+            branch_timeline_id,
+            ancestor_timeline_id=timeline_id,
+            ancestor_start_timestamp=tbl[i][1],
+        )
+        branch_name = f"test_branch_at_timestamp_{i}"
+        env.neon_cli.map_branch(branch_name, env.initial_tenant, branch_timeline_id)
+        endpoint_here = env.endpoints.create_start(branch_name)
+        # Only the rows committed up to the timestamp are visible on the branch
+        assert endpoint_here.safe_psql("SELECT max(x) FROM foo")[0][0] == i
+        endpoint_here.stop_and_destroy()
+
+    # A timestamp before any data on the timeline cannot be branched at
+    with pytest.raises(TimelineCreate406, match="predates the GC cutoff"):
+        client.timeline_create(
+            env.pg_version,
+            env.initial_tenant,
+            TimelineId.generate(),
+            ancestor_timeline_id=timeline_id,
+            ancestor_start_timestamp=tbl[0][1] - timedelta(hours=10),
+        )
+
+
 # Test pageserver get_timestamp_of_lsn API
 def test_ts_of_lsn_api(neon_env_builder: NeonEnvBuilder):
     env = neon_env_builder.init_start()
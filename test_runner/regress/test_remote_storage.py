@@ -600,7 +600,7 @@ def test_timeline_deletion_with_files_stuck_in_upload_queue(
     filtered = [
         path
         for path in remote_timeline_path.iterdir()
-        if not (path.name.endswith("initdb.tar.zst"))
+        if not (path.name.endswith("initdb.tar.zst") or path.name.endswith("initdb.tar.zst.crc32c"))
     ]
     assert len(filtered) == 0
 
@@ -29,6 +29,12 @@ def test_empty_tenant_size(neon_simple_env: NeonEnv, test_output_dir: Path):
     # we should never have zero, because there should be the initdb "changes"
     assert initial_size > 0, "initial implementation returns ~initdb tenant_size"
 
+    # nothing to report once the calculation is done
+    assert http_client.tenant_size_progress(tenant_id) == {
+        "timelines_processed": None,
+        "timelines": 1,
+    }
+
     main_branch_name = "main"
 
     branch_name, main_timeline_id = env.neon_cli.list_timelines(tenant_id)[0]
@@ -902,6 +902,79 @@ def test_ondemand_activation(neon_env_builder: NeonEnvBuilder):
     assert len(get_tenant_states()) == n_tenants - 2
 
 
+def test_ondemand_activation_disabled(neon_env_builder: NeonEnvBuilder):
+    """
+    With `disable_on_demand_activation`, client requests must not let a tenant jump the warmup
+    queue: tenants only go active in the order they acquire a warmup permit, and a client
+    accessing a tenant that is still waiting for warmup just waits (and here, times out).
+    """
+
+    neon_env_builder.pageserver_config_override = (
+        "concurrent_tenant_warmup = '1';disable_on_demand_activation = true"
+    )
+
+    env = neon_env_builder.init_start()
+    pageserver_http = env.pageserver.http_client()
+
+    n_tenants = 4
+    tenant_ids = {env.initial_tenant}
+    for _i in range(0, n_tenants - 1):
+        tenant_id = TenantId.generate()
+        env.neon_cli.create_tenant(tenant_id)
+        tenant_ids.add(tenant_id)
+
+    # Restart pageserver with logical size calculations paused, so that the first tenant to
+    # get a warmup permit holds onto it.
+    env.pageserver.stop()
+    env.pageserver.start(
+        extra_env_vars={"FAILPOINTS": "timeline-calculate-logical-size-pause=pause"}
+    )
+
+    def get_tenant_states():
+        states = {}
+        for tenant_id in tenant_ids:
+            tenant = pageserver_http.tenant_status(tenant_id=tenant_id)
+            states[tenant_id] = tenant["state"]["slug"]
+        log.info(f"Tenant states: {states}")
+        return states
+
+    def at_least_one_active():
+        assert "Active" in set(get_tenant_states().values())
+
+    wait_until(10, 1, at_least_one_active)
+
+    stuck_tenant_id = list(
+        [(tid, s) for (tid, s) in get_tenant_states().items() if s == "Attaching"]
+    )[0][0]
+
+    # A client request for a tenant still waiting for warmup does not activate it: the request
+    # waits for the tenant to become active, and gives up when that doesn't happen in time.
+    with pytest.raises(PageserverApiException, match="Timed out waiting"):
+        pageserver_http.timeline_create(env.pg_version, stuck_tenant_id, TimelineId.generate())
+
+    states = get_tenant_states()
+    assert states[stuck_tenant_id] == "Attaching"
+    assert len([s for s in states.values() if s == "Active"]) == 1
+
+    # Once warmup can proceed, every tenant goes active via the warmup route, and none of them
+    # were activated on-demand.
+    pageserver_http.configure_failpoints(("timeline-calculate-logical-size-pause", "off"))
+
+    def all_active():
+        assert all(s == "Active" for s in get_tenant_states().values())
+
+    wait_until(10, 1, all_active)
+
+    assert env.pageserver.log_contains("Activating tenant \\(on-demand\\)") is None
+    for tenant_id in tenant_ids:
+        assert (
+            env.pageserver.log_contains(
+                f".*attach{{tenant_id={tenant_id} shard_id=0000 gen=[0-9a-f]+}}: Activating tenant \\(warmup\\).*"
+            )
+            is not None
+        )
+
+
 def test_timeline_logical_size_task_priority(neon_env_builder: NeonEnvBuilder):
     """
     /v1/tenant/:tenant_shard_id/timeline and /v1/tenant/:tenant_shard_id
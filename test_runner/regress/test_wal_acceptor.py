@@ -797,6 +797,9 @@ def test_sync_safekeepers(
             },
         )
         lsn = Lsn(res["inserted_wal"]["end_lsn"])
+        # set_commit_lsn makes both flush and commit LSN reach the record end
+        assert Lsn(res["flush_lsn"]) == lsn
+        assert Lsn(res["commit_lsn"]) == lsn
         lsn_after_append.append(lsn)
         log.info(f"safekeeper[{i}] lsn after append: {lsn}")
 
@@ -1412,6 +1415,7 @@ class SafekeeperEnv:
             str(i),
             "--broker-endpoint",
             self.broker.client_url(),
+            "--disable-wal-backup",
         ]
         log.info(f'Running command "{" ".join(cmd)}"')
 
@@ -1984,3 +1988,18 @@ def test_patch_control_file(neon_env_builder: NeonEnvBuilder):
     )
     log.info(f"dump_control_file response: {res}")
     assert res["timelines"][0]["control_file"]["timeline_start_lsn"] == "0/1"
+
+    # peer_horizon_lsn can be reset, but not beyond flush_lsn
+    endpoint.stop()
+    http_cli = env.safekeepers[0].http_client()
+    flush_lsn = http_cli.timeline_status(tenant_id, timeline_id).flush_lsn
+    with pytest.raises(http_cli.HTTPError) as excinfo:
+        http_cli.reset_peer_horizon_lsn(tenant_id, timeline_id, flush_lsn + 1)
+    assert excinfo.value.response.status_code == 400
+    res = http_cli.reset_peer_horizon_lsn(tenant_id, timeline_id, Lsn("0/1"))
+    assert res["new_control_file"]["peer_horizon_lsn"] == "0/1"
+
+    # ...and the reset is durable
+    env.safekeepers[0].stop().start()
+    res = http_cli.debug_dump({"dump_control_file": "true", "timeline_id": str(timeline_id)})
+    assert res["timelines"][0]["control_file"]["peer_horizon_lsn"] == "0/1"